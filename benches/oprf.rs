@@ -6,12 +6,18 @@
 #[macro_use]
 extern crate criterion;
 
-use criterion::Criterion;
+use criterion::{BatchSize, Criterion};
 use curve25519_dalek::ristretto::RistrettoPoint;
 use generic_array::arr;
 use opaque_ke::{
+    ciphersuite::CipherSuite,
     group::Group,
-    oprf::{blind_shim, evaluate_shim, finalize_shim},
+    key_exchange::{hmqv::HMQV, tripledh::TripleDH},
+    oprf::{blind, blind_verifiable, evaluate, evaluate_batch, finalize, BlindedElement},
+    slow_hash::NoOpHash,
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, ServerLogin, ServerLoginFinishParameters,
+    ServerLoginStartParameters, ServerLoginStartRequest, ServerRegistration, ServerSetup,
 };
 use rand::{prelude::ThreadRng, thread_rng};
 use sha2::Sha512;
@@ -22,7 +28,7 @@ fn oprf1(c: &mut Criterion) {
 
     c.bench_function("blind with Ristretto", move |b| {
         b.iter(|| {
-            blind_shim::<_, RistrettoPoint, Sha512>(&input[..], &mut csprng).unwrap();
+            blind::<_, RistrettoPoint, Sha512>(&input[..], &mut csprng).unwrap();
         })
     });
 }
@@ -31,7 +37,7 @@ fn oprf2(c: &mut Criterion) {
     let mut csprng: ThreadRng = thread_rng();
     let input = b"hunter2";
 
-    let (_, alpha) = blind_shim::<_, RistrettoPoint, Sha512>(&input[..], &mut csprng).unwrap();
+    let (_, alpha) = blind::<_, RistrettoPoint, Sha512>(&input[..], &mut csprng).unwrap();
     let salt_bytes = arr![
         u8; 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
         24, 25, 26, 27, 28, 29, 30, 31, 32,
@@ -40,7 +46,7 @@ fn oprf2(c: &mut Criterion) {
 
     c.bench_function("evaluate with Ristretto", move |b| {
         b.iter(|| {
-            let _beta = evaluate_shim::<RistrettoPoint>(alpha, &salt);
+            let _beta = evaluate::<RistrettoPoint>(&alpha, &salt);
         })
     });
 }
@@ -49,20 +55,260 @@ fn oprf3(c: &mut Criterion) {
     let mut csprng: ThreadRng = thread_rng();
     let input = b"hunter2";
 
-    let (token, alpha) = blind_shim::<_, RistrettoPoint, Sha512>(&input[..], &mut csprng).unwrap();
+    let (token, alpha) = blind::<_, RistrettoPoint, Sha512>(&input[..], &mut csprng).unwrap();
     let salt_bytes = arr![
         u8; 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
         24, 25, 26, 27, 28, 29, 30, 31, 32,
     ];
     let salt = RistrettoPoint::from_scalar_slice(&salt_bytes).unwrap();
-    let beta = evaluate_shim::<RistrettoPoint>(alpha, &salt);
+    let beta = evaluate::<RistrettoPoint>(&alpha, &salt);
 
     c.bench_function("finalize with Ristretto", move |b| {
         b.iter(|| {
-            let _res = finalize_shim::<RistrettoPoint, Sha512>(&token, beta).unwrap();
+            let _res = finalize::<RistrettoPoint, Sha512>(&token, &beta);
         })
     });
 }
 
-criterion_group!(oprf_benches, oprf1, oprf2, oprf3);
+const BATCH_SIZE: usize = 32;
+
+// Blinds BATCH_SIZE inputs against the public key of the OPRF key
+fn blinded_elements(
+    csprng: &mut ThreadRng,
+    oprf_key: &<RistrettoPoint as Group>::Scalar,
+) -> Vec<BlindedElement<RistrettoPoint>> {
+    let public_key = RistrettoPoint::base_point() * oprf_key;
+    (0..BATCH_SIZE)
+        .map(|_| {
+            blind_verifiable::<_, RistrettoPoint, Sha512>(b"hunter2", &public_key, csprng)
+                .unwrap()
+                .1
+        })
+        .collect()
+}
+
+fn oprf4(c: &mut Criterion) {
+    let mut csprng: ThreadRng = thread_rng();
+    let oprf_key = RistrettoPoint::random_nonzero_scalar(&mut csprng);
+    let blinded_elements = blinded_elements(&mut csprng, &oprf_key);
+
+    c.bench_function(
+        "verifiable evaluate with Ristretto, one proof per element",
+        move |b| {
+            b.iter(|| {
+                for blinded_element in blinded_elements.chunks(1) {
+                    evaluate_batch::<_, RistrettoPoint, Sha512>(
+                        &mut csprng,
+                        blinded_element,
+                        &oprf_key,
+                    )
+                    .unwrap();
+                }
+            })
+        },
+    );
+}
+
+fn oprf5(c: &mut Criterion) {
+    let mut csprng: ThreadRng = thread_rng();
+    let oprf_key = RistrettoPoint::random_nonzero_scalar(&mut csprng);
+    let blinded_elements = blinded_elements(&mut csprng, &oprf_key);
+
+    c.bench_function(
+        "verifiable evaluate with Ristretto, one proof per batch",
+        move |b| {
+            b.iter(|| {
+                evaluate_batch::<_, RistrettoPoint, Sha512>(
+                    &mut csprng,
+                    &blinded_elements,
+                    &oprf_key,
+                )
+                .unwrap();
+            })
+        },
+    );
+}
+
+struct Default;
+impl CipherSuite for Default {
+    type Group = RistrettoPoint;
+    type KeyExchange = TripleDH;
+    type Hash = Sha512;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0001;
+}
+
+struct DefaultHmqv;
+impl CipherSuite for DefaultHmqv {
+    type Group = RistrettoPoint;
+    type KeyExchange = HMQV;
+    type Hash = Sha512;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0002;
+}
+
+// Registers BATCH_SIZE credentials, and returns their password files
+fn password_files<CS: CipherSuite>(
+    csprng: &mut ThreadRng,
+    server_setup: &ServerSetup<CS>,
+) -> Vec<ServerRegistration<CS>> {
+    (0..BATCH_SIZE)
+        .map(|_| {
+            let client_registration_start_result =
+                ClientRegistration::<CS>::start(csprng, b"hunter2").unwrap();
+            let server_registration_start_result = ServerRegistration::start(
+                csprng,
+                server_setup,
+                client_registration_start_result.message,
+                b"username",
+            )
+            .unwrap();
+            let client_registration_finish_result = client_registration_start_result
+                .state
+                .finish(
+                    csprng,
+                    server_registration_start_result.message,
+                    ClientRegistrationFinishParameters::default(),
+                )
+                .unwrap();
+            ServerRegistration::finish(
+                client_registration_finish_result.message,
+                server_registration_start_result.key_ids,
+            )
+            .unwrap()
+        })
+        .collect()
+}
+
+// Returns login requests for the given password files
+fn login_requests<CS: CipherSuite>(
+    csprng: &mut ThreadRng,
+    password_files: &[ServerRegistration<CS>],
+) -> Vec<ServerLoginStartRequest<'static, CS>> {
+    password_files
+        .iter()
+        .map(|password_file| ServerLoginStartRequest {
+            message: ClientLogin::<CS>::start(csprng, b"hunter2")
+                .unwrap()
+                .message,
+            credential_identifier: b"username",
+            password_file: Some(password_file.clone()),
+            params: ServerLoginStartParameters::default(),
+        })
+        .collect()
+}
+
+fn oprf8(c: &mut Criterion) {
+    let mut csprng: ThreadRng = thread_rng();
+    let server_setup = ServerSetup::<Default>::new(&mut csprng);
+    let password_files = password_files(&mut csprng, &server_setup);
+    let requests = login_requests(&mut csprng, &password_files);
+
+    c.bench_function(
+        "server login start with Ristretto, one at a time",
+        move |b| {
+            b.iter_batched(
+                || requests.clone(),
+                |requests| {
+                    for request in requests {
+                        ServerLogin::start(
+                            &mut csprng,
+                            &server_setup,
+                            request.password_file,
+                            request.message,
+                            request.credential_identifier,
+                            request.params,
+                        )
+                        .unwrap();
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        },
+    );
+}
+
+fn oprf9(c: &mut Criterion) {
+    let mut csprng: ThreadRng = thread_rng();
+    let server_setup = ServerSetup::<Default>::new(&mut csprng);
+    let password_files = password_files(&mut csprng, &server_setup);
+    let requests = login_requests(&mut csprng, &password_files);
+
+    c.bench_function("server login start with Ristretto, batched", move |b| {
+        b.iter_batched(
+            || requests.clone(),
+            |requests| {
+                for result in ServerLogin::start_batch(&mut csprng, &server_setup, requests) {
+                    result.unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+// Runs the login flow on BATCH_SIZE credentials, which is dominated by the
+// key exchange when no slow hash is used
+fn login<CS: CipherSuite>(c: &mut Criterion, name: &str) {
+    let mut csprng: ThreadRng = thread_rng();
+    let server_setup = ServerSetup::<CS>::new(&mut csprng);
+    let password_files = password_files(&mut csprng, &server_setup);
+
+    c.bench_function(name, move |b| {
+        b.iter_batched(
+            || password_files.clone(),
+            |password_files| {
+                for password_file in password_files {
+                    let client_login_start_result =
+                        ClientLogin::<CS>::start(&mut csprng, b"hunter2").unwrap();
+                    let server_login_start_result = ServerLogin::start(
+                        &mut csprng,
+                        &server_setup,
+                        Some(password_file),
+                        client_login_start_result.message,
+                        b"username",
+                        ServerLoginStartParameters::default(),
+                    )
+                    .unwrap();
+                    let client_login_finish_result = client_login_start_result
+                        .state
+                        .finish(
+                            server_login_start_result.message,
+                            ClientLoginFinishParameters::default(),
+                        )
+                        .unwrap();
+                    server_login_start_result
+                        .state
+                        .finish(
+                            client_login_finish_result.message,
+                            ServerLoginFinishParameters::default(),
+                        )
+                        .unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn oprf6(c: &mut Criterion) {
+    login::<Default>(c, "login with Ristretto and 3DH");
+}
+
+fn oprf7(c: &mut Criterion) {
+    login::<DefaultHmqv>(c, "login with Ristretto and HMQV");
+}
+
+criterion_group!(
+    oprf_benches,
+    oprf1,
+    oprf2,
+    oprf3,
+    oprf4,
+    oprf5,
+    oprf6,
+    oprf7,
+    oprf8,
+    oprf9
+);
 criterion_main!(oprf_benches);
@@ -36,7 +36,7 @@ use opaque_ke::{
     ClientLogin, ClientLoginFinishParameters, ClientRegistration,
     ClientRegistrationFinishParameters, CredentialFinalization, CredentialRequest,
     CredentialResponse, RegistrationRequest, RegistrationResponse, RegistrationUpload, ServerLogin,
-    ServerLoginStartParameters, ServerRegistration, ServerSetup,
+    ServerLoginFinishParameters, ServerLoginStartParameters, ServerRegistration, ServerSetup,
 };
 
 // The ciphersuite trait allows to specify the underlying primitives
@@ -48,6 +48,7 @@ impl CipherSuite for Default {
     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
     type Hash = sha2::Sha512;
     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+    const ID: u16 = 0x0001;
 }
 
 struct Locker {
@@ -92,7 +93,9 @@ fn register_locker(
     let registration_request_bytes = client_registration_start_result.message.serialize();
 
     // Client sends registration_request_bytes to server
+    let mut server_rng = OsRng;
     let server_registration_start_result = ServerRegistration::<Default>::start(
+        &mut server_rng,
         &server_setup,
         RegistrationRequest::deserialize(&registration_request_bytes[..]).unwrap(),
         &locker_id.to_be_bytes(),
@@ -122,7 +125,9 @@ fn register_locker(
 
     let password_file = ServerRegistration::finish(
         RegistrationUpload::<Default>::deserialize(&message_bytes[..]).unwrap(),
-    );
+        server_registration_start_result.key_ids,
+    )
+    .unwrap();
 
     Locker {
         contents: ciphertext,
@@ -176,7 +181,10 @@ fn open_locker(
 
     let server_login_finish_result = server_login_start_result
         .state
-        .finish(CredentialFinalization::deserialize(&credential_finalization_bytes[..]).unwrap())
+        .finish(
+            CredentialFinalization::deserialize(&credential_finalization_bytes[..]).unwrap(),
+            ServerLoginFinishParameters::default(),
+        )
         .unwrap();
 
     // Server sends locker contents, encrypted under the session key, to the client
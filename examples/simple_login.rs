@@ -29,7 +29,8 @@ use opaque_ke::{
     ciphersuite::CipherSuite, rand::rngs::OsRng, ClientLogin, ClientLoginFinishParameters,
     ClientRegistration, ClientRegistrationFinishParameters, CredentialFinalization,
     CredentialRequest, CredentialResponse, RegistrationRequest, RegistrationResponse,
-    RegistrationUpload, ServerLogin, ServerLoginStartParameters, ServerRegistration, ServerSetup,
+    RegistrationUpload, ServerLogin, ServerLoginFinishParameters, ServerLoginStartParameters,
+    ServerRegistration, ServerSetup,
 };
 
 // The ciphersuite trait allows to specify the underlying primitives
@@ -41,6 +42,7 @@ impl CipherSuite for Default {
     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
     type Hash = sha2::Sha512;
     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+    const ID: u16 = 0x0001;
 }
 
 // Password-based registration between a client and server
@@ -56,7 +58,9 @@ fn account_registration(
 
     // Client sends registration_request_bytes to server
 
+    let mut server_rng = OsRng;
     let server_registration_start_result = ServerRegistration::<Default>::start(
+        &mut server_rng,
         &server_setup,
         RegistrationRequest::deserialize(&registration_request_bytes[..]).unwrap(),
         username.as_bytes(),
@@ -80,7 +84,9 @@ fn account_registration(
 
     let password_file = ServerRegistration::finish(
         RegistrationUpload::<Default>::deserialize(&message_bytes[..]).unwrap(),
-    );
+        server_registration_start_result.key_ids,
+    )
+    .unwrap();
     password_file.serialize()
 }
 
@@ -129,7 +135,10 @@ fn account_login(
 
     let server_login_finish_result = server_login_start_result
         .state
-        .finish(CredentialFinalization::deserialize(&credential_finalization_bytes[..]).unwrap())
+        .finish(
+            CredentialFinalization::deserialize(&credential_finalization_bytes[..]).unwrap(),
+            ServerLoginFinishParameters::default(),
+        )
         .unwrap();
 
     client_login_finish_result.session_key == server_login_finish_result.session_key
@@ -0,0 +1,269 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Accounts holding several password credentials
+
+use crate::{
+    ciphersuite::CipherSuite,
+    errors::{PakeError, ProtocolError},
+    opaque::ServerRegistration,
+    serialization::{serialize, tokenize, MAX_FIELD_LEN},
+};
+
+/// Returns the credential identifier under which the credential of the given
+/// alias of an account is registered and logged into. It is distinct for each
+/// alias, so that each credential has its own OPRF key
+pub fn credential_identifier(
+    account_identifier: &[u8],
+    alias: &[u8],
+) -> Result<Vec<u8>, ProtocolError> {
+    if account_identifier.len() > MAX_FIELD_LEN || alias.len() > MAX_FIELD_LEN {
+        return Err(PakeError::SerializationError.into());
+    }
+    Ok([serialize(account_identifier, 2), serialize(alias, 2)].concat())
+}
+
+/// The record of an account holding several independent password
+/// credentials, such as a primary password and break-glass passphrases, each
+/// of which is a [ServerRegistration] under its own alias.
+///
+/// Each credential is registered and logged into under the credential
+/// identifier returned by [credential_identifier] for its alias. A login with
+/// [ServerLogin::start_for_account](crate::ServerLogin::start_for_account)
+/// for an alias that the account does not hold proceeds against a dummy
+/// password file, as [ServerLogin::start](crate::ServerLogin::start) does for
+/// an unregistered client, so that the responses do not reveal how many
+/// credentials the account has. All of the credentials open the same client
+/// static keypair, which they are registered with in the external envelope
+/// mode, and a secret shared by all of them can be sealed as their
+/// credentials
+pub struct ServerAccount<CS: CipherSuite> {
+    password_files: Vec<(Vec<u8>, ServerRegistration<CS>)>,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for ServerAccount<CS> {
+    fn clone(&self) -> Self {
+        Self {
+            password_files: self.password_files.clone(),
+        }
+    }
+}
+
+// Cannot be derived because it would require for CS to be Default.
+impl<CS: CipherSuite> Default for ServerAccount<CS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<CS: CipherSuite> ServerAccount<CS> {
+    /// Creates an account without any credentials
+    pub fn new() -> Self {
+        Self {
+            password_files: Vec::new(),
+        }
+    }
+
+    /// Adds the password file of a credential under the given alias,
+    /// replacing the one already held under it if any. The password file
+    /// must have been registered with the same client static keypair as the
+    /// other credentials of the account, and is otherwise rejected with
+    /// [IncompatibleClientStaticPublicKeyError](PakeError::IncompatibleClientStaticPublicKeyError)
+    pub fn insert(
+        &mut self,
+        alias: &[u8],
+        password_file: ServerRegistration<CS>,
+    ) -> Result<(), ProtocolError> {
+        if alias.len() > MAX_FIELD_LEN || password_file.serialize().len() > MAX_FIELD_LEN {
+            return Err(PakeError::SerializationError.into());
+        }
+        if self.password_files.iter().any(|(other, other_file)| {
+            other[..] != alias[..]
+                && other_file.upload.client_s_pk != password_file.upload.client_s_pk
+        }) {
+            return Err(PakeError::IncompatibleClientStaticPublicKeyError.into());
+        }
+        match self.position(alias) {
+            Some(index) => self.password_files[index].1 = password_file,
+            None => self.password_files.push((alias.to_vec(), password_file)),
+        }
+        Ok(())
+    }
+
+    /// Removes the credential of the given alias, returning its password
+    /// file if the account held it
+    pub fn remove(&mut self, alias: &[u8]) -> Option<ServerRegistration<CS>> {
+        self.position(alias)
+            .map(|index| self.password_files.remove(index).1)
+    }
+
+    /// Returns the password file of the credential of the given alias, if the
+    /// account holds it
+    pub fn get(&self, alias: &[u8]) -> Option<&ServerRegistration<CS>> {
+        self.position(alias)
+            .map(|index| &self.password_files[index].1)
+    }
+
+    /// Returns the aliases of the credentials of the account, in the order
+    /// they were added in
+    pub fn aliases(&self) -> Vec<&[u8]> {
+        self.password_files
+            .iter()
+            .map(|(alias, _)| &alias[..])
+            .collect()
+    }
+
+    /// Returns the number of credentials of the account
+    pub fn len(&self) -> usize {
+        self.password_files.len()
+    }
+
+    /// Returns whether the account has no credentials
+    pub fn is_empty(&self) -> bool {
+        self.password_files.is_empty()
+    }
+
+    fn position(&self, alias: &[u8]) -> Option<usize> {
+        self.password_files
+            .iter()
+            .position(|(other, _)| other[..] == alias[..])
+    }
+
+    /// Serialization into bytes, as the sequence of the length-prefixed
+    /// aliases, each followed by its length-prefixed password file
+    pub fn serialize(&self) -> Vec<u8> {
+        self.password_files
+            .iter()
+            .flat_map(|(alias, password_file)| {
+                [
+                    serialize(alias, 2),
+                    serialize(&password_file.serialize(), 2),
+                ]
+                .concat()
+            })
+            .collect()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let mut account = Self::new();
+        let mut remainder = input.to_vec();
+        while !remainder.is_empty() {
+            let (alias, rest) = tokenize(&remainder, 2)?;
+            let (password_file_bytes, rest) = tokenize(&rest, 2)?;
+            if account.position(&alias).is_some() {
+                return Err(PakeError::SerializationError.into());
+            }
+            account.insert(
+                &alias,
+                ServerRegistration::deserialize(&password_file_bytes)?,
+            )?;
+            remainder = rest;
+        }
+        Ok(account)
+    }
+}
+
+impl_serialize_and_deserialize_for!(ServerAccount);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{key_exchange::tripledh::TripleDH, opaque::ServerSetup, slow_hash::NoOpHash};
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use rand::rngs::OsRng;
+
+    struct Default;
+    impl CipherSuite for Default {
+        type Group = RistrettoPoint;
+        type KeyExchange = TripleDH;
+        type Hash = sha2::Sha512;
+        type SlowHash = NoOpHash;
+        const ID: u16 = 0x0001;
+    }
+
+    #[test]
+    fn account_roundtrip() -> Result<(), ProtocolError> {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<Default>::new(&mut rng);
+        let primary = ServerRegistration::dummy(&mut rng, &server_setup);
+        let break_glass = ServerRegistration::dummy(&mut rng, &server_setup);
+
+        let mut account = ServerAccount::<Default>::new();
+        assert!(account.is_empty());
+        account.insert(b"primary", primary.clone())?;
+        account.insert(b"break-glass", primary)?;
+        account.insert(b"break-glass", break_glass.clone())?;
+        assert_eq!(account.len(), 2);
+        assert_eq!(
+            account.aliases(),
+            vec![&b"primary"[..], &b"break-glass"[..]]
+        );
+
+        let account = ServerAccount::<Default>::deserialize(&account.serialize())?;
+        assert_eq!(account.aliases().len(), 2);
+        assert_eq!(
+            account.get(b"break-glass").map(|file| file.serialize()),
+            Some(break_glass.serialize())
+        );
+        assert!(account.get(b"other").is_none());
+        assert_eq!(
+            ServerAccount::<Default>::deserialize(&[])?.serialize(),
+            Vec::<u8>::new()
+        );
+
+        // Duplicate aliases are rejected
+        let bytes = account.serialize();
+        let first_len = bytes.len()
+            - serialize(b"break-glass", 2).len()
+            - serialize(&break_glass.serialize(), 2).len();
+        assert!(ServerAccount::<Default>::deserialize(
+            &[&bytes[..first_len], &bytes[..first_len]].concat()
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn account_rejects_other_client_keypair() -> Result<(), ProtocolError> {
+        let mut rng = OsRng;
+        let server_setup = ServerSetup::<Default>::new(&mut rng);
+        let primary = ServerRegistration::dummy(&mut rng, &server_setup);
+        // The dummy password file of another server setup has another client
+        // public key
+        let other_setup = ServerSetup::<Default>::new(&mut rng);
+        let other = ServerRegistration::dummy(&mut rng, &other_setup);
+
+        let mut account = ServerAccount::<Default>::new();
+        account.insert(b"primary", primary)?;
+        assert!(matches!(
+            account.insert(b"break-glass", other.clone()),
+            Err(ProtocolError::VerificationError(
+                PakeError::IncompatibleClientStaticPublicKeyError
+            ))
+        ));
+        assert_eq!(account.len(), 1);
+
+        // The only credential of the account can be replaced
+        account.insert(b"primary", other)?;
+        assert_eq!(account.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn credential_identifiers_are_distinct() -> Result<(), ProtocolError> {
+        assert_ne!(
+            credential_identifier(b"alice", b"primary")?,
+            credential_identifier(b"alice", b"break-glass")?
+        );
+        assert_ne!(
+            credential_identifier(b"ab", b"c")?,
+            credential_identifier(b"a", b"bc")?
+        );
+        assert!(credential_identifier(&[0u8; 0x10000], b"").is_err());
+        Ok(())
+    }
+}
@@ -9,7 +9,6 @@ use crate::{
     hash::Hash, key_exchange::traits::KeyExchange, map_to_curve::GroupWithMapToCurve,
     slow_hash::SlowHash,
 };
-use digest::Digest;
 
 /// Configures the underlying primitives used in OPAQUE
 /// * `Group`: a finite cyclic group along with a point representation, along
@@ -19,16 +18,22 @@ use digest::Digest;
 /// * `KeyExchange`: The key exchange protocol to use in the login step
 /// * `Hash`: The main hashing function to use
 /// * `SlowHash`: A slow hashing function, typically used for password hashing
+/// * `ID`: An identifier for the ciphersuite, stored in password files
 pub trait CipherSuite {
     /// A finite cyclic group along with a point representation along with
     /// an extension trait PasswordToCurve that allows some customization on
     /// how to hash a password to a curve point. See `group::Group` and
     /// `map_to_curve::GroupWithMapToCurve`.
-    type Group: GroupWithMapToCurve<UniformBytesLen = <Self::Hash as Digest>::OutputSize>;
+    type Group: GroupWithMapToCurve;
     /// A key exchange protocol
     type KeyExchange: KeyExchange<Self::Hash, Self::Group>;
     /// The main hash function use (for HKDF computations and hashing transcripts)
     type Hash: Hash;
     /// A slow hashing function, typically used for password hashing
     type SlowHash: SlowHash<Self::Hash>;
+
+    /// Identifies the ciphersuite in serialized password files, so that
+    /// records created under a different ciphersuite are rejected. It must
+    /// differ between ciphersuites which differ in any of their components
+    const ID: u16;
 }
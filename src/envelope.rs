@@ -5,40 +5,63 @@
 
 use crate::{
     ciphersuite::CipherSuite,
-    errors::{utils::check_slice_size, InternalPakeError, PakeError, ProtocolError},
+    errors::{utils::check_slice_size_atleast, InternalPakeError, PakeError, ProtocolError},
     group::Group,
     hash::Hash,
-    keypair::{KeyPair, PrivateKey, PublicKey},
+    keypair::{KeyPair, PublicKey},
     map_to_curve::GroupWithMapToCurve,
     opaque::{bytestrings_from_identifiers, Identifiers},
+    serialization::{serialize, tokenize},
 };
 use digest::Digest;
 use generic_array::{typenum::Unsigned, GenericArray};
-use generic_bytes::SizedBytes;
 use hkdf::Hkdf;
 use hmac::{Hmac, Mac, NewMac};
 use rand::{CryptoRng, RngCore};
 use std::convert::TryFrom;
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
 
 // Constant string used as salt for HKDF computation
 const STR_AUTH_KEY: &[u8] = b"AuthKey";
 const STR_EXPORT_KEY: &[u8] = b"ExportKey";
 const STR_PRIVATE_KEY: &[u8] = b"PrivateKey";
+const STR_PAD: &[u8] = b"Pad";
+const STR_CREDENTIALS_PAD: &[u8] = b"CredentialsPad";
 const STR_OPAQUE_HASH_TO_SCALAR: &[u8] = b"OPAQUE-HashToScalar";
 
 const NONCE_LEN: usize = 32;
 
+// The first byte of the fields which may follow an envelope in a message,
+// which is neither a valid mode nor the first byte of the length of valid
+// credentials
+pub(crate) const END_OF_ENVELOPE: u8 = 0xff;
+
+/// The layout of the envelopes of a deployment, which the server and its
+/// clients must agree on. It fixes the length of the masked response in
+/// every [CredentialResponse](crate::CredentialResponse) sent by the server,
+/// so that this length reveals neither whether a client is registered nor
+/// which envelope mode it registered in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EnvelopeLayout {
+    /// Whether clients may register in the external envelope mode, in which
+    /// case the envelopes in the internal mode are padded to the length of
+    /// those in the external mode
+    pub external_mode: bool,
+    /// The length of the encrypted credentials which every envelope carries,
+    /// or zero if the envelopes carry no credentials
+    pub credentials_len: usize,
+}
+
 fn build_inner_envelope_internal<CS: CipherSuite>(
     random_pwd: &[u8],
     nonce: &[u8],
 ) -> Result<PublicKey, InternalPakeError> {
     let h = Hkdf::<CS::Hash>::new(None, random_pwd);
-    let mut keypair_seed = vec![0u8; <PrivateKey as SizedBytes>::Len::to_usize()];
+    let mut keypair_seed = vec![0u8; <CS::Group as Group>::ScalarLen::to_usize()];
     h.expand(&[nonce, STR_PRIVATE_KEY].concat(), &mut keypair_seed)
         .map_err(|_| InternalPakeError::HkdfError)?;
     let client_static_keypair =
-        KeyPair::<CS::Group>::from_private_key_slice(CS::Group::scalar_as_bytes(
+        KeyPair::from_private_key_slice_static::<CS>(&CS::Group::scalar_as_bytes(
             &CS::Group::hash_to_scalar::<CS::Hash>(&keypair_seed[..], STR_OPAQUE_HASH_TO_SCALAR)?,
         ))?;
 
@@ -50,22 +73,81 @@ fn recover_keys_internal<CS: CipherSuite>(
     nonce: &[u8],
 ) -> Result<KeyPair<CS::Group>, InternalPakeError> {
     let h = Hkdf::<CS::Hash>::new(None, random_pwd);
-    let mut keypair_seed = vec![0u8; <PrivateKey as SizedBytes>::Len::to_usize()];
+    let mut keypair_seed = vec![0u8; <CS::Group as Group>::ScalarLen::to_usize()];
     h.expand(&[nonce, STR_PRIVATE_KEY].concat(), &mut keypair_seed)
         .map_err(|_| InternalPakeError::HkdfError)?;
     let client_static_keypair =
-        KeyPair::<CS::Group>::from_private_key_slice(CS::Group::scalar_as_bytes(
+        KeyPair::from_private_key_slice_static::<CS>(&CS::Group::scalar_as_bytes(
             &CS::Group::hash_to_scalar::<CS::Hash>(&keypair_seed[..], STR_OPAQUE_HASH_TO_SCALAR)?,
         ))?;
 
     Ok(client_static_keypair)
 }
 
-#[derive(Clone, Copy, PartialEq, Zeroize)]
+fn build_inner_envelope_external<CS: CipherSuite>(
+    random_pwd: &[u8],
+    nonce: &[u8],
+    client_s_sk: &[u8],
+) -> Result<(PublicKey, Vec<u8>), InternalPakeError> {
+    let client_static_keypair = KeyPair::from_private_key_slice_static::<CS>(client_s_sk)?;
+    let pad = derive_pad::<CS>(random_pwd, nonce, STR_PAD, client_s_sk.len())?;
+    let encrypted_private_key = xor(&pad, client_static_keypair.private());
+
+    Ok((
+        client_static_keypair.public().clone(),
+        encrypted_private_key,
+    ))
+}
+
+fn recover_keys_external<CS: CipherSuite>(
+    random_pwd: &[u8],
+    nonce: &[u8],
+    encrypted_private_key: &[u8],
+) -> Result<KeyPair<CS::Group>, InternalPakeError> {
+    let pad = derive_pad::<CS>(random_pwd, nonce, STR_PAD, encrypted_private_key.len())?;
+    let mut client_s_sk = xor(&pad, encrypted_private_key);
+    // Decrypting with the wrong key yields an unrelated private key, which
+    // may not even be valid, so this must fail as an invalid MAC would
+    let client_static_keypair = KeyPair::from_private_key_slice_static::<CS>(&client_s_sk)
+        .map_err(|_| InternalPakeError::SealOpenHmacError);
+    client_s_sk.zeroize();
+
+    client_static_keypair
+}
+
+// The pad which the client's private key in the external mode, or its
+// credentials, are XORed with
+fn derive_pad<CS: CipherSuite>(
+    random_pwd: &[u8],
+    nonce: &[u8],
+    label: &[u8],
+    len: usize,
+) -> Result<Zeroizing<Vec<u8>>, InternalPakeError> {
+    let h = Hkdf::<CS::Hash>::new(None, random_pwd);
+    let mut pad = Zeroizing::new(vec![0u8; len]);
+    h.expand(&[nonce, label].concat(), &mut pad)
+        .map_err(|_| InternalPakeError::HkdfError)?;
+    Ok(pad)
+}
+
+#[derive(Clone, PartialEq, Zeroize)]
 #[zeroize(drop)]
 pub(crate) enum InnerEnvelopeMode {
     Zero = 0,
     Internal = 1,
+    External = 2,
+}
+
+impl InnerEnvelopeMode {
+    // The discriminant cannot be obtained with a cast, as the type implements
+    // Drop
+    fn to_u8(&self) -> u8 {
+        match self {
+            InnerEnvelopeMode::Zero => 0,
+            InnerEnvelopeMode::Internal => 1,
+            InnerEnvelopeMode::External => 2,
+        }
+    }
 }
 
 impl TryFrom<u8> for InnerEnvelopeMode {
@@ -73,6 +155,7 @@ impl TryFrom<u8> for InnerEnvelopeMode {
     fn try_from(x: u8) -> Result<Self, Self::Error> {
         match x {
             1 => Ok(InnerEnvelopeMode::Internal),
+            2 => Ok(InnerEnvelopeMode::External),
             _ => Err(PakeError::SerializationError),
         }
     }
@@ -88,9 +171,23 @@ impl TryFrom<u8> for InnerEnvelopeMode {
 /// The specification update has simplified this assumption by taking
 /// an XOR-based approach without compromising on security, and to avoid
 /// the confusion around the implementation of an RKR-secure encryption.
+///
+/// In the internal mode, the client's static keypair is derived from the
+/// password, and the envelope serializes to the nonce followed by the MAC, as
+/// in the specification. In the external mode, the client's static private
+/// key is encrypted into the envelope, whose serialization is then followed by
+/// the mode and the encrypted private key. The envelope can also carry the
+/// client's credentials, which are encrypted and authenticated along with the
+/// rest of the envelope, in which case the mode is always serialized, and
+/// followed by the length-prefixed encrypted credentials.
 pub(crate) struct Envelope<CS: CipherSuite> {
     mode: InnerEnvelopeMode,
     nonce: Vec<u8>,
+    // The client's static private key in the external mode, and empty
+    // otherwise
+    encrypted_private_key: Vec<u8>,
+    // The client's credentials, which are empty if it has none
+    encrypted_credentials: Vec<u8>,
     hmac: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
 }
 
@@ -98,8 +195,10 @@ pub(crate) struct Envelope<CS: CipherSuite> {
 impl<CS: CipherSuite> Clone for Envelope<CS> {
     fn clone(&self) -> Self {
         Self {
-            mode: self.mode,
+            mode: self.mode.clone(),
             nonce: self.nonce.clone(),
+            encrypted_private_key: self.encrypted_private_key.clone(),
+            encrypted_credentials: self.encrypted_credentials.clone(),
             hmac: self.hmac.clone(),
         }
     }
@@ -113,6 +212,7 @@ pub(crate) struct OpenedEnvelope<CS: CipherSuite> {
     pub(crate) export_key: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
     pub(crate) id_u: Vec<u8>,
     pub(crate) id_s: Vec<u8>,
+    pub(crate) credentials: Zeroizing<Vec<u8>>,
 }
 
 pub(crate) struct OpenedInnerEnvelope<D: Hash> {
@@ -128,16 +228,98 @@ impl<CS: CipherSuite> Envelope<CS> {
         <CS::Hash as Digest>::OutputSize::to_usize()
     }
 
+    // The length of an envelope in the internal mode, which envelopes in the
+    // other modes start with
     pub(crate) fn len() -> usize {
         <CS::Hash as Digest>::OutputSize::to_usize() + NONCE_LEN
     }
 
+    // The maximum length of the credentials, such that the masked response
+    // of an envelope in the external mode carrying them does not exceed the
+    // output length of HKDF
+    pub(crate) fn max_credentials_len() -> usize {
+        255 * <CS::Hash as Digest>::OutputSize::to_usize()
+            - <CS::Group as Group>::ElemLen::to_usize()
+            - Self::len()
+            - 1
+            - <CS::Group as Group>::ScalarLen::to_usize()
+            - 2
+    }
+
+    // The length of the envelopes of a deployment with the given layout,
+    // which a masked response always carries, padding shorter envelopes
+    pub(crate) fn padded_len(layout: &EnvelopeLayout) -> usize {
+        let mut len = Self::len();
+        if layout.external_mode || layout.credentials_len > 0 {
+            len += 1;
+        }
+        if layout.external_mode {
+            len += <CS::Group as Group>::ScalarLen::to_usize();
+        }
+        if layout.credentials_len > 0 {
+            len += 2 + layout.credentials_len;
+        }
+        len
+    }
+
+    // Whether the envelope has a mode allowed by the layout and carries
+    // credentials of the length it fixes
+    pub(crate) fn matches_layout(&self, layout: &EnvelopeLayout) -> bool {
+        (layout.external_mode || !self.is_external_mode())
+            && self.encrypted_credentials.len() == layout.credentials_len
+    }
+
+    pub(crate) fn is_external_mode(&self) -> bool {
+        self.mode == InnerEnvelopeMode::External
+    }
+
     pub(crate) fn serialize(&self) -> Vec<u8> {
-        [&self.nonce[..], &self.hmac[..]].concat()
+        [
+            &self.nonce[..],
+            &self.hmac[..],
+            &serialize_mode_contents(
+                &self.mode,
+                &self.encrypted_private_key,
+                &self.encrypted_credentials,
+            ),
+        ]
+        .concat()
+    }
+
+    // Serializes the envelope followed by its padding up to the length fixed
+    // by the layout, which consists of END_OF_ENVELOPE followed by zeros
+    pub(crate) fn serialize_padded(
+        &self,
+        layout: &EnvelopeLayout,
+    ) -> Result<Vec<u8>, ProtocolError> {
+        let mut output = self.serialize();
+        let padded_len = Self::padded_len(layout);
+        if output.len() > padded_len {
+            return Err(PakeError::SerializationError.into());
+        }
+        if output.len() < padded_len {
+            output.push(END_OF_ENVELOPE);
+            output.resize(padded_len, 0);
+        }
+        Ok(output)
     }
-    pub(crate) fn deserialize(bytes: &[u8]) -> Result<Self, ProtocolError> {
-        let mode = InnerEnvelopeMode::Internal; // Better way to hard-code this?
 
+    // Deserializes an envelope followed by the padding added by
+    // serialize_padded
+    pub(crate) fn deserialize_padded(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let (envelope, padding) = Self::deserialize_partial(bytes)?;
+        match padding.split_first() {
+            None => Ok(envelope),
+            Some((&END_OF_ENVELOPE, zeros)) if zeros.iter().all(|&b| b == 0) => Ok(envelope),
+            _ => Err(ProtocolError::VerificationError(
+                PakeError::SerializationError,
+            )),
+        }
+    }
+
+    // Deserializes an envelope which may be followed by other fields, starting
+    // with END_OF_ENVELOPE, and returns these fields along with it
+    pub(crate) fn deserialize_partial(bytes: &[u8]) -> Result<(Self, Vec<u8>), ProtocolError> {
         if bytes.len() < NONCE_LEN {
             return Err(ProtocolError::VerificationError(
                 PakeError::SerializationError,
@@ -145,28 +327,46 @@ impl<CS: CipherSuite> Envelope<CS> {
         }
         let nonce = bytes[..NONCE_LEN].to_vec();
 
-        let remainder = match mode {
-            InnerEnvelopeMode::Zero => {
-                return Err(InternalPakeError::IncompatibleEnvelopeModeError.into())
-            }
-            InnerEnvelopeMode::Internal => bytes[NONCE_LEN..].to_vec(),
-        };
-
         let hmac_key_size = Self::hmac_key_size();
-        let hmac = check_slice_size(&remainder, hmac_key_size, "hmac_key_size")?;
+        let remainder =
+            check_slice_size_atleast(&bytes[NONCE_LEN..], hmac_key_size, "hmac_key_size")?;
+        let hmac = &remainder[..hmac_key_size];
+
+        // An envelope in the internal mode without credentials ends with its
+        // MAC, while the others are followed by their mode and its contents
+        let contents = &remainder[hmac_key_size..];
+        let (mode, encrypted_private_key, encrypted_credentials, remainder) =
+            match contents.split_first() {
+                None | Some((&END_OF_ENVELOPE, _)) => (
+                    InnerEnvelopeMode::Internal,
+                    Vec::new(),
+                    Vec::new(),
+                    contents.to_vec(),
+                ),
+                Some((&mode, contents)) => deserialize_mode_contents::<CS>(mode, contents)
+                    .map_err(|_| ProtocolError::VerificationError(PakeError::SerializationError))?,
+            };
 
-        Ok(Self {
-            mode,
-            nonce,
-            hmac: GenericArray::clone_from_slice(hmac),
-        })
+        Ok((
+            Self {
+                mode,
+                nonce,
+                encrypted_private_key,
+                encrypted_credentials,
+                hmac: GenericArray::clone_from_slice(hmac),
+            },
+            remainder,
+        ))
     }
 
-    // Creates a dummy envelope object that serializes to the all-zeros byte string
+    // Creates a dummy envelope object whose nonce and MAC are all zeros, and
+    // which is padded to the length fixed by the layout when masked
     pub(crate) fn dummy() -> Self {
         Self {
             mode: InnerEnvelopeMode::Zero,
             nonce: vec![0u8; NONCE_LEN],
+            encrypted_private_key: Vec::new(),
+            encrypted_credentials: Vec::new(),
             hmac: GenericArray::clone_from_slice(&vec![
                 0u8;
                 <CS::Hash as Digest>::OutputSize::to_usize()
@@ -174,12 +374,21 @@ impl<CS: CipherSuite> Envelope<CS> {
         }
     }
 
+    /// Samples the nonce of a new envelope
+    pub(crate) fn generate_nonce<R: RngCore + CryptoRng>(rng: &mut R) -> Vec<u8> {
+        let mut nonce = vec![0u8; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+        nonce
+    }
+
     #[allow(clippy::type_complexity)]
-    pub(crate) fn seal<R: RngCore + CryptoRng>(
-        rng: &mut R,
+    pub(crate) fn seal(
+        nonce: &[u8],
         key: &[u8],
         server_s_pk: &[u8],
         optional_ids: Option<Identifiers>,
+        client_static_keypair: Option<&KeyPair<CS::Group>>,
+        credentials: &[u8],
     ) -> Result<
         (
             Self,
@@ -188,19 +397,37 @@ impl<CS: CipherSuite> Envelope<CS> {
         ),
         InternalPakeError,
     > {
-        let mut nonce = vec![0u8; NONCE_LEN];
-        rng.fill_bytes(&mut nonce);
-
-        let (mode, client_s_pk) = (
-            InnerEnvelopeMode::Internal,
-            build_inner_envelope_internal::<CS>(key, &nonce)?,
-        );
+        let (mode, client_s_pk, encrypted_private_key) = match client_static_keypair {
+            None => (
+                InnerEnvelopeMode::Internal,
+                build_inner_envelope_internal::<CS>(key, nonce)?,
+                Vec::new(),
+            ),
+            Some(keypair) => {
+                let (client_s_pk, encrypted_private_key) =
+                    build_inner_envelope_external::<CS>(key, nonce, keypair.private())?;
+                (
+                    InnerEnvelopeMode::External,
+                    client_s_pk,
+                    encrypted_private_key,
+                )
+            }
+        };
 
-        let (id_u, id_s) =
-            bytestrings_from_identifiers(&optional_ids, &client_s_pk.to_arr(), server_s_pk);
+        let (id_u, id_s) = bytestrings_from_identifiers(&optional_ids, &client_s_pk, server_s_pk);
         let aad = construct_aad(&id_u, &id_s, server_s_pk);
 
-        let (envelope, export_key) = Self::seal_raw(key, &nonce, &aad, mode)?;
+        let pad = derive_pad::<CS>(key, nonce, STR_CREDENTIALS_PAD, credentials.len())?;
+        let encrypted_credentials = xor(&pad, credentials);
+
+        let (envelope, export_key) = Self::seal_raw(
+            key,
+            nonce,
+            &encrypted_private_key,
+            &encrypted_credentials,
+            &aad,
+            mode,
+        )?;
         Ok((envelope, client_s_pk, export_key))
     }
 
@@ -210,6 +437,8 @@ impl<CS: CipherSuite> Envelope<CS> {
     pub(crate) fn seal_raw(
         key: &[u8],
         nonce: &[u8],
+        encrypted_private_key: &[u8],
+        encrypted_credentials: &[u8],
         aad: &[u8],
         mode: InnerEnvelopeMode,
     ) -> Result<(Self, GenericArray<u8, <CS::Hash as Digest>::OutputSize>), InternalPakeError> {
@@ -225,6 +454,11 @@ impl<CS: CipherSuite> Envelope<CS> {
         let mut hmac =
             Hmac::<CS::Hash>::new_varkey(&hmac_key).map_err(|_| InternalPakeError::HmacError)?;
         hmac.update(nonce);
+        hmac.update(&serialize_mode_contents(
+            &mode,
+            encrypted_private_key,
+            encrypted_credentials,
+        ));
         hmac.update(aad);
 
         let hmac_bytes = hmac.finalize().into_bytes();
@@ -233,6 +467,8 @@ impl<CS: CipherSuite> Envelope<CS> {
             Self {
                 mode,
                 nonce: nonce.to_vec(),
+                encrypted_private_key: encrypted_private_key.to_vec(),
+                encrypted_credentials: encrypted_credentials.to_vec(),
                 hmac: hmac_bytes,
             },
             GenericArray::clone_from_slice(&export_key),
@@ -250,22 +486,31 @@ impl<CS: CipherSuite> Envelope<CS> {
                 return Err(InternalPakeError::IncompatibleEnvelopeModeError)
             }
             InnerEnvelopeMode::Internal => recover_keys_internal::<CS>(key, &self.nonce)?,
+            InnerEnvelopeMode::External => {
+                recover_keys_external::<CS>(key, &self.nonce, &self.encrypted_private_key)?
+            }
         };
 
-        let (id_u, id_s) = bytestrings_from_identifiers(
-            optional_ids,
-            &client_static_keypair.public().to_arr(),
-            server_s_pk,
-        );
+        let (id_u, id_s) =
+            bytestrings_from_identifiers(optional_ids, client_static_keypair.public(), server_s_pk);
         let aad = construct_aad(&id_u, &id_s, server_s_pk);
 
         let opened = self.open_raw(key, &aad)?;
 
+        let pad = derive_pad::<CS>(
+            key,
+            &self.nonce,
+            STR_CREDENTIALS_PAD,
+            self.encrypted_credentials.len(),
+        )?;
+        let credentials = Zeroizing::new(xor(&pad, &self.encrypted_credentials));
+
         Ok(OpenedEnvelope {
             client_static_keypair,
             export_key: opened.export_key,
             id_u,
             id_s,
+            credentials,
         })
     }
 
@@ -288,6 +533,11 @@ impl<CS: CipherSuite> Envelope<CS> {
         let mut hmac =
             Hmac::<CS::Hash>::new_varkey(&hmac_key).map_err(|_| InternalPakeError::HmacError)?;
         hmac.update(&self.nonce);
+        hmac.update(&serialize_mode_contents(
+            &self.mode,
+            &self.encrypted_private_key,
+            &self.encrypted_credentials,
+        ));
         hmac.update(aad);
         if hmac.verify(&self.hmac).is_err() {
             return Err(InternalPakeError::SealOpenHmacError);
@@ -302,7 +552,17 @@ impl<CS: CipherSuite> Envelope<CS> {
 
     #[cfg(test)]
     pub fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
-        vec![(self.hmac.as_ptr(), self.hmac.len())]
+        vec![
+            (
+                self.encrypted_private_key.as_ptr(),
+                self.encrypted_private_key.len(),
+            ),
+            (
+                self.encrypted_credentials.as_ptr(),
+                self.encrypted_credentials.len(),
+            ),
+            (self.hmac.as_ptr(), self.hmac.len()),
+        ]
     }
 }
 
@@ -311,6 +571,8 @@ impl<CS: CipherSuite> Zeroize for Envelope<CS> {
     fn zeroize(&mut self) {
         self.mode.zeroize();
         self.nonce.zeroize();
+        self.encrypted_private_key.zeroize();
+        self.encrypted_credentials.zeroize();
         self.hmac.zeroize();
     }
 }
@@ -326,3 +588,67 @@ impl<CS: CipherSuite> Drop for Envelope<CS> {
 fn construct_aad(id_u: &[u8], id_s: &[u8], server_s_pk: &[u8]) -> Vec<u8> {
     [server_s_pk, id_s, id_u].concat()
 }
+
+// The part of an envelope following its MAC, which is empty in the internal
+// mode without credentials, and otherwise consists of the mode, the encrypted
+// private key in the external mode, and the length-prefixed encrypted
+// credentials if there are any
+fn serialize_mode_contents(
+    mode: &InnerEnvelopeMode,
+    encrypted_private_key: &[u8],
+    encrypted_credentials: &[u8],
+) -> Vec<u8> {
+    if *mode != InnerEnvelopeMode::External && encrypted_credentials.is_empty() {
+        return Vec::new();
+    }
+    let mut output = vec![mode.to_u8()];
+    output.extend_from_slice(encrypted_private_key);
+    if !encrypted_credentials.is_empty() {
+        output.extend_from_slice(&serialize(encrypted_credentials, 2));
+    }
+    output
+}
+
+#[allow(clippy::type_complexity)]
+fn deserialize_mode_contents<CS: CipherSuite>(
+    mode: u8,
+    contents: &[u8],
+) -> Result<(InnerEnvelopeMode, Vec<u8>, Vec<u8>, Vec<u8>), PakeError> {
+    let mode = InnerEnvelopeMode::try_from(mode)?;
+    let private_key_len = match mode {
+        InnerEnvelopeMode::External => <CS::Group as Group>::ScalarLen::to_usize(),
+        _ => 0,
+    };
+    if contents.len() < private_key_len {
+        return Err(PakeError::SerializationError);
+    }
+    let (encrypted_private_key, remainder) = contents.split_at(private_key_len);
+
+    let (encrypted_credentials, remainder) = match remainder.first() {
+        None | Some(&END_OF_ENVELOPE) => (Vec::new(), remainder.to_vec()),
+        Some(_) => {
+            let (value, remainder) = tokenize(remainder, 2)?;
+            // Empty credentials are never serialized
+            if value.is_empty() || matches!(remainder.first(), Some(&b) if b != END_OF_ENVELOPE) {
+                return Err(PakeError::SerializationError);
+            }
+            (value, remainder)
+        }
+    };
+    // Neither is the mode of an envelope in the internal mode without
+    // credentials
+    if mode == InnerEnvelopeMode::Internal && encrypted_credentials.is_empty() {
+        return Err(PakeError::SerializationError);
+    }
+
+    Ok((
+        mode,
+        encrypted_private_key.to_vec(),
+        encrypted_credentials,
+        remainder,
+    ))
+}
+
+fn xor(x: &[u8], y: &[u8]) -> Vec<u8> {
+    x.iter().zip(y.iter()).map(|(&x1, &x2)| x1 ^ x2).collect()
+}
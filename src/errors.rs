@@ -29,6 +29,9 @@ pub enum InternalPakeError {
     HashingFailure,
     /// Computing the hash-to-curve function failed
     HashToCurveError,
+    /// The hash function does not match the one pinned by the hash-to-curve
+    /// suite of the group
+    HashToCurveSuiteMismatchError,
     /// Computing HKDF failed while deriving subkeys
     HkdfError,
     /// Computing HMAC failed while supplying a secret key
@@ -53,6 +56,19 @@ pub enum InternalPakeError {
     /// This error occurs when the envelope is opened and deserialization
     /// fails
     UnexpectedEnvelopeContentsError,
+    /// This error occurs when the threshold of an OPRF key sharing is zero
+    /// or larger than the number of shares, or when no partial evaluations
+    /// are provided
+    /// Invalid threshold for the OPRF key sharing.
+    InvalidThresholdError,
+    /// This error occurs when partial evaluations of the OPRF are combined
+    /// while coming from the same share
+    /// Partial evaluations must come from distinct shares.
+    DuplicateShareIndexError,
+    /// This error occurs when an encapsulation or decapsulation key of a KEM
+    /// is malformed
+    /// Invalid KEM key.
+    KemKeyError,
 }
 
 /// Represents an error in password checking
@@ -67,8 +83,14 @@ pub enum PakeError {
     IncompleteKeysError,
     /// The provided server public key doesn't match the sealed one
     IncompatibleServerStaticPublicKeyError,
+    /// The client public key of the new password file doesn't match the one
+    /// authenticated in the login
+    IncompatibleClientStaticPublicKeyError,
     /// Error in key exchange protocol when attempting to validate MACs
     KeyExchangeMacValidationError,
+    /// Error in validating the proof that the server evaluated the OPRF with
+    /// its public OPRF key
+    InvalidOprfProofError,
     /// Error in validating credentials
     InvalidLoginError,
     /// Error with serializing / deserializing protocol messages
@@ -103,6 +125,47 @@ pub enum ProtocolError {
     /// This error occurs when the client request cannot be handled
     /// Client request cannot be handled.
     ClientError,
+    /// This error occurs when a password file was created with a different
+    /// ciphersuite than the one it is deserialized with
+    ///
+    /// Password file was created with ciphersuite {actual}, but {expected} was expected
+    CipherSuiteMismatchError {
+        /// expected
+        expected: u16,
+        /// actual
+        actual: u16,
+    },
+    /// This error occurs when a password file refers to an OPRF seed which
+    /// the server setup does not hold
+    ///
+    /// No OPRF seed with key ID {0}
+    UnknownOprfKeyIdError(u32),
+    /// This error occurs when a password file refers to a server keypair
+    /// which the server setup does not hold
+    ///
+    /// No server keypair with key ID {0}
+    UnknownServerKeyIdError(u32),
+    /// This error occurs when a registration upload carries an envelope whose
+    /// mode or length of credentials does not match the envelope layout of
+    /// the server
+    EnvelopeLayoutMismatchError,
+    /// This error occurs when a password change was not started under the
+    /// credential identifier and the OPRF key ID of the password file it
+    /// replaces, or does not keep its envelope mode
+    PasswordChangeMismatchError,
+    /// This error occurs when an authenticated registration was not started
+    /// under the credential identifier of the login it is authenticated by
+    CredentialIdentifierMismatchError,
+    /// This error occurs when the proof of a partial evaluation of the OPRF
+    /// does not verify against the public share of the same index, or there
+    /// is no such public share
+    ///
+    /// Invalid partial evaluation from the share with index {0}
+    InvalidPartialEvaluationError(u16),
+    /// This error occurs when the threshold mode is used with a server setup
+    /// in the verifiable mode of the OPRF, since the proof sent to the client
+    /// requires the OPRF key
+    VerifiableThresholdError,
 }
 
 // This is meant to express future(ly) non-trivial ways of converting the
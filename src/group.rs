@@ -14,12 +14,24 @@ use curve25519_dalek::{
     scalar::Scalar,
     traits::Identity,
 };
-use generic_array::{
-    typenum::{U32, U64},
-    ArrayLength, GenericArray,
-};
+use generic_array::{typenum::U32, ArrayLength, GenericArray};
 use std::convert::TryInto;
 
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+use elliptic_curve::{
+    group::{
+        ff::{Field, PrimeField},
+        GroupEncoding,
+    },
+    ops::Reduce,
+};
+#[cfg(feature = "p256")]
+use generic_array::typenum::U33;
+#[cfg(feature = "p384")]
+use generic_array::typenum::{U48, U49};
+#[cfg(feature = "p521")]
+use generic_array::typenum::{U66, U67};
+
 use rand::{CryptoRng, RngCore};
 use std::ops::Mul;
 use zeroize::Zeroize;
@@ -38,9 +50,17 @@ pub trait Group: Copy + Sized + for<'a> Mul<&'a <Self as Group>::Scalar, Output
     /// picks a scalar at random
     fn random_nonzero_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Self::Scalar;
     /// Serializes a scalar to bytes
-    fn scalar_as_bytes(scalar: &Self::Scalar) -> &GenericArray<u8, Self::ScalarLen>;
+    fn scalar_as_bytes(scalar: &Self::Scalar) -> GenericArray<u8, Self::ScalarLen>;
     /// The multiplicative inverse of this scalar
     fn scalar_invert(scalar: &Self::Scalar) -> Self::Scalar;
+    /// Returns the scalar corresponding to an integer
+    fn scalar_from_u64(value: u64) -> Self::Scalar;
+    /// The sum of two scalars
+    fn scalar_add(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar;
+    /// The difference of two scalars
+    fn scalar_sub(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar;
+    /// The product of two scalars
+    fn scalar_mul(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar;
 
     /// The byte length necessary to represent group elements
     type ElemLen: ArrayLength<u8>;
@@ -51,21 +71,15 @@ pub trait Group: Copy + Sized + for<'a> Mul<&'a <Self as Group>::Scalar, Output
     /// Serializes the `self` group element
     fn to_arr(&self) -> GenericArray<u8, Self::ElemLen>;
 
-    /// Hashes points presumed to be uniformly random to the curve. The
-    /// impl is allowed to perform additional hashes if it needs to, but this
-    /// may not be necessary as this function is going to be called with the
-    /// output of a kdf.
-    type UniformBytesLen: ArrayLength<u8>;
-
-    /// Hashes a slice of pseudo-random bytes of the correct length to a curve point
-    fn hash_to_curve(uniform_bytes: &GenericArray<u8, Self::UniformBytesLen>) -> Self;
-
     /// Get the base point for the group
     fn base_point() -> Self;
 
     /// Multiply the point by a scalar, represented as a slice
     fn mult_by_slice(&self, scalar: &GenericArray<u8, Self::ScalarLen>) -> Self;
 
+    /// The sum of two group elements
+    fn add_element(&self, other: &Self) -> Self;
+
     /// Returns if the group element is equal to the identity (1)
     fn is_identity(&self) -> bool;
 }
@@ -105,12 +119,24 @@ impl Group for RistrettoPoint {
             }
         }
     }
-    fn scalar_as_bytes(scalar: &Self::Scalar) -> &GenericArray<u8, Self::ScalarLen> {
-        GenericArray::from_slice(scalar.as_bytes())
+    fn scalar_as_bytes(scalar: &Self::Scalar) -> GenericArray<u8, Self::ScalarLen> {
+        *GenericArray::from_slice(scalar.as_bytes())
     }
     fn scalar_invert(scalar: &Self::Scalar) -> Self::Scalar {
         scalar.invert()
     }
+    fn scalar_from_u64(value: u64) -> Self::Scalar {
+        Scalar::from(value)
+    }
+    fn scalar_add(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar {
+        a + b
+    }
+    fn scalar_sub(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar {
+        a - b
+    }
+    fn scalar_mul(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar {
+        a * b
+    }
 
     // The byte length necessary to represent group elements
     type ElemLen = U32;
@@ -127,17 +153,6 @@ impl Group for RistrettoPoint {
         *GenericArray::from_slice(c.as_bytes())
     }
 
-    type UniformBytesLen = U64;
-    fn hash_to_curve(uniform_bytes: &GenericArray<u8, Self::UniformBytesLen>) -> Self {
-        // https://caniuse.rs/features/array_gt_32_impls
-        let bits: [u8; 64] = {
-            let mut bytes = [0u8; 64];
-            bytes.copy_from_slice(uniform_bytes);
-            bytes
-        };
-        RistrettoPoint::from_uniform_bytes(&bits)
-    }
-
     fn base_point() -> Self {
         RISTRETTO_BASEPOINT_POINT
     }
@@ -147,8 +162,102 @@ impl Group for RistrettoPoint {
         self * Scalar::from_bits(arr)
     }
 
+    fn add_element(&self, other: &Self) -> Self {
+        self + other
+    }
+
     /// Returns if the group element is equal to the identity (1)
     fn is_identity(&self) -> bool {
         self == &Self::identity()
     }
 }
+
+// The NIST curves share their arithmetic API through the `primeorder` crate,
+// so their implementations only differ in the lengths and types involved
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+macro_rules! impl_group_for_nist_curve {
+    ($krate:ident, $name:literal, $scalar_len:ty, $elem_len:ty, $uint:ty, $top_byte_mask:literal) => {
+        #[doc = concat!("The implementation of such a subgroup for NIST ", $name)]
+        impl Group for $krate::ProjectivePoint {
+            type Scalar = $krate::Scalar;
+            type ScalarLen = $scalar_len;
+            fn from_scalar_slice(
+                scalar_bits: &GenericArray<u8, Self::ScalarLen>,
+            ) -> Result<Self::Scalar, InternalPakeError> {
+                Option::from($krate::Scalar::from_repr(scalar_bits.clone()))
+                    .ok_or(InternalPakeError::InvalidByteSequence)
+            }
+            fn random_nonzero_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Self::Scalar {
+                // Rejection sampling keeps the conversion from the rng output to a
+                // scalar exact, which the tests rely on
+                loop {
+                    let mut scalar_bytes = GenericArray::<u8, Self::ScalarLen>::default();
+                    rng.fill_bytes(&mut scalar_bytes);
+                    scalar_bytes[0] &= $top_byte_mask;
+                    let scalar: Option<Self::Scalar> =
+                        $krate::Scalar::from_repr(scalar_bytes).into();
+
+                    match scalar {
+                        Some(scalar) if !bool::from(Field::is_zero(&scalar)) => break scalar,
+                        _ => continue,
+                    }
+                }
+            }
+            fn scalar_as_bytes(scalar: &Self::Scalar) -> GenericArray<u8, Self::ScalarLen> {
+                scalar.to_repr()
+            }
+            fn scalar_invert(scalar: &Self::Scalar) -> Self::Scalar {
+                Option::from(scalar.invert()).unwrap_or($krate::Scalar::ZERO)
+            }
+            fn scalar_from_u64(value: u64) -> Self::Scalar {
+                $krate::Scalar::from(value)
+            }
+            fn scalar_add(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar {
+                a + b
+            }
+            fn scalar_sub(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar {
+                a - b
+            }
+            fn scalar_mul(a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar {
+                a * b
+            }
+
+            // SEC1 compressed encoding of a group element
+            type ElemLen = $elem_len;
+            fn from_element_slice(
+                element_bits: &GenericArray<u8, Self::ElemLen>,
+            ) -> Result<Self, InternalPakeError> {
+                Option::from(<Self as GroupEncoding>::from_bytes(element_bits))
+                    .ok_or(InternalPakeError::PointError)
+            }
+            // serialization of a group element
+            fn to_arr(&self) -> GenericArray<u8, Self::ElemLen> {
+                <Self as GroupEncoding>::to_bytes(self)
+            }
+
+            fn base_point() -> Self {
+                $krate::ProjectivePoint::GENERATOR
+            }
+
+            fn mult_by_slice(&self, scalar: &GenericArray<u8, Self::ScalarLen>) -> Self {
+                self * &<$krate::Scalar as Reduce<$uint>>::reduce_bytes(scalar)
+            }
+
+            fn add_element(&self, other: &Self) -> Self {
+                self + other
+            }
+
+            /// Returns if the group element is equal to the identity (1)
+            fn is_identity(&self) -> bool {
+                self == &$krate::ProjectivePoint::IDENTITY
+            }
+        }
+    };
+}
+
+#[cfg(feature = "p256")]
+impl_group_for_nist_curve!(p256_, "P-256", U32, U33, p256_::U256, 0xff);
+#[cfg(feature = "p384")]
+impl_group_for_nist_curve!(p384_, "P-384", U48, U49, p384_::U384, 0xff);
+#[cfg(feature = "p521")]
+impl_group_for_nist_curve!(p521_, "P-521", U66, U67, p521_::U576, 0x01);
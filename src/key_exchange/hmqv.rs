@@ -0,0 +1,383 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! An implementation of the HMQV key exchange protocol
+//!
+//! Instead of the three Diffie-Hellman computations of [TripleDH], each party
+//! combines its static and ephemeral keys, as well as those of its peer, into a
+//! single scalar multiplication. The resulting shared secret is then expanded
+//! into the session key and the MAC keys in the same way as for the triple
+//! Diffie-Hellman key exchange, whose first message is reused as is.
+#[cfg(test)]
+use crate::key_exchange::traits::AsBytePointers;
+use crate::{
+    ciphersuite::CipherSuite,
+    errors::{
+        utils::{check_slice_size, check_slice_size_atleast},
+        InternalPakeError, PakeError, ProtocolError,
+    },
+    group::Group,
+    hash::Hash,
+    key_exchange::{
+        traits::{client_mac, FromBytes, Ke2Inputs, Ke3Inputs, KeyExchange, ToBytes},
+        tripledh::{derive_keys, generate_nonce, Ke1Message, Ke1State, NonceLen, TripleDH},
+    },
+    keypair::{KeyPair, PrivateKey, PublicKey},
+    serialization::serialize,
+};
+use digest::{Digest, FixedOutput};
+use generic_array::{typenum::Unsigned, ArrayLength, GenericArray};
+use hmac::{Hmac, Mac, NewMac};
+use rand::{CryptoRng, RngCore};
+use std::convert::TryInto;
+use zeroize::Zeroize;
+
+static STR_HMQV_CLIENT_EXPONENT: &[u8] = b"OPAQUE-HMQV-ClientExponent";
+static STR_HMQV_SERVER_EXPONENT: &[u8] = b"OPAQUE-HMQV-ServerExponent";
+
+#[allow(clippy::upper_case_acronyms)]
+/// The HMQV key exchange implementation
+pub struct HMQV;
+
+impl<D: Hash, G: Group> KeyExchange<D, G> for HMQV {
+    type KE1State = Ke1State;
+    type KE2State = Ke2State<<D as FixedOutput>::OutputSize>;
+    type KE1Message = Ke1Message;
+    type KE2Message = Ke2Message<<D as FixedOutput>::OutputSize>;
+    type KE3Message = Ke3Message<<D as FixedOutput>::OutputSize>;
+
+    fn generate_ke1<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> Result<(Self::KE1State, Self::KE1Message), ProtocolError> {
+        // The first message only carries an ephemeral key and a nonce, and is
+        // therefore the same as in the triple Diffie-Hellman key exchange
+        <TripleDH as KeyExchange<D, G>>::generate_ke1(rng)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_ke2<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ke1_message: Self::KE1Message,
+        inputs: Ke2Inputs<'_>,
+    ) -> Result<(Self::KE2State, Self::KE2Message), ProtocolError> {
+        let server_e_kp = KeyPair::<G>::generate_random(rng);
+        let server_nonce = generate_nonce::<R>(rng);
+
+        let mut transcript_hasher = inputs
+            .transcript
+            .hasher::<D>()
+            .chain(&server_nonce[..])
+            .chain(&server_e_kp.public()[..]);
+
+        let client_exponent = hmqv_exponent::<D, G>(
+            STR_HMQV_CLIENT_EXPONENT,
+            &ke1_message.client_e_pk,
+            inputs.transcript.id_s,
+        );
+        let server_exponent = hmqv_exponent::<D, G>(
+            STR_HMQV_SERVER_EXPONENT,
+            server_e_kp.public(),
+            inputs.transcript.id_u,
+        );
+        let (session_key, km2, km3) = derive_keys::<D>(
+            &hmqv_shared_secret::<G>(HMQVComponents {
+                peer_e_pk: ke1_message.client_e_pk,
+                peer_s_pk: inputs.client_s_pk.clone(),
+                peer_exponent: client_exponent,
+                e_sk: server_e_kp.private().clone(),
+                s_sk: inputs.server_s_sk.clone(),
+                exponent: server_exponent,
+            })?,
+            &transcript_hasher.clone().finalize(),
+        )?;
+
+        let mut mac_hasher =
+            Hmac::<D>::new_varkey(&km2).map_err(|_| InternalPakeError::HmacError)?;
+        mac_hasher.update(&transcript_hasher.clone().finalize());
+        let mac = mac_hasher.finalize().into_bytes();
+
+        transcript_hasher.update(&mac);
+
+        Ok((
+            Ke2State {
+                km3,
+                hashed_transcript: transcript_hasher.finalize(),
+                session_key,
+            },
+            Ke2Message {
+                server_nonce,
+                server_e_pk: server_e_kp.public().clone(),
+                mac,
+            },
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_ke3(
+        ke2_message: Self::KE2Message,
+        ke1_state: &Self::KE1State,
+        inputs: Ke3Inputs<'_>,
+    ) -> Result<(Vec<u8>, Self::KE3Message), ProtocolError> {
+        let mut transcript_hasher = inputs
+            .transcript
+            .hasher::<D>()
+            .chain(&ke2_message.server_nonce[..])
+            .chain(&ke2_message.server_e_pk[..]);
+
+        let client_exponent = hmqv_exponent::<D, G>(
+            STR_HMQV_CLIENT_EXPONENT,
+            &KeyPair::<G>::public_from_private(&ke1_state.client_e_sk),
+            inputs.transcript.id_s,
+        );
+        let server_exponent = hmqv_exponent::<D, G>(
+            STR_HMQV_SERVER_EXPONENT,
+            &ke2_message.server_e_pk,
+            inputs.transcript.id_u,
+        );
+        let (session_key, km2, km3) = derive_keys::<D>(
+            &hmqv_shared_secret::<G>(HMQVComponents {
+                peer_e_pk: ke2_message.server_e_pk.clone(),
+                peer_s_pk: inputs.server_s_pk.clone(),
+                peer_exponent: server_exponent,
+                e_sk: ke1_state.client_e_sk.clone(),
+                s_sk: inputs.client_s_sk.clone(),
+                exponent: client_exponent,
+            })?,
+            &transcript_hasher.clone().finalize(),
+        )?;
+
+        let mut server_mac =
+            Hmac::<D>::new_varkey(&km2).map_err(|_| InternalPakeError::HmacError)?;
+        server_mac.update(&transcript_hasher.clone().finalize());
+
+        if server_mac.verify(&ke2_message.mac).is_err() {
+            return Err(ProtocolError::VerificationError(
+                PakeError::KeyExchangeMacValidationError,
+            ));
+        }
+
+        transcript_hasher.update(&ke2_message.mac);
+
+        let client_payload = inputs.client_payload.serialize::<D>(&session_key)?;
+        let client_mac = client_mac::<D>(&km3, &transcript_hasher.finalize(), &client_payload)?;
+
+        Ok((
+            session_key.to_vec(),
+            Ke3Message {
+                mac: client_mac.finalize().into_bytes(),
+            },
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn finish_ke(
+        ke3_message: Self::KE3Message,
+        ke2_state: &Self::KE2State,
+        client_payload: &[u8],
+    ) -> Result<Vec<u8>, ProtocolError> {
+        let client_mac =
+            client_mac::<D>(&ke2_state.km3, &ke2_state.hashed_transcript, client_payload)?;
+
+        if client_mac.verify(&ke3_message.mac).is_err() {
+            return Err(ProtocolError::VerificationError(
+                PakeError::KeyExchangeMacValidationError,
+            ));
+        }
+
+        Ok(ke2_state.session_key.to_vec())
+    }
+
+    fn ke2_message_size() -> usize {
+        NonceLen::to_usize()
+            + G::ElemLen::to_usize()
+            + <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
+    }
+
+    fn ke3_message_size() -> usize {
+        <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
+    }
+}
+
+/// The server state produced after the second key exchange message
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
+pub struct Ke2State<HashLen: ArrayLength<u8>> {
+    km3: GenericArray<u8, HashLen>,
+    hashed_transcript: GenericArray<u8, HashLen>,
+    session_key: GenericArray<u8, HashLen>,
+}
+
+impl<HashLen: ArrayLength<u8>> ToBytes for Ke2State<HashLen> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.km3[..],
+            &self.hashed_transcript[..],
+            &self.session_key[..],
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+impl<HashLen: ArrayLength<u8>> AsBytePointers for Ke2State<HashLen> {
+    fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
+        vec![
+            (self.km3.as_ptr(), HashLen::to_usize()),
+            (self.hashed_transcript.as_ptr(), HashLen::to_usize()),
+            (self.session_key.as_ptr(), HashLen::to_usize()),
+        ]
+    }
+}
+
+impl<HashLen: ArrayLength<u8>> FromBytes for Ke2State<HashLen> {
+    fn from_bytes<CS: CipherSuite>(input: &[u8]) -> Result<Self, PakeError> {
+        let hash_len = HashLen::to_usize();
+        let checked_bytes = check_slice_size(input, 3 * hash_len, "ke2_state")?;
+
+        Ok(Self {
+            km3: GenericArray::clone_from_slice(&checked_bytes[..hash_len]),
+            hashed_transcript: GenericArray::clone_from_slice(
+                &checked_bytes[hash_len..2 * hash_len],
+            ),
+            session_key: GenericArray::clone_from_slice(&checked_bytes[2 * hash_len..]),
+        })
+    }
+}
+
+/// The second key exchange message
+#[derive(Clone)]
+pub struct Ke2Message<HashLen: ArrayLength<u8>> {
+    server_nonce: GenericArray<u8, NonceLen>,
+    server_e_pk: PublicKey,
+    mac: GenericArray<u8, HashLen>,
+}
+
+impl<HashLen: ArrayLength<u8>> ToBytes for Ke2Message<HashLen> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [&self.server_nonce[..], &self.server_e_pk[..], &self.mac[..]].concat()
+    }
+}
+
+impl<HashLen: ArrayLength<u8>> FromBytes for Ke2Message<HashLen> {
+    fn from_bytes<CS: CipherSuite>(input: &[u8]) -> Result<Self, PakeError> {
+        let key_len = <CS::Group as Group>::ElemLen::to_usize();
+        let nonce_len = NonceLen::to_usize();
+        let checked_nonce = check_slice_size_atleast(input, nonce_len, "ke2_message nonce")?;
+
+        let unchecked_server_e_pk = check_slice_size_atleast(
+            &checked_nonce[nonce_len..],
+            key_len,
+            "ke2_message server_e_pk",
+        )?;
+        let checked_mac = check_slice_size(
+            &unchecked_server_e_pk[key_len..],
+            HashLen::to_usize(),
+            "ke2_message mac",
+        )?;
+
+        // Check the public key bytes
+        let server_e_pk = KeyPair::<CS::Group>::check_public_key(PublicKey::from_bytes(
+            &unchecked_server_e_pk[..key_len],
+        ))?;
+
+        Ok(Self {
+            server_nonce: GenericArray::clone_from_slice(&checked_nonce[..nonce_len]),
+            server_e_pk,
+            mac: GenericArray::clone_from_slice(checked_mac),
+        })
+    }
+}
+
+/// The third key exchange message
+#[derive(Clone)]
+pub struct Ke3Message<HashLen: ArrayLength<u8>> {
+    mac: GenericArray<u8, HashLen>,
+}
+
+impl<HashLen: ArrayLength<u8>> ToBytes for Ke3Message<HashLen> {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.mac.to_vec()
+    }
+}
+
+impl<HashLen: ArrayLength<u8>> FromBytes for Ke3Message<HashLen> {
+    fn from_bytes<CS: CipherSuite>(bytes: &[u8]) -> Result<Self, PakeError> {
+        let checked_bytes = check_slice_size(bytes, HashLen::to_usize(), "ke3_message")?;
+
+        Ok(Self {
+            mac: GenericArray::clone_from_slice(checked_bytes),
+        })
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+// The keys of a party and of its peer used in the HMQV computation, along with
+// the exponents which combine their static and ephemeral keys
+struct HMQVComponents<G: Group> {
+    peer_e_pk: PublicKey,
+    peer_s_pk: PublicKey,
+    peer_exponent: G::Scalar,
+    e_sk: PrivateKey,
+    s_sk: PrivateKey,
+    exponent: G::Scalar,
+}
+
+// Helper functions
+
+// Internal function which computes the HMQV shared secret
+// (peer_e_pk + peer_exponent * peer_s_pk) * (e_sk + exponent * s_sk)
+fn hmqv_shared_secret<G: Group>(
+    mut components: HMQVComponents<G>,
+) -> Result<Vec<u8>, ProtocolError> {
+    let peer_e_pk = element_from_public_key::<G>(&components.peer_e_pk)?;
+    let peer_s_pk = element_from_public_key::<G>(&components.peer_s_pk)?;
+    let mut e_sk = scalar_from_private_key::<G>(&components.e_sk)?;
+    let mut s_sk = scalar_from_private_key::<G>(&components.s_sk)?;
+
+    let mut combined_sk = G::scalar_add(&e_sk, &G::scalar_mul(&components.exponent, &s_sk));
+    let shared_secret =
+        peer_e_pk.add_element(&(peer_s_pk * &components.peer_exponent)) * &combined_sk;
+
+    e_sk.zeroize();
+    s_sk.zeroize();
+    combined_sk.zeroize();
+    components.exponent.zeroize();
+
+    if shared_secret.is_identity() {
+        return Err(PakeError::IdentityGroupElementError.into());
+    }
+
+    Ok(shared_secret.to_arr().to_vec())
+}
+
+// Computes the 128-bit exponent H(label || public_key || identity) which
+// combines the static key of a party with its ephemeral key
+fn hmqv_exponent<D: Hash, G: Group>(label: &[u8], public_key: &[u8], identity: &[u8]) -> G::Scalar {
+    let hash = D::new()
+        .chain(label)
+        .chain(serialize(public_key, 2))
+        .chain(serialize(identity, 2))
+        .finalize();
+
+    // The exponent is built through scalar arithmetic, so that it does not
+    // depend on the byte order of the scalars of the group
+    let high = G::scalar_from_u64(u64::from_be_bytes(hash[..8].try_into().unwrap()));
+    let low = G::scalar_from_u64(u64::from_be_bytes(hash[8..16].try_into().unwrap()));
+    let two_to_the_32 = G::scalar_from_u64(1 << 32);
+    let two_to_the_64 = G::scalar_mul(&two_to_the_32, &two_to_the_32);
+    G::scalar_add(&G::scalar_mul(&high, &two_to_the_64), &low)
+}
+
+fn element_from_public_key<G: Group>(public_key: &PublicKey) -> Result<G, InternalPakeError> {
+    let checked_bytes = check_slice_size(public_key, G::ElemLen::to_usize(), "public_key")?;
+    G::from_element_slice(GenericArray::from_slice(checked_bytes))
+}
+
+fn scalar_from_private_key<G: Group>(
+    private_key: &PrivateKey,
+) -> Result<G::Scalar, InternalPakeError> {
+    let checked_bytes = check_slice_size(private_key, G::ScalarLen::to_usize(), "private_key")?;
+    G::from_scalar_slice(GenericArray::from_slice(checked_bytes))
+}
@@ -0,0 +1,468 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! An implementation of a hybrid key exchange protocol, which combines the
+//! Triple Diffie-Hellman key exchange with a key encapsulation mechanism
+//!
+//! The first key exchange message additionally carries a fresh encapsulation
+//! key of the client, to which the server encapsulates a shared secret in the
+//! second key exchange message. This shared secret is appended to the output of
+//! the Diffie-Hellman computations before deriving the session key, so that
+//! the session key remains secret as long as either the group or the KEM is
+//! secure. Instantiating the [Kem] trait with a post-quantum KEM such as
+//! ML-KEM thereby protects the session keys against an adversary recording
+//! the exchange in order to decrypt it once quantum computers are available.
+//! An implementation of ML-KEM-768 is provided by the `ml_kem` module, behind
+//! the `ml-kem` feature.
+#[cfg(test)]
+use crate::key_exchange::traits::AsBytePointers;
+use crate::{
+    ciphersuite::CipherSuite,
+    errors::{
+        utils::{check_slice_size, check_slice_size_atleast},
+        InternalPakeError, PakeError, ProtocolError,
+    },
+    group::Group,
+    hash::Hash,
+    key_exchange::{
+        traits::{client_mac, FromBytes, Ke2Inputs, Ke3Inputs, KeyExchange, ToBytes},
+        tripledh::{
+            derive_3dh_keys, generate_nonce, Ke2State, Ke3Message, NonceLen, TripleDH,
+            TripleDHComponents,
+        },
+    },
+    keypair::{KeyPair, PrivateKey, PublicKey},
+};
+use digest::{Digest, FixedOutput};
+use generic_array::{typenum::Unsigned, ArrayLength, GenericArray};
+use hmac::{Hmac, Mac, NewMac};
+use rand::{CryptoRng, RngCore};
+use std::marker::PhantomData;
+use zeroize::Zeroize;
+
+/// A key encapsulation mechanism, whose keys, ciphertexts and shared secrets
+/// are represented as fixed-length byte arrays
+pub trait Kem {
+    /// The byte length of an encapsulation key
+    type EncapsulationKeyLen: ArrayLength<u8>;
+    /// The byte length of a decapsulation key
+    type DecapsulationKeyLen: ArrayLength<u8>;
+    /// The byte length of a ciphertext
+    type CiphertextLen: ArrayLength<u8>;
+    /// The byte length of a shared secret
+    type SharedSecretLen: ArrayLength<u8>;
+
+    /// Generates a fresh pair of decapsulation and encapsulation keys
+    fn generate_keypair<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> (
+        GenericArray<u8, Self::DecapsulationKeyLen>,
+        GenericArray<u8, Self::EncapsulationKeyLen>,
+    );
+
+    /// Checks an encapsulation key received from the client, as its ephemeral
+    /// public key is checked to be a valid group element. The default
+    /// implementation accepts every key of the right length
+    fn check_encapsulation_key(
+        _encapsulation_key: &GenericArray<u8, Self::EncapsulationKeyLen>,
+    ) -> Result<(), InternalPakeError> {
+        Ok(())
+    }
+
+    /// Encapsulates a fresh shared secret to an encapsulation key, returning
+    /// the ciphertext along with the shared secret
+    #[allow(clippy::type_complexity)]
+    fn encapsulate<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        encapsulation_key: &GenericArray<u8, Self::EncapsulationKeyLen>,
+    ) -> Result<
+        (
+            GenericArray<u8, Self::CiphertextLen>,
+            GenericArray<u8, Self::SharedSecretLen>,
+        ),
+        InternalPakeError,
+    >;
+
+    /// Recovers the shared secret from a ciphertext with the decapsulation key
+    fn decapsulate(
+        decapsulation_key: &GenericArray<u8, Self::DecapsulationKeyLen>,
+        ciphertext: &GenericArray<u8, Self::CiphertextLen>,
+    ) -> Result<GenericArray<u8, Self::SharedSecretLen>, InternalPakeError>;
+}
+
+#[allow(clippy::upper_case_acronyms)]
+/// The hybrid key exchange implementation, combining the Triple Diffie-Hellman
+/// key exchange with the KEM `K`
+pub struct HybridTripleDH<K: Kem>(PhantomData<K>);
+
+impl<D: Hash, G: Group, K: Kem> KeyExchange<D, G> for HybridTripleDH<K> {
+    type KE1State = Ke1State<K>;
+    type KE2State = Ke2State<<D as FixedOutput>::OutputSize>;
+    type KE1Message = Ke1Message<K>;
+    type KE2Message = Ke2Message<K, <D as FixedOutput>::OutputSize>;
+    type KE3Message = Ke3Message<<D as FixedOutput>::OutputSize>;
+
+    fn generate_ke1<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> Result<(Self::KE1State, Self::KE1Message), ProtocolError> {
+        let client_e_kp = KeyPair::<G>::generate_random(rng);
+        let client_nonce = generate_nonce::<R>(rng);
+        let (decapsulation_key, encapsulation_key) = K::generate_keypair(rng);
+
+        let ke1_message = Ke1Message {
+            client_nonce,
+            client_e_pk: client_e_kp.public().clone(),
+            encapsulation_key,
+        };
+
+        Ok((
+            Ke1State {
+                client_e_sk: client_e_kp.private().clone(),
+                client_nonce,
+                decapsulation_key,
+            },
+            ke1_message,
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_ke2<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ke1_message: Self::KE1Message,
+        inputs: Ke2Inputs<'_>,
+    ) -> Result<(Self::KE2State, Self::KE2Message), ProtocolError> {
+        let server_e_kp = KeyPair::<G>::generate_random(rng);
+        let server_nonce = generate_nonce::<R>(rng);
+        let (ciphertext, mut shared_secret) = K::encapsulate(rng, &ke1_message.encapsulation_key)?;
+
+        let mut transcript_hasher = inputs
+            .transcript
+            .hasher::<D>()
+            .chain(&server_nonce[..])
+            .chain(&server_e_kp.public()[..])
+            .chain(&ciphertext[..]);
+
+        let derivation_result = derive_3dh_keys::<D, G>(
+            TripleDHComponents {
+                pk1: ke1_message.client_e_pk.clone(),
+                sk1: server_e_kp.private().clone(),
+                pk2: ke1_message.client_e_pk,
+                sk2: inputs.server_s_sk.clone(),
+                pk3: inputs.client_s_pk.clone(),
+                sk3: server_e_kp.private().clone(),
+            },
+            &shared_secret,
+            &transcript_hasher.clone().finalize(),
+        );
+        shared_secret.zeroize();
+        let (session_key, km2, km3) = derivation_result?;
+
+        let mut mac_hasher =
+            Hmac::<D>::new_varkey(&km2).map_err(|_| InternalPakeError::HmacError)?;
+        mac_hasher.update(&transcript_hasher.clone().finalize());
+        let mac = mac_hasher.finalize().into_bytes();
+
+        transcript_hasher.update(&mac);
+
+        Ok((
+            Ke2State {
+                km3,
+                hashed_transcript: transcript_hasher.finalize(),
+                session_key,
+            },
+            Ke2Message {
+                server_nonce,
+                server_e_pk: server_e_kp.public().clone(),
+                ciphertext,
+                mac,
+            },
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_ke3(
+        ke2_message: Self::KE2Message,
+        ke1_state: &Self::KE1State,
+        inputs: Ke3Inputs<'_>,
+    ) -> Result<(Vec<u8>, Self::KE3Message), ProtocolError> {
+        let mut transcript_hasher = inputs
+            .transcript
+            .hasher::<D>()
+            .chain(&ke2_message.server_nonce[..])
+            .chain(&ke2_message.server_e_pk[..])
+            .chain(&ke2_message.ciphertext[..]);
+
+        let mut shared_secret =
+            K::decapsulate(&ke1_state.decapsulation_key, &ke2_message.ciphertext)?;
+        let derivation_result = derive_3dh_keys::<D, G>(
+            TripleDHComponents {
+                pk1: ke2_message.server_e_pk.clone(),
+                sk1: ke1_state.client_e_sk.clone(),
+                pk2: inputs.server_s_pk.clone(),
+                sk2: ke1_state.client_e_sk.clone(),
+                pk3: ke2_message.server_e_pk.clone(),
+                sk3: inputs.client_s_sk.clone(),
+            },
+            &shared_secret,
+            &transcript_hasher.clone().finalize(),
+        );
+        shared_secret.zeroize();
+        let (session_key, km2, km3) = derivation_result?;
+
+        let mut server_mac =
+            Hmac::<D>::new_varkey(&km2).map_err(|_| InternalPakeError::HmacError)?;
+        server_mac.update(&transcript_hasher.clone().finalize());
+
+        if server_mac.verify(&ke2_message.mac).is_err() {
+            return Err(ProtocolError::VerificationError(
+                PakeError::KeyExchangeMacValidationError,
+            ));
+        }
+
+        transcript_hasher.update(&ke2_message.mac);
+
+        let client_payload = inputs.client_payload.serialize::<D>(&session_key)?;
+        let client_mac = client_mac::<D>(&km3, &transcript_hasher.finalize(), &client_payload)?;
+
+        Ok((
+            session_key.to_vec(),
+            Ke3Message {
+                mac: client_mac.finalize().into_bytes(),
+            },
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn finish_ke(
+        ke3_message: Self::KE3Message,
+        ke2_state: &Self::KE2State,
+        client_payload: &[u8],
+    ) -> Result<Vec<u8>, ProtocolError> {
+        <TripleDH as KeyExchange<D, G>>::finish_ke(ke3_message, ke2_state, client_payload)
+    }
+
+    fn ke2_message_size() -> usize {
+        NonceLen::to_usize()
+            + G::ElemLen::to_usize()
+            + K::CiphertextLen::to_usize()
+            + <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
+    }
+
+    fn ke3_message_size() -> usize {
+        <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
+    }
+}
+
+/// The client state produced after the first key exchange message
+pub struct Ke1State<K: Kem> {
+    client_e_sk: PrivateKey,
+    client_nonce: GenericArray<u8, NonceLen>,
+    decapsulation_key: GenericArray<u8, K::DecapsulationKeyLen>,
+}
+
+// Cannot be derived because it would require for K to be Clone.
+impl<K: Kem> Clone for Ke1State<K> {
+    fn clone(&self) -> Self {
+        Self {
+            client_e_sk: self.client_e_sk.clone(),
+            client_nonce: self.client_nonce,
+            decapsulation_key: self.decapsulation_key.clone(),
+        }
+    }
+}
+
+impl<K: Kem> Zeroize for Ke1State<K> {
+    fn zeroize(&mut self) {
+        self.client_e_sk.zeroize();
+        self.client_nonce.zeroize();
+        self.decapsulation_key.zeroize();
+    }
+}
+
+impl<K: Kem> Drop for Ke1State<K> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<K: Kem> FromBytes for Ke1State<K> {
+    fn from_bytes<CS: CipherSuite>(bytes: &[u8]) -> Result<Self, PakeError> {
+        let key_len = <CS::Group as Group>::ScalarLen::to_usize();
+        let nonce_len = NonceLen::to_usize();
+        let checked_bytes = check_slice_size(
+            bytes,
+            key_len + nonce_len + K::DecapsulationKeyLen::to_usize(),
+            "ke1_state",
+        )?;
+
+        Ok(Self {
+            client_e_sk: PrivateKey::from_bytes(&checked_bytes[..key_len]),
+            client_nonce: GenericArray::clone_from_slice(
+                &checked_bytes[key_len..key_len + nonce_len],
+            ),
+            decapsulation_key: GenericArray::clone_from_slice(
+                &checked_bytes[key_len + nonce_len..],
+            ),
+        })
+    }
+}
+
+impl<K: Kem> ToBytes for Ke1State<K> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.client_e_sk[..],
+            &self.client_nonce[..],
+            &self.decapsulation_key[..],
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+impl<K: Kem> AsBytePointers for Ke1State<K> {
+    fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
+        vec![
+            (self.client_e_sk.as_ptr(), self.client_e_sk.len()),
+            (self.client_nonce.as_ptr(), NonceLen::to_usize()),
+            (
+                self.decapsulation_key.as_ptr(),
+                K::DecapsulationKeyLen::to_usize(),
+            ),
+        ]
+    }
+}
+
+/// The first key exchange message
+pub struct Ke1Message<K: Kem> {
+    client_nonce: GenericArray<u8, NonceLen>,
+    client_e_pk: PublicKey,
+    encapsulation_key: GenericArray<u8, K::EncapsulationKeyLen>,
+}
+
+// Cannot be derived because it would require for K to be Clone.
+impl<K: Kem> Clone for Ke1Message<K> {
+    fn clone(&self) -> Self {
+        Self {
+            client_nonce: self.client_nonce,
+            client_e_pk: self.client_e_pk.clone(),
+            encapsulation_key: self.encapsulation_key.clone(),
+        }
+    }
+}
+
+impl<K: Kem> ToBytes for Ke1Message<K> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.client_nonce[..],
+            &self.client_e_pk[..],
+            &self.encapsulation_key[..],
+        ]
+        .concat()
+    }
+}
+
+impl<K: Kem> FromBytes for Ke1Message<K> {
+    fn from_bytes<CS: CipherSuite>(ke1_message_bytes: &[u8]) -> Result<Self, PakeError> {
+        let key_len = <CS::Group as Group>::ElemLen::to_usize();
+        let nonce_len = NonceLen::to_usize();
+        let checked_nonce =
+            check_slice_size_atleast(ke1_message_bytes, nonce_len, "ke1_message nonce")?;
+
+        let unchecked_client_e_pk = check_slice_size_atleast(
+            &checked_nonce[nonce_len..],
+            key_len,
+            "ke1_message client_e_pk",
+        )?;
+        let unchecked_encapsulation_key = check_slice_size(
+            &unchecked_client_e_pk[key_len..],
+            K::EncapsulationKeyLen::to_usize(),
+            "ke1_message encapsulation_key",
+        )?;
+
+        // Check the public key bytes
+        let client_e_pk = KeyPair::<CS::Group>::check_public_key(PublicKey::from_bytes(
+            &unchecked_client_e_pk[..key_len],
+        ))?;
+
+        // Check the encapsulation key bytes
+        let encapsulation_key = GenericArray::clone_from_slice(unchecked_encapsulation_key);
+        K::check_encapsulation_key(&encapsulation_key)?;
+
+        Ok(Self {
+            client_nonce: GenericArray::clone_from_slice(&checked_nonce[..nonce_len]),
+            client_e_pk,
+            encapsulation_key,
+        })
+    }
+}
+
+/// The second key exchange message
+pub struct Ke2Message<K: Kem, HashLen: ArrayLength<u8>> {
+    server_nonce: GenericArray<u8, NonceLen>,
+    server_e_pk: PublicKey,
+    ciphertext: GenericArray<u8, K::CiphertextLen>,
+    mac: GenericArray<u8, HashLen>,
+}
+
+// Cannot be derived because it would require for K to be Clone.
+impl<K: Kem, HashLen: ArrayLength<u8>> Clone for Ke2Message<K, HashLen> {
+    fn clone(&self) -> Self {
+        Self {
+            server_nonce: self.server_nonce,
+            server_e_pk: self.server_e_pk.clone(),
+            ciphertext: self.ciphertext.clone(),
+            mac: self.mac.clone(),
+        }
+    }
+}
+
+impl<K: Kem, HashLen: ArrayLength<u8>> ToBytes for Ke2Message<K, HashLen> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.server_nonce[..],
+            &self.server_e_pk[..],
+            &self.ciphertext[..],
+            &self.mac[..],
+        ]
+        .concat()
+    }
+}
+
+impl<K: Kem, HashLen: ArrayLength<u8>> FromBytes for Ke2Message<K, HashLen> {
+    fn from_bytes<CS: CipherSuite>(input: &[u8]) -> Result<Self, PakeError> {
+        let key_len = <CS::Group as Group>::ElemLen::to_usize();
+        let nonce_len = NonceLen::to_usize();
+        let ciphertext_len = K::CiphertextLen::to_usize();
+        let checked_nonce = check_slice_size_atleast(input, nonce_len, "ke2_message nonce")?;
+
+        let unchecked_server_e_pk = check_slice_size_atleast(
+            &checked_nonce[nonce_len..],
+            key_len,
+            "ke2_message server_e_pk",
+        )?;
+        let checked_ciphertext = check_slice_size_atleast(
+            &unchecked_server_e_pk[key_len..],
+            ciphertext_len,
+            "ke2_message ciphertext",
+        )?;
+        let checked_mac = check_slice_size(
+            &checked_ciphertext[ciphertext_len..],
+            HashLen::to_usize(),
+            "ke2_message mac",
+        )?;
+
+        // Check the public key bytes
+        let server_e_pk = KeyPair::<CS::Group>::check_public_key(PublicKey::from_bytes(
+            &unchecked_server_e_pk[..key_len],
+        ))?;
+
+        Ok(Self {
+            server_nonce: GenericArray::clone_from_slice(&checked_nonce[..nonce_len]),
+            server_e_pk,
+            ciphertext: GenericArray::clone_from_slice(&checked_ciphertext[..ciphertext_len]),
+            mac: GenericArray::clone_from_slice(checked_mac),
+        })
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! The ML-KEM-768 key encapsulation mechanism, as specified in
+//! [FIPS 203](https://doi.org/10.6028/NIST.FIPS.203), for use with the hybrid
+//! key exchange
+//!
+//! This is a thin wrapper around the RustCrypto `ml-kem` crate. The
+//! decapsulation key is kept in the 64-byte seed form of FIPS 203, from which
+//! the expanded key is recomputed on decapsulation. Encapsulation performs the
+//! modulus check of FIPS 203 on the encapsulation key, and an invalid
+//! ciphertext is implicitly rejected, yielding a pseudorandom shared secret
+//! which makes the key confirmation of the key exchange fail.
+use crate::{errors::InternalPakeError, key_exchange::hybrid::Kem};
+use generic_array::{
+    typenum::{Sum, U1024, U160, U32, U64},
+    GenericArray,
+};
+use ml_kem_::{
+    array::Array,
+    ml_kem_768::{Ciphertext, DecapsulationKey, EncapsulationKey},
+    Decapsulate, KeyExport, B32,
+};
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroize;
+
+/// The ML-KEM-768 key encapsulation mechanism
+pub struct MlKem768;
+
+impl Kem for MlKem768 {
+    type EncapsulationKeyLen = Sum<U1024, U160>;
+    type DecapsulationKeyLen = U64;
+    type CiphertextLen = Sum<U1024, U64>;
+    type SharedSecretLen = U32;
+
+    fn generate_keypair<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> (
+        GenericArray<u8, Self::DecapsulationKeyLen>,
+        GenericArray<u8, Self::EncapsulationKeyLen>,
+    ) {
+        // The seed is d || z, drawn in this order as in FIPS 203
+        let mut seed = GenericArray::<u8, Self::DecapsulationKeyLen>::default();
+        rng.fill_bytes(&mut seed);
+
+        let mut ml_kem_seed = Array::from_fn(|i| seed[i]);
+        let decapsulation_key = DecapsulationKey::from_seed(ml_kem_seed);
+        ml_kem_seed.zeroize();
+        let encapsulation_key =
+            GenericArray::clone_from_slice(&decapsulation_key.encapsulation_key().to_bytes());
+
+        (seed, encapsulation_key)
+    }
+
+    fn check_encapsulation_key(
+        encapsulation_key: &GenericArray<u8, Self::EncapsulationKeyLen>,
+    ) -> Result<(), InternalPakeError> {
+        parse_encapsulation_key(encapsulation_key).map(|_| ())
+    }
+
+    fn encapsulate<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        encapsulation_key: &GenericArray<u8, Self::EncapsulationKeyLen>,
+    ) -> Result<
+        (
+            GenericArray<u8, Self::CiphertextLen>,
+            GenericArray<u8, Self::SharedSecretLen>,
+        ),
+        InternalPakeError,
+    > {
+        let encapsulation_key = parse_encapsulation_key(encapsulation_key)?;
+
+        // Drawing the message m from the rng is exactly how the randomized
+        // encapsulation of ml-kem proceeds, but keeps it on the rand 0.8 rng
+        let mut m = B32::default();
+        rng.fill_bytes(&mut m);
+        let (ciphertext, mut shared_secret) = encapsulation_key.encapsulate_deterministic(&m);
+        m.zeroize();
+
+        let result = (
+            GenericArray::clone_from_slice(&ciphertext),
+            GenericArray::clone_from_slice(&shared_secret),
+        );
+        shared_secret.zeroize();
+        Ok(result)
+    }
+
+    fn decapsulate(
+        decapsulation_key: &GenericArray<u8, Self::DecapsulationKeyLen>,
+        ciphertext: &GenericArray<u8, Self::CiphertextLen>,
+    ) -> Result<GenericArray<u8, Self::SharedSecretLen>, InternalPakeError> {
+        let mut seed = Array::from_fn(|i| decapsulation_key[i]);
+        let decapsulation_key = DecapsulationKey::from_seed(seed);
+        seed.zeroize();
+
+        let ciphertext: Ciphertext = Array::from_fn(|i| ciphertext[i]);
+        let mut shared_secret = decapsulation_key.decapsulate(&ciphertext);
+        let result = GenericArray::clone_from_slice(&shared_secret);
+        shared_secret.zeroize();
+        Ok(result)
+    }
+}
+
+fn parse_encapsulation_key(
+    encapsulation_key: &GenericArray<u8, <MlKem768 as Kem>::EncapsulationKeyLen>,
+) -> Result<EncapsulationKey, InternalPakeError> {
+    EncapsulationKey::new(&Array::from_fn(|i| encapsulation_key[i]))
+        .map_err(|_| InternalPakeError::KemKeyError)
+}
@@ -6,5 +6,11 @@
 //! Includes instantiations of key exchange protocols used in the
 //! login step for OPAQUE
 
-pub(crate) mod traits;
+pub mod hmqv;
+pub mod hybrid;
+#[cfg(feature = "ml-kem")]
+pub mod ml_kem;
+#[cfg(feature = "sigma")]
+pub mod sigma;
+pub mod traits;
 pub mod tripledh;
@@ -0,0 +1,401 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! An implementation of the SIGMA-I key exchange protocol, in which the
+//! parties authenticate with Ed25519 signatures over the transcript instead of
+//! Diffie-Hellman operations with their static keys
+//!
+//! The Ed25519 secret keys of the client and of the server are derived from
+//! their static private keys with HKDF, so that the same bytes are never used
+//! both as a scalar of the group and as an Ed25519 secret key, and their static
+//! public keys are the corresponding Ed25519 public keys. Only the ephemeral
+//! keys take part in the Diffie-Hellman computation, which is why this key
+//! exchange requires a group with 32-byte elements, such as ristretto255.
+#[cfg(test)]
+use crate::key_exchange::traits::AsBytePointers;
+use crate::{
+    ciphersuite::CipherSuite,
+    errors::{
+        utils::{check_slice_size, check_slice_size_atleast},
+        InternalPakeError, PakeError, ProtocolError,
+    },
+    group::Group,
+    hash::Hash,
+    key_exchange::{
+        traits::{client_mac, FromBytes, Ke2Inputs, Ke3Inputs, KeyExchange, ToBytes},
+        tripledh::{derive_keys, generate_nonce, Ke1Message, Ke1State, NonceLen, TripleDH},
+    },
+    keypair::{KeyPair, PrivateKey, PublicKey},
+};
+use digest::{Digest, FixedOutput};
+use ed25519_dalek::{
+    ExpandedSecretKey, PublicKey as VerifyingKey, SecretKey as SigningKey, Signature,
+};
+use generic_array::{
+    typenum::{Unsigned, U32, U64},
+    ArrayLength, GenericArray,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac, NewMac};
+use rand::{CryptoRng, RngCore};
+use std::convert::TryFrom;
+use zeroize::Zeroize;
+
+type SignatureLen = U64;
+
+static STR_CLIENT_SIGNATURE: &[u8] = b"OPAQUE-SIGMA-ClientSignature";
+static STR_SERVER_SIGNATURE: &[u8] = b"OPAQUE-SIGMA-ServerSignature";
+static STR_SIGNING_KEY: &[u8] = b"OPAQUE-SIGMA-SigningKey";
+
+#[allow(clippy::upper_case_acronyms)]
+/// The SIGMA-I key exchange implementation, authenticated with Ed25519
+/// signatures
+pub struct Sigma;
+
+impl<D: Hash, G: Group<ElemLen = U32>> KeyExchange<D, G> for Sigma {
+    type KE1State = Ke1State;
+    type KE2State = Ke2State<<D as FixedOutput>::OutputSize>;
+    type KE1Message = Ke1Message;
+    type KE2Message = Ke2Message<<D as FixedOutput>::OutputSize>;
+    type KE3Message = Ke3Message<<D as FixedOutput>::OutputSize>;
+
+    fn generate_ke1<R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> Result<(Self::KE1State, Self::KE1Message), ProtocolError> {
+        // The first message only carries an ephemeral key and a nonce, and is
+        // therefore the same as in the triple Diffie-Hellman key exchange
+        <TripleDH as KeyExchange<D, G>>::generate_ke1(rng)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_ke2<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ke1_message: Self::KE1Message,
+        inputs: Ke2Inputs<'_>,
+    ) -> Result<(Self::KE2State, Self::KE2Message), ProtocolError> {
+        let server_e_kp = KeyPair::<G>::generate_random(rng);
+        let server_nonce = generate_nonce::<R>(rng);
+
+        let mut transcript_hasher = inputs
+            .transcript
+            .hasher::<D>()
+            .chain(&server_nonce[..])
+            .chain(&server_e_kp.public()[..]);
+
+        let ikm =
+            KeyPair::<G>::diffie_hellman(ke1_message.client_e_pk, server_e_kp.private().clone())?;
+        let hashed_preamble = transcript_hasher.clone().finalize();
+        let (session_key, km2, km3) = derive_keys::<D>(&ikm, &hashed_preamble)?;
+
+        let signature = sign::<D>(inputs.server_s_sk, STR_SERVER_SIGNATURE, &hashed_preamble)?;
+        transcript_hasher.update(signature);
+
+        let mut mac_hasher =
+            Hmac::<D>::new_varkey(&km2).map_err(|_| InternalPakeError::HmacError)?;
+        mac_hasher.update(&transcript_hasher.clone().finalize());
+        let mac = mac_hasher.finalize().into_bytes();
+
+        transcript_hasher.update(&mac);
+
+        Ok((
+            Ke2State {
+                km3,
+                hashed_transcript: transcript_hasher.finalize(),
+                session_key,
+                client_s_pk: GenericArray::clone_from_slice(&inputs.client_s_pk[..]),
+            },
+            Ke2Message {
+                server_nonce,
+                server_e_pk: server_e_kp.public().clone(),
+                signature,
+                mac,
+            },
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_ke3(
+        ke2_message: Self::KE2Message,
+        ke1_state: &Self::KE1State,
+        inputs: Ke3Inputs<'_>,
+    ) -> Result<(Vec<u8>, Self::KE3Message), ProtocolError> {
+        let mut transcript_hasher = inputs
+            .transcript
+            .hasher::<D>()
+            .chain(&ke2_message.server_nonce[..])
+            .chain(&ke2_message.server_e_pk[..]);
+
+        let ikm = KeyPair::<G>::diffie_hellman(
+            ke2_message.server_e_pk.clone(),
+            ke1_state.client_e_sk.clone(),
+        )?;
+        let hashed_preamble = transcript_hasher.clone().finalize();
+        let (session_key, km2, km3) = derive_keys::<D>(&ikm, &hashed_preamble)?;
+
+        verify(
+            inputs.server_s_pk,
+            STR_SERVER_SIGNATURE,
+            &hashed_preamble,
+            &ke2_message.signature,
+        )?;
+        transcript_hasher.update(ke2_message.signature);
+
+        let mut server_mac =
+            Hmac::<D>::new_varkey(&km2).map_err(|_| InternalPakeError::HmacError)?;
+        server_mac.update(&transcript_hasher.clone().finalize());
+
+        if server_mac.verify(&ke2_message.mac).is_err() {
+            return Err(ProtocolError::VerificationError(
+                PakeError::KeyExchangeMacValidationError,
+            ));
+        }
+
+        transcript_hasher.update(&ke2_message.mac);
+        let hashed_transcript = transcript_hasher.finalize();
+
+        let signature = sign::<D>(inputs.client_s_sk, STR_CLIENT_SIGNATURE, &hashed_transcript)?;
+
+        let client_payload = inputs.client_payload.serialize::<D>(&session_key)?;
+        let client_mac = client_mac::<D>(&km3, &hashed_transcript, &client_payload)?;
+
+        Ok((
+            session_key.to_vec(),
+            Ke3Message {
+                signature,
+                mac: client_mac.finalize().into_bytes(),
+            },
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn finish_ke(
+        ke3_message: Self::KE3Message,
+        ke2_state: &Self::KE2State,
+        client_payload: &[u8],
+    ) -> Result<Vec<u8>, ProtocolError> {
+        let client_mac =
+            client_mac::<D>(&ke2_state.km3, &ke2_state.hashed_transcript, client_payload)?;
+
+        if client_mac.verify(&ke3_message.mac).is_err() {
+            return Err(ProtocolError::VerificationError(
+                PakeError::KeyExchangeMacValidationError,
+            ));
+        }
+
+        verify(
+            &PublicKey::from_bytes(&ke2_state.client_s_pk),
+            STR_CLIENT_SIGNATURE,
+            &ke2_state.hashed_transcript,
+            &ke3_message.signature,
+        )?;
+
+        Ok(ke2_state.session_key.to_vec())
+    }
+
+    fn ke2_message_size() -> usize {
+        NonceLen::to_usize()
+            + G::ElemLen::to_usize()
+            + SignatureLen::to_usize()
+            + <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
+    }
+
+    fn ke3_message_size() -> usize {
+        SignatureLen::to_usize() + <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
+    }
+
+    fn derive_static_public_key(private_key: &PrivateKey) -> Result<PublicKey, InternalPakeError> {
+        let signing_key = signing_key::<D>(private_key)?;
+        Ok(PublicKey::from_bytes(
+            VerifyingKey::from(&signing_key).as_bytes(),
+        ))
+    }
+
+    fn check_static_public_key(key: PublicKey) -> Result<PublicKey, InternalPakeError> {
+        let checked_bytes = check_slice_size(&key, G::ElemLen::to_usize(), "public_key")?;
+        VerifyingKey::from_bytes(checked_bytes)
+            .map_err(|_| InternalPakeError::PointError)
+            .map(|_| key)
+    }
+}
+
+/// The server state produced after the second key exchange message
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
+pub struct Ke2State<HashLen: ArrayLength<u8>> {
+    km3: GenericArray<u8, HashLen>,
+    hashed_transcript: GenericArray<u8, HashLen>,
+    session_key: GenericArray<u8, HashLen>,
+    client_s_pk: GenericArray<u8, U32>,
+}
+
+impl<HashLen: ArrayLength<u8>> ToBytes for Ke2State<HashLen> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.km3[..],
+            &self.hashed_transcript[..],
+            &self.session_key[..],
+            &self.client_s_pk[..],
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+impl<HashLen: ArrayLength<u8>> AsBytePointers for Ke2State<HashLen> {
+    fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
+        vec![
+            (self.km3.as_ptr(), HashLen::to_usize()),
+            (self.hashed_transcript.as_ptr(), HashLen::to_usize()),
+            (self.session_key.as_ptr(), HashLen::to_usize()),
+            (self.client_s_pk.as_ptr(), U32::to_usize()),
+        ]
+    }
+}
+
+impl<HashLen: ArrayLength<u8>> FromBytes for Ke2State<HashLen> {
+    fn from_bytes<CS: CipherSuite>(input: &[u8]) -> Result<Self, PakeError> {
+        let hash_len = HashLen::to_usize();
+        let checked_bytes = check_slice_size(input, 3 * hash_len + U32::to_usize(), "ke2_state")?;
+
+        Ok(Self {
+            km3: GenericArray::clone_from_slice(&checked_bytes[..hash_len]),
+            hashed_transcript: GenericArray::clone_from_slice(
+                &checked_bytes[hash_len..2 * hash_len],
+            ),
+            session_key: GenericArray::clone_from_slice(&checked_bytes[2 * hash_len..3 * hash_len]),
+            client_s_pk: GenericArray::clone_from_slice(&checked_bytes[3 * hash_len..]),
+        })
+    }
+}
+
+/// The second key exchange message
+#[derive(Clone)]
+pub struct Ke2Message<HashLen: ArrayLength<u8>> {
+    server_nonce: GenericArray<u8, NonceLen>,
+    server_e_pk: PublicKey,
+    signature: GenericArray<u8, SignatureLen>,
+    mac: GenericArray<u8, HashLen>,
+}
+
+impl<HashLen: ArrayLength<u8>> ToBytes for Ke2Message<HashLen> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.server_nonce[..],
+            &self.server_e_pk[..],
+            &self.signature[..],
+            &self.mac[..],
+        ]
+        .concat()
+    }
+}
+
+impl<HashLen: ArrayLength<u8>> FromBytes for Ke2Message<HashLen> {
+    fn from_bytes<CS: CipherSuite>(input: &[u8]) -> Result<Self, PakeError> {
+        let key_len = <CS::Group as Group>::ElemLen::to_usize();
+        let nonce_len = NonceLen::to_usize();
+        let signature_len = SignatureLen::to_usize();
+        let checked_nonce = check_slice_size_atleast(input, nonce_len, "ke2_message nonce")?;
+
+        let unchecked_server_e_pk = check_slice_size_atleast(
+            &checked_nonce[nonce_len..],
+            key_len,
+            "ke2_message server_e_pk",
+        )?;
+        let checked_signature = check_slice_size_atleast(
+            &unchecked_server_e_pk[key_len..],
+            signature_len,
+            "ke2_message signature",
+        )?;
+        let checked_mac = check_slice_size(
+            &checked_signature[signature_len..],
+            HashLen::to_usize(),
+            "ke2_message mac",
+        )?;
+
+        // Check the public key bytes
+        let server_e_pk = KeyPair::<CS::Group>::check_public_key(PublicKey::from_bytes(
+            &unchecked_server_e_pk[..key_len],
+        ))?;
+
+        Ok(Self {
+            server_nonce: GenericArray::clone_from_slice(&checked_nonce[..nonce_len]),
+            server_e_pk,
+            signature: GenericArray::clone_from_slice(&checked_signature[..signature_len]),
+            mac: GenericArray::clone_from_slice(checked_mac),
+        })
+    }
+}
+
+/// The third key exchange message
+#[derive(Clone)]
+pub struct Ke3Message<HashLen: ArrayLength<u8>> {
+    signature: GenericArray<u8, SignatureLen>,
+    mac: GenericArray<u8, HashLen>,
+}
+
+impl<HashLen: ArrayLength<u8>> ToBytes for Ke3Message<HashLen> {
+    fn to_bytes(&self) -> Vec<u8> {
+        [&self.signature[..], &self.mac[..]].concat()
+    }
+}
+
+impl<HashLen: ArrayLength<u8>> FromBytes for Ke3Message<HashLen> {
+    fn from_bytes<CS: CipherSuite>(bytes: &[u8]) -> Result<Self, PakeError> {
+        let signature_len = SignatureLen::to_usize();
+        let checked_bytes =
+            check_slice_size(bytes, signature_len + HashLen::to_usize(), "ke3_message")?;
+
+        Ok(Self {
+            signature: GenericArray::clone_from_slice(&checked_bytes[..signature_len]),
+            mac: GenericArray::clone_from_slice(&checked_bytes[signature_len..]),
+        })
+    }
+}
+
+// Helper functions
+
+// Derives the Ed25519 secret key of a party from its static private key
+fn signing_key<D: Hash>(private_key: &PrivateKey) -> Result<SigningKey, InternalPakeError> {
+    let mut seed = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+    Hkdf::<D>::new(None, private_key)
+        .expand(STR_SIGNING_KEY, &mut seed)
+        .map_err(|_| InternalPakeError::HkdfError)?;
+    let signing_key =
+        SigningKey::from_bytes(&seed).map_err(|_| InternalPakeError::InvalidByteSequence);
+    seed.zeroize();
+    signing_key
+}
+
+// Signs the hashed transcript, prefixed by a label which separates the
+// signatures of the client from those of the server
+fn sign<D: Hash>(
+    private_key: &PrivateKey,
+    label: &[u8],
+    hashed_transcript: &[u8],
+) -> Result<GenericArray<u8, SignatureLen>, InternalPakeError> {
+    let signing_key = signing_key::<D>(private_key)?;
+    let verifying_key = VerifyingKey::from(&signing_key);
+    let signature = ExpandedSecretKey::from(&signing_key)
+        .sign(&[label, hashed_transcript].concat(), &verifying_key);
+    Ok(GenericArray::clone_from_slice(&signature.to_bytes()))
+}
+
+// Verifies a signature produced by sign(), rejecting malleable signatures and
+// weak public keys. A failure is reported like a MAC failure, so that callers
+// see the same login failure whichever key exchange is used
+fn verify(
+    public_key: &PublicKey,
+    label: &[u8],
+    hashed_transcript: &[u8],
+    signature: &[u8],
+) -> Result<(), ProtocolError> {
+    let verifying_key = VerifyingKey::from_bytes(public_key)
+        .map_err(|_| PakeError::KeyExchangeMacValidationError)?;
+    let signature =
+        Signature::try_from(signature).map_err(|_| PakeError::KeyExchangeMacValidationError)?;
+    verifying_key
+        .verify_strict(&[label, hashed_transcript].concat(), &signature)
+        .map_err(|_| ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError))
+}
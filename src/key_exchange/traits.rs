@@ -3,74 +3,296 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+//! Defines the KeyExchange trait, which can be implemented outside of this
+//! crate in order to run OPAQUE with a custom key exchange protocol
+//!
+//! The login step of OPAQUE carries the messages of the key exchange alongside
+//! the messages of the OPRF: the first key exchange message is appended to the
+//! [CredentialRequest](crate::CredentialRequest), the second to the
+//! [CredentialResponse](crate::CredentialResponse), and the third forms the
+//! [CredentialFinalization](crate::CredentialFinalization). An implementation
+//! must uphold the following invariants:
+//!
+//! * Transcript binding: the session key, as well as the authentication of the
+//!   server checked in [KeyExchange::generate_ke3] and the authentication of
+//!   the client checked in [KeyExchange::finish_ke], must cover all of the
+//!   [TranscriptInputs] and all of the second key exchange message. This is
+//!   what ensures that the client and the server agree on the identities, the
+//!   context and the OPRF messages, and the simplest way to achieve it is to
+//!   start from [TranscriptInputs::hasher].
+//! * Payload binding: the authentication of the client must also cover the
+//!   application payload of the client, as it is sent to the server. On the
+//!   client, it is obtained from [ClientPayload::serialize] on the
+//!   [Ke3Inputs], and on the server, it is passed to [KeyExchange::finish_ke].
+//!   The simplest way to achieve it is to compute the MAC of the client with
+//!   [client_mac].
+//! * Authentication failures are reported as
+//!   [KeyExchangeMacValidationError](crate::errors::PakeError::KeyExchangeMacValidationError),
+//!   which the server surfaces as
+//!   [InvalidLoginError](crate::errors::PakeError::InvalidLoginError).
+//! * The second and third key exchange messages always serialize to
+//!   [KeyExchange::ke2_message_size] and [KeyExchange::ke3_message_size]
+//!   bytes, since they are not length-prefixed in the
+//!   [CredentialResponse](crate::CredentialResponse) and the
+//!   [CredentialFinalization](crate::CredentialFinalization).
+//! * [FromBytes] rejects group elements which are not valid for the group of
+//!   the ciphersuite.
+//! * The states zeroize their secrets when dropped.
+
 use crate::{
     ciphersuite::CipherSuite,
-    errors::{PakeError, ProtocolError},
+    errors::{InternalPakeError, PakeError, ProtocolError},
     group::Group,
     hash::Hash,
-    keypair::{PrivateKey, PublicKey},
+    key_exchange::tripledh::STR_RFC,
+    keypair::{KeyPair, PrivateKey, PublicKey},
+    serialization::serialize,
 };
+use digest::Digest;
+use generic_array::typenum::Unsigned;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac, NewMac};
 use rand::{CryptoRng, RngCore};
 use zeroize::Zeroize;
 
+static STR_CHANNEL_BINDING: &[u8] = b"ChannelBinding";
+static STR_CLIENT_PAYLOAD: &[u8] = b"ClientPayload";
+static STR_CLIENT_PAYLOAD_PAD: &[u8] = b"ClientPayloadPad";
+
+/// The public inputs of the login step which must be bound into the transcript
+/// of the key exchange
+#[derive(Clone, Copy, Debug)]
+pub struct TranscriptInputs<'a> {
+    /// The context which the client and the server must agree on
+    pub context: &'a [u8],
+    /// The identity of the client, prefixed with its two-byte length. It
+    /// defaults to the static public key of the client
+    pub id_u: &'a [u8],
+    /// The serialized credential request, including the first key exchange
+    /// message
+    pub serialized_credential_request: &'a [u8],
+    /// The identity of the server, prefixed with its two-byte length. It
+    /// defaults to the static public key of the server
+    pub id_s: &'a [u8],
+    /// The serialized credential response, without the second key exchange
+    /// message
+    pub serialized_credential_response: &'a [u8],
+    /// The channel binding which the client and the server must agree on,
+    /// such as a TLS exporter value. It is empty if there is none
+    pub channel_binding: &'a [u8],
+}
+
+impl TranscriptInputs<'_> {
+    /// Returns a hasher which has absorbed the transcript inputs, in the order
+    /// specified by the OPAQUE draft, followed by the channel binding under
+    /// its own label if there is one. The fields of the second key exchange
+    /// message are then expected to be absorbed by the key exchange
+    pub fn hasher<D: Hash>(&self) -> D {
+        let mut hasher = D::new()
+            .chain(STR_RFC)
+            .chain(serialize(self.context, 2))
+            .chain(self.id_u)
+            .chain(self.serialized_credential_request)
+            .chain(self.id_s)
+            .chain(self.serialized_credential_response);
+        if !self.channel_binding.is_empty() {
+            hasher.update(STR_CHANNEL_BINDING);
+            hasher.update(serialize(self.channel_binding, 2));
+        }
+        hasher
+    }
+}
+
+/// The inputs of the server for generating the second key exchange message
+#[derive(Clone, Copy)]
+pub struct Ke2Inputs<'a> {
+    /// The public inputs to bind into the transcript
+    pub transcript: TranscriptInputs<'a>,
+    /// The static public key of the client
+    pub client_s_pk: &'a PublicKey,
+    /// The static private key of the server
+    pub server_s_sk: &'a PrivateKey,
+}
+
+/// The inputs of the client for generating the third key exchange message
+#[derive(Clone, Copy)]
+pub struct Ke3Inputs<'a> {
+    /// The public inputs to bind into the transcript
+    pub transcript: TranscriptInputs<'a>,
+    /// The static public key of the server
+    pub server_s_pk: &'a PublicKey,
+    /// The static private key of the client
+    pub client_s_sk: &'a PrivateKey,
+    /// The application payload of the client, which must be covered by the
+    /// third key exchange message
+    pub client_payload: ClientPayload<'a>,
+}
+
+/// The application payload which the client sends along with the third key
+/// exchange message
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ClientPayload<'a> {
+    /// The payload in plaintext, which is empty if there is none
+    pub payload: &'a [u8],
+    /// Whether the payload is encrypted under a key derived from the session
+    /// key
+    pub encrypt: bool,
+}
+
+impl ClientPayload<'_> {
+    /// Returns the payload as it is sent to the server, encrypted under the
+    /// session key if requested, and prefixed with a byte telling whether it
+    /// is encrypted. This is the input which the MAC of the client must cover,
+    /// and it is empty if there is no payload
+    pub fn serialize<D: Hash>(&self, session_key: &[u8]) -> Result<Vec<u8>, InternalPakeError> {
+        if self.encrypt {
+            let ciphertext = crypt_client_payload::<D>(session_key, self.payload)?;
+            Ok(client_payload_component(&ciphertext, true))
+        } else {
+            Ok(client_payload_component(self.payload, false))
+        }
+    }
+}
+
+// Serializes the application payload of the client as it is sent, prefixed
+// with a byte telling whether it is encrypted
+pub(crate) fn client_payload_component(payload: &[u8], encrypted: bool) -> Vec<u8> {
+    if payload.is_empty() {
+        return Vec::new();
+    }
+    let mut output = vec![encrypted as u8];
+    output.extend_from_slice(&serialize(payload, 2));
+    output
+}
+
+// Encrypts or decrypts the application payload of the client, by XORing it
+// with a pad expanded from the session key. Since an expansion is limited to
+// 255 hash outputs, the pad is expanded in blocks, each under its own index
+pub(crate) fn crypt_client_payload<D: Hash>(
+    session_key: &[u8],
+    payload: &[u8],
+) -> Result<Vec<u8>, InternalPakeError> {
+    let hkdf = Hkdf::<D>::new(None, session_key);
+    let block_len = 255 * <D as Digest>::OutputSize::to_usize();
+    let mut output = Vec::with_capacity(payload.len());
+    for (index, block) in payload.chunks(block_len).enumerate() {
+        let mut xor_pad = vec![0u8; block.len()];
+        hkdf.expand(
+            &[STR_CLIENT_PAYLOAD_PAD, &(index as u16).to_be_bytes()].concat(),
+            &mut xor_pad,
+        )
+        .map_err(|_| InternalPakeError::HkdfError)?;
+        output.extend(xor_pad.iter().zip(block).map(|(&x1, &x2)| x1 ^ x2));
+    }
+    Ok(output)
+}
+
+/// Returns a MAC keyed with `key` which has absorbed the hashed transcript,
+/// followed by the serialized application payload of the client under its own
+/// label if there is one. The client is expected to authenticate itself with
+/// it in the third key exchange message
+pub fn client_mac<D: Hash>(
+    key: &[u8],
+    hashed_transcript: &[u8],
+    client_payload: &[u8],
+) -> Result<Hmac<D>, InternalPakeError> {
+    let mut mac = Hmac::<D>::new_varkey(key).map_err(|_| InternalPakeError::HmacError)?;
+    mac.update(hashed_transcript);
+    if !client_payload.is_empty() {
+        mac.update(STR_CLIENT_PAYLOAD);
+        mac.update(client_payload);
+    }
+    Ok(mac)
+}
+
+/// A key exchange protocol with three messages, in which the client and the
+/// server authenticate each other with their static keys. See the
+/// [module documentation](self) for the invariants which an implementation
+/// must uphold
+///
+/// The trait is implemented on a marker type and is used through its
+/// associated types and functions, with the key exchange chosen statically by
+/// the `KeyExchange` of the `CipherSuite`. It is therefore not object-safe.
 pub trait KeyExchange<D: Hash, G: Group> {
-    type KE1State: FromBytes + ToBytesWithPointers + Zeroize + Clone;
-    type KE2State: FromBytes + ToBytesWithPointers + Zeroize + Clone;
+    /// The state of the client after the first key exchange message
+    type KE1State: FromBytes + ToBytes + Zeroize + Clone;
+    /// The state of the server after the second key exchange message
+    type KE2State: FromBytes + ToBytes + Zeroize + Clone;
+    /// The first key exchange message, sent by the client
     type KE1Message: FromBytes + ToBytes + Clone;
+    /// The second key exchange message, sent by the server
     type KE2Message: FromBytes + ToBytes + Clone;
+    /// The third key exchange message, sent by the client
     type KE3Message: FromBytes + ToBytes + Clone;
 
+    /// Generates the first key exchange message, on the client
     fn generate_ke1<R: RngCore + CryptoRng>(
         rng: &mut R,
     ) -> Result<(Self::KE1State, Self::KE1Message), ProtocolError>;
 
-    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    /// Generates the second key exchange message, on the server
+    #[allow(clippy::type_complexity)]
     fn generate_ke2<R: RngCore + CryptoRng>(
         rng: &mut R,
-        l1_bytes: Vec<u8>,
-        l2_bytes: Vec<u8>,
         ke1_message: Self::KE1Message,
-        client_s_pk: PublicKey,
-        server_s_sk: PrivateKey,
-        id_u: Vec<u8>,
-        id_s: Vec<u8>,
-        context: Vec<u8>,
+        inputs: Ke2Inputs<'_>,
     ) -> Result<(Self::KE2State, Self::KE2Message), ProtocolError>;
 
-    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    /// Authenticates the server and generates the third key exchange message,
+    /// on the client. Returns the session key along with the message
+    #[allow(clippy::type_complexity)]
     fn generate_ke3(
-        l2_component: Vec<u8>,
         ke2_message: Self::KE2Message,
         ke1_state: &Self::KE1State,
-        serialized_credential_request: &[u8],
-        server_s_pk: PublicKey,
-        client_s_sk: PrivateKey,
-        id_u: Vec<u8>,
-        id_s: Vec<u8>,
-        context: Vec<u8>,
+        inputs: Ke3Inputs<'_>,
     ) -> Result<(Vec<u8>, Self::KE3Message), ProtocolError>;
 
+    /// Authenticates the client, on the server, along with its serialized
+    /// application payload as produced by [ClientPayload::serialize]. Returns
+    /// the session key
     #[allow(clippy::type_complexity)]
     fn finish_ke(
         ke3_message: Self::KE3Message,
         ke2_state: &Self::KE2State,
+        client_payload: &[u8],
     ) -> Result<Vec<u8>, ProtocolError>;
 
+    /// The size of the serialized second key exchange message
     fn ke2_message_size() -> usize;
+
+    /// The size of the serialized third key exchange message
+    fn ke3_message_size() -> usize;
+
+    /// Derives the static public key of a party from its static private key.
+    /// By default, this is the corresponding group element, as used in
+    /// Diffie-Hellman
+    fn derive_static_public_key(private_key: &PrivateKey) -> Result<PublicKey, InternalPakeError> {
+        Ok(KeyPair::<G>::public_from_private(private_key))
+    }
+
+    /// Checks a static public key received from the other party. By default,
+    /// it must be a valid group element
+    fn check_static_public_key(key: PublicKey) -> Result<PublicKey, InternalPakeError> {
+        KeyPair::<G>::check_public_key(key)
+    }
 }
 
+/// Deserialization of the messages and states of a key exchange
 pub trait FromBytes: Sized {
+    /// Deserializes from bytes, with the sizes of the given ciphersuite
     fn from_bytes<CS: CipherSuite>(input: &[u8]) -> Result<Self, PakeError>;
 }
 
+/// Serialization of the messages and states of a key exchange
 pub trait ToBytes {
+    /// Serializes into bytes
     fn to_bytes(&self) -> Vec<u8>;
 }
 
-pub trait ToBytesWithPointers {
-    fn to_bytes(&self) -> Vec<u8>;
-
-    // Only used for tests to grab raw pointers to data
-    #[cfg(test)]
+// Only used for tests to grab raw pointers to the data of the states of the
+// key exchanges implemented in this crate
+#[cfg(test)]
+pub trait AsBytePointers {
+    /// Returns the raw pointers to the data
     fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)>;
 }
@@ -4,6 +4,8 @@
 // LICENSE file in the root directory of this source tree.
 
 //! An implementation of the Triple Diffie-Hellman key exchange protocol
+#[cfg(test)]
+use crate::key_exchange::traits::AsBytePointers;
 use crate::{
     ciphersuite::CipherSuite,
     errors::{
@@ -12,8 +14,8 @@ use crate::{
     },
     group::Group,
     hash::Hash,
-    key_exchange::traits::{FromBytes, KeyExchange, ToBytes, ToBytesWithPointers},
-    keypair::{KeyPair, PrivateKey, PublicKey, SizedBytesExt},
+    key_exchange::traits::{client_mac, FromBytes, Ke2Inputs, Ke3Inputs, KeyExchange, ToBytes},
+    keypair::{KeyPair, PrivateKey, PublicKey},
     serialization::serialize,
 };
 use digest::{Digest, FixedOutput};
@@ -21,16 +23,14 @@ use generic_array::{
     typenum::{Unsigned, U32},
     ArrayLength, GenericArray,
 };
-use generic_bytes::SizedBytes;
 use hkdf::Hkdf;
 use hmac::{Hmac, Mac, NewMac};
 use rand::{CryptoRng, RngCore};
 use zeroize::Zeroize;
 
-const KEY_LEN: usize = 32;
 pub(crate) type NonceLen = U32;
 
-static STR_RFC: &[u8] = b"RFCXXXX";
+pub(crate) static STR_RFC: &[u8] = b"RFCXXXX";
 static STR_CLIENT_MAC: &[u8] = b"ClientMAC";
 static STR_HANDSHAKE_SECRET: &[u8] = b"HandshakeSecret";
 static STR_SERVER_MAC: &[u8] = b"ServerMAC";
@@ -71,37 +71,28 @@ impl<D: Hash, G: Group> KeyExchange<D, G> for TripleDH {
     #[allow(clippy::type_complexity)]
     fn generate_ke2<R: RngCore + CryptoRng>(
         rng: &mut R,
-        serialized_credential_request: Vec<u8>,
-        l2_bytes: Vec<u8>,
         ke1_message: Self::KE1Message,
-        client_s_pk: PublicKey,
-        server_s_sk: PrivateKey,
-        id_u: Vec<u8>,
-        id_s: Vec<u8>,
-        context: Vec<u8>,
+        inputs: Ke2Inputs<'_>,
     ) -> Result<(Self::KE2State, Self::KE2Message), ProtocolError> {
         let server_e_kp = KeyPair::<G>::generate_random(rng);
         let server_nonce = generate_nonce::<R>(rng);
 
-        let mut transcript_hasher = D::new()
-            .chain(STR_RFC)
-            .chain(&serialize(&context, 2))
-            .chain(&id_u)
-            .chain(&serialized_credential_request[..])
-            .chain(&id_s)
-            .chain(&l2_bytes[..])
+        let mut transcript_hasher = inputs
+            .transcript
+            .hasher::<D>()
             .chain(&server_nonce[..])
-            .chain(&server_e_kp.public().to_arr());
+            .chain(&server_e_kp.public()[..]);
 
         let (session_key, km2, km3) = derive_3dh_keys::<D, G>(
             TripleDHComponents {
                 pk1: ke1_message.client_e_pk.clone(),
                 sk1: server_e_kp.private().clone(),
                 pk2: ke1_message.client_e_pk,
-                sk2: server_s_sk,
-                pk3: client_s_pk,
+                sk2: inputs.server_s_sk.clone(),
+                pk3: inputs.client_s_pk.clone(),
                 sk3: server_e_kp.private().clone(),
             },
+            &[],
             &transcript_hasher.clone().finalize(),
         )?;
 
@@ -128,34 +119,25 @@ impl<D: Hash, G: Group> KeyExchange<D, G> for TripleDH {
 
     #[allow(clippy::type_complexity)]
     fn generate_ke3(
-        l2_component: Vec<u8>,
         ke2_message: Self::KE2Message,
         ke1_state: &Self::KE1State,
-        serialized_credential_request: &[u8],
-        server_s_pk: PublicKey,
-        client_s_sk: PrivateKey,
-        id_u: Vec<u8>,
-        id_s: Vec<u8>,
-        context: Vec<u8>,
+        inputs: Ke3Inputs<'_>,
     ) -> Result<(Vec<u8>, Self::KE3Message), ProtocolError> {
-        let mut transcript_hasher = D::new()
-            .chain(STR_RFC)
-            .chain(&serialize(&context, 2))
-            .chain(&id_u)
-            .chain(&serialized_credential_request)
-            .chain(&id_s)
-            .chain(&l2_component[..])
+        let mut transcript_hasher = inputs
+            .transcript
+            .hasher::<D>()
             .chain(&ke2_message.to_bytes_without_info_or_mac());
 
         let (session_key, km2, km3) = derive_3dh_keys::<D, G>(
             TripleDHComponents {
                 pk1: ke2_message.server_e_pk.clone(),
                 sk1: ke1_state.client_e_sk.clone(),
-                pk2: server_s_pk,
+                pk2: inputs.server_s_pk.clone(),
                 sk2: ke1_state.client_e_sk.clone(),
                 pk3: ke2_message.server_e_pk.clone(),
-                sk3: client_s_sk,
+                sk3: inputs.client_s_sk.clone(),
             },
+            &[],
             &transcript_hasher.clone().finalize(),
         )?;
 
@@ -171,9 +153,8 @@ impl<D: Hash, G: Group> KeyExchange<D, G> for TripleDH {
 
         transcript_hasher.update(ke2_message.mac.to_vec());
 
-        let mut client_mac =
-            Hmac::<D>::new_varkey(&km3).map_err(|_| InternalPakeError::HmacError)?;
-        client_mac.update(&transcript_hasher.finalize());
+        let client_payload = inputs.client_payload.serialize::<D>(&session_key)?;
+        let client_mac = client_mac::<D>(&km3, &transcript_hasher.finalize(), &client_payload)?;
 
         Ok((
             session_key.to_vec(),
@@ -187,10 +168,10 @@ impl<D: Hash, G: Group> KeyExchange<D, G> for TripleDH {
     fn finish_ke(
         ke3_message: Self::KE3Message,
         ke2_state: &Self::KE2State,
+        client_payload: &[u8],
     ) -> Result<Vec<u8>, ProtocolError> {
-        let mut client_mac =
-            Hmac::<D>::new_varkey(&ke2_state.km3).map_err(|_| InternalPakeError::HmacError)?;
-        client_mac.update(&ke2_state.hashed_transcript);
+        let client_mac =
+            client_mac::<D>(&ke2_state.km3, &ke2_state.hashed_transcript, client_payload)?;
 
         if client_mac.verify(&ke3_message.mac).is_err() {
             return Err(ProtocolError::VerificationError(
@@ -202,7 +183,13 @@ impl<D: Hash, G: Group> KeyExchange<D, G> for TripleDH {
     }
 
     fn ke2_message_size() -> usize {
-        NonceLen::to_usize() + KEY_LEN + <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
+        NonceLen::to_usize()
+            + G::ElemLen::to_usize()
+            + <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
+    }
+
+    fn ke3_message_size() -> usize {
+        <<D as FixedOutput>::OutputSize as Unsigned>::to_usize()
     }
 }
 
@@ -210,8 +197,8 @@ impl<D: Hash, G: Group> KeyExchange<D, G> for TripleDH {
 #[derive(PartialEq, Eq, Zeroize, Clone)]
 #[zeroize(drop)]
 pub struct Ke1State {
-    client_e_sk: PrivateKey,
-    client_nonce: GenericArray<u8, NonceLen>,
+    pub(crate) client_e_sk: PrivateKey,
+    pub(crate) client_nonce: GenericArray<u8, NonceLen>,
 }
 
 /// The first key exchange message
@@ -223,31 +210,31 @@ pub struct Ke1Message {
 
 impl FromBytes for Ke1State {
     fn from_bytes<CS: CipherSuite>(bytes: &[u8]) -> Result<Self, PakeError> {
+        let key_len = <CS::Group as Group>::ScalarLen::to_usize();
         let nonce_len = NonceLen::to_usize();
-        let checked_bytes = check_slice_size_atleast(bytes, KEY_LEN + nonce_len, "ke1_state")?;
+        let checked_bytes = check_slice_size_atleast(bytes, key_len + nonce_len, "ke1_state")?;
 
         Ok(Self {
-            client_e_sk: PrivateKey::from_bytes(&checked_bytes[..KEY_LEN])?,
+            client_e_sk: PrivateKey::from_bytes(&checked_bytes[..key_len]),
             client_nonce: GenericArray::clone_from_slice(
-                &checked_bytes[KEY_LEN..KEY_LEN + nonce_len],
+                &checked_bytes[key_len..key_len + nonce_len],
             ),
         })
     }
 }
 
-impl ToBytesWithPointers for Ke1State {
+impl ToBytes for Ke1State {
     fn to_bytes(&self) -> Vec<u8> {
-        let output: Vec<u8> = [&self.client_e_sk.to_arr(), &self.client_nonce[..]].concat();
+        let output: Vec<u8> = [&self.client_e_sk[..], &self.client_nonce[..]].concat();
         output
     }
+}
 
-    #[cfg(test)]
+#[cfg(test)]
+impl AsBytePointers for Ke1State {
     fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
         vec![
-            (
-                self.client_e_sk.as_ptr(),
-                <PrivateKey as SizedBytes>::Len::to_usize(),
-            ),
+            (self.client_e_sk.as_ptr(), self.client_e_sk.len()),
             (self.client_nonce.as_ptr(), NonceLen::to_usize()),
         ]
     }
@@ -255,28 +242,29 @@ impl ToBytesWithPointers for Ke1State {
 
 impl ToBytes for Ke1Message {
     fn to_bytes(&self) -> Vec<u8> {
-        [&self.client_nonce[..], &self.client_e_pk.to_arr()].concat()
+        [&self.client_nonce[..], &self.client_e_pk[..]].concat()
     }
 }
 
 impl FromBytes for Ke1Message {
     fn from_bytes<CS: CipherSuite>(ke1_message_bytes: &[u8]) -> Result<Self, PakeError> {
+        let key_len = <CS::Group as Group>::ElemLen::to_usize();
         let nonce_len = NonceLen::to_usize();
         let checked_nonce =
-            check_slice_size(ke1_message_bytes, nonce_len + KEY_LEN, "ke1_message nonce")?;
+            check_slice_size(ke1_message_bytes, nonce_len + key_len, "ke1_message nonce")?;
 
         Ok(Self {
             client_nonce: GenericArray::clone_from_slice(&checked_nonce[..nonce_len]),
-            client_e_pk: PublicKey::from_bytes(&checked_nonce[nonce_len..])?,
+            client_e_pk: PublicKey::from_bytes(&checked_nonce[nonce_len..]),
         })
     }
 }
 /// The server state produced after the second key exchange message
 #[derive(Clone)]
 pub struct Ke2State<HashLen: ArrayLength<u8>> {
-    km3: GenericArray<u8, HashLen>,
-    hashed_transcript: GenericArray<u8, HashLen>,
-    session_key: GenericArray<u8, HashLen>,
+    pub(crate) km3: GenericArray<u8, HashLen>,
+    pub(crate) hashed_transcript: GenericArray<u8, HashLen>,
+    pub(crate) session_key: GenericArray<u8, HashLen>,
 }
 
 // This can't be derived because of the use of a phantom parameter
@@ -294,7 +282,7 @@ impl<HashLen: ArrayLength<u8>> Drop for Ke2State<HashLen> {
     }
 }
 
-impl<HashLen: ArrayLength<u8>> ToBytesWithPointers for Ke2State<HashLen> {
+impl<HashLen: ArrayLength<u8>> ToBytes for Ke2State<HashLen> {
     fn to_bytes(&self) -> Vec<u8> {
         [
             &self.km3[..],
@@ -303,8 +291,10 @@ impl<HashLen: ArrayLength<u8>> ToBytesWithPointers for Ke2State<HashLen> {
         ]
         .concat()
     }
+}
 
-    #[cfg(test)]
+#[cfg(test)]
+impl<HashLen: ArrayLength<u8>> AsBytePointers for Ke2State<HashLen> {
     fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
         vec![
             (self.km3.as_ptr(), HashLen::to_usize()),
@@ -345,34 +335,35 @@ impl<HashLen: ArrayLength<u8>> ToBytes for Ke2Message<HashLen> {
 
 impl<HashLen: ArrayLength<u8>> Ke2Message<HashLen> {
     fn to_bytes_without_info_or_mac(&self) -> Vec<u8> {
-        [&self.server_nonce[..], &self.server_e_pk.to_arr()].concat()
+        [&self.server_nonce[..], &self.server_e_pk[..]].concat()
     }
 }
 
 impl<HashLen: ArrayLength<u8>> FromBytes for Ke2Message<HashLen> {
     fn from_bytes<CS: CipherSuite>(input: &[u8]) -> Result<Self, PakeError> {
+        let key_len = <CS::Group as Group>::ElemLen::to_usize();
         let nonce_len = NonceLen::to_usize();
         let checked_nonce = check_slice_size_atleast(input, nonce_len, "ke2_message nonce")?;
 
         let unchecked_server_e_pk = check_slice_size_atleast(
             &checked_nonce[nonce_len..],
-            KEY_LEN,
+            key_len,
             "ke2_message server_e_pk",
         )?;
         let checked_mac = check_slice_size(
-            &unchecked_server_e_pk[KEY_LEN..],
+            &unchecked_server_e_pk[key_len..],
             HashLen::to_usize(),
             "ke1_message mac",
         )?;
 
         // Check the public key bytes
         let server_e_pk = KeyPair::<CS::Group>::check_public_key(PublicKey::from_bytes(
-            &unchecked_server_e_pk[..KEY_LEN],
-        )?)?;
+            &unchecked_server_e_pk[..key_len],
+        ))?;
 
         Ok(Self {
             server_nonce: GenericArray::clone_from_slice(&checked_nonce[..nonce_len]),
-            server_e_pk: PublicKey::from_bytes(&server_e_pk)?,
+            server_e_pk: PublicKey::from_bytes(&server_e_pk),
             mac: GenericArray::clone_from_slice(checked_mac),
         })
     }
@@ -380,18 +371,18 @@ impl<HashLen: ArrayLength<u8>> FromBytes for Ke2Message<HashLen> {
 
 #[allow(clippy::upper_case_acronyms)]
 // The triple of public and private components used in the 3DH computation
-struct TripleDHComponents {
-    pk1: PublicKey,
-    sk1: PrivateKey,
-    pk2: PublicKey,
-    sk2: PrivateKey,
-    pk3: PublicKey,
-    sk3: PrivateKey,
+pub(crate) struct TripleDHComponents {
+    pub(crate) pk1: PublicKey,
+    pub(crate) sk1: PrivateKey,
+    pub(crate) pk2: PublicKey,
+    pub(crate) sk2: PrivateKey,
+    pub(crate) pk3: PublicKey,
+    pub(crate) sk3: PrivateKey,
 }
 
 #[allow(clippy::upper_case_acronyms)]
 // Consists of a session key, followed by two mac keys: (session_key, km2, km3)
-type TripleDHDerivationResult<D> = (
+pub(crate) type TripleDHDerivationResult<D> = (
     GenericArray<u8, <D as FixedOutput>::OutputSize>,
     GenericArray<u8, <D as FixedOutput>::OutputSize>,
     GenericArray<u8, <D as FixedOutput>::OutputSize>,
@@ -400,7 +391,7 @@ type TripleDHDerivationResult<D> = (
 /// The third key exchange message
 #[derive(Clone)]
 pub struct Ke3Message<HashLen: ArrayLength<u8>> {
-    mac: GenericArray<u8, HashLen>,
+    pub(crate) mac: GenericArray<u8, HashLen>,
 }
 
 impl<HashLen: ArrayLength<u8>> ToBytes for Ke3Message<HashLen> {
@@ -422,19 +413,33 @@ impl<HashLen: ArrayLength<u8>> FromBytes for Ke3Message<HashLen> {
 // Helper functions
 
 // Internal function which takes the public and private components of the client and server keypairs, along
-// with some auxiliary metadata, to produce the session key and two MAC keys
-fn derive_3dh_keys<D: Hash, G: Group>(
+// with some auxiliary metadata, to produce the session key and two MAC keys. Any additional input keying material,
+// such as the shared secret of a KEM, is appended to the output of the Diffie-Hellman computations
+pub(crate) fn derive_3dh_keys<D: Hash, G: Group>(
     dh: TripleDHComponents,
+    additional_ikm: &[u8],
     hashed_derivation_transcript: &[u8],
 ) -> Result<TripleDHDerivationResult<D>, ProtocolError> {
-    let ikm: Vec<u8> = [
+    let mut ikm: Vec<u8> = [
         &KeyPair::<G>::diffie_hellman(dh.pk1, dh.sk1)?[..],
         &KeyPair::<G>::diffie_hellman(dh.pk2, dh.sk2)?[..],
         &KeyPair::<G>::diffie_hellman(dh.pk3, dh.sk3)?[..],
+        additional_ikm,
     ]
     .concat();
 
-    let extracted_ikm = Hkdf::<D>::new(None, &ikm);
+    let result = derive_keys::<D>(&ikm, hashed_derivation_transcript);
+    ikm.zeroize();
+    result
+}
+
+// Internal function which expands the input keying material of a key exchange,
+// along with the hashed transcript, into the session key and two MAC keys
+pub(crate) fn derive_keys<D: Hash>(
+    ikm: &[u8],
+    hashed_derivation_transcript: &[u8],
+) -> Result<TripleDHDerivationResult<D>, ProtocolError> {
+    let extracted_ikm = Hkdf::<D>::new(None, ikm);
     let handshake_secret = derive_secrets::<D>(
         &extracted_ikm,
         STR_HANDSHAKE_SECRET,
@@ -466,7 +471,7 @@ fn derive_3dh_keys<D: Hash, G: Group>(
     ))
 }
 
-fn hkdf_expand_label<D: Hash>(
+pub(crate) fn hkdf_expand_label<D: Hash>(
     secret: &[u8],
     label: &[u8],
     context: &[u8],
@@ -513,7 +518,7 @@ fn derive_secrets<D: Hash>(
 }
 
 // Generate a random nonce up to NonceLen::to_usize() bytes.
-fn generate_nonce<R: RngCore + CryptoRng>(rng: &mut R) -> GenericArray<u8, NonceLen> {
+pub(crate) fn generate_nonce<R: RngCore + CryptoRng>(rng: &mut R) -> GenericArray<u8, NonceLen> {
     let mut nonce_bytes = vec![0u8; NonceLen::to_usize()];
     rng.fill_bytes(&mut nonce_bytes);
     GenericArray::clone_from_slice(&nonce_bytes)
@@ -7,11 +7,11 @@
 
 #![allow(unsafe_code)]
 
-use crate::errors::InternalPakeError;
+use crate::ciphersuite::CipherSuite;
+use crate::errors::{utils::check_slice_size, InternalPakeError};
 use crate::group::Group;
-#[cfg(test)]
-use generic_array::typenum::Unsigned;
-use generic_array::{typenum::U32, GenericArray};
+use crate::key_exchange::traits::KeyExchange;
+use generic_array::{typenum::Unsigned, GenericArray};
 use generic_bytes::{SizedBytes, TryFromSizedBytesError};
 #[cfg(test)]
 use proptest::prelude::*;
@@ -81,7 +81,7 @@ impl<G: Group> KeyPair<G> {
     pub(crate) fn generate_random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
         let sk = G::random_nonzero_scalar(rng);
         let sk_bytes = G::scalar_as_bytes(&sk);
-        let pk = G::base_point().mult_by_slice(sk_bytes);
+        let pk = G::base_point().mult_by_slice(&sk_bytes);
         Self {
             pk: PublicKey(Key(pk.to_arr().to_vec())),
             sk: PrivateKey(Key(sk_bytes.to_vec())),
@@ -104,7 +104,8 @@ impl<G: Group> KeyPair<G> {
     /// representation (i.e. can be mapped to a curve point), but presents
     /// some risk - e.g. small subgroup check
     pub(crate) fn check_public_key(key: PublicKey) -> Result<PublicKey, InternalPakeError> {
-        G::from_element_slice(GenericArray::from_slice(&key.0)).map(|_| key)
+        let checked_bytes = check_slice_size(&key.0, G::ElemLen::to_usize(), "public_key")?;
+        G::from_element_slice(GenericArray::from_slice(checked_bytes)).map(|_| key)
     }
 
     /// Computes the diffie hellman function on a public key and private key
@@ -112,24 +113,67 @@ impl<G: Group> KeyPair<G> {
         pk: PublicKey,
         sk: PrivateKey,
     ) -> Result<Vec<u8>, InternalPakeError> {
-        let pk_data = GenericArray::<u8, G::ElemLen>::from_slice(&pk.0[..]);
-        let point = G::from_element_slice(pk_data)?;
-        let secret_data = GenericArray::<u8, G::ScalarLen>::from_slice(&sk.0[..]);
+        let pk_data = check_slice_size(&pk.0[..], G::ElemLen::to_usize(), "public_key")?;
+        let point = G::from_element_slice(GenericArray::from_slice(pk_data))?;
+        let secret_data = GenericArray::<u8, G::ScalarLen>::from_slice(check_slice_size(
+            &sk.0[..],
+            G::ScalarLen::to_usize(),
+            "private_key",
+        )?);
         Ok(G::mult_by_slice(&point, secret_data).to_arr().to_vec())
     }
 
     /// Obtains a KeyPair from a slice representing the private key
     pub fn from_private_key_slice(input: &[u8]) -> Result<Self, InternalPakeError> {
-        let sk = PrivateKey(Key::from_arr(GenericArray::from_slice(input))?);
+        let checked_bytes = check_slice_size(input, G::ScalarLen::to_usize(), "private_key")?;
+        let sk = PrivateKey(Key(checked_bytes.to_vec()));
         let pk = Self::public_from_private(&sk);
         Self::new(pk, sk)
     }
 
+    /// Generating a random static keypair given a cryptographic rng, whose
+    /// public key is the one expected by the key exchange of the ciphersuite.
+    /// A private key from which the key exchange fails to derive a public key
+    /// is discarded, and another one is drawn
+    pub(crate) fn generate_random_static<CS: CipherSuite<Group = G>, R: RngCore + CryptoRng>(
+        rng: &mut R,
+    ) -> Self {
+        loop {
+            let mut keypair = Self::generate_random(rng);
+            if let Ok(pk) =
+                <CS::KeyExchange as KeyExchange<CS::Hash, G>>::derive_static_public_key(&keypair.sk)
+            {
+                keypair.pk = pk;
+                return keypair;
+            }
+        }
+    }
+
+    /// Obtains a static keypair from a slice representing the private key,
+    /// whose public key is the one expected by the key exchange of the
+    /// ciphersuite
+    pub(crate) fn from_private_key_slice_static<CS: CipherSuite<Group = G>>(
+        input: &[u8],
+    ) -> Result<Self, InternalPakeError> {
+        let mut keypair = Self::from_private_key_slice(input)?;
+        keypair.pk =
+            <CS::KeyExchange as KeyExchange<CS::Hash, G>>::derive_static_public_key(&keypair.sk)?;
+        Ok(keypair)
+    }
+
+    /// Check whether a static public key is valid for the key exchange of the
+    /// ciphersuite
+    pub(crate) fn check_static_public_key<CS: CipherSuite<Group = G>>(
+        key: PublicKey,
+    ) -> Result<PublicKey, InternalPakeError> {
+        <CS::KeyExchange as KeyExchange<CS::Hash, G>>::check_static_public_key(key)
+    }
+
     #[cfg(test)]
     pub fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
         vec![
-            (self.pk.as_ptr(), KeyLen::to_usize()),
-            (self.sk.as_ptr(), KeyLen::to_usize()),
+            (self.pk.as_ptr(), G::ElemLen::to_usize()),
+            (self.sk.as_ptr(), G::ScalarLen::to_usize()),
         ]
     }
 }
@@ -151,9 +195,8 @@ impl<G: Group + Debug> KeyPair<G> {
     }
 }
 
-type KeyLen = U32;
-
-/// A minimalist key type built around a \[u8; 32\]
+/// A minimalist key type built around a byte vector, whose length is
+/// determined by the group it is used with
 #[derive(Debug, PartialEq, Eq, Clone, Zeroize)]
 // Ensure Key material is zeroed after use.
 #[zeroize(drop)]
@@ -168,18 +211,6 @@ impl Deref for Key {
     }
 }
 
-// Don't make it implement SizedBytes so that it's not constructible outside of this module.
-impl Key {
-    fn to_arr(&self) -> GenericArray<u8, KeyLen> {
-        GenericArray::clone_from_slice(&self.0[..])
-    }
-
-    #[allow(clippy::unnecessary_wraps)]
-    fn from_arr(key_bytes: &GenericArray<u8, KeyLen>) -> Result<Self, TryFromSizedBytesError> {
-        Ok(Key(key_bytes.to_vec()))
-    }
-}
-
 /// Wrapper around a Key to enforce that it's a private one.
 #[derive(Debug, PartialEq, Eq, Clone, Zeroize)]
 // Ensure Key material is zeroed after use.
@@ -195,15 +226,11 @@ impl Deref for PrivateKey {
     }
 }
 
-impl SizedBytes for PrivateKey {
-    type Len = KeyLen;
-
-    fn to_arr(&self) -> GenericArray<u8, Self::Len> {
-        self.0.to_arr()
-    }
-
-    fn from_arr(key_bytes: &GenericArray<u8, Self::Len>) -> Result<Self, TryFromSizedBytesError> {
-        Ok(PrivateKey(Key::from_arr(key_bytes)?))
+impl PrivateKey {
+    /// Convert from bytes. The length is checked against the group when the
+    /// key is used.
+    pub fn from_bytes(key_bytes: &[u8]) -> Self {
+        PrivateKey(Key(key_bytes.to_vec()))
     }
 }
 
@@ -222,15 +249,11 @@ impl Deref for PublicKey {
     }
 }
 
-impl SizedBytes for PublicKey {
-    type Len = KeyLen;
-
-    fn to_arr(&self) -> GenericArray<u8, Self::Len> {
-        self.0.to_arr()
-    }
-
-    fn from_arr(key_bytes: &GenericArray<u8, Self::Len>) -> Result<Self, TryFromSizedBytesError> {
-        Ok(PublicKey(Key::from_arr(key_bytes)?))
+impl PublicKey {
+    /// Convert from bytes. The length is checked against the group when the
+    /// key is used.
+    pub fn from_bytes(key_bytes: &[u8]) -> Self {
+        PublicKey(Key(key_bytes.to_vec()))
     }
 }
 
@@ -239,13 +262,12 @@ mod tests {
     use super::*;
     use crate::errors::*;
     use curve25519_dalek::ristretto::RistrettoPoint;
-    use generic_array::typenum::Unsigned;
     use rand::rngs::OsRng;
     use std::slice::from_raw_parts;
 
     #[test]
     fn test_zeroize_key() -> Result<(), ProtocolError> {
-        let key_len = KeyLen::to_usize();
+        let key_len = 32;
         let mut key = Key(vec![1u8; key_len]);
         let ptr = key.as_ptr();
 
@@ -307,4 +329,23 @@ mod tests {
             prop_assert_eq!(sk_bytes, kp2_private_bytes);
         }
     }
+
+    #[cfg(feature = "p256")]
+    proptest! {
+        #[test]
+        fn test_p256_check(kp in KeyPair::<p256_::ProjectivePoint>::uniform_keypair_strategy()) {
+            let pk = kp.public();
+            prop_assert!(KeyPair::<p256_::ProjectivePoint>::check_public_key(pk.clone()).is_ok());
+        }
+
+        #[test]
+        fn test_p256_dh(kp1 in KeyPair::<p256_::ProjectivePoint>::uniform_keypair_strategy(),
+                        kp2 in KeyPair::<p256_::ProjectivePoint>::uniform_keypair_strategy()) {
+
+            let dh1 = KeyPair::<p256_::ProjectivePoint>::diffie_hellman(kp1.public().clone(), kp2.private().clone())?;
+            let dh2 = KeyPair::<p256_::ProjectivePoint>::diffie_hellman(kp2.public().clone(), kp1.private().clone())?;
+
+            prop_assert_eq!(dh1, dh2);
+        }
+    }
 }
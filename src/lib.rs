@@ -26,14 +26,28 @@
 //!     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //!     type Hash = sha2::Sha512;
 //!     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//!     const ID: u16 = 0x0001;
 //! }
 //! ```
+//! The `ID` identifies the ciphersuite in the password files, and must differ between ciphersuites which differ in any of
+//! their primitives.
+//!
+//! Besides [key_exchange::tripledh::TripleDH], the `KeyExchange` can also be [key_exchange::hmqv::HMQV], which replaces
+//! the three Diffie-Hellman computations of each party with a single scalar multiplication, or
+//! [key_exchange::hybrid::HybridTripleDH], which additionally mixes in the shared secret of a key encapsulation mechanism
+//! implementing [key_exchange::hybrid::Kem], such as the post-quantum ML-KEM-768 provided by the `ml-kem` feature.
+//!
 //! See [examples/simple_login.rs](https://github.com/novifinancial/opaque-ke/blob/master/examples/simple_login.rs)
 //! for a working example of a simple password-based login using OPAQUE.
 //!
 //! Note that our choice of slow hashing function in this example, `NoOpHash`, is selected only to ensure
-//! that the tests execute quickly. A real application should use an actual slow hashing function, such as `scrypt`,
-//! which can be enabled through the `slow-hash` feature. See more details in the [features](#features) section.
+//! that the tests execute quickly. A real application should use an actual slow hashing function, such as `scrypt`
+//! or Argon2id, which can be enabled through the `slow-hash` and `slow-hash-argon2` features. See more details in the [features](#features) section.
+//!
+//! The cost parameters of the slow hashing function can be chosen at runtime, by passing a value of the `SlowHash` type
+//! of the `CipherSuite` through the `slow_hash` field of [ClientRegistrationFinishParameters] and
+//! [ClientLoginFinishParameters]. When none is supplied, `SlowHash::default_params` is used. Since the server does not
+//! keep track of these parameters, the client must supply the ones used during registration when logging in.
 //!
 //! ## Setup
 //! To set up the protocol, the server begins by creating a `ServerSetup` object:
@@ -47,6 +61,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! use rand::{rngs::OsRng, RngCore};
 //! let mut rng = OsRng;
@@ -79,6 +94,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! use opaque_ke::ClientRegistration;
 //! use rand::{rngs::OsRng, RngCore};
@@ -109,6 +125,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -119,7 +136,7 @@
 //! use opaque_ke::ServerRegistration;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! let server_registration_start_result = ServerRegistration::<Default>::start(
+//! let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng,
 //!     &server_setup,
 //!     client_registration_start_result.message,
 //!     b"alice@example.com",
@@ -146,6 +163,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -155,7 +173,7 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! let client_registration_finish_result = client_registration_start_result.state.finish(
 //!     &mut client_rng,
 //!     server_registration_start_result.message,
@@ -167,14 +185,17 @@
 //! ### Server Registration Finish
 //! In the fourth step of registration, the server takes as input
 //! a [RegistrationUpload] from the client, and
-//! a [ServerRegistration] from the second step.
+//! the [RegistrationKeyIds] returned by the second step.
 //! The server runs [ServerRegistration::finish] to produce a finalized [ServerRegistration].
 //! At this point, the client can be considered as successfully registered, and the server can invoke
-//! [ServerRegistration::serialize] to store the password file for use during the login protocol.
+//! [ServerRegistration::serialize] to store the password file for use during the login protocol. The serialized
+//! password file records the identifier of the ciphersuite, the key IDs of the OPRF seed and of the server keypair (see [Key Rotation](#key-rotation))
+//! and the parameters of the slow hashing function, which the client sends along with its [RegistrationUpload].
 //! ```
 //! # use opaque_ke::{
 //! #   errors::ProtocolError,
-//! #   ClientRegistration, ClientRegistrationFinishParameters, ServerRegistration, ServerSetup,
+//! #   ClientRegistration, ClientRegistrationFinishParameters, ServerRegistration,
+//! #   ServerSetup,
 //! #   slow_hash::NoOpHash,
 //! # };
 //! # use opaque_ke::CipherSuite;
@@ -184,6 +205,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -193,11 +215,12 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
 //! let password_file = ServerRegistration::<Default>::finish(
 //!     client_registration_finish_result.message,
-//! );
+//!     server_registration_start_result.key_ids,
+//! )?;
 //! # Ok::<(), ProtocolError>(())
 //! ```
 //!
@@ -226,6 +249,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! use opaque_ke::ClientLogin;
@@ -259,6 +283,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -268,9 +293,9 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
-//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
+//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message, server_registration_start_result.key_ids)?.serialize();
 //! # let client_login_start_result = ClientLogin::<Default>::start(
 //! #   &mut client_rng,
 //! #   b"password",
@@ -312,6 +337,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -321,9 +347,9 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
-//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
+//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message, server_registration_start_result.key_ids)?.serialize();
 //! # let client_login_start_result = ClientLogin::<Default>::start(
 //! #     &mut client_rng,
 //! #     b"password",
@@ -347,7 +373,7 @@
 //! ```
 //! # use opaque_ke::{
 //! #   errors::ProtocolError,
-//! #   ClientRegistration, ClientRegistrationFinishParameters, ServerRegistration, ClientLogin, ClientLoginFinishParameters, ServerLogin, ServerLoginStartParameters, CredentialFinalization, ServerSetup,
+//! #   ClientRegistration, ClientRegistrationFinishParameters, ServerRegistration, ClientLogin, ClientLoginFinishParameters, ServerLogin, ServerLoginFinishParameters, ServerLoginStartParameters, CredentialFinalization, ServerSetup,
 //! #   slow_hash::NoOpHash,
 //! # };
 //! # use opaque_ke::CipherSuite;
@@ -357,6 +383,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -366,9 +393,9 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
-//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
+//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message, server_registration_start_result.key_ids)?.serialize();
 //! # let client_login_start_result = ClientLogin::<Default>::start(
 //! #   &mut client_rng,
 //! #   b"password",
@@ -385,6 +412,7 @@
 //! # )?;
 //! let server_login_finish_result = server_login_start_result.state.finish(
 //!     client_login_finish_result.message,
+//!     ServerLoginFinishParameters::default(),
 //! )?;
 //!
 //! assert_eq!(
@@ -413,6 +441,17 @@
 //! The session key can be accessed from the `session_key` field of [ClientLoginFinishResult] and [ServerLoginFinishResult]. See
 //! the combination of [Client Login Finish](#client-login-finish) and [Server Login Finish](#server-login-finish) for example usage.
 //!
+//! Rather than using the session key directly, applications which need several keys, such as separate encryption, MAC and
+//! resumption keys, can call `session_keys()` on either result. The returned [SessionKeys] borrows the session key, which
+//! the result zeroizes when dropped, and derives a key for any label, context and length through [SessionKeys::export],
+//! in the style of the exporters of TLS 1.3.
+//!
+//! Both results also contain a `transcript_hash` field, which is the hash of the whole login transcript, up to and
+//! including the [CredentialFinalization], and which can be used for channel binding. In addition, a
+//! [ServerLoginFinishResult] records the identities `id_u` and `id_s` used in the login, and the client's static public
+//! key `client_s_pk` from the password file. For auditing, [ServerLogin::record_kind] tells apart the logins processed
+//! with the dummy password file described in [Dummy Server Login](#dummy-server-login), which can never succeed.
+//!
 //! ## Checking Server Consistency
 //!
 //! A [ClientLoginFinishResult] contains the `server_s_pk` field, which is represents the static public key of the server that is established
@@ -431,6 +470,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -441,12 +481,12 @@
 //! # let mut server_rng = OsRng;
 //! // During setup, server generates its static keypair
 //! let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //!
 //! // During setup or registration, the server transmits its static public key to the client
 //! let server_s_pk = server_setup.keypair().public(); // obtained from the server
 //! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::default())?;
-//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
+//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message, server_registration_start_result.key_ids)?.serialize();
 //! # let client_login_start_result = ClientLogin::<Default>::start(
 //! #     &mut client_rng,
 //! #     b"password",
@@ -506,6 +546,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -515,14 +556,14 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! // During registration...
 //! let client_registration_finish_result = client_registration_start_result.state.finish(
 //!     &mut client_rng,
 //!     server_registration_start_result.message,
 //!     ClientRegistrationFinishParameters::default()
 //! )?;
-//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
+//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message, server_registration_start_result.key_ids)?.serialize();
 //! # let client_login_start_result = ClientLogin::<Default>::start(
 //! #     &mut client_rng,
 //! #     b"password",
@@ -557,7 +598,7 @@
 //!
 //! But, for applications that wish to cryptographically bind these identities to
 //! the registered password file as well as the session key output by the login phase, these custom identifiers can be specified through
-//! [ClientRegistrationFinishParameters::WithIdentifiers] in [Client Registration Finish](#client-registration-finish):
+//! the `identifiers` field of [ClientRegistrationFinishParameters] in [Client Registration Finish](#client-registration-finish):
 //! ```
 //! # use opaque_ke::{
 //! #   errors::ProtocolError,
@@ -571,6 +612,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -580,21 +622,22 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
 //! let client_registration_finish_result = client_registration_start_result.state.finish(
 //!     &mut client_rng,
 //!     server_registration_start_result.message,
-//!     ClientRegistrationFinishParameters::WithIdentifiers(
-//!         Identifiers::ClientAndServerIdentifiers(
+//!     ClientRegistrationFinishParameters::new(
+//!         Some(Identifiers::ClientAndServerIdentifiers(
 //!             b"Alice_the_Cryptographer".to_vec(),
 //!             b"Facebook".to_vec(),
-//!         ),
+//!         )),
+//!         None,
 //!     ),
 //! )?;
 //! # Ok::<(), ProtocolError>(())
 //! ```
 //!
-//! The same identifiers must also be supplied using [ServerLoginStartParameters::WithIdentifiers] in [Server Login Start](#server-login-start):
+//! The same identifiers must also be supplied using the `identifiers` field of [ServerLoginStartParameters] in [Server Login Start](#server-login-start):
 //! ```
 //! # use opaque_ke::{
 //! #   errors::ProtocolError,
@@ -608,6 +651,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -617,9 +661,9 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
-//! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::WithIdentifiers(Identifiers::ClientAndServerIdentifiers(b"Alice_the_Cryptographer".to_vec(), b"Facebook".to_vec())))?;
-//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::new(Some(Identifiers::ClientAndServerIdentifiers(b"Alice_the_Cryptographer".to_vec(), b"Facebook".to_vec())), None))?;
+//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message, server_registration_start_result.key_ids)?.serialize();
 //! # let client_login_start_result = ClientLogin::<Default>::start(
 //! #   &mut client_rng,
 //! #   b"password",
@@ -633,17 +677,18 @@
 //!     Some(password_file),
 //!     client_login_start_result.message,
 //!     b"alice@example.com",
-//!     ServerLoginStartParameters::WithIdentifiers(
-//!         Identifiers::ClientAndServerIdentifiers(
+//!     ServerLoginStartParameters::new(
+//!         None,
+//!         Some(Identifiers::ClientAndServerIdentifiers(
 //!             b"Alice_the_Cryptographer".to_vec(),
 //!             b"Facebook".to_vec(),
-//!         ),
+//!         )),
 //!     ),
 //! )?;
 //! # Ok::<(), ProtocolError>(())
 //! ```
 //!
-//! as well as [ClientLoginFinishParameters::WithIdentifiers] in [Client Login Finish](#client-login-finish):
+//! as well as the `identifiers` field of [ClientLoginFinishParameters] in [Client Login Finish](#client-login-finish):
 //! ```
 //! # use opaque_ke::{
 //! #   errors::ProtocolError,
@@ -657,6 +702,7 @@
 //! #     type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDH;
 //! #     type Hash = sha2::Sha512;
 //! #     type SlowHash = opaque_ke::slow_hash::NoOpHash;
+//! #     const ID: u16 = 0x0001;
 //! # }
 //! # use rand::{rngs::OsRng, RngCore};
 //! # let mut client_rng = OsRng;
@@ -666,9 +712,9 @@
 //! # )?;
 //! # let mut server_rng = OsRng;
 //! # let server_setup = ServerSetup::<Default>::new(&mut server_rng);
-//! # let server_registration_start_result = ServerRegistration::<Default>::start(&server_setup, client_registration_start_result.message, b"alice@example.com")?;
-//! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::WithIdentifiers(Identifiers::ClientAndServerIdentifiers(b"Alice_the_Cryptographer".to_vec(), b"Facebook".to_vec())))?;
-//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message).serialize();
+//! # let server_registration_start_result = ServerRegistration::<Default>::start(&mut server_rng, &server_setup, client_registration_start_result.message, b"alice@example.com")?;
+//! # let client_registration_finish_result = client_registration_start_result.state.finish(&mut client_rng, server_registration_start_result.message, ClientRegistrationFinishParameters::new(Some(Identifiers::ClientAndServerIdentifiers(b"Alice_the_Cryptographer".to_vec(), b"Facebook".to_vec())), None))?;
+//! # let password_file_bytes = ServerRegistration::<Default>::finish(client_registration_finish_result.message, server_registration_start_result.key_ids)?.serialize();
 //! # let client_login_start_result = ClientLogin::<Default>::start(
 //! #     &mut client_rng,
 //! #     b"password",
@@ -678,14 +724,16 @@
 //! #     &password_file_bytes[..],
 //! #   )?;
 //! # let server_login_start_result =
-//! #     ServerLogin::start(&mut server_rng, &server_setup, Some(password_file), client_login_start_result.message, b"alice@example.com", ServerLoginStartParameters::WithIdentifiers(Identifiers::ClientAndServerIdentifiers(b"Alice_the_Cryptographer".to_vec(), b"Facebook".to_vec())))?;
+//! #     ServerLogin::start(&mut server_rng, &server_setup, Some(password_file), client_login_start_result.message, b"alice@example.com", ServerLoginStartParameters::new(None, Some(Identifiers::ClientAndServerIdentifiers(b"Alice_the_Cryptographer".to_vec(), b"Facebook".to_vec()))))?;
 //! let client_login_finish_result = client_login_start_result.state.finish(
 //!     server_login_start_result.message,
-//!     ClientLoginFinishParameters::WithIdentifiers(
-//!         Identifiers::ClientAndServerIdentifiers(
+//!     ClientLoginFinishParameters::new(
+//!         None,
+//!         Some(Identifiers::ClientAndServerIdentifiers(
 //!             b"Alice_the_Cryptographer".to_vec(),
 //!             b"Facebook".to_vec(),
-//!         ),
+//!         )),
+//!         None,
 //!     ),
 //! )?;
 //!
@@ -702,12 +750,174 @@
 //! A key exchange protocol typically allows for the specifying of shared "context" information between the two parties before the exchange is complete,
 //! so as to bind the integrity of application-specific data or configuration parameters to the security of the key exchange.
 //! During the login phase, the client and server can specify this context using:
-//! - The second login message, where the server can populate [ServerLoginStartParameters::WithContext], and
-//! - The third login message, where the client can populate [ClientLoginFinishParameters::WithContext].
+//! - The second login message, where the server can populate the `context` field of [ServerLoginStartParameters], and
+//! - The third login message, where the client can populate the `context` field of [ClientLoginFinishParameters].
+//!
+//! Both parties can populate the `identifiers` field along with the `context` field, to specify these fields in addition to
+//! [custom identifiers](#custom-identifiers).
+//!
+//! ## Channel Binding
+//!
+//! When OPAQUE runs inside a secure channel such as TLS, the login can be bound to that channel by having both parties
+//! supply a channel binding, such as a TLS exporter value. The server populates the `channel_binding` field of
+//! [ServerLoginStartParameters], and the client populates the `channel_binding` field of
+//! [ClientLoginFinishParameters]. The channel binding enters the key exchange transcript under its own label, separately
+//! from the context, and a mismatch causes the client to fail with
+//! [KeyExchangeMacValidationError](errors::PakeError::KeyExchangeMacValidationError).
+//!
+//! Since the parameters are structs with optional fields, the channel binding can be combined with the context, the
+//! identifiers and the [application payloads](#application-payloads), by filling in the fields which are needed and
+//! leaving the others to their defaults:
+//! ```
+//! use opaque_ke::ServerLoginStartParameters;
+//! # let tls_exporter = vec![0u8; 32];
+//! let server_login_start_parameters = ServerLoginStartParameters {
+//!     channel_binding: Some(tls_exporter),
+//!     payload: Some(b"server policy".to_vec()),
+//!     ..ServerLoginStartParameters::new(Some(b"context".to_vec()), None)
+//! };
+//! ```
+//!
+//! ## Application Payloads
+//!
+//! The login messages can also carry application data, such as a policy from the server or device information from the
+//! client, which would otherwise require an additional round trip. The server populates the
+//! `payload` field of [ServerLoginStartParameters], and its payload is appended
+//! to the [CredentialResponse] and bound into the key exchange transcript. The client receives it as
+//! `client_login_finish_result.server_payload`, after the server has been authenticated. Similarly, the client populates the
+//! `payload` field of [ClientLoginFinishParameters], which is appended to the [CredentialFinalization] and covered by the
+//! MAC of the third key exchange message, and the server receives it as `server_login_finish_result.client_payload` once
+//! the client has been authenticated. Setting `encrypt_payload` additionally encrypts the payload of the client under a
+//! key derived from the session key. The payload of the server is sent before any key is established, and is therefore
+//! never encrypted. Payloads are at most 65535 bytes long.
+//!
+//! ## Password File Upgrade
+//!
+//! A password file can be replaced during a successful login, for instance in order to move an existing registration to
+//! stronger slow hashing parameters without requiring the client to register again. The client requests this by calling
+//! [ClientLogin::finish_with_upgrade] with the new slow hashing parameters instead of [ClientLogin::finish], in which case
+//! `client_login_finish_result.password_file_upgrade` contains a [PasswordFileUpgrade] message to be sent along with the [CredentialFinalization]. The new registration is
+//! authenticated under the session key, and the export key corresponding to it is returned as
+//! `client_login_finish_result.upgrade_export_key`.
+//!
+//! The server passes this message through the `password_file_upgrade` field of [ServerLoginFinishParameters], and the new
+//! password file records the slow hashing parameters which the client used for the upgrade. Once the client has been authenticated and the message verified,
+//! `server_login_finish_result.password_file` contains the [ServerRegistration] which should replace the stored password file.
+//!
+//! ## Password Change
+//!
+//! Registering a new password from scratch produces a new static keypair for the client, along with a new export key. A
+//! client can instead change its password during a login, by calling [ClientPasswordChange::start] with its [ClientLogin]
+//! and the new password instead of [ClientLogin::finish]. Along with the [ClientLoginFinishResult], this returns a
+//! [RegistrationRequest] to be sent with the [CredentialFinalization]. Once the login is finished, the server answers it
+//! through [ServerRegistration::start_password_change] with its [ServerLoginFinishResult], which evaluates the OPRF
+//! under the credential identifier and the OPRF seed of the password file of the login. [ClientPasswordChange::finish]
+//! then seals a new envelope under the new password in the envelope mode of the existing password file, keeping the
+//! static keypair of the client in the external mode. The returned [PasswordFileUpgrade] message is authenticated under
+//! the session key of the login, and passed by the server to [ServerRegistration::finish_password_change] along with its
+//! [ServerLoginFinishResult], which returns the [ServerRegistration] replacing the stored password file. The password
+//! change is rejected with [PasswordChangeMismatchError](errors::ProtocolError::PasswordChangeMismatchError) if it was
+//! started under another credential identifier or OPRF key ID, or if it changes the envelope mode. Since the export key
+//! changes along with the password, the client is given both its `old_export_key` and its new `export_key`, for
+//! re-encrypting the data protected by the former under the latter.
+//!
+//! ## Authenticated Registration
 //!
-//! For both of these messages, the `WithContextAndIdentifiers` variant can be used to specify these fields in addition to
-//! [custom identifiers](#custom-identifiers), with the ordering of the fields as
-//! `WithContextAndIdentifiers(context, Identifiers::ClientAndServerIdentifiers(username, server_name))`.
+//! [ServerRegistration::finish] accepts any [RegistrationUpload], and nothing ties it to an authenticated client. For
+//! endpoints which replace the registration of an existing client, such as re-registration or password reset, the
+//! client can pass the session key of a prior login through the `session_key` field of
+//! [ClientRegistrationFinishParameters], along with the credential identifier under which the server registers the
+//! upload through its `credential_identifier` field, in which case the [RegistrationUpload] carries a MAC keyed by the
+//! session key over the upload and the credential identifier. The server then calls
+//! [ServerRegistration::finish_authenticated] with its [ServerLoginFinishResult] of the same login, which only accepts
+//! the upload if its MAC is valid and if the login was for the credential identifier under which the registration was
+//! started. The MAC is not recorded in the resulting password file.
+//!
+//! ## Key Rotation
+//!
+//! The OPRF keys of all password files are derived from a seed held in [ServerSetup]. This seed can be replaced by calling
+//! [ServerSetup::rotate_oprf_seed], which returns the key ID of the new seed. New registrations then use the new seed, while the
+//! previous seeds are retained so that existing password files remain usable. Each password file records the key ID of the
+//! seed it was registered with (see [ServerRegistration::oprf_key_id]), which [ServerLogin::start] uses to select the seed.
+//!
+//! Similarly, the server's static keypair can be replaced by calling [ServerSetup::rotate_keypair]. Since the public key is
+//! bound to the envelope of each password file, [ServerLogin::start] uses the keypair recorded in the password file (see
+//! [ServerRegistration::server_key_id]), so that both keypairs are accepted until the clients have re-registered. A client
+//! can detect the change through `client_login_finish_result.server_s_pk`, as described in
+//! [Checking Server Consistency](#checking-server-consistency).
+//!
+//! For this to work, the server must pass the `key_ids` field of the result of [ServerRegistration::start] to
+//! [ServerRegistration::finish]. A server which does not keep this result between the two steps can store the
+//! serialization of its [RegistrationKeyIds]. Once all clients have re-registered, a previous seed
+//! or keypair can be dropped with [ServerSetup::remove_oprf_seed] or [ServerSetup::remove_keypair], after which logging in with a
+//! password file still referring to it fails with [UnknownOprfKeyIdError](errors::ProtocolError::UnknownOprfKeyIdError) or
+//! [UnknownServerKeyIdError](errors::ProtocolError::UnknownServerKeyIdError).
+//!
+//! ## Threshold OPRF
+//!
+//! The OPRF key of a credential can instead be split across multiple servers with [threshold::OprfKeyShare::generate], so
+//! that no single server is able to evaluate the OPRF on its own. The server running the protocol holds one of the shares,
+//! in place of the OPRF seed of its [ServerSetup]. Each of the other share holders evaluates its share on the blinded
+//! password of the [RegistrationRequest] or [CredentialRequest], and returns the resulting [threshold::PartialEvaluation] to
+//! the server running the protocol, along with a proof that it used its share. This server then calls
+//! [ServerRegistration::start_threshold] or [ServerLogin::start_threshold] with its own share, the partial evaluations and
+//! the [threshold::PublicShare]s published when the key was split, which verifies each partial evaluation against the
+//! public share of the same index and combines them into the evaluation under the OPRF key without ever reconstructing
+//! the key. A partial evaluation which fails to verify is rejected with
+//! [InvalidPartialEvaluationError](errors::ProtocolError::InvalidPartialEvaluationError), which identifies its share. The
+//! client proceeds as it would with a single server. With fewer than `threshold` shares in total, the combination is
+//! unrelated to the OPRF key, and logging in fails with [InvalidLoginError](errors::PakeError::InvalidLoginError). Since
+//! the server running the protocol cannot prove its evaluation to the client, the threshold mode is not available in the
+//! [verifiable mode](#verifiable-oprf) of the OPRF.
+//!
+//! ## Verifiable OPRF
+//!
+//! By default, the client cannot tell which key the server evaluated the OPRF with. After calling
+//! [ServerSetup::set_verifiable_oprf], the [RegistrationResponse] and [CredentialResponse] messages also carry a proof
+//! that the OPRF was evaluated with the OPRF key of the credential. The client pins the public part of this key, as
+//! returned by [ServerSetup::oprf_public_key] for the OPRF key ID of the credential, by starting with
+//! [ClientRegistration::start_verifiable] or [ClientLogin::start_verifiable], which run the OPRF in its verifiable mode.
+//! [ClientRegistration::finish] and [ClientLogin::finish] then fail with
+//! [InvalidOprfProofError](errors::PakeError::InvalidOprfProofError) on a response without a valid proof for the
+//! pinned key, and a client which pinned no key rejects a response carrying a proof. Since the output of the OPRF
+//! depends on its mode, a server must not switch modes once clients have registered.
+//!
+//! ## External Envelope Mode
+//!
+//! By default, the client's static keypair is derived from the password and the envelope's nonce (internal mode). A
+//! client which already holds a static keypair, for instance one bound to its device, can instead pass it through the
+//! `client_static_keypair` field of [ClientRegistrationFinishParameters], in which case its private key is encrypted
+//! into the envelope (external mode) and recovered by [ClientLogin::finish]. Since the envelope is longer in this mode,
+//! a server whose clients may register in the external mode should call [ServerSetup::set_external_envelope_mode].
+//! The masked response of every [CredentialResponse] is then padded to the length of an envelope in the external mode,
+//! whether the client registered in the internal mode, in the external mode, or not at all, so that the length of the
+//! response reveals none of these. The server's [EnvelopeLayout], returned by [ServerSetup::envelope_layout], is fixed
+//! for the deployment and shared with its clients, which deserialize the responses with
+//! [CredentialResponse::deserialize_with_layout].
+//!
+//! ## Envelope Credentials
+//!
+//! The client can seal secrets of its own into the envelope, such as a recovery code or a wrapped key, by passing them
+//! through the `credentials` field of [ClientRegistrationFinishParameters]. They are encrypted and authenticated along
+//! with the rest of the envelope, and returned as `credentials` by [ClientLogin::finish]. Since they lengthen the
+//! envelope, all of the clients of a server use credentials of the same length, which the server passes to
+//! [ServerSetup::set_envelope_credentials_len]. As for the external mode, the masked response of every
+//! [CredentialResponse] then has room for credentials of this length, and the clients deserialize the responses with
+//! [CredentialResponse::deserialize_with_layout]. [ServerRegistration::finish] rejects an upload whose envelope carries
+//! credentials of another length, or is in the external mode on a server which does not allow it, with
+//! [EnvelopeLayoutMismatchError](errors::ProtocolError::EnvelopeLayoutMismatchError).
+//!
+//! ## Password Aliases
+//!
+//! An account can hold several independent credentials, such as a primary password and break-glass passphrases, in a
+//! [ServerAccount]. Each of them is registered as usual under the credential identifier returned by
+//! [credential_identifier] for its alias, and the resulting password file is added to the account with
+//! [ServerAccount::insert]. The login for one of the credentials is then started with [ServerLogin::start_for_account],
+//! which answers for an alias that the account does not hold with a dummy credential response, so that the server does
+//! not reveal how many credentials the account has. All of the credentials open the same client static keypair: the
+//! client registers each of them with this keypair in the external envelope mode, and [ServerAccount::insert] rejects a
+//! password file with another client public key. The client can also seal a secret shared by all of them as their
+//! envelope credentials.
 //!
 //! ## Dummy Server Login
 //!
@@ -718,13 +928,16 @@
 //!
 //! # Features
 //!
-//! - The `slow-hash` feature, when enabled, introduces a dependency on `scrypt` and implements the `SlowHash` trait for `scrypt`
-//! with a set of default parameters. In general, secure instantiations should choose to invoke a memory-hard password
+//! - The `slow-hash` feature, when enabled, introduces a dependency on `scrypt` and provides `slow_hash::Scrypt`, which
+//! implements the `SlowHash` trait for `scrypt` with a set of default parameters. In general, secure instantiations should choose to invoke a memory-hard password
 //! hashing function when the client's password is expected to have low entropy, instead of relying on [slow_hash::NoOpHash]
 //! as done in the above example. The more computationally intensive the `SlowHash` function is, the more resistant the server's
 //! password file records will be against offline dictionary and precomputation attacks; see
 //! [the OPAQUE paper](https://eprint.iacr.org/2018/163.pdf) for more details.
 //!
+//! - The `slow-hash-argon2` feature, when enabled, introduces a dependency on `argon2` and provides
+//! `slow_hash::Argon2id`, the memory-hard function recommended by the OPAQUE draft.
+//!
 //! - The `serialize` feature, enabled by default, provides convenience functions for serializing and deserializing with
 //! [serde](https://serde.rs/).
 //!
@@ -732,6 +945,25 @@
 //! [curve25519-dalek](https://doc.dalek.rs/curve25519_dalek/index.html#backends-and-features) and allow for selecting
 //! the corresponding backend for the curve arithmetic used. The `u64_backend` feature is included as the default.
 //!
+//! - The `p256` feature, when enabled, introduces a dependency on `p256` and implements the `Group` and
+//! `GroupWithMapToCurve` traits for NIST P-256, with elements encoded in SEC1 compressed form and hashing to the
+//! curve performed with the `P256_XMD:SHA-256_SSWU_RO_` suite. The suite pins SHA-256 as the `Hash` of the
+//! `CipherSuite`, and hashing to the curve with any other hash function fails.
+//!
+//! - The `p384` and `p521` features do the same for NIST P-384 and P-521, with the `P384_XMD:SHA-384_SSWU_RO_` and
+//! `P521_XMD:SHA-512_SSWU_RO_` suites respectively, which pin SHA-384 and SHA-512.
+//!
+//! - The `sigma` feature, when enabled, introduces a dependency on `ed25519-dalek` and provides
+//! `key_exchange::sigma::Sigma`, a SIGMA-I key exchange in which the client and the server authenticate with Ed25519
+//! signatures over the transcript, so that their static keys are never used in a Diffie-Hellman computation. It can be
+//! used as the `KeyExchange` of a `CipherSuite` whose `Group` is ristretto255, in which case the static public keys, such
+//! as the one of [ServerSetup::keypair], are Ed25519 public keys.
+//!
+//! - The `ml-kem` feature, when enabled, introduces a dependency on the RustCrypto `ml-kem` crate and provides `key_exchange::ml_kem::MlKem768`,
+//! an implementation of the ML-KEM-768 key encapsulation mechanism of FIPS 203. Combined with
+//! [key_exchange::hybrid::HybridTripleDH] as `HybridTripleDH<MlKem768>`, it makes the session keys of a ristretto255
+//! ciphersuite resistant to an adversary that records the exchange and later gains access to a quantum computer.
+//!
 //! - The `bench` feature is used only for running performance benchmarks for this implementation.
 //!
 
@@ -753,8 +985,12 @@ mod serialization;
 // High-level API
 mod opaque;
 
+mod account;
+
 mod messages;
 
+mod session;
+
 pub mod ciphersuite;
 mod envelope;
 pub mod hash;
@@ -766,13 +1002,12 @@ pub mod map_to_curve;
 pub mod key_exchange;
 pub mod keypair;
 
-#[cfg(feature = "bench")]
 pub mod oprf;
-#[cfg(not(feature = "bench"))]
-mod oprf;
 
 pub mod slow_hash;
 
+pub mod threshold;
+
 #[cfg(test)]
 mod tests;
 
@@ -782,18 +1017,25 @@ pub use rand;
 
 pub use ciphersuite::CipherSuite;
 
+pub use crate::account::{credential_identifier, ServerAccount};
+pub use crate::envelope::EnvelopeLayout;
 pub use crate::messages::{
-    CredentialFinalization, CredentialRequest, CredentialResponse, RegistrationRequest,
-    RegistrationResponse, RegistrationUpload,
+    CredentialFinalization, CredentialRequest, CredentialResponse, PasswordFileUpgrade,
+    RegistrationRequest, RegistrationResponse, RegistrationUpload,
 };
 pub use crate::opaque::{
-    ClientLogin, ClientRegistration, ServerLogin, ServerRegistration, ServerSetup,
+    ClientLogin, ClientPasswordChange, ClientRegistration, ServerLogin, ServerRegistration,
+    ServerSetup,
 };
 pub use crate::opaque::{
-    ClientLoginFinishParameters, ClientRegistrationFinishParameters, ServerLoginStartParameters,
+    ClientLoginFinishParameters, ClientRegistrationFinishParameters, ServerLoginFinishParameters,
+    ServerLoginStartParameters, ServerLoginStartRequest,
 };
+pub use crate::session::SessionKeys;
+
 pub use crate::opaque::{
-    ClientLoginFinishResult, ClientLoginStartResult, ClientRegistrationFinishResult,
-    ClientRegistrationStartResult, Identifiers, ServerLoginFinishResult, ServerLoginStartResult,
+    ClientLoginFinishResult, ClientLoginStartResult, ClientPasswordChangeFinishResult,
+    ClientPasswordChangeStartResult, ClientRegistrationFinishResult, ClientRegistrationStartResult,
+    Identifiers, RecordKind, RegistrationKeyIds, ServerLoginFinishResult, ServerLoginStartResult,
     ServerRegistrationStartResult,
 };
@@ -13,7 +13,10 @@ use crate::serialization::i2osp;
 use curve25519_dalek::ristretto::RistrettoPoint;
 use digest::{BlockInput, Digest};
 use generic_array::typenum::Unsigned;
-use generic_array::GenericArray;
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+use elliptic_curve::hash2curve::{ExpandMsg, Expander, GroupDigest};
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+use std::marker::PhantomData;
 
 /// A subtrait of Group specifying how to hash a password into a point
 pub trait GroupWithMapToCurve: Group {
@@ -41,11 +44,12 @@ impl GroupWithMapToCurve for RistrettoPoint {
     // Implements the hash_to_ristretto255() function from
     // https://www.ietf.org/archive/id/draft-irtf-cfrg-hash-to-curve-10.txt
     fn map_to_curve<H: Hash>(msg: &[u8], dst: &[u8]) -> Result<Self, InternalPakeError> {
-        let uniform_bytes =
-            expand_message_xmd::<H>(msg, dst, <H as Digest>::OutputSize::to_usize())?;
-        Ok(<Self as Group>::hash_to_curve(
-            &GenericArray::clone_from_slice(&uniform_bytes[..]),
-        ))
+        const LEN_IN_BYTES: usize = 64;
+        let uniform_bytes = expand_message_xmd::<H>(msg, dst, LEN_IN_BYTES)?;
+        let mut bits = [0u8; LEN_IN_BYTES];
+        bits.copy_from_slice(&uniform_bytes[..]);
+
+        Ok(RistrettoPoint::from_uniform_bytes(&bits))
     }
 
     fn hash_to_scalar<H: Hash>(
@@ -61,6 +65,113 @@ impl GroupWithMapToCurve for RistrettoPoint {
     }
 }
 
+// The NIST curves hash to the curve with the simplified SWU map, as
+// implemented in constant time by their respective crates, and only differ in
+// the hash function pinned by their suite. Since the OPRF hashes with the hash
+// function of the ciphersuite, any other hash function is rejected
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+macro_rules! impl_map_to_curve_for_nist_curve {
+    ($krate:ident, $curve:ident, $hash:ty, $suite_id:literal, $suite_name:literal) => {
+        impl GroupWithMapToCurve for $krate::ProjectivePoint {
+            const SUITE_ID: usize = $suite_id;
+
+            #[doc = concat!("Implements the ", $suite_name, " suite from")]
+            /// <https://www.ietf.org/archive/id/draft-irtf-cfrg-hash-to-curve-10.txt>,
+            /// whose hash function is pinned by the suite
+            fn map_to_curve<H: Hash>(msg: &[u8], dst: &[u8]) -> Result<Self, InternalPakeError> {
+                check_pinned_hash::<H, $hash>()?;
+                $krate::$curve::hash_from_bytes::<ExpandMsgXmdAdapter<$hash>>(&[msg], &[dst])
+                    .map_err(|_| InternalPakeError::HashToCurveError)
+            }
+
+            fn hash_to_scalar<H: Hash>(
+                input: &[u8],
+                dst: &[u8],
+            ) -> Result<Self::Scalar, InternalPakeError> {
+                check_pinned_hash::<H, $hash>()?;
+                $krate::$curve::hash_to_scalar::<ExpandMsgXmdAdapter<$hash>>(&[input], &[dst])
+                    .map_err(|_| InternalPakeError::HashToCurveError)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "p256")]
+impl_map_to_curve_for_nist_curve!(
+    p256_,
+    NistP256,
+    sha2::Sha256,
+    0x0003,
+    "P256_XMD:SHA-256_SSWU_RO_"
+);
+#[cfg(feature = "p384")]
+impl_map_to_curve_for_nist_curve!(
+    p384_,
+    NistP384,
+    sha2::Sha384,
+    0x0004,
+    "P384_XMD:SHA-384_SSWU_RO_"
+);
+#[cfg(feature = "p521")]
+impl_map_to_curve_for_nist_curve!(
+    p521_,
+    NistP521,
+    sha2::Sha512,
+    0x0005,
+    "P521_XMD:SHA-512_SSWU_RO_"
+);
+
+// Rejects a hash function other than the one pinned by a hash-to-curve suite,
+// which is told apart by its digest of the empty string
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+fn check_pinned_hash<H: Hash, P: Hash>() -> Result<(), InternalPakeError> {
+    if H::digest(b"")[..] != P::digest(b"")[..] {
+        return Err(InternalPakeError::HashToCurveSuiteMismatchError);
+    }
+    Ok(())
+}
+
+// Provides expand_message_xmd to the hash-to-curve implementations of the
+// NIST curves, so that they hash with the same version of the hash function as
+// the rest of the crate
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+struct ExpandMsgXmdAdapter<H: Hash>(PhantomData<H>);
+
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+struct ExpandedMsg {
+    uniform_bytes: Vec<u8>,
+    offset: usize,
+}
+
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+impl<'a, H: Hash> ExpandMsg<'a> for ExpandMsgXmdAdapter<H> {
+    type Expander = ExpandedMsg;
+
+    fn expand_message(
+        msgs: &[&[u8]],
+        dsts: &'a [&'a [u8]],
+        len_in_bytes: usize,
+    ) -> elliptic_curve::Result<Self::Expander> {
+        let uniform_bytes = expand_message_xmd::<H>(&msgs.concat(), &dsts.concat(), len_in_bytes)
+            .map_err(|_| elliptic_curve::Error)?;
+        Ok(ExpandedMsg {
+            uniform_bytes,
+            offset: 0,
+        })
+    }
+}
+
+#[cfg(any(feature = "p256", feature = "p384", feature = "p521"))]
+impl Expander for ExpandedMsg {
+    // The hash-to-field implementation reads exactly the number of bytes it
+    // asked for
+    fn fill_bytes(&mut self, okm: &mut [u8]) {
+        let end = self.offset + okm.len();
+        okm.copy_from_slice(&self.uniform_bytes[self.offset..end]);
+        self.offset = end;
+    }
+}
+
 // Computes ceil(x / y)
 fn div_ceil(x: usize, y: usize) -> usize {
     let additive = (x % y != 0) as usize;
@@ -238,4 +349,113 @@ mod tests {
             assert_eq!(tv.uniform_bytes, hex::encode(uniform_bytes));
         }
     }
+
+    #[cfg(feature = "p256")]
+    #[test]
+    fn test_hash_to_curve_p256() {
+        use super::GroupWithMapToCurve;
+        use p256_::elliptic_curve::sec1::ToEncodedPoint;
+
+        // Test vectors taken from Section G.1.1 of https://www.ietf.org/archive/id/draft-irtf-cfrg-hash-to-curve-10.txt
+        let test_vectors: Vec<(&str, &str, &str)> = vec![
+            (
+                "",
+                "2c15230b26dbc6fc9a37051158c95b79656e17a1a920b11394ca91c44247d3e4",
+                "8a7a74985cc5c776cdfe4b1f19884970453912e9d31528c060be9ab5c43e8415",
+            ),
+            (
+                "abc",
+                "0bb8b87485551aa43ed54f009230450b492fead5f1cc91658775dac4a3388a0f",
+                "5c41b3d0731a27a7b14bc0bf0ccded2d8751f83493404c84a88e71ffd424212e",
+            ),
+        ];
+        let dst = "QUUX-V01-CS02-with-P256_XMD:SHA-256_SSWU_RO_";
+
+        for (msg, x, y) in test_vectors {
+            let point = p256_::ProjectivePoint::map_to_curve::<sha2::Sha256>(
+                msg.as_bytes(),
+                dst.as_bytes(),
+            )
+            .unwrap();
+            let encoded = p256_::AffinePoint::from(point).to_encoded_point(false);
+            assert_eq!(x, hex::encode(encoded.x().unwrap()));
+            assert_eq!(y, hex::encode(encoded.y().unwrap()));
+        }
+
+        // The suite pins SHA-256
+        assert!(matches!(
+            p256_::ProjectivePoint::map_to_curve::<sha2::Sha512>(b"abc", dst.as_bytes()),
+            Err(crate::errors::InternalPakeError::HashToCurveSuiteMismatchError)
+        ));
+        assert!(matches!(
+            p256_::ProjectivePoint::hash_to_scalar::<sha2::Sha384>(b"abc", dst.as_bytes()),
+            Err(crate::errors::InternalPakeError::HashToCurveSuiteMismatchError)
+        ));
+    }
+
+    #[cfg(feature = "p384")]
+    #[test]
+    fn test_hash_to_curve_p384() {
+        use super::GroupWithMapToCurve;
+        use p384_::elliptic_curve::sec1::ToEncodedPoint;
+
+        // Test vectors taken from Section G.2.1 of https://www.ietf.org/archive/id/draft-irtf-cfrg-hash-to-curve-10.txt
+        let test_vectors: Vec<(&str, &str, &str)> = vec![
+            (
+                "",
+                "eb9fe1b4f4e14e7140803c1d99d0a93cd823d2b024040f9c067a8eca1f5a2eeac9ad604973527a356f3fa3aeff0e4d83",
+                "0c21708cff382b7f4643c07b105c2eaec2cead93a917d825601e63c8f21f6abd9abc22c93c2bed6f235954b25048bb1a",
+            ),
+            (
+                "abc",
+                "e02fc1a5f44a7519419dd314e29863f30df55a514da2d655775a81d413003c4d4e7fd59af0826dfaad4200ac6f60abe1",
+                "01f638d04d98677d65bef99aef1a12a70a4cbb9270ec55248c04530d8bc1f8f90f8a6a859a7c1f1ddccedf8f96d675f6",
+            ),
+        ];
+        let dst = "QUUX-V01-CS02-with-P384_XMD:SHA-384_SSWU_RO_";
+
+        for (msg, x, y) in test_vectors {
+            let point = p384_::ProjectivePoint::map_to_curve::<sha2::Sha384>(
+                msg.as_bytes(),
+                dst.as_bytes(),
+            )
+            .unwrap();
+            let encoded = p384_::AffinePoint::from(point).to_encoded_point(false);
+            assert_eq!(x, hex::encode(encoded.x().unwrap()));
+            assert_eq!(y, hex::encode(encoded.y().unwrap()));
+        }
+    }
+
+    #[cfg(feature = "p521")]
+    #[test]
+    fn test_hash_to_curve_p521() {
+        use super::GroupWithMapToCurve;
+        use p521_::elliptic_curve::sec1::ToEncodedPoint;
+
+        // Test vectors taken from Section G.3.1 of https://www.ietf.org/archive/id/draft-irtf-cfrg-hash-to-curve-10.txt
+        let test_vectors: Vec<(&str, &str, &str)> = vec![
+            (
+                "",
+                "00fd767cebb2452030358d0e9cf907f525f50920c8f607889a6a35680727f64f4d66b161fafeb2654bea0d35086bec0a10b30b14adef3556ed9f7f1bc23cecc9c088",
+                "0169ba78d8d851e930680322596e39c78f4fe31b97e57629ef6460ddd68f8763fd7bd767a4e94a80d3d21a3c2ee98347e024fc73ee1c27166dc3fe5eeef782be411d",
+            ),
+            (
+                "abc",
+                "002f89a1677b28054b50d15e1f81ed6669b5a2158211118ebdef8a6efc77f8ccaa528f698214e4340155abc1fa08f8f613ef14a043717503d57e267d57155cf784a4",
+                "010e0be5dc8e753da8ce51091908b72396d3deed14ae166f66d8ebf0a4e7059ead169ea4bead0232e9b700dd380b316e9361cfdba55a08c73545563a80966ecbb86d",
+            ),
+        ];
+        let dst = "QUUX-V01-CS02-with-P521_XMD:SHA-512_SSWU_RO_";
+
+        for (msg, x, y) in test_vectors {
+            let point = p521_::ProjectivePoint::map_to_curve::<sha2::Sha512>(
+                msg.as_bytes(),
+                dst.as_bytes(),
+            )
+            .unwrap();
+            let encoded = p521_::AffinePoint::from(point).to_encoded_point(false);
+            assert_eq!(x, hex::encode(encoded.x().unwrap()));
+            assert_eq!(y, hex::encode(encoded.y().unwrap()));
+        }
+    }
 }
@@ -7,19 +7,21 @@
 
 use crate::{
     ciphersuite::CipherSuite,
-    envelope::Envelope,
+    envelope::{Envelope, EnvelopeLayout, END_OF_ENVELOPE},
     errors::{
         utils::{check_slice_size, check_slice_size_atleast},
         PakeError, ProtocolError,
     },
     group::Group,
     key_exchange::traits::{FromBytes, KeyExchange, ToBytes},
-    keypair::{KeyPair, PublicKey, SizedBytesExt},
+    keypair::{KeyPair, PublicKey},
     opaque::ServerSetup,
+    oprf::Proof,
+    serialization::{serialize, tokenize},
+    slow_hash::SlowHash,
 };
 use digest::Digest;
 use generic_array::{typenum::Unsigned, GenericArray};
-use generic_bytes::SizedBytes;
 use rand::{CryptoRng, RngCore};
 
 // Messages
@@ -78,6 +80,9 @@ pub struct RegistrationResponse<CS: CipherSuite> {
     pub(crate) beta: CS::Group,
     /// Server's static public key
     pub(crate) server_s_pk: PublicKey,
+    /// The proof that beta was computed with the OPRF key pinned by the
+    /// client, in the verifiable mode of the OPRF
+    pub(crate) proof: Option<Proof<CS::Group>>,
 }
 
 // Cannot be derived because it would require for CS to be Clone.
@@ -86,22 +91,32 @@ impl<CS: CipherSuite> Clone for RegistrationResponse<CS> {
         Self {
             beta: self.beta,
             server_s_pk: self.server_s_pk.clone(),
+            proof: self.proof.clone(),
         }
     }
 }
 
 impl<CS: CipherSuite> RegistrationResponse<CS> {
-    /// Serialization into bytes
+    /// Serialization into bytes. In the verifiable mode of the OPRF, the
+    /// message is followed by the proof of the evaluation
     pub fn serialize(&self) -> Vec<u8> {
-        [self.beta.to_arr().to_vec(), self.server_s_pk.to_vec()].concat()
+        [
+            self.beta.to_arr().to_vec(),
+            self.server_s_pk.to_vec(),
+            serialize_extensions(self.proof.as_ref(), &[]),
+        ]
+        .concat()
     }
 
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
         let elem_len = <CS::Group as Group>::ElemLen::to_usize();
-        let key_len = <PublicKey as SizedBytes>::Len::to_usize();
+        let (input, proof, payload) = split_extensions::<CS>(input, elem_len + elem_len)?;
+        if !payload.is_empty() {
+            return Err(PakeError::SerializationError.into());
+        }
         let checked_slice =
-            check_slice_size(input, elem_len + key_len, "registration_response_bytes")?;
+            check_slice_size(input, elem_len + elem_len, "registration_response_bytes")?;
 
         // Check that the message is actually containing an element of the
         // correct subgroup
@@ -114,11 +129,15 @@ impl<CS: CipherSuite> RegistrationResponse<CS> {
         }
 
         // Ensure that public key is valid
-        let server_s_pk = KeyPair::<CS::Group>::check_public_key(PublicKey::from_bytes(
+        let server_s_pk = KeyPair::check_static_public_key::<CS>(PublicKey::from_bytes(
             &checked_slice[elem_len..],
-        )?)?;
+        ))?;
 
-        Ok(Self { server_s_pk, beta })
+        Ok(Self {
+            server_s_pk,
+            beta,
+            proof,
+        })
     }
 }
 
@@ -134,6 +153,12 @@ pub struct RegistrationUpload<CS: CipherSuite> {
     pub(crate) masking_key: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
     /// The user's public key
     pub(crate) client_s_pk: PublicKey,
+    /// The parameters of the slow hashing function used by the user, which
+    /// are recorded in the password file
+    pub(crate) slow_hash: Option<CS::SlowHash>,
+    /// A MAC over the rest of the upload, keyed by the session key of a
+    /// prior login, if the registration is authenticated
+    pub(crate) mac: Option<GenericArray<u8, <CS::Hash as Digest>::OutputSize>>,
 }
 
 // Cannot be derived because it would require for CS to be Clone.
@@ -143,36 +168,78 @@ impl<CS: CipherSuite> Clone for RegistrationUpload<CS> {
             envelope: self.envelope.clone(),
             masking_key: self.masking_key.clone(),
             client_s_pk: self.client_s_pk.clone(),
+            slow_hash: self.slow_hash.clone(),
+            mac: self.mac.clone(),
         }
     }
 }
 
 impl<CS: CipherSuite> RegistrationUpload<CS> {
-    /// Serialization into bytes
+    /// Serialization into bytes, followed by the length-prefixed parameters
+    /// of the slow hashing function unless they are empty, and by the MAC of
+    /// an authenticated registration
     pub fn serialize(&self) -> Vec<u8> {
-        [
-            self.client_s_pk.to_arr().to_vec(),
+        let mut output = [
+            self.client_s_pk.to_vec(),
             self.masking_key.to_vec(),
             self.envelope.serialize(),
         ]
-        .concat()
+        .concat();
+        let slow_hash = self
+            .slow_hash
+            .as_ref()
+            .map(|slow_hash| slow_hash.serialize())
+            .unwrap_or_default();
+        if !slow_hash.is_empty() || self.mac.is_some() {
+            output.push(END_OF_ENVELOPE);
+            output.extend_from_slice(&serialize(&slow_hash, 2));
+        }
+        if let Some(mac) = &self.mac {
+            output.extend_from_slice(mac);
+        }
+        output
     }
 
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
-        let key_len = <PublicKey as SizedBytes>::Len::to_usize();
+        let key_len = <CS::Group as Group>::ElemLen::to_usize();
         let hash_len = <CS::Hash as Digest>::OutputSize::to_usize();
         let checked_slice =
             check_slice_size_atleast(input, key_len + hash_len, "registration_upload_bytes")?;
-        let envelope = Envelope::<CS>::deserialize(&checked_slice[key_len + hash_len..])?;
+        let (envelope, remainder) =
+            Envelope::<CS>::deserialize_partial(&checked_slice[key_len + hash_len..])?;
+        // The envelope is followed by the parameters of the slow hashing
+        // function and the MAC of an authenticated registration, if any
+        let (slow_hash, mac) = match remainder.split_first() {
+            None => (None, None),
+            Some((&END_OF_ENVELOPE, remainder)) => {
+                let (slow_hash_bytes, remainder) = tokenize(remainder, 2)?;
+                let slow_hash = match slow_hash_bytes.len() {
+                    0 => None,
+                    _ => Some(CS::SlowHash::deserialize(&slow_hash_bytes)?),
+                };
+                let mac = match remainder.len() {
+                    0 => None,
+                    len if len == hash_len => Some(GenericArray::clone_from_slice(&remainder)),
+                    _ => return Err(PakeError::SerializationError.into()),
+                };
+                if slow_hash.is_none() && mac.is_none() {
+                    return Err(PakeError::SerializationError.into());
+                }
+                (slow_hash, mac)
+            }
+            _ => return Err(PakeError::SerializationError.into()),
+        };
         Ok(Self {
             envelope,
             masking_key: GenericArray::clone_from_slice(
                 &checked_slice[key_len..key_len + hash_len],
             ),
-            client_s_pk: KeyPair::<CS::Group>::check_public_key(PublicKey::from_bytes(
+            client_s_pk: KeyPair::check_static_public_key::<CS>(PublicKey::from_bytes(
                 &checked_slice[..key_len],
-            )?)?,
+            ))?,
+            slow_hash,
+            mac,
         })
     }
 
@@ -188,6 +255,8 @@ impl<CS: CipherSuite> RegistrationUpload<CS> {
             envelope: Envelope::<CS>::dummy(),
             masking_key: GenericArray::clone_from_slice(&masking_key),
             client_s_pk: server_setup.fake_keypair.public().clone(),
+            slow_hash: None,
+            mac: None,
         }
     }
 }
@@ -252,6 +321,12 @@ pub struct CredentialResponse<CS: CipherSuite> {
     pub(crate) masking_nonce: Vec<u8>,
     pub(crate) masked_response: Vec<u8>,
     pub(crate) ke2_message: <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::KE2Message,
+    /// The proof that beta was computed with the OPRF key pinned by the
+    /// client, in the verifiable mode of the OPRF
+    pub(crate) proof: Option<Proof<CS::Group>>,
+    /// The application payload of the server, which is bound into the key
+    /// exchange transcript
+    pub(crate) payload: Vec<u8>,
 }
 
 // Cannot be derived because it would require for CS to be Clone.
@@ -262,16 +337,21 @@ impl<CS: CipherSuite> Clone for CredentialResponse<CS> {
             masking_nonce: self.masking_nonce.clone(),
             masked_response: self.masked_response.clone(),
             ke2_message: self.ke2_message.clone(),
+            proof: self.proof.clone(),
+            payload: self.payload.clone(),
         }
     }
 }
 
 impl<CS: CipherSuite> CredentialResponse<CS> {
-    /// Serialization into bytes
+    /// Serialization into bytes. In the verifiable mode of the OPRF, the
+    /// message is followed by the proof of the evaluation, and then by the
+    /// application payload of the server if there is one
     pub fn serialize(&self) -> Vec<u8> {
         [
             Self::serialize_without_ke(&self.beta, &self.masking_nonce, &self.masked_response),
             self.ke2_message.to_bytes(),
+            serialize_extensions(self.proof.as_ref(), &self.payload),
         ]
         .concat()
     }
@@ -284,15 +364,45 @@ impl<CS: CipherSuite> CredentialResponse<CS> {
         [&beta.to_arr(), masking_nonce, masked_response].concat()
     }
 
-    /// Deserialization from bytes
+    // The part of the message which is bound into the key exchange
+    // transcript, consisting of the message without the key exchange,
+    // followed by the application payload of the server if there is one
+    pub(crate) fn transcript_component(
+        beta: &CS::Group,
+        masking_nonce: &[u8],
+        masked_response: &[u8],
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mut output = Self::serialize_without_ke(beta, masking_nonce, masked_response);
+        if !payload.is_empty() {
+            output.extend_from_slice(&serialize(payload, 2));
+        }
+        output
+    }
+
+    /// Deserialization from bytes, for a server whose envelopes have the
+    /// default [EnvelopeLayout], in the internal mode without credentials
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        Self::deserialize_with_layout(input, &EnvelopeLayout::default())
+    }
+
+    /// Deserialization from bytes, for a server whose envelopes have the
+    /// given layout, as returned by
+    /// [ServerSetup::envelope_layout](crate::ServerSetup::envelope_layout),
+    /// which fixes the length of the masked response
+    pub fn deserialize_with_layout(
+        input: &[u8],
+        layout: &EnvelopeLayout,
+    ) -> Result<Self, ProtocolError> {
         let elem_len = <CS::Group as Group>::ElemLen::to_usize();
-        let key_len = <PublicKey as SizedBytes>::Len::to_usize();
         let nonce_len: usize = 32;
-        let envelope_len = Envelope::<CS>::len();
-        let masked_response_len = key_len + envelope_len;
+        let masked_response_len = elem_len + Envelope::<CS>::padded_len(layout);
         let ke2_message_len = CS::KeyExchange::ke2_message_size();
 
+        let (input, proof, payload) = split_extensions::<CS>(
+            input,
+            elem_len + nonce_len + masked_response_len + ke2_message_len,
+        )?;
         let checked_slice = check_slice_size_atleast(
             input,
             elem_len + nonce_len + masked_response_len + ke2_message_len,
@@ -324,6 +434,8 @@ impl<CS: CipherSuite> CredentialResponse<CS> {
             masking_nonce,
             masked_response,
             ke2_message,
+            proof,
+            payload,
         })
     }
 }
@@ -334,6 +446,12 @@ impl_serialize_and_deserialize_for!(CredentialResponse);
 /// sealed envelope
 pub struct CredentialFinalization<CS: CipherSuite> {
     pub(crate) ke3_message: <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::KE3Message,
+    /// The application payload of the client, as it is sent, which is empty
+    /// if there is none. It is covered by the MAC of the third key exchange
+    /// message
+    pub(crate) payload: Vec<u8>,
+    /// Whether the application payload is encrypted under the session key
+    pub(crate) payload_encrypted: bool,
 }
 
 // Cannot be derived because it would require for CS to be Clone.
@@ -341,24 +459,168 @@ impl<CS: CipherSuite> Clone for CredentialFinalization<CS> {
     fn clone(&self) -> Self {
         Self {
             ke3_message: self.ke3_message.clone(),
+            payload: self.payload.clone(),
+            payload_encrypted: self.payload_encrypted,
         }
     }
 }
 
 impl<CS: CipherSuite> CredentialFinalization<CS> {
-    /// Serialization into bytes
+    /// Serialization into bytes. The message is followed by the application
+    /// payload of the client, if there is one, under a tag telling whether it
+    /// is encrypted
     pub fn serialize(&self) -> Vec<u8> {
-        self.ke3_message.to_bytes()
+        let mut output = self.ke3_message.to_bytes();
+        if !self.payload.is_empty() {
+            output.push(if self.payload_encrypted {
+                EXTENSION_ENCRYPTED_PAYLOAD
+            } else {
+                EXTENSION_PAYLOAD
+            });
+            output.extend_from_slice(&serialize(&self.payload, 2));
+        }
+        output
     }
 
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let ke3_message_len = CS::KeyExchange::ke3_message_size();
+        let checked_slice = check_slice_size_atleast(input, ke3_message_len, "ke3_message")?;
         let ke3_message =
             <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::KE3Message::from_bytes::<CS>(
-                input,
+                &checked_slice[..ke3_message_len],
             )?;
-        Ok(Self { ke3_message })
+
+        let extensions = &checked_slice[ke3_message_len..];
+        let (payload, payload_encrypted) = match extensions.split_first() {
+            None => (Vec::new(), false),
+            Some((&tag, remainder))
+                if tag == EXTENSION_PAYLOAD || tag == EXTENSION_ENCRYPTED_PAYLOAD =>
+            {
+                let (payload, remainder) = tokenize(remainder, 2)?;
+                // An empty payload is never serialized
+                if payload.is_empty() || !remainder.is_empty() {
+                    return Err(PakeError::SerializationError.into());
+                }
+                (payload, tag == EXTENSION_ENCRYPTED_PAYLOAD)
+            }
+            Some(_) => return Err(PakeError::SerializationError.into()),
+        };
+
+        Ok(Self {
+            ke3_message,
+            payload,
+            payload_encrypted,
+        })
     }
 }
 
 impl_serialize_and_deserialize_for!(CredentialFinalization);
+
+/// Sent by the client along with a [CredentialFinalization], in order to
+/// replace its password file with a new registration, for instance with
+/// stronger slow hashing parameters. It is also sent to complete a password
+/// change, in which case it is authenticated for that purpose only
+pub struct PasswordFileUpgrade<CS: CipherSuite> {
+    /// The new registration
+    pub(crate) registration_upload: RegistrationUpload<CS>,
+    /// A MAC over the new registration, keyed by the session key of the login
+    pub(crate) mac: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for PasswordFileUpgrade<CS> {
+    fn clone(&self) -> Self {
+        Self {
+            registration_upload: self.registration_upload.clone(),
+            mac: self.mac.clone(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> PasswordFileUpgrade<CS> {
+    /// Serialization into bytes
+    pub fn serialize(&self) -> Vec<u8> {
+        [self.mac.to_vec(), self.registration_upload.serialize()].concat()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let hash_len = <CS::Hash as Digest>::OutputSize::to_usize();
+        let checked_slice = check_slice_size_atleast(input, hash_len, "password_file_upgrade")?;
+        Ok(Self {
+            registration_upload: RegistrationUpload::deserialize(&checked_slice[hash_len..])?,
+            mac: GenericArray::clone_from_slice(&checked_slice[..hash_len]),
+        })
+    }
+}
+
+impl_serialize_and_deserialize_for!(PasswordFileUpgrade);
+
+// The optional fields which may follow a message, in this order, each
+// prefixed with its tag
+const EXTENSION_PROOF: u8 = 1;
+const EXTENSION_PAYLOAD: u8 = 2;
+// The application payload of the client, when it is encrypted, which takes the
+// place of EXTENSION_PAYLOAD
+const EXTENSION_ENCRYPTED_PAYLOAD: u8 = 3;
+
+// Serializes the optional fields of a server response: the proof of the
+// verifiable mode of the OPRF, and the length-prefixed application payload of
+// the server
+fn serialize_extensions<G: Group>(proof: Option<&Proof<G>>, payload: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    if let Some(proof) = proof {
+        output.push(EXTENSION_PROOF);
+        output.extend_from_slice(&proof.serialize());
+    }
+    if !payload.is_empty() {
+        output.push(EXTENSION_PAYLOAD);
+        output.extend_from_slice(&serialize(payload, 2));
+    }
+    output
+}
+
+// Splits off the optional fields of a server response, which are present if
+// the input is longer than the base message of length message_len
+#[allow(clippy::type_complexity)]
+fn split_extensions<CS: CipherSuite>(
+    input: &[u8],
+    message_len: usize,
+) -> Result<(&[u8], Option<Proof<CS::Group>>, Vec<u8>), ProtocolError> {
+    if input.len() <= message_len {
+        return Ok((input, None, Vec::new()));
+    }
+    let mut extensions = &input[message_len..];
+
+    let mut proof = None;
+    if extensions[0] == EXTENSION_PROOF {
+        let proof_len = Proof::<CS::Group>::len();
+        let checked_slice = check_slice_size_atleast(&extensions[1..], proof_len, "oprf_proof")?;
+        proof = Some(Proof::deserialize(&checked_slice[..proof_len])?);
+        extensions = &checked_slice[proof_len..];
+    }
+
+    let (payload, extensions) = split_field(extensions, EXTENSION_PAYLOAD)?;
+
+    if !extensions.is_empty() {
+        return Err(PakeError::SerializationError.into());
+    }
+    Ok((&input[..message_len], proof, payload))
+}
+
+// Splits off a length-prefixed optional field if it is next, returning it
+// along with the remaining fields, or an empty value if it is absent
+fn split_field(extensions: &[u8], tag: u8) -> Result<(Vec<u8>, &[u8]), ProtocolError> {
+    match extensions.split_first() {
+        Some((&next_tag, remainder)) if next_tag == tag => {
+            let (value, remainder) = tokenize(remainder, 2)?;
+            // An empty field is never serialized
+            if value.is_empty() {
+                return Err(PakeError::SerializationError.into());
+            }
+            Ok((value, &extensions[extensions.len() - remainder.len()..]))
+        }
+        _ => Ok((Vec::new(), extensions)),
+    }
+}
@@ -5,32 +5,62 @@
 
 //! Provides the main OPAQUE API
 
+#[cfg(test)]
+use crate::key_exchange::traits::AsBytePointers;
 use crate::{
+    account::{credential_identifier, ServerAccount},
     ciphersuite::CipherSuite,
-    envelope::Envelope,
-    errors::{utils::check_slice_size, InternalPakeError, PakeError, ProtocolError},
+    envelope::{Envelope, EnvelopeLayout},
+    errors::{
+        utils::{check_slice_size, check_slice_size_atleast},
+        InternalPakeError, PakeError, ProtocolError,
+    },
     group::Group,
     hash::Hash,
-    key_exchange::traits::{FromBytes, KeyExchange, ToBytesWithPointers},
-    keypair::{KeyPair, PrivateKey, PublicKey},
+    key_exchange::traits::{
+        client_payload_component, crypt_client_payload, ClientPayload, FromBytes, Ke2Inputs,
+        Ke3Inputs, KeyExchange, ToBytes, TranscriptInputs,
+    },
+    keypair::{KeyPair, PublicKey},
     map_to_curve::GroupWithMapToCurve,
     oprf,
-    serialization::{serialize, tokenize},
+    serialization::{serialize, tokenize, MAX_FIELD_LEN},
+    session::SessionKeys,
     slow_hash::SlowHash,
-    CredentialFinalization, CredentialRequest, CredentialResponse, RegistrationRequest,
-    RegistrationResponse, RegistrationUpload,
+    threshold::{OprfKeyShare, PartialEvaluation, PublicShare},
+    CredentialFinalization, CredentialRequest, CredentialResponse, PasswordFileUpgrade,
+    RegistrationRequest, RegistrationResponse, RegistrationUpload,
 };
 use digest::Digest;
 use generic_array::{typenum::Unsigned, GenericArray};
-use generic_bytes::SizedBytes;
 use hkdf::Hkdf;
+use hmac::{Hmac, Mac, NewMac};
 use rand::{CryptoRng, RngCore};
-use std::marker::PhantomData;
-use zeroize::Zeroize;
+use std::{convert::TryInto, marker::PhantomData};
+use zeroize::{Zeroize, Zeroizing};
 
 const STR_CREDENTIAL_RESPONSE_PAD: &[u8] = b"CredentialResponsePad";
 const STR_MASKING_KEY: &[u8] = b"MaskingKey";
 const STR_OPRF_KEY: &[u8] = b"OprfKey";
+const STR_PASSWORD_FILE_UPGRADE_MAC: &[u8] = b"PasswordFileUpgradeMac";
+const STR_PASSWORD_CHANGE_MAC: &[u8] = b"PasswordChangeMac";
+const STR_REGISTRATION_UPLOAD_MAC: &[u8] = b"RegistrationUploadMac";
+
+// The version of the format produced by ServerRegistration::serialize
+const PASSWORD_FILE_VERSION: u8 = 3;
+// The versions which predate the recording of the OPRF and server key IDs,
+// whose password files are treated as using the initial seed and keypair
+const PASSWORD_FILE_VERSION_WITHOUT_KEY_IDS: u8 = 1;
+const PASSWORD_FILE_VERSION_WITHOUT_SERVER_KEY_ID: u8 = 2;
+
+// The version of the format produced by ServerSetup::serialize. A server
+// setup serialized without a version holds only the OPRF seed and the
+// keypairs
+const SERVER_SETUP_VERSION: u8 = 1;
+
+// The length of a serialized RegistrationKeyIds, without the credential
+// identifier
+const REGISTRATION_KEY_IDS_LEN: usize = 11;
 
 // Server Setup
 // ============
@@ -38,50 +68,393 @@ const STR_OPRF_KEY: &[u8] = b"OprfKey";
 /// The state elements the server holds upon setup
 pub struct ServerSetup<CS: CipherSuite> {
     oprf_seed: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+    oprf_key_id: u32,
+    // Seeds replaced by a rotation, which are kept around for evaluating
+    // password files which have not been re-registered yet
+    #[allow(clippy::type_complexity)]
+    previous_oprf_seeds: Vec<(u32, GenericArray<u8, <CS::Hash as Digest>::OutputSize>)>,
     keypair: KeyPair<CS::Group>,
+    server_key_id: u32,
+    // Keypairs replaced by a rotation, which are kept around for the password
+    // files whose envelopes were sealed under their public key
+    previous_keypairs: Vec<(u32, KeyPair<CS::Group>)>,
     pub(crate) fake_keypair: KeyPair<CS::Group>,
+    verifiable_oprf: bool,
+    envelope_layout: EnvelopeLayout,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for ServerSetup<CS> {
+    fn clone(&self) -> Self {
+        Self {
+            oprf_seed: self.oprf_seed.clone(),
+            oprf_key_id: self.oprf_key_id,
+            previous_oprf_seeds: self.previous_oprf_seeds.clone(),
+            keypair: self.keypair.clone(),
+            server_key_id: self.server_key_id,
+            previous_keypairs: self.previous_keypairs.clone(),
+            fake_keypair: self.fake_keypair.clone(),
+            verifiable_oprf: self.verifiable_oprf,
+            envelope_layout: self.envelope_layout,
+        }
+    }
 }
 
 impl<CS: CipherSuite> ServerSetup<CS> {
     /// Generate a new instance of server setup
     pub fn new<R: CryptoRng + RngCore>(rng: &mut R) -> Self {
-        let mut seed = vec![0u8; <CS::Hash as Digest>::OutputSize::to_usize()];
-        rng.fill_bytes(&mut seed);
-
         Self {
-            oprf_seed: GenericArray::clone_from_slice(&seed[..]),
-            keypair: KeyPair::<CS::Group>::generate_random(rng),
-            fake_keypair: KeyPair::<CS::Group>::generate_random(rng),
+            oprf_seed: generate_oprf_seed::<CS::Hash, R>(rng),
+            oprf_key_id: 0,
+            previous_oprf_seeds: Vec::new(),
+            keypair: KeyPair::generate_random_static::<CS, _>(rng),
+            server_key_id: 0,
+            previous_keypairs: Vec::new(),
+            fake_keypair: KeyPair::generate_random_static::<CS, _>(rng),
+            verifiable_oprf: false,
+            envelope_layout: EnvelopeLayout::default(),
         }
     }
 
-    /// Serialization into bytes
+    /// Serialization into bytes, prefixed with a version. The current OPRF
+    /// seed and the keypairs are followed by the current OPRF and server key
+    /// IDs, by the previous OPRF seeds and server private keys along with
+    /// their key IDs, by whether the verifiable mode of the OPRF is enabled,
+    /// by whether the password files use the external envelope mode, and by
+    /// the length of the credentials carried by their envelopes
     pub fn serialize(&self) -> Vec<u8> {
+        let previous_oprf_seeds: Vec<u8> = self
+            .previous_oprf_seeds
+            .iter()
+            .flat_map(|(key_id, seed)| [&key_id.to_be_bytes()[..], seed].concat())
+            .collect();
+        let previous_keypairs: Vec<u8> = self
+            .previous_keypairs
+            .iter()
+            .flat_map(|(key_id, keypair)| [&key_id.to_be_bytes()[..], keypair.private()].concat())
+            .collect();
+
         [
+            vec![SERVER_SETUP_VERSION],
             self.oprf_seed.to_vec(),
-            self.keypair.private().to_arr().to_vec(),
-            self.fake_keypair.private().to_arr().to_vec(),
+            self.keypair.private().to_vec(),
+            self.fake_keypair.private().to_vec(),
+            self.oprf_key_id.to_be_bytes().to_vec(),
+            self.server_key_id.to_be_bytes().to_vec(),
+            serialize(&previous_oprf_seeds, 2),
+            serialize(&previous_keypairs, 2),
+            vec![
+                self.verifiable_oprf as u8,
+                self.envelope_layout.external_mode as u8,
+            ],
+            (self.envelope_layout.credentials_len as u16)
+                .to_be_bytes()
+                .to_vec(),
         ]
         .concat()
     }
 
-    /// Deserialization from bytes
+    /// Deserialization from bytes. A server setup serialized without a
+    /// version is given the key ID 0 for both its OPRF seed and its keypair,
+    /// and the verifiable mode of the OPRF, the external envelope mode and the
+    /// credentials of the envelopes are disabled
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
         let seed_len = <CS::Hash as Digest>::OutputSize::to_usize();
-        let key_len = <PrivateKey as SizedBytes>::Len::to_usize();
-        let checked_slice = check_slice_size(input, seed_len + key_len + key_len, "server_setup")?;
+        let key_len = <CS::Group as Group>::ScalarLen::to_usize();
+        let setup_len = seed_len + key_len + key_len;
+        let versioned = input.len() != setup_len;
+        if versioned && input.first() != Some(&SERVER_SETUP_VERSION) {
+            return Err(PakeError::SerializationError.into());
+        }
+        let checked_slice =
+            check_slice_size_atleast(&input[versioned as usize..], setup_len, "server_setup")?;
+
+        let mut oprf_key_id = 0;
+        let mut server_key_id = 0;
+        let mut previous_oprf_seeds = Vec::new();
+        let mut previous_keypairs = Vec::new();
+        let mut verifiable_oprf = false;
+        let mut envelope_layout = EnvelopeLayout::default();
+
+        if versioned {
+            let key_ids =
+                check_slice_size_atleast(&checked_slice[setup_len..], 8, "server_setup_key_ids")?;
+            oprf_key_id = u32::from_be_bytes(key_ids[..4].try_into().unwrap());
+            server_key_id = u32::from_be_bytes(key_ids[4..8].try_into().unwrap());
+
+            let (oprf_seed_bytes, remainder) = tokenize(&key_ids[8..], 2)?;
+            let (keypair_bytes, remainder) = tokenize(&remainder, 2)?;
+            let flags = check_slice_size(&remainder, 4, "server_setup_flags")?;
+            envelope_layout.credentials_len = u16::from_be_bytes([flags[2], flags[3]]) as usize;
+            if flags[..2].iter().any(|&flag| flag > 1)
+                || envelope_layout.credentials_len > Envelope::<CS>::max_credentials_len()
+            {
+                return Err(PakeError::SerializationError.into());
+            }
+            verifiable_oprf = flags[0] == 1;
+            envelope_layout.external_mode = flags[1] == 1;
+            if oprf_seed_bytes.len() % (4 + seed_len) != 0
+                || keypair_bytes.len() % (4 + key_len) != 0
+            {
+                return Err(PakeError::SerializationError.into());
+            }
+
+            for entry in oprf_seed_bytes.chunks_exact(4 + seed_len) {
+                previous_oprf_seeds.push((
+                    u32::from_be_bytes(entry[..4].try_into().unwrap()),
+                    GenericArray::clone_from_slice(&entry[4..]),
+                ));
+            }
+            for entry in keypair_bytes.chunks_exact(4 + key_len) {
+                previous_keypairs.push((
+                    u32::from_be_bytes(entry[..4].try_into().unwrap()),
+                    KeyPair::from_private_key_slice_static::<CS>(&entry[4..])?,
+                ));
+            }
+        }
 
         Ok(Self {
             oprf_seed: GenericArray::clone_from_slice(&checked_slice[..seed_len]),
-            keypair: KeyPair::from_private_key_slice(&checked_slice[seed_len..seed_len + key_len])?,
-            fake_keypair: KeyPair::from_private_key_slice(&checked_slice[seed_len + key_len..])?,
+            oprf_key_id,
+            previous_oprf_seeds,
+            keypair: KeyPair::from_private_key_slice_static::<CS>(
+                &checked_slice[seed_len..seed_len + key_len],
+            )?,
+            server_key_id,
+            previous_keypairs,
+            fake_keypair: KeyPair::from_private_key_slice_static::<CS>(
+                &checked_slice[seed_len + key_len..setup_len],
+            )?,
+            verifiable_oprf,
+            envelope_layout,
         })
     }
 
-    /// Returns the keypair
+    /// Returns the current keypair, which is used for new registrations
     pub fn keypair(&self) -> &KeyPair<CS::Group> {
         &self.keypair
     }
+
+    /// Returns the key ID of the current keypair
+    pub fn server_key_id(&self) -> u32 {
+        self.server_key_id
+    }
+
+    /// Returns the key IDs of the keypairs which were replaced by a rotation,
+    /// but can still be used to log in
+    pub fn previous_server_key_ids(&self) -> Vec<u32> {
+        self.previous_keypairs
+            .iter()
+            .map(|(key_id, _)| *key_id)
+            .collect()
+    }
+
+    /// Generates a new keypair for new registrations, and returns its key ID.
+    /// The replaced keypair is retained so that existing password files
+    /// remain usable until their clients re-register
+    pub fn rotate_keypair<R: CryptoRng + RngCore>(&mut self, rng: &mut R) -> u32 {
+        let new_key_id = next_key_id(
+            self.previous_keypairs
+                .iter()
+                .map(|(key_id, _)| *key_id)
+                .chain(std::iter::once(self.server_key_id)),
+        );
+        let previous_keypair = std::mem::replace(
+            &mut self.keypair,
+            KeyPair::generate_random_static::<CS, _>(rng),
+        );
+        self.previous_keypairs
+            .push((self.server_key_id, previous_keypair));
+        self.server_key_id = new_key_id;
+        new_key_id
+    }
+
+    /// Removes a previous keypair, closing the window during which password
+    /// files registered under it can be used. The current keypair cannot be
+    /// removed
+    pub fn remove_keypair(&mut self, key_id: u32) -> Result<(), ProtocolError> {
+        let index = self
+            .previous_keypairs
+            .iter()
+            .position(|(id, _)| *id == key_id)
+            .ok_or(ProtocolError::UnknownServerKeyIdError(key_id))?;
+        self.previous_keypairs.remove(index);
+        Ok(())
+    }
+
+    /// Returns the key ID of the current OPRF seed, which is used for new
+    /// registrations
+    pub fn oprf_key_id(&self) -> u32 {
+        self.oprf_key_id
+    }
+
+    /// Returns the key IDs of the OPRF seeds which were replaced by a
+    /// rotation, but can still be used to log in
+    pub fn previous_oprf_key_ids(&self) -> Vec<u32> {
+        self.previous_oprf_seeds
+            .iter()
+            .map(|(key_id, _)| *key_id)
+            .collect()
+    }
+
+    /// Generates a new OPRF seed for new registrations, and returns its key
+    /// ID. The replaced seed is retained so that existing password files
+    /// remain usable until their clients re-register
+    pub fn rotate_oprf_seed<R: CryptoRng + RngCore>(&mut self, rng: &mut R) -> u32 {
+        let new_key_id = next_key_id(
+            self.previous_oprf_seeds
+                .iter()
+                .map(|(key_id, _)| *key_id)
+                .chain(std::iter::once(self.oprf_key_id)),
+        );
+        let previous_seed =
+            std::mem::replace(&mut self.oprf_seed, generate_oprf_seed::<CS::Hash, R>(rng));
+        self.previous_oprf_seeds
+            .push((self.oprf_key_id, previous_seed));
+        self.oprf_key_id = new_key_id;
+        new_key_id
+    }
+
+    /// Removes a previous OPRF seed, once all of the password files using it
+    /// have been re-registered. The current OPRF seed cannot be removed
+    pub fn remove_oprf_seed(&mut self, key_id: u32) -> Result<(), ProtocolError> {
+        let index = self
+            .previous_oprf_seeds
+            .iter()
+            .position(|(id, _)| *id == key_id)
+            .ok_or(ProtocolError::UnknownOprfKeyIdError(key_id))?;
+        self.previous_oprf_seeds.remove(index);
+        Ok(())
+    }
+
+    /// Enables or disables the verifiable mode of the OPRF, in which the
+    /// responses to the client carry a proof that the OPRF was evaluated with
+    /// the key whose public part the client pinned. Since the output of the
+    /// OPRF depends on its mode, the clients of a server in the verifiable
+    /// mode must register and log in with
+    /// [ClientRegistration::start_verifiable] and
+    /// [ClientLogin::start_verifiable]
+    pub fn set_verifiable_oprf(&mut self, verifiable_oprf: bool) {
+        self.verifiable_oprf = verifiable_oprf;
+    }
+
+    /// Returns whether the verifiable mode of the OPRF is enabled
+    pub fn verifiable_oprf(&self) -> bool {
+        self.verifiable_oprf
+    }
+
+    /// Sets whether the clients may register in the external envelope mode.
+    /// Since the envelope of this mode is longer, the masked response of
+    /// every [CredentialResponse] is then padded to its length, including
+    /// those of the clients registered in the internal mode and the dummy
+    /// ones, so that its length reveals neither whether the client is
+    /// registered nor which mode it uses. The clients must deserialize the
+    /// responses with the resulting [envelope_layout](Self::envelope_layout)
+    pub fn set_external_envelope_mode(&mut self, external_envelope_mode: bool) {
+        self.envelope_layout.external_mode = external_envelope_mode;
+    }
+
+    /// Returns whether the clients may register in the external envelope
+    /// mode
+    pub fn external_envelope_mode(&self) -> bool {
+        self.envelope_layout.external_mode
+    }
+
+    /// Sets the length of the encrypted credentials which the clients seal
+    /// into their envelopes, which the masked response of every
+    /// [CredentialResponse] then makes room for, as for the external envelope
+    /// mode. The clients must deserialize the responses with the resulting
+    /// [envelope_layout](Self::envelope_layout)
+    pub fn set_envelope_credentials_len(
+        &mut self,
+        envelope_credentials_len: usize,
+    ) -> Result<(), ProtocolError> {
+        if envelope_credentials_len > Envelope::<CS>::max_credentials_len() {
+            return Err(PakeError::SerializationError.into());
+        }
+        self.envelope_layout.credentials_len = envelope_credentials_len;
+        Ok(())
+    }
+
+    /// Returns the length of the credentials which the clients seal into
+    /// their envelopes
+    pub fn envelope_credentials_len(&self) -> usize {
+        self.envelope_layout.credentials_len
+    }
+
+    /// Returns the layout of the envelopes, which the clients need to
+    /// deserialize a [CredentialResponse] with
+    /// [CredentialResponse::deserialize_with_layout]
+    pub fn envelope_layout(&self) -> EnvelopeLayout {
+        self.envelope_layout
+    }
+
+    /// Returns the public OPRF key of a credential under the OPRF seed with
+    /// the given key ID, such as the [oprf_key_id](ServerRegistration::oprf_key_id)
+    /// of its password file, which is distributed to the client for it to
+    /// pin in the verifiable mode of the OPRF
+    pub fn oprf_public_key(
+        &self,
+        key_id: u32,
+        credential_identifier: &[u8],
+    ) -> Result<GenericArray<u8, <CS::Group as Group>::ElemLen>, ProtocolError> {
+        let oprf_key = oprf_key_from_seed::<CS::Group, CS::Hash>(
+            self.oprf_seed(key_id)?,
+            credential_identifier,
+        )?;
+        Ok((CS::Group::base_point() * &oprf_key).to_arr())
+    }
+
+    // Creates the proof of the verifiable mode of the OPRF, if enabled
+    fn oprf_proof<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        oprf_key: &<CS::Group as Group>::Scalar,
+        alpha: CS::Group,
+        beta: CS::Group,
+    ) -> Result<Option<oprf::Proof<CS::Group>>, ProtocolError> {
+        if !self.verifiable_oprf {
+            return Ok(None);
+        }
+        Ok(Some(oprf::generate_proof::<R, CS::Group, CS::Hash>(
+            rng,
+            oprf_key,
+            &[alpha],
+            &[beta],
+        )?))
+    }
+
+    // Looks up the OPRF seed with the given key ID
+    fn oprf_seed(
+        &self,
+        key_id: u32,
+    ) -> Result<&GenericArray<u8, <CS::Hash as Digest>::OutputSize>, ProtocolError> {
+        if key_id == self.oprf_key_id {
+            return Ok(&self.oprf_seed);
+        }
+        self.previous_oprf_seeds
+            .iter()
+            .find(|(id, _)| *id == key_id)
+            .map(|(_, seed)| seed)
+            .ok_or(ProtocolError::UnknownOprfKeyIdError(key_id))
+    }
+
+    // Looks up the keypair with the given key ID
+    fn keypair_with_id(&self, key_id: u32) -> Result<&KeyPair<CS::Group>, ProtocolError> {
+        if key_id == self.server_key_id {
+            return Ok(&self.keypair);
+        }
+        self.previous_keypairs
+            .iter()
+            .find(|(id, _)| *id == key_id)
+            .map(|(_, keypair)| keypair)
+            .ok_or(ProtocolError::UnknownServerKeyIdError(key_id))
+    }
+}
+
+// Picks the key ID following all of the ones in use
+fn next_key_id<I: Iterator<Item = u32>>(key_ids: I) -> u32 {
+    key_ids.max().unwrap_or_default().wrapping_add(1)
 }
 
 // Registration
@@ -107,6 +480,7 @@ impl<CS: CipherSuite> ClientRegistration<CS> {
     pub fn serialize(&self) -> Vec<u8> {
         [
             &CS::Group::scalar_as_bytes(&self.token.blind)[..],
+            &serialize_oprf_public_key(&self.token.public_key),
             &self.token.data,
         ]
         .concat()
@@ -130,11 +504,12 @@ impl<CS: CipherSuite> ClientRegistration<CS> {
         let scalar_len = min_expected_len;
         let blinding_factor_bytes = GenericArray::from_slice(&checked_slice[..scalar_len]);
         let blinding_factor = CS::Group::from_scalar_slice(blinding_factor_bytes)?;
-        let password = checked_slice[scalar_len..].to_vec();
+        let (oprf_public_key, password) = tokenize(&checked_slice[scalar_len..], 2)?;
         Ok(Self {
             token: oprf::Token {
                 data: password,
                 blind: blinding_factor,
+                public_key: deserialize_oprf_public_key(&oprf_public_key)?,
             },
         })
     }
@@ -179,17 +554,70 @@ pub(crate) fn bytestrings_from_identifiers(
 }
 
 /// Optional parameters for client registration finish
-#[derive(Clone)]
-pub enum ClientRegistrationFinishParameters {
+pub struct ClientRegistrationFinishParameters<'h, CS: CipherSuite> {
     /// Specifying the identifiers idU and idS
-    WithIdentifiers(Identifiers),
-    /// No identifiers or private key specified
-    Default,
+    pub identifiers: Option<Identifiers>,
+    /// Specifying the parameters of the slow hashing function, in place of
+    /// the ones returned by `SlowHash::default_params`
+    pub slow_hash: Option<&'h CS::SlowHash>,
+    /// Specifying a static keypair of the client, in which case its private
+    /// key is encrypted into the envelope (external mode) instead of being
+    /// derived from the password (internal mode)
+    pub client_static_keypair: Option<&'h KeyPair<CS::Group>>,
+    /// Specifying credentials of the client, such as a recovery code or a
+    /// wrapped key, which are encrypted into the envelope and returned by
+    /// [ClientLogin::finish]
+    pub credentials: Option<&'h [u8]>,
+    /// Specifying the session key of a prior login, in which case the
+    /// registration upload carries a MAC keyed by it, for the server to
+    /// authenticate the registration through
+    /// [ServerRegistration::finish_authenticated]
+    pub session_key: Option<&'h [u8]>,
+    /// Specifying the credential identifier under which the server registers
+    /// the upload, which the MAC keyed by `session_key` binds, and which
+    /// defaults to an empty one
+    pub credential_identifier: Option<&'h [u8]>,
 }
 
-impl Default for ClientRegistrationFinishParameters {
+impl<'h, CS: CipherSuite> ClientRegistrationFinishParameters<'h, CS> {
+    /// Create a new [`ClientRegistrationFinishParameters`]
+    pub fn new(identifiers: Option<Identifiers>, slow_hash: Option<&'h CS::SlowHash>) -> Self {
+        Self {
+            identifiers,
+            slow_hash,
+            client_static_keypair: None,
+            credentials: None,
+            session_key: None,
+            credential_identifier: None,
+        }
+    }
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for ClientRegistrationFinishParameters<'_, CS> {
+    fn clone(&self) -> Self {
+        Self {
+            identifiers: self.identifiers.clone(),
+            slow_hash: self.slow_hash,
+            client_static_keypair: self.client_static_keypair,
+            credentials: self.credentials,
+            session_key: self.session_key,
+            credential_identifier: self.credential_identifier,
+        }
+    }
+}
+
+// Cannot be derived because it would require for CS to be Default.
+impl<CS: CipherSuite> Default for ClientRegistrationFinishParameters<'_, CS> {
     fn default() -> Self {
-        Self::Default
+        Self {
+            identifiers: None,
+            slow_hash: None,
+            client_static_keypair: None,
+            credentials: None,
+            session_key: None,
+            credential_identifier: None,
+        }
     }
 }
 
@@ -217,7 +645,33 @@ impl<CS: CipherSuite> ClientRegistration<CS> {
         blinding_factor_rng: &mut R,
         password: &[u8],
     ) -> Result<ClientRegistrationStartResult<CS>, ProtocolError> {
-        let (token, alpha) = oprf::blind::<R, CS::Group, CS::Hash>(password, blinding_factor_rng)?;
+        Self::start_inner(blinding_factor_rng, password, None)
+    }
+
+    /// Same as [ClientRegistration::start], in the verifiable mode of the
+    /// OPRF, in which the server's response must contain a proof that the
+    /// OPRF was evaluated with the key whose public part is given, as
+    /// returned by [ServerSetup::oprf_public_key]
+    pub fn start_verifiable<R: RngCore + CryptoRng>(
+        blinding_factor_rng: &mut R,
+        password: &[u8],
+        oprf_public_key: &[u8],
+    ) -> Result<ClientRegistrationStartResult<CS>, ProtocolError> {
+        let oprf_public_key =
+            deserialize_oprf_public_key(oprf_public_key)?.ok_or(PakeError::SerializationError)?;
+        Self::start_inner(blinding_factor_rng, password, Some(oprf_public_key))
+    }
+
+    fn start_inner<R: RngCore + CryptoRng>(
+        blinding_factor_rng: &mut R,
+        password: &[u8],
+        oprf_public_key: Option<CS::Group>,
+    ) -> Result<ClientRegistrationStartResult<CS>, ProtocolError> {
+        let (token, alpha) = oprf::blind_point::<R, CS::Group, CS::Hash>(
+            password,
+            oprf_public_key,
+            blinding_factor_rng,
+        )?;
 
         Ok(ClientRegistrationStartResult {
             message: RegistrationRequest::<CS> { alpha },
@@ -256,30 +710,34 @@ impl<CS: CipherSuite> ClientRegistration<CS> {
         self,
         rng: &mut R,
         r2: RegistrationResponse<CS>,
-        params: ClientRegistrationFinishParameters,
+        params: ClientRegistrationFinishParameters<CS>,
     ) -> Result<ClientRegistrationFinishResult<CS>, ProtocolError> {
-        let optional_ids = match params {
-            ClientRegistrationFinishParameters::WithIdentifiers(ids) => Some(ids),
-            ClientRegistrationFinishParameters::Default => None,
-        };
-
-        let password_derived_key =
-            get_password_derived_key::<CS::Group, CS::SlowHash, CS::Hash>(&self.token, r2.beta)?;
+        verify_oprf_proof::<CS>(&self.token, r2.beta, r2.proof.as_ref())?;
 
-        let h = Hkdf::<CS::Hash>::new(None, &password_derived_key);
-        let mut masking_key = vec![0u8; <CS::Hash as Digest>::OutputSize::to_usize()];
-        h.expand(STR_MASKING_KEY, &mut masking_key)
-            .map_err(|_| InternalPakeError::HkdfError)?;
-
-        let (envelope, client_s_pk, export_key) =
-            Envelope::<CS>::seal(rng, &password_derived_key, &r2.server_s_pk, optional_ids)?;
+        let (mut registration_upload, export_key) = seal_registration::<CS>(
+            &Envelope::<CS>::generate_nonce(rng),
+            &self.token,
+            r2.beta,
+            &r2.server_s_pk,
+            params.identifiers,
+            params.slow_hash,
+            params.client_static_keypair,
+            params.credentials.unwrap_or_default(),
+        )?;
+        if let Some(session_key) = params.session_key {
+            registration_upload.mac = Some(
+                registration_upload_mac::<CS>(
+                    session_key,
+                    params.credential_identifier.unwrap_or_default(),
+                    &registration_upload,
+                )?
+                .finalize()
+                .into_bytes(),
+            );
+        }
 
         Ok(ClientRegistrationFinishResult {
-            message: RegistrationUpload {
-                envelope,
-                masking_key: GenericArray::clone_from_slice(&masking_key[..]),
-                client_s_pk,
-            },
+            message: registration_upload,
             export_key,
             #[cfg(test)]
             state: self,
@@ -292,6 +750,9 @@ impl<CS: CipherSuite> ClientRegistration<CS> {
 pub struct ServerRegistrationStartResult<CS: CipherSuite> {
     /// The registration resposne message to send to the client
     pub message: RegistrationResponse<CS>,
+    /// The key IDs used for this registration, to be passed to
+    /// [ServerRegistration::finish]
+    pub key_ids: RegistrationKeyIds,
 }
 
 // Cannot be derived because it would require for CS to be Clone.
@@ -299,43 +760,178 @@ impl<CS: CipherSuite> Clone for ServerRegistrationStartResult<CS> {
     fn clone(&self) -> Self {
         Self {
             message: self.message.clone(),
+            key_ids: self.key_ids.clone(),
         }
     }
 }
 
+/// The key IDs of the OPRF seed and of the server keypair of the
+/// [ServerSetup] with which a registration was started, which
+/// [ServerRegistration::finish] records in the password file, along with the
+/// [EnvelopeLayout] which the uploaded envelope must match and the credential
+/// identifier under which the OPRF was evaluated, which is empty in the
+/// [threshold](crate::threshold) mode. A server which does not keep the
+/// [ServerRegistrationStartResult] until the registration is finished can
+/// store its serialization instead
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistrationKeyIds {
+    pub(crate) oprf_key_id: u32,
+    pub(crate) server_key_id: u32,
+    pub(crate) envelope_layout: EnvelopeLayout,
+    pub(crate) credential_identifier: Vec<u8>,
+}
+
+impl RegistrationKeyIds {
+    fn from_server_setup<CS: CipherSuite>(
+        server_setup: &ServerSetup<CS>,
+        credential_identifier: &[u8],
+    ) -> Self {
+        Self {
+            oprf_key_id: server_setup.oprf_key_id,
+            server_key_id: server_setup.server_key_id,
+            envelope_layout: server_setup.envelope_layout,
+            credential_identifier: credential_identifier.to_vec(),
+        }
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &self.oprf_key_id.to_be_bytes()[..],
+            &self.server_key_id.to_be_bytes(),
+            &[self.envelope_layout.external_mode as u8],
+            &(self.envelope_layout.credentials_len as u16).to_be_bytes(),
+            &serialize(&self.credential_identifier, 2),
+        ]
+        .concat()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let checked_slice =
+            check_slice_size_atleast(input, REGISTRATION_KEY_IDS_LEN, "registration_key_ids")?;
+        let (credential_identifier, remainder) =
+            tokenize(&checked_slice[REGISTRATION_KEY_IDS_LEN..], 2)?;
+        if checked_slice[8] > 1 || !remainder.is_empty() {
+            return Err(PakeError::SerializationError.into());
+        }
+        Ok(Self {
+            oprf_key_id: u32::from_be_bytes(checked_slice[..4].try_into().unwrap()),
+            server_key_id: u32::from_be_bytes(checked_slice[4..8].try_into().unwrap()),
+            envelope_layout: EnvelopeLayout {
+                external_mode: checked_slice[8] == 1,
+                credentials_len: u16::from_be_bytes([checked_slice[9], checked_slice[10]]) as usize,
+            },
+            credential_identifier,
+        })
+    }
+}
+
 /// The state elements the server holds to record a registration
-pub struct ServerRegistration<CS: CipherSuite>(RegistrationUpload<CS>);
+pub struct ServerRegistration<CS: CipherSuite> {
+    pub(crate) upload: RegistrationUpload<CS>,
+    pub(crate) slow_hash: CS::SlowHash,
+    pub(crate) oprf_key_id: u32,
+    pub(crate) server_key_id: u32,
+}
 
 // Cannot be derived because it would require for CS to be Clone.
 impl<CS: CipherSuite> Clone for ServerRegistration<CS> {
     fn clone(&self) -> Self {
-        Self(self.0.clone())
+        Self {
+            upload: self.upload.clone(),
+            slow_hash: self.slow_hash.clone(),
+            oprf_key_id: self.oprf_key_id,
+            server_key_id: self.server_key_id,
+        }
     }
 }
 
 impl<CS: CipherSuite> ServerRegistration<CS> {
-    /// Serialization into bytes
+    /// Serialization into bytes, prefixed with a version, the ciphersuite
+    /// identifier, the OPRF and server key IDs and the parameters of the slow
+    /// hashing function
     pub fn serialize(&self) -> Vec<u8> {
-        self.0.serialize()
+        [
+            vec![PASSWORD_FILE_VERSION],
+            CS::ID.to_be_bytes().to_vec(),
+            self.oprf_key_id.to_be_bytes().to_vec(),
+            self.server_key_id.to_be_bytes().to_vec(),
+            serialize(&self.slow_hash.serialize(), 2),
+            self.upload.serialize(),
+        ]
+        .concat()
     }
 
     /// Deserialization from bytes
     pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
-        Ok(Self(RegistrationUpload::deserialize(input)?))
+        let key_ids_len = match input.first() {
+            Some(&PASSWORD_FILE_VERSION_WITHOUT_KEY_IDS) => 0,
+            Some(&PASSWORD_FILE_VERSION_WITHOUT_SERVER_KEY_ID) => 4,
+            Some(&PASSWORD_FILE_VERSION) => 8,
+            _ => return Err(PakeError::SerializationError.into()),
+        };
+        if input.len() < 3 {
+            return Err(PakeError::SerializationError.into());
+        }
+
+        let suite_id = u16::from_be_bytes([input[1], input[2]]);
+        if suite_id != CS::ID {
+            return Err(ProtocolError::CipherSuiteMismatchError {
+                expected: CS::ID,
+                actual: suite_id,
+            });
+        }
+
+        let checked_slice = check_slice_size_atleast(&input[3..], key_ids_len, "key_ids")?;
+        let mut key_ids = [0u32; 2];
+        for (key_id, bytes) in key_ids
+            .iter_mut()
+            .zip(checked_slice[..key_ids_len].chunks(4))
+        {
+            *key_id = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+
+        let (slow_hash_bytes, remainder) = tokenize(&checked_slice[key_ids_len..], 2)?;
+        Ok(Self {
+            upload: RegistrationUpload::deserialize(&remainder)?,
+            slow_hash: CS::SlowHash::deserialize(&slow_hash_bytes)?,
+            oprf_key_id: key_ids[0],
+            server_key_id: key_ids[1],
+        })
+    }
+
+    /// Returns the parameters of the slow hashing function that the client
+    /// used during registration
+    pub fn slow_hash(&self) -> &CS::SlowHash {
+        &self.slow_hash
+    }
+
+    /// Returns the key ID of the OPRF seed this password file was registered
+    /// with
+    pub fn oprf_key_id(&self) -> u32 {
+        self.oprf_key_id
+    }
+
+    /// Returns the key ID of the server keypair whose public key this
+    /// password file was registered with
+    pub fn server_key_id(&self) -> u32 {
+        self.server_key_id
     }
 
     #[cfg(test)]
     pub fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
         [
-            self.0.envelope.as_byte_ptrs(),
-            vec![(self.0.client_s_pk.as_ptr(), self.0.client_s_pk.len())],
+            self.upload.envelope.as_byte_ptrs(),
+            vec![(self.upload.client_s_pk.as_ptr(), self.upload.client_s_pk.len())],
             /* cannot provide raw pointer to self.oprf_key until this is exposed in curve25519_dalek::scalar::Scalar */
         ].concat()
     }
 
     /// From the client's "blinded" password, returns a response to be
     /// sent back to the client, as well as a ServerRegistration
-    pub fn start(
+    pub fn start<R: RngCore + CryptoRng>(
+        rng: &mut R,
         server_setup: &ServerSetup<CS>,
         message: RegistrationRequest<CS>,
         credential_identifier: &[u8],
@@ -346,20 +942,186 @@ impl<CS: CipherSuite> ServerRegistration<CS> {
         )?;
 
         // Compute beta = alpha^oprf_key
-        let beta = oprf::evaluate::<CS::Group>(message.alpha, &oprf_key);
+        let beta = oprf::evaluate_point::<CS::Group>(message.alpha, &oprf_key);
+        let proof = server_setup.oprf_proof(rng, &oprf_key, message.alpha, beta)?;
+
+        Ok(ServerRegistrationStartResult {
+            message: RegistrationResponse {
+                beta,
+                server_s_pk: server_setup.keypair.public().clone(),
+                proof,
+            },
+            key_ids: RegistrationKeyIds::from_server_setup(server_setup, credential_identifier),
+        })
+    }
+
+    /// In the [threshold](crate::threshold) mode, from the client's "blinded"
+    /// password, returns a response to be sent back to the client, in which
+    /// the evaluation of the OPRF under the server's own [OprfKeyShare] is
+    /// combined with the [PartialEvaluation]s of other share holders, once
+    /// each of them is verified against the [PublicShare] of the same index.
+    /// The server never holds the OPRF key of the credential, and so cannot
+    /// prove the evaluation to the client: a server setup in the verifiable
+    /// mode is rejected with
+    /// [VerifiableThresholdError](ProtocolError::VerifiableThresholdError)
+    pub fn start_threshold(
+        server_setup: &ServerSetup<CS>,
+        message: RegistrationRequest<CS>,
+        oprf_key_share: &OprfKeyShare<CS>,
+        partial_evaluations: &[PartialEvaluation<CS>],
+        public_shares: &[PublicShare<CS>],
+    ) -> Result<ServerRegistrationStartResult<CS>, ProtocolError> {
+        if server_setup.verifiable_oprf {
+            return Err(ProtocolError::VerifiableThresholdError);
+        }
+        let beta = oprf_key_share.evaluate_and_combine(
+            message.alpha,
+            partial_evaluations,
+            public_shares,
+        )?;
 
         Ok(ServerRegistrationStartResult {
             message: RegistrationResponse {
                 beta,
                 server_s_pk: server_setup.keypair.public().clone(),
+                proof: None,
             },
+            key_ids: RegistrationKeyIds::from_server_setup(server_setup, &[]),
         })
     }
 
     /// From the client's cryptographic identifiers, fully populates and
-    /// returns a ServerRegistration
-    pub fn finish(message: RegistrationUpload<CS>) -> Self {
-        Self(message)
+    /// returns a ServerRegistration, which records the parameters of the
+    /// slow hashing function used by the client, and the key IDs returned by
+    /// [ServerRegistration::start]. The registration is rejected with
+    /// [EnvelopeLayoutMismatchError](ProtocolError::EnvelopeLayoutMismatchError)
+    /// unless its envelope matches the [EnvelopeLayout] of the server, since
+    /// the length of the credential responses would otherwise reveal that the
+    /// account exists
+    pub fn finish(
+        mut message: RegistrationUpload<CS>,
+        key_ids: RegistrationKeyIds,
+    ) -> Result<Self, ProtocolError> {
+        if !message.envelope.matches_layout(&key_ids.envelope_layout) {
+            return Err(ProtocolError::EnvelopeLayoutMismatchError);
+        }
+        // Neither the MAC nor the parameters of the slow hashing function are
+        // kept in the password file, which holds the latter separately
+        message.mac = None;
+        let slow_hash = message.slow_hash.take();
+
+        Ok(Self {
+            upload: message,
+            slow_hash: slow_hash.unwrap_or_else(CS::SlowHash::default_params),
+            oprf_key_id: key_ids.oprf_key_id,
+            server_key_id: key_ids.server_key_id,
+        })
+    }
+
+    /// Same as [ServerRegistration::finish], for a registration which the
+    /// client authenticated under the session key of a prior login, through
+    /// the `session_key` field of [ClientRegistrationFinishParameters]. The
+    /// registration is only accepted if its MAC is valid under the session
+    /// key of the given login and over the credential identifier under which
+    /// the registration was started, and is rejected with
+    /// [CredentialIdentifierMismatchError](ProtocolError::CredentialIdentifierMismatchError)
+    /// unless the login was for the same credential identifier
+    pub fn finish_authenticated(
+        mut message: RegistrationUpload<CS>,
+        key_ids: RegistrationKeyIds,
+        login_result: &ServerLoginFinishResult<CS>,
+    ) -> Result<Self, ProtocolError> {
+        let mac = message
+            .mac
+            .take()
+            .ok_or(PakeError::KeyExchangeMacValidationError)?;
+        registration_upload_mac::<CS>(
+            &login_result.session_key,
+            &key_ids.credential_identifier,
+            &message,
+        )?
+        .verify(&mac)
+        .map_err(|_| PakeError::KeyExchangeMacValidationError)?;
+        if key_ids.credential_identifier != login_result.key_ids.credential_identifier {
+            return Err(ProtocolError::CredentialIdentifierMismatchError);
+        }
+        Self::finish(message, key_ids)
+    }
+
+    /// From the client's "blinded" new password, sent along with the
+    /// [CredentialFinalization] of a login through [ClientPasswordChange::start],
+    /// returns a response to be sent back to the client. The OPRF is evaluated
+    /// under the credential identifier and the OPRF seed of the password file
+    /// of the login, so that the new password file replaces it in place, even
+    /// if the seed has been rotated since. The response is sealed with the
+    /// current server keypair. In the [threshold](crate::threshold) mode, the
+    /// registration is instead started with
+    /// [ServerRegistration::start_threshold]
+    pub fn start_password_change<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS>,
+        message: RegistrationRequest<CS>,
+        login_result: &ServerLoginFinishResult<CS>,
+    ) -> Result<ServerRegistrationStartResult<CS>, ProtocolError> {
+        let key_ids = RegistrationKeyIds {
+            oprf_key_id: login_result.key_ids.oprf_key_id,
+            ..RegistrationKeyIds::from_server_setup(
+                server_setup,
+                &login_result.key_ids.credential_identifier,
+            )
+        };
+        let oprf_key = oprf_key_from_seed::<CS::Group, CS::Hash>(
+            server_setup.oprf_seed(key_ids.oprf_key_id)?,
+            &key_ids.credential_identifier,
+        )?;
+
+        let beta = oprf::evaluate_point::<CS::Group>(message.alpha, &oprf_key);
+        let proof = server_setup.oprf_proof(rng, &oprf_key, message.alpha, beta)?;
+
+        Ok(ServerRegistrationStartResult {
+            message: RegistrationResponse {
+                beta,
+                server_s_pk: server_setup.keypair.public().clone(),
+                proof,
+            },
+            key_ids,
+        })
+    }
+
+    /// From the [PasswordFileUpgrade] sent by the client to change its
+    /// password after a login, checks that it was produced during this login,
+    /// and returns the password file replacing the existing one. The password
+    /// change is rejected with
+    /// [PasswordChangeMismatchError](ProtocolError::PasswordChangeMismatchError)
+    /// unless the registration was started under the credential identifier
+    /// and the OPRF key ID of the existing password file, as done by
+    /// [ServerRegistration::start_password_change], and unless it keeps the
+    /// envelope mode of the existing password file. In the external mode, the
+    /// client's static public key must be kept as well
+    pub fn finish_password_change(
+        message: PasswordFileUpgrade<CS>,
+        login_result: &ServerLoginFinishResult<CS>,
+        key_ids: RegistrationKeyIds,
+    ) -> Result<Self, ProtocolError> {
+        session_key_mac::<CS>(
+            STR_PASSWORD_CHANGE_MAC,
+            &login_result.session_key,
+            &message.registration_upload,
+        )?
+        .verify(&message.mac)
+        .map_err(|_| PakeError::KeyExchangeMacValidationError)?;
+        if key_ids.credential_identifier != login_result.key_ids.credential_identifier
+            || key_ids.oprf_key_id != login_result.key_ids.oprf_key_id
+            || message.registration_upload.envelope.is_external_mode() != login_result.external_mode
+        {
+            return Err(ProtocolError::PasswordChangeMismatchError);
+        }
+        if login_result.external_mode
+            && message.registration_upload.client_s_pk != login_result.client_s_pk
+        {
+            return Err(PakeError::IncompatibleClientStaticPublicKeyError.into());
+        }
+        Self::finish(message.registration_upload, key_ids)
     }
 
     // Creates a dummy instance used for faking a [CredentialResponse]
@@ -367,7 +1129,12 @@ impl<CS: CipherSuite> ServerRegistration<CS> {
         rng: &mut R,
         server_setup: &ServerSetup<CS>,
     ) -> Self {
-        Self(RegistrationUpload::dummy(rng, server_setup))
+        Self {
+            upload: RegistrationUpload::dummy(rng, server_setup),
+            slow_hash: CS::SlowHash::default_params(),
+            oprf_key_id: server_setup.oprf_key_id,
+            server_key_id: server_setup.server_key_id,
+        }
     }
 }
 
@@ -400,6 +1167,7 @@ impl<CS: CipherSuite> ClientLogin<CS> {
     pub fn serialize(&self) -> Vec<u8> {
         let output: Vec<u8> = [
             &CS::Group::scalar_as_bytes(&self.token.blind)[..],
+            &serialize_oprf_public_key(&self.token.public_key),
             &serialize(&self.serialized_credential_request, 2),
             &serialize(&self.ke1_state.to_bytes(), 2),
             &self.token.data,
@@ -424,7 +1192,8 @@ impl<CS: CipherSuite> ClientLogin<CS> {
         let blinding_factor_bytes = GenericArray::from_slice(&checked_slice[..scalar_len]);
         let blinding_factor = CS::Group::from_scalar_slice(blinding_factor_bytes)?;
 
-        let (serialized_credential_request, remainder) = tokenize(&checked_slice[scalar_len..], 2)?;
+        let (oprf_public_key, remainder) = tokenize(&checked_slice[scalar_len..], 2)?;
+        let (serialized_credential_request, remainder) = tokenize(&remainder, 2)?;
         let (ke1_state_bytes, password) = tokenize(&remainder, 2)?;
 
         let ke1_state =
@@ -435,13 +1204,19 @@ impl<CS: CipherSuite> ClientLogin<CS> {
             token: oprf::Token {
                 data: password,
                 blind: blinding_factor,
+                public_key: deserialize_oprf_public_key(&oprf_public_key)?,
             },
             ke1_state,
             serialized_credential_request,
         })
     }
+}
 
-    #[cfg(test)]
+#[cfg(test)]
+impl<CS: CipherSuite> ClientLogin<CS>
+where
+    <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::KE1State: AsBytePointers,
+{
     pub fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
         [
             vec![
@@ -473,22 +1248,70 @@ impl<CS: CipherSuite> Clone for ClientLoginStartResult<CS> {
 }
 
 /// Optional parameters for client login finish
-#[derive(Clone)]
-pub enum ClientLoginFinishParameters {
+pub struct ClientLoginFinishParameters<'h, CS: CipherSuite> {
     /// Specifying a context field that the server must agree on
-    WithContext(Vec<u8>),
+    pub context: Option<Vec<u8>>,
     /// Specifying a user identifier and server identifier that will be matched against the server
-    WithIdentifiers(Identifiers),
-    /// Specifying a context field that the server must agree on,
-    /// along with a user identifier and server identifier and context that will be matched against the server
-    WithContextAndIdentifiers(Vec<u8>, Identifiers),
-    /// No custom identifiers and no context
-    Default,
+    pub identifiers: Option<Identifiers>,
+    /// Specifying the parameters of the slow hashing function, which must
+    /// match the ones used during registration
+    pub slow_hash: Option<&'h CS::SlowHash>,
+    /// Specifying an application payload to send to the server along with
+    /// the [CredentialFinalization], authenticated by the third key exchange
+    /// message. It is at most 65535 bytes long
+    pub payload: Option<Vec<u8>>,
+    /// Whether to encrypt the application payload under a key derived from
+    /// the session key. Otherwise, it is sent in plaintext
+    pub encrypt_payload: bool,
+    /// Specifying a channel binding, such as a TLS exporter value, that the
+    /// server must agree on. It is bound into the key exchange transcript
+    /// separately from the context field, and is at most 65535 bytes long
+    pub channel_binding: Option<Vec<u8>>,
 }
 
-impl Default for ClientLoginFinishParameters {
+impl<'h, CS: CipherSuite> ClientLoginFinishParameters<'h, CS> {
+    /// Create a new [`ClientLoginFinishParameters`]
+    pub fn new(
+        context: Option<Vec<u8>>,
+        identifiers: Option<Identifiers>,
+        slow_hash: Option<&'h CS::SlowHash>,
+    ) -> Self {
+        Self {
+            context,
+            identifiers,
+            slow_hash,
+            payload: None,
+            encrypt_payload: false,
+            channel_binding: None,
+        }
+    }
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for ClientLoginFinishParameters<'_, CS> {
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+            identifiers: self.identifiers.clone(),
+            slow_hash: self.slow_hash,
+            payload: self.payload.clone(),
+            encrypt_payload: self.encrypt_payload,
+            channel_binding: self.channel_binding.clone(),
+        }
+    }
+}
+
+// Cannot be derived because it would require for CS to be Default.
+impl<CS: CipherSuite> Default for ClientLoginFinishParameters<'_, CS> {
     fn default() -> Self {
-        Self::Default
+        Self {
+            context: None,
+            identifiers: None,
+            slow_hash: None,
+            payload: None,
+            encrypt_payload: false,
+            channel_binding: None,
+        }
     }
 }
 
@@ -496,12 +1319,28 @@ impl Default for ClientLoginFinishParameters {
 pub struct ClientLoginFinishResult<CS: CipherSuite> {
     /// The message to send to the server to complete the protocol
     pub message: CredentialFinalization<CS>,
-    /// The session key
-    pub session_key: Vec<u8>,
+    /// The session key, which is zeroized when dropped
+    pub session_key: Zeroizing<Vec<u8>>,
     /// The client-side export key
     pub export_key: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
     /// The server's static public key
     pub server_s_pk: PublicKey,
+    /// The message to send to the server along with `message` in order to
+    /// replace the password file, if requested through
+    /// [ClientLogin::finish_with_upgrade]
+    pub password_file_upgrade: Option<PasswordFileUpgrade<CS>>,
+    /// The client-side export key corresponding to the upgraded password file
+    pub upgrade_export_key: Option<GenericArray<u8, <CS::Hash as Digest>::OutputSize>>,
+    /// The application payload sent by the server, which is empty if the
+    /// server did not send one
+    pub server_payload: Vec<u8>,
+    /// The hash of the whole transcript of the login, up to and including
+    /// the [CredentialFinalization], which is also returned to the server and
+    /// can be used for channel binding
+    pub transcript_hash: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+    /// The credentials sealed into the envelope during registration, which
+    /// are empty if there were none
+    pub credentials: Zeroizing<Vec<u8>>,
     /// Instance of the ClientLogin, only used in tests for checking zeroize
     #[cfg(test)]
     pub state: ClientLogin<CS>,
@@ -515,19 +1354,55 @@ impl<CS: CipherSuite> Clone for ClientLoginFinishResult<CS> {
             session_key: self.session_key.clone(),
             export_key: self.export_key.clone(),
             server_s_pk: self.server_s_pk.clone(),
+            password_file_upgrade: self.password_file_upgrade.clone(),
+            upgrade_export_key: self.upgrade_export_key.clone(),
+            server_payload: self.server_payload.clone(),
+            transcript_hash: self.transcript_hash.clone(),
+            credentials: self.credentials.clone(),
             #[cfg(test)]
             state: self.state.clone(),
         }
     }
 }
 
+impl<CS: CipherSuite> ClientLoginFinishResult<CS> {
+    /// Returns the [SessionKeys] borrowing the session key, from which keys
+    /// for the application can be exported
+    pub fn session_keys(&self) -> SessionKeys<'_, CS> {
+        SessionKeys::new(&self.session_key)
+    }
+}
+
 impl<CS: CipherSuite> ClientLogin<CS> {
     /// Returns an initial "blinded" password request to send to the server, as well as a ClientLogin
     pub fn start<R: RngCore + CryptoRng>(
         rng: &mut R,
         password: &[u8],
     ) -> Result<ClientLoginStartResult<CS>, ProtocolError> {
-        let (token, alpha) = oprf::blind::<R, CS::Group, CS::Hash>(password, rng)?;
+        Self::start_inner(rng, password, None)
+    }
+
+    /// Same as [ClientLogin::start], in the verifiable mode of the OPRF, in
+    /// which the server's response must contain a proof that the OPRF was
+    /// evaluated with the key whose public part is given, as returned by
+    /// [ServerSetup::oprf_public_key]
+    pub fn start_verifiable<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        password: &[u8],
+        oprf_public_key: &[u8],
+    ) -> Result<ClientLoginStartResult<CS>, ProtocolError> {
+        let oprf_public_key =
+            deserialize_oprf_public_key(oprf_public_key)?.ok_or(PakeError::SerializationError)?;
+        Self::start_inner(rng, password, Some(oprf_public_key))
+    }
+
+    fn start_inner<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        password: &[u8],
+        oprf_public_key: Option<CS::Group>,
+    ) -> Result<ClientLoginStartResult<CS>, ProtocolError> {
+        let (token, alpha) =
+            oprf::blind_point::<R, CS::Group, CS::Hash>(password, oprf_public_key, rng)?;
 
         let (ke1_state, ke1_message) = CS::KeyExchange::generate_ke1(rng)?;
 
@@ -549,21 +1424,60 @@ impl<CS: CipherSuite> ClientLogin<CS> {
     pub fn finish(
         self,
         credential_response: CredentialResponse<CS>,
-        params: ClientLoginFinishParameters,
+        params: ClientLoginFinishParameters<CS>,
     ) -> Result<ClientLoginFinishResult<CS>, ProtocolError> {
-        let (context, optional_ids) = match params {
-            ClientLoginFinishParameters::Default => (vec![], None),
-            ClientLoginFinishParameters::WithContext(context) => (context, None),
-            ClientLoginFinishParameters::WithIdentifiers(ids) => (vec![], Some(ids)),
-            // add context
-            ClientLoginFinishParameters::WithContextAndIdentifiers(context, ids) => {
-                (context, Some(ids))
-            }
-        };
+        self.finish_inner(credential_response, params, None)
+            .map(|(result, _)| result)
+    }
 
-        let password_derived_key = get_password_derived_key::<CS::Group, CS::SlowHash, CS::Hash>(
+    /// Same as [ClientLogin::finish], and additionally returns a
+    /// [PasswordFileUpgrade], which re-registers the password with the given
+    /// parameters of the slow hashing function, keeping the envelope mode
+    /// and the credentials of the existing password file
+    pub fn finish_with_upgrade<R: RngCore + CryptoRng>(
+        self,
+        rng: &mut R,
+        credential_response: CredentialResponse<CS>,
+        params: ClientLoginFinishParameters<CS>,
+        upgrade_slow_hash: &CS::SlowHash,
+    ) -> Result<ClientLoginFinishResult<CS>, ProtocolError> {
+        let envelope_nonce = Envelope::<CS>::generate_nonce(rng);
+        self.finish_inner(
+            credential_response,
+            params,
+            Some((upgrade_slow_hash, &envelope_nonce)),
+        )
+        .map(|(result, _)| result)
+    }
+
+    // Along with the result of the login, returns the static keypair of the
+    // client if its envelope is in the external mode, for a password change
+    #[allow(clippy::type_complexity)]
+    fn finish_inner(
+        self,
+        credential_response: CredentialResponse<CS>,
+        params: ClientLoginFinishParameters<CS>,
+        upgrade: Option<(&CS::SlowHash, &[u8])>,
+    ) -> Result<(ClientLoginFinishResult<CS>, Option<KeyPair<CS::Group>>), ProtocolError> {
+        let context = params.context.unwrap_or_default();
+        let optional_ids = params.identifiers;
+        let payload = params.payload.unwrap_or_default();
+        let channel_binding = params.channel_binding.unwrap_or_default();
+        if payload.len() > MAX_FIELD_LEN || channel_binding.len() > MAX_FIELD_LEN {
+            return Err(PakeError::SerializationError.into());
+        }
+
+        verify_oprf_proof::<CS>(
             &self.token,
             credential_response.beta,
+            credential_response.proof.as_ref(),
+        )?;
+        let beta = credential_response.beta;
+
+        let password_derived_key = get_password_derived_key::<CS::Group, CS::SlowHash, CS::Hash>(
+            &self.token,
+            beta,
+            params.slow_hash,
         )?;
 
         let h = Hkdf::<CS::Hash>::new(None, &password_derived_key);
@@ -583,7 +1497,7 @@ impl<CS: CipherSuite> ClientLogin<CS> {
             }
             err => err,
         })?;
-        let server_s_pk_bytes = server_s_pk.to_arr().to_vec();
+        let server_s_pk_bytes = server_s_pk.to_vec();
 
         let opened_envelope = &envelope
             .open(&password_derived_key, &server_s_pk_bytes, &optional_ids)
@@ -592,38 +1506,128 @@ impl<CS: CipherSuite> ClientLogin<CS> {
                 err => PakeError::from(err),
             })?;
 
-        let credential_response_component = CredentialResponse::<CS>::serialize_without_ke(
+        let credential_response_component = CredentialResponse::<CS>::transcript_component(
             &credential_response.beta,
             &credential_response.masking_nonce,
             &credential_response.masked_response,
+            &credential_response.payload,
         );
 
+        let transcript = TranscriptInputs {
+            context: &context,
+            id_u: &opened_envelope.id_u,
+            serialized_credential_request: &self.serialized_credential_request,
+            id_s: &opened_envelope.id_s,
+            serialized_credential_response: &credential_response_component,
+            channel_binding: &channel_binding,
+        };
+        let ke2_transcript_hash = transcript
+            .hasher::<CS::Hash>()
+            .chain(credential_response.ke2_message.to_bytes())
+            .finalize();
         let (session_key, ke3_message) = CS::KeyExchange::generate_ke3(
-            credential_response_component,
             credential_response.ke2_message,
             &self.ke1_state,
-            &self.serialized_credential_request,
-            server_s_pk.clone(),
-            opened_envelope.client_static_keypair.private().clone(),
-            opened_envelope.id_u.clone(),
-            opened_envelope.id_s.clone(),
-            context,
+            Ke3Inputs {
+                transcript,
+                server_s_pk: &server_s_pk,
+                client_s_sk: opened_envelope.client_static_keypair.private(),
+                client_payload: ClientPayload {
+                    payload: &payload,
+                    encrypt: params.encrypt_payload,
+                },
+            },
         )?;
 
-        Ok(ClientLoginFinishResult {
-            message: CredentialFinalization { ke3_message },
-            session_key,
+        let (password_file_upgrade, upgrade_export_key) = match upgrade {
+            Some((upgrade_slow_hash, envelope_nonce)) => {
+                let (registration_upload, export_key) = seal_registration::<CS>(
+                    envelope_nonce,
+                    &self.token,
+                    beta,
+                    &server_s_pk,
+                    optional_ids,
+                    Some(upgrade_slow_hash),
+                    // The upgrade keeps the envelope mode, along with the
+                    // static keypair of the external mode, and the
+                    // credentials
+                    if envelope.is_external_mode() {
+                        Some(&opened_envelope.client_static_keypair)
+                    } else {
+                        None
+                    },
+                    &opened_envelope.credentials,
+                )?;
+                let mac = session_key_mac::<CS>(
+                    STR_PASSWORD_FILE_UPGRADE_MAC,
+                    &session_key,
+                    &registration_upload,
+                )?
+                .finalize()
+                .into_bytes();
+                (
+                    Some(PasswordFileUpgrade {
+                        registration_upload,
+                        mac,
+                    }),
+                    Some(export_key),
+                )
+            }
+            None => (None, None),
+        };
+
+        let payload = if params.encrypt_payload {
+            crypt_client_payload::<CS::Hash>(&session_key, &payload)?
+        } else {
+            payload
+        };
+
+        let message = CredentialFinalization {
+            ke3_message,
+            payload,
+            payload_encrypted: params.encrypt_payload,
+        };
+        let transcript_hash =
+            finalize_transcript_hash::<CS>(&ke2_transcript_hash, &message.serialize());
+        let client_static_keypair = if envelope.is_external_mode() {
+            Some(opened_envelope.client_static_keypair.clone())
+        } else {
+            None
+        };
+
+        let result = ClientLoginFinishResult {
+            message,
+            session_key: Zeroizing::new(session_key),
             export_key: opened_envelope.export_key.clone(),
             server_s_pk,
+            password_file_upgrade,
+            upgrade_export_key,
+            server_payload: credential_response.payload,
+            transcript_hash,
+            credentials: opened_envelope.credentials.clone(),
             #[cfg(test)]
             state: self,
-        })
+        };
+        Ok((result, client_static_keypair))
     }
 }
 
 /// The state elements the server holds to record a login
 pub struct ServerLogin<CS: CipherSuite> {
     ke2_state: <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::KE2State,
+    // The key IDs and the credential identifier of the password file, and the
+    // envelope layout of the server, for a password file upgrade or change
+    key_ids: RegistrationKeyIds,
+    // Whether the envelope of the password file is in the external mode,
+    // which a password change must keep
+    external_mode: bool,
+    record_kind: RecordKind,
+    // The hash of the transcript up to the second key exchange message, which
+    // is extended with the CredentialFinalization once it is received
+    transcript_hash: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+    client_s_pk: PublicKey,
+    id_u: Vec<u8>,
+    id_s: Vec<u8>,
     _cs: PhantomData<CS>,
 }
 
@@ -632,27 +1636,82 @@ impl<CS: CipherSuite> Clone for ServerLogin<CS> {
     fn clone(&self) -> Self {
         Self {
             ke2_state: self.ke2_state.clone(),
+            key_ids: self.key_ids.clone(),
+            external_mode: self.external_mode,
+            record_kind: self.record_kind,
+            transcript_hash: self.transcript_hash.clone(),
+            client_s_pk: self.client_s_pk.clone(),
+            id_u: self.id_u.clone(),
+            id_s: self.id_s.clone(),
             _cs: PhantomData,
         }
     }
 }
 
+/// Whether a login was processed with a registered password file, or with the
+/// dummy one that [ServerLogin::start] uses when given none. This is only known
+/// to the server, and contains no information about the client, so that it can
+/// be recorded in audit logs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordKind {
+    /// The password file of a registered client
+    Registered,
+    /// A dummy password file, against which the login cannot succeed
+    Dummy,
+}
+
 /// Optional parameters for server login start
-#[derive(Clone)]
-pub enum ServerLoginStartParameters {
+#[derive(Clone, Default)]
+pub struct ServerLoginStartParameters {
     /// Specifying a context field that the client must agree on
-    WithContext(Vec<u8>),
+    pub context: Option<Vec<u8>>,
     /// Specifying a user identifier and server identifier that will be matched against the client
-    WithIdentifiers(Identifiers),
-    /// Specifying a context field that the client must agree on,
-    /// along with a user identifier and and server identifier that will be matched against the client
-    /// (in that order)
-    WithContextAndIdentifiers(Vec<u8>, Identifiers),
+    pub identifiers: Option<Identifiers>,
+    /// Specifying an application payload to send to the client along with the
+    /// [CredentialResponse], authenticated by the key exchange. It is not
+    /// encrypted, and is at most 65535 bytes long
+    pub payload: Option<Vec<u8>>,
+    /// Specifying a channel binding, such as a TLS exporter value, that the
+    /// client must agree on. It is bound into the key exchange transcript
+    /// separately from the context field, and is at most 65535 bytes long
+    pub channel_binding: Option<Vec<u8>>,
 }
 
-impl Default for ServerLoginStartParameters {
-    fn default() -> Self {
-        Self::WithContext(Vec::new())
+impl ServerLoginStartParameters {
+    /// Create a new [`ServerLoginStartParameters`]
+    pub fn new(context: Option<Vec<u8>>, identifiers: Option<Identifiers>) -> Self {
+        Self {
+            context,
+            identifiers,
+            payload: None,
+            channel_binding: None,
+        }
+    }
+}
+
+/// A login request to be processed as part of a batch by
+/// [ServerLogin::start_batch], along with the arguments that
+/// [ServerLogin::start] takes for it
+pub struct ServerLoginStartRequest<'a, CS: CipherSuite> {
+    /// The message received from the client
+    pub message: CredentialRequest<CS>,
+    /// The credential identifier of the client
+    pub credential_identifier: &'a [u8],
+    /// The password file of the client, if it is registered
+    pub password_file: Option<ServerRegistration<CS>>,
+    /// The parameters of the login
+    pub params: ServerLoginStartParameters,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<'a, CS: CipherSuite> Clone for ServerLoginStartRequest<'a, CS> {
+    fn clone(&self) -> Self {
+        Self {
+            message: self.message.clone(),
+            credential_identifier: self.credential_identifier,
+            password_file: self.password_file.clone(),
+            params: self.params.clone(),
+        }
     }
 }
 
@@ -674,10 +1733,70 @@ impl<CS: CipherSuite> Clone for ServerLoginStartResult<CS> {
     }
 }
 
+/// Optional parameters for server login finish
+pub struct ServerLoginFinishParameters<CS: CipherSuite> {
+    /// Specifying the [PasswordFileUpgrade] sent by the client, which is
+    /// verified against the session key before producing a new password
+    /// file, recording the parameters of the slow hashing function that the
+    /// client used for the upgrade
+    pub password_file_upgrade: Option<PasswordFileUpgrade<CS>>,
+}
+
+impl<CS: CipherSuite> ServerLoginFinishParameters<CS> {
+    /// Create a new [`ServerLoginFinishParameters`]
+    pub fn new(password_file_upgrade: Option<PasswordFileUpgrade<CS>>) -> Self {
+        Self {
+            password_file_upgrade,
+        }
+    }
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for ServerLoginFinishParameters<CS> {
+    fn clone(&self) -> Self {
+        Self {
+            password_file_upgrade: self.password_file_upgrade.clone(),
+        }
+    }
+}
+
+// Cannot be derived because it would require for CS to be Default.
+impl<CS: CipherSuite> Default for ServerLoginFinishParameters<CS> {
+    fn default() -> Self {
+        Self {
+            password_file_upgrade: None,
+        }
+    }
+}
+
 /// Contains the fields that are returned by a server login finish
 pub struct ServerLoginFinishResult<CS: CipherSuite> {
-    /// The session key between client and server
-    pub session_key: Vec<u8>,
+    /// The session key between client and server, which is zeroized when
+    /// dropped
+    pub session_key: Zeroizing<Vec<u8>>,
+    /// The password file replacing the existing one, if the client sent a
+    /// [PasswordFileUpgrade]
+    pub password_file: Option<ServerRegistration<CS>>,
+    /// The application payload sent by the client, which is empty if the
+    /// client did not send one
+    pub client_payload: Vec<u8>,
+    /// The hash of the whole transcript of the login, up to and including
+    /// the [CredentialFinalization], which is also returned to the client and
+    /// can be used for channel binding
+    pub transcript_hash: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+    /// The client's static public key, from the password file against which
+    /// the client was authenticated
+    pub client_s_pk: PublicKey,
+    /// The client identity used in the login, which defaults to the client's
+    /// static public key
+    pub id_u: Vec<u8>,
+    /// The server identity used in the login, which defaults to the server's
+    /// static public key
+    pub id_s: Vec<u8>,
+    // The key IDs and the envelope mode of the password file, which a
+    // password change must keep
+    pub(crate) key_ids: RegistrationKeyIds,
+    pub(crate) external_mode: bool,
     _cs: PhantomData<CS>,
     /// Instance of the ClientRegistration, only used in tests for checking zeroize
     #[cfg(test)]
@@ -689,6 +1808,14 @@ impl<CS: CipherSuite> Clone for ServerLoginFinishResult<CS> {
     fn clone(&self) -> Self {
         Self {
             session_key: self.session_key.clone(),
+            password_file: self.password_file.clone(),
+            client_payload: self.client_payload.clone(),
+            transcript_hash: self.transcript_hash.clone(),
+            client_s_pk: self.client_s_pk.clone(),
+            id_u: self.id_u.clone(),
+            id_s: self.id_s.clone(),
+            key_ids: self.key_ids.clone(),
+            external_mode: self.external_mode,
             _cs: PhantomData,
             #[cfg(test)]
             state: self.state.clone(),
@@ -696,22 +1823,77 @@ impl<CS: CipherSuite> Clone for ServerLoginFinishResult<CS> {
     }
 }
 
+impl<CS: CipherSuite> ServerLoginFinishResult<CS> {
+    /// Returns the [SessionKeys] borrowing the session key, from which keys
+    /// for the application can be exported
+    pub fn session_keys(&self) -> SessionKeys<'_, CS> {
+        SessionKeys::new(&self.session_key)
+    }
+}
+
 impl<CS: CipherSuite> ServerLogin<CS> {
     /// Serialization into bytes
     pub fn serialize(&self) -> Vec<u8> {
-        self.ke2_state.to_bytes()
+        [
+            serialize(&self.key_ids.serialize(), 2),
+            vec![self.external_mode as u8],
+            vec![match self.record_kind {
+                RecordKind::Registered => 0u8,
+                RecordKind::Dummy => 1u8,
+            }],
+            self.transcript_hash.to_vec(),
+            self.client_s_pk.to_vec(),
+            serialize(&self.id_u, 2),
+            serialize(&self.id_s, 2),
+            self.ke2_state.to_bytes(),
+        ]
+        .concat()
     }
 
     /// Deserialization from bytes
     pub fn deserialize(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let hash_len = <CS::Hash as Digest>::OutputSize::to_usize();
+        let key_len = <CS::Group as Group>::ElemLen::to_usize();
+        let (key_ids_bytes, remainder) = tokenize(bytes, 2)?;
+        let key_ids = RegistrationKeyIds::deserialize(&key_ids_bytes)?;
+        let checked_slice =
+            check_slice_size_atleast(&remainder, 2 + hash_len + key_len, "server_login_state")?;
+        if checked_slice[0] > 1 {
+            return Err(PakeError::SerializationError.into());
+        }
+        let external_mode = checked_slice[0] == 1;
+        let record_kind = match checked_slice[1] {
+            0 => RecordKind::Registered,
+            1 => RecordKind::Dummy,
+            _ => return Err(PakeError::SerializationError.into()),
+        };
+        let transcript_hash = GenericArray::clone_from_slice(&checked_slice[2..2 + hash_len]);
+        let client_s_pk =
+            PublicKey::from_bytes(&checked_slice[2 + hash_len..2 + hash_len + key_len]);
+        let (id_u, remainder) = tokenize(&checked_slice[2 + hash_len + key_len..], 2)?;
+        let (id_s, remainder) = tokenize(&remainder, 2)?;
         Ok(Self {
             _cs: PhantomData,
             ke2_state: <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::KE2State::from_bytes::<
                 CS,
-            >(bytes)?,
+            >(&remainder)?,
+            key_ids,
+            external_mode,
+            record_kind,
+            transcript_hash,
+            client_s_pk,
+            id_u,
+            id_s,
         })
     }
 
+    /// Returns the kind of the password file used in the login, which allows
+    /// for telling apart the logins of unregistered clients when auditing
+    /// failed logins
+    pub fn record_kind(&self) -> RecordKind {
+        self.record_kind
+    }
+
     /// From the client's "blinded" password, returns a challenge to be
     /// sent back to the client, as well as a ServerLogin
     pub fn start<R: RngCore + CryptoRng>(
@@ -722,68 +1904,210 @@ impl<CS: CipherSuite> ServerLogin<CS> {
         credential_identifier: &[u8],
         params: ServerLoginStartParameters,
     ) -> Result<ServerLoginStartResult<CS>, ProtocolError> {
-        let record = match password_file {
-            Some(x) => x,
-            None => ServerRegistration::dummy(rng, server_setup),
-        };
+        Self::start_with_evaluation(
+            rng,
+            server_setup,
+            OprfEvaluation::Derived(&mut OprfKeyDerivations::new(), credential_identifier),
+            password_file,
+            l1,
+            params,
+        )
+    }
 
-        let client_s_pk = record.0.client_s_pk.clone();
+    /// In the [threshold](crate::threshold) mode, from the client's "blinded"
+    /// password, returns a challenge to be sent back to the client, as well
+    /// as a ServerLogin. The evaluation of the OPRF under the server's own
+    /// [OprfKeyShare] is combined with the verified [PartialEvaluation]s of
+    /// other share holders, as in [ServerRegistration::start_threshold],
+    /// which also rejects a server setup in the verifiable mode
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_threshold<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS>,
+        password_file: Option<ServerRegistration<CS>>,
+        l1: CredentialRequest<CS>,
+        oprf_key_share: &OprfKeyShare<CS>,
+        partial_evaluations: &[PartialEvaluation<CS>],
+        public_shares: &[PublicShare<CS>],
+        params: ServerLoginStartParameters,
+    ) -> Result<ServerLoginStartResult<CS>, ProtocolError> {
+        if server_setup.verifiable_oprf {
+            return Err(ProtocolError::VerifiableThresholdError);
+        }
+        Self::start_with_evaluation(
+            rng,
+            server_setup,
+            OprfEvaluation::Threshold(oprf_key_share, partial_evaluations, public_shares),
+            password_file,
+            l1,
+            params,
+        )
+    }
 
-        let (context, optional_ids) = match params {
-            ServerLoginStartParameters::WithContext(context) => (context, None),
-            ServerLoginStartParameters::WithIdentifiers(ids) => (Vec::new(), Some(ids)),
-            ServerLoginStartParameters::WithContextAndIdentifiers(context, ids) => {
-                (context, Some(ids))
-            }
+    /// From the client's "blinded" password, returns a challenge to be sent
+    /// back to the client for the credential of the given alias of an
+    /// account, as well as a ServerLogin. If the account does not hold a
+    /// credential under the alias, or there is no such account, the login
+    /// proceeds against a dummy password file in the same way as
+    /// [ServerLogin::start], so that the response does not reveal which
+    /// credentials the account has
+    pub fn start_for_account<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS>,
+        account: Option<&ServerAccount<CS>>,
+        alias: &[u8],
+        l1: CredentialRequest<CS>,
+        account_identifier: &[u8],
+        params: ServerLoginStartParameters,
+    ) -> Result<ServerLoginStartResult<CS>, ProtocolError> {
+        let credential_identifier = credential_identifier(account_identifier, alias)?;
+        Self::start(
+            rng,
+            server_setup,
+            account.and_then(|account| account.get(alias)).cloned(),
+            l1,
+            &credential_identifier,
+            params,
+        )
+    }
+
+    /// Processes a batch of login requests, in the same way as
+    /// [ServerLogin::start] would for each of them. The HKDF extract of each
+    /// OPRF seed is set up once for the whole batch, from which the OPRF key
+    /// of each credential is expanded, and the blinded password of each
+    /// request is evaluated under the OPRF key of its own credential. A
+    /// failure to process one of the requests is returned in its place, and
+    /// does not affect the other requests
+    pub fn start_batch<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS>,
+        requests: Vec<ServerLoginStartRequest<CS>>,
+    ) -> Vec<Result<ServerLoginStartResult<CS>, ProtocolError>> {
+        let mut derivations = OprfKeyDerivations::new();
+        requests
+            .into_iter()
+            .map(|request| {
+                Self::start_with_evaluation(
+                    rng,
+                    server_setup,
+                    OprfEvaluation::Derived(&mut derivations, request.credential_identifier),
+                    request.password_file,
+                    request.message,
+                    request.params,
+                )
+            })
+            .collect()
+    }
+
+    fn start_with_evaluation<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        server_setup: &ServerSetup<CS>,
+        oprf_evaluation: OprfEvaluation<CS>,
+        password_file: Option<ServerRegistration<CS>>,
+        l1: CredentialRequest<CS>,
+        params: ServerLoginStartParameters,
+    ) -> Result<ServerLoginStartResult<CS>, ProtocolError> {
+        let (record, record_kind) = match password_file {
+            Some(x) => (x, RecordKind::Registered),
+            None => (
+                ServerRegistration::dummy(rng, server_setup),
+                RecordKind::Dummy,
+            ),
         };
 
-        let server_s_sk = server_setup.keypair.private();
-        let server_s_pk = KeyPair::<CS::Group>::public_from_private(server_s_sk);
+        let client_s_pk = record.upload.client_s_pk.clone();
+
+        let context = params.context.unwrap_or_default();
+        let optional_ids = params.identifiers;
+        let payload = params.payload.unwrap_or_default();
+        let channel_binding = params.channel_binding.unwrap_or_default();
+        if payload.len() > MAX_FIELD_LEN || channel_binding.len() > MAX_FIELD_LEN {
+            return Err(PakeError::SerializationError.into());
+        }
+
+        // The envelope of the password file was sealed with the public key
+        // of the keypair it was registered with, which may have been rotated
+        // since
+        let server_keypair = server_setup.keypair_with_id(record.server_key_id)?;
+        let server_s_sk = server_keypair.private();
+        let server_s_pk = server_keypair.public().clone();
 
         let mut masking_nonce = vec![0u8; 32];
         rng.fill_bytes(&mut masking_nonce);
 
         let masked_response = mask_response(
-            &record.0.masking_key,
+            &record.upload.masking_key,
             &masking_nonce,
             &server_s_pk,
-            &record.0.envelope,
+            &record.upload.envelope,
+            &server_setup.envelope_layout,
         )?;
 
-        let (id_u, id_s) = bytestrings_from_identifiers(
-            &optional_ids,
-            &client_s_pk.to_arr(),
-            &server_s_pk.to_arr(),
-        );
+        let (id_u, id_s) = bytestrings_from_identifiers(&optional_ids, &client_s_pk, &server_s_pk);
 
         let l1_bytes = &l1.serialize();
 
-        let oprf_key = oprf_key_from_seed::<CS::Group, CS::Hash>(
-            &server_setup.oprf_seed,
-            credential_identifier,
-        )?;
-        let beta = oprf::evaluate(l1.alpha, &oprf_key);
+        let (beta, proof, credential_identifier) = match oprf_evaluation {
+            OprfEvaluation::Derived(derivations, credential_identifier) => {
+                let oprf_key = derivations.oprf_key(
+                    server_setup,
+                    record.oprf_key_id,
+                    credential_identifier,
+                )?;
+                let beta = oprf::evaluate_point(l1.alpha, &oprf_key);
+                (
+                    beta,
+                    server_setup.oprf_proof(rng, &oprf_key, l1.alpha, beta)?,
+                    credential_identifier,
+                )
+            }
+            OprfEvaluation::Threshold(oprf_key_share, partial_evaluations, public_shares) => (
+                oprf_key_share.evaluate_and_combine(
+                    l1.alpha,
+                    partial_evaluations,
+                    public_shares,
+                )?,
+                None,
+                &[][..],
+            ),
+        };
 
-        let credential_response_component =
-            CredentialResponse::<CS>::serialize_without_ke(&beta, &masking_nonce, &masked_response);
+        let credential_response_component = CredentialResponse::<CS>::transcript_component(
+            &beta,
+            &masking_nonce,
+            &masked_response,
+            &payload,
+        );
 
+        let transcript = TranscriptInputs {
+            context: &context,
+            id_u: &id_u,
+            serialized_credential_request: l1_bytes,
+            id_s: &id_s,
+            serialized_credential_response: &credential_response_component,
+            channel_binding: &channel_binding,
+        };
         let (ke2_state, ke2_message) = CS::KeyExchange::generate_ke2(
             rng,
-            l1_bytes.to_vec(),
-            credential_response_component,
             l1.ke1_message,
-            client_s_pk,
-            server_s_sk.clone(),
-            id_u,
-            id_s,
-            context,
+            Ke2Inputs {
+                transcript,
+                client_s_pk: &client_s_pk,
+                server_s_sk,
+            },
         )?;
+        let transcript_hash = transcript
+            .hasher::<CS::Hash>()
+            .chain(ke2_message.to_bytes())
+            .finalize();
 
         let credential_response = CredentialResponse {
             beta,
             masking_nonce,
             masked_response,
             ke2_message,
+            proof,
+            payload,
         };
 
         Ok(ServerLoginStartResult {
@@ -791,6 +2115,19 @@ impl<CS: CipherSuite> ServerLogin<CS> {
             state: Self {
                 _cs: PhantomData,
                 ke2_state,
+                key_ids: RegistrationKeyIds {
+                    oprf_key_id: record.oprf_key_id,
+                    server_key_id: record.server_key_id,
+                    envelope_layout: server_setup.envelope_layout,
+                    credential_identifier: credential_identifier.to_vec(),
+                },
+                external_mode: record.upload.envelope.is_external_mode(),
+                record_kind,
+                transcript_hash,
+                client_s_pk,
+                // The identities are stored without their length prefix
+                id_u: id_u[2..].to_vec(),
+                id_s: id_s[2..].to_vec(),
             },
         })
     }
@@ -800,10 +2137,14 @@ impl<CS: CipherSuite> ServerLogin<CS> {
     pub fn finish(
         self,
         message: CredentialFinalization<CS>,
+        params: ServerLoginFinishParameters<CS>,
     ) -> Result<ServerLoginFinishResult<CS>, ProtocolError> {
+        let transcript_hash =
+            finalize_transcript_hash::<CS>(&self.transcript_hash, &message.serialize());
         let session_key = <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::finish_ke(
             message.ke3_message,
             &self.ke2_state,
+            &client_payload_component(&message.payload, message.payload_encrypted),
         )
         .map_err(|e| match e {
             ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError) => {
@@ -812,15 +2153,55 @@ impl<CS: CipherSuite> ServerLogin<CS> {
             err => err,
         })?;
 
+        let client_payload = if message.payload_encrypted {
+            crypt_client_payload::<CS::Hash>(&session_key, &message.payload)?
+        } else {
+            message.payload
+        };
+
+        // The upgrade is only accepted once the client has been authenticated,
+        // and if it was produced during this session
+        let password_file = match params.password_file_upgrade {
+            Some(upgrade) => {
+                session_key_mac::<CS>(
+                    STR_PASSWORD_FILE_UPGRADE_MAC,
+                    &session_key,
+                    &upgrade.registration_upload,
+                )?
+                .verify(&upgrade.mac)
+                .map_err(|_| PakeError::KeyExchangeMacValidationError)?;
+                // The new registration relies on the same OPRF output as the
+                // current one, and is sealed with the same server public key
+                Some(ServerRegistration::finish(
+                    upgrade.registration_upload,
+                    self.key_ids.clone(),
+                )?)
+            }
+            None => None,
+        };
+
         Ok(ServerLoginFinishResult {
-            session_key,
+            session_key: Zeroizing::new(session_key),
+            password_file,
+            client_payload,
+            transcript_hash,
+            client_s_pk: self.client_s_pk.clone(),
+            id_u: self.id_u.clone(),
+            id_s: self.id_s.clone(),
+            key_ids: self.key_ids.clone(),
+            external_mode: self.external_mode,
             _cs: PhantomData,
             #[cfg(test)]
             state: self,
         })
     }
+}
 
-    #[cfg(test)]
+#[cfg(test)]
+impl<CS: CipherSuite> ServerLogin<CS>
+where
+    <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::KE2State: AsBytePointers,
+{
     pub fn as_byte_ptrs(&self) -> Vec<(*const u8, usize)> {
         self.ke2_state.as_byte_ptrs()
     }
@@ -828,13 +2209,136 @@ impl<CS: CipherSuite> ServerLogin<CS> {
 
 impl_serialize_and_deserialize_for!(ServerLogin);
 
+// Password Change
+// ===============
+
+/// The state elements the client holds to change its password after a
+/// login, keeping the envelope mode of its password file, along with its
+/// static keypair in the external mode. It holds the static private key of
+/// the client, and therefore cannot be cloned
+pub struct ClientPasswordChange<CS: CipherSuite> {
+    registration: ClientRegistration<CS>,
+    client_static_keypair: Option<KeyPair<CS::Group>>,
+    session_key: Vec<u8>,
+    export_key: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+    credentials: Vec<u8>,
+}
+
+/// Contains the fields that are returned by a client password change start
+pub struct ClientPasswordChangeStartResult<CS: CipherSuite> {
+    /// The result of the login with the current password, whose message must
+    /// be sent to the server along with `message`
+    pub login_result: ClientLoginFinishResult<CS>,
+    /// The registration request message to be sent to the server, which
+    /// passes it to [ServerRegistration::start_password_change]
+    pub message: RegistrationRequest<CS>,
+    /// The client state that must be persisted in order to complete the
+    /// password change
+    pub state: ClientPasswordChange<CS>,
+}
+
+/// Contains the fields that are returned by a client password change finish
+pub struct ClientPasswordChangeFinishResult<CS: CipherSuite> {
+    /// The message to be sent to the server, which passes it to
+    /// [ServerRegistration::finish_password_change]
+    pub message: PasswordFileUpgrade<CS>,
+    /// The export key of the login, under which the data of the client was
+    /// encrypted until now
+    pub old_export_key: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+    /// The export key replacing `old_export_key` once the new password file
+    /// is in place
+    pub export_key: GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for ClientPasswordChangeFinishResult<CS> {
+    fn clone(&self) -> Self {
+        Self {
+            message: self.message.clone(),
+            old_export_key: self.old_export_key.clone(),
+            export_key: self.export_key.clone(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> ClientPasswordChange<CS> {
+    /// Same as [ClientLogin::finish], and additionally returns an initial
+    /// "blinded" request for the new password to send to the server, along
+    /// with the [CredentialFinalization] of the login
+    pub fn start<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        client_login: ClientLogin<CS>,
+        credential_response: CredentialResponse<CS>,
+        params: ClientLoginFinishParameters<CS>,
+        new_password: &[u8],
+    ) -> Result<ClientPasswordChangeStartResult<CS>, ProtocolError> {
+        // The new password is blinded against the same public OPRF key as
+        // the current one in the verifiable mode
+        let oprf_public_key = client_login.token.public_key;
+        let (login_result, client_static_keypair) =
+            client_login.finish_inner(credential_response, params, None)?;
+        let (token, alpha) =
+            oprf::blind_point::<R, CS::Group, CS::Hash>(new_password, oprf_public_key, rng)?;
+
+        let state = Self {
+            registration: ClientRegistration { token },
+            client_static_keypair,
+            session_key: login_result.session_key.to_vec(),
+            export_key: login_result.export_key.clone(),
+            credentials: login_result.credentials.to_vec(),
+        };
+        Ok(ClientPasswordChangeStartResult {
+            login_result,
+            message: RegistrationRequest::<CS> { alpha },
+            state,
+        })
+    }
+
+    /// "Unblinds" the server's answer and seals a new envelope under the new
+    /// password, in the envelope mode of the existing password file. The
+    /// credentials of the existing envelope are kept, unless replaced through
+    /// the `credentials` field of the parameters, and their
+    /// `client_static_keypair` and `session_key` fields are ignored
+    pub fn finish<R: CryptoRng + RngCore>(
+        self,
+        rng: &mut R,
+        r2: RegistrationResponse<CS>,
+        params: ClientRegistrationFinishParameters<CS>,
+    ) -> Result<ClientPasswordChangeFinishResult<CS>, ProtocolError> {
+        let credentials = params.credentials.unwrap_or(&self.credentials);
+        let result = self.registration.clone().finish(
+            rng,
+            r2,
+            ClientRegistrationFinishParameters {
+                client_static_keypair: self.client_static_keypair.as_ref(),
+                credentials: Some(credentials),
+                session_key: None,
+                credential_identifier: None,
+                ..params
+            },
+        )?;
+        let mac =
+            session_key_mac::<CS>(STR_PASSWORD_CHANGE_MAC, &self.session_key, &result.message)?
+                .finalize()
+                .into_bytes();
+
+        Ok(ClientPasswordChangeFinishResult {
+            message: PasswordFileUpgrade {
+                registration_upload: result.message,
+                mac,
+            },
+            old_export_key: self.export_key.clone(),
+            export_key: result.export_key,
+        })
+    }
+}
+
 // Zeroize on drop implementations
 
 // This can't be derived because of the use of a phantom parameter
 impl<CS: CipherSuite> Zeroize for ClientRegistration<CS> {
     fn zeroize(&mut self) {
-        self.token.data.zeroize();
-        self.token.blind.zeroize();
+        self.token.zeroize();
     }
 }
 
@@ -847,9 +2351,9 @@ impl<CS: CipherSuite> Drop for ClientRegistration<CS> {
 // This can't be derived because of the use of a phantom parameter
 impl<CS: CipherSuite> Zeroize for ServerRegistration<CS> {
     fn zeroize(&mut self) {
-        self.0.envelope.zeroize();
-        self.0.masking_key.zeroize();
-        self.0.client_s_pk.zeroize();
+        self.upload.envelope.zeroize();
+        self.upload.masking_key.zeroize();
+        self.upload.client_s_pk.zeroize();
     }
 }
 
@@ -862,8 +2366,7 @@ impl<CS: CipherSuite> Drop for ServerRegistration<CS> {
 // This can't be derived because of the use of a phantom parameter
 impl<CS: CipherSuite> Zeroize for ClientLogin<CS> {
     fn zeroize(&mut self) {
-        self.token.data.zeroize();
-        self.token.blind.zeroize();
+        self.token.zeroize();
         self.ke1_state.zeroize();
         self.serialized_credential_request.zeroize();
     }
@@ -888,38 +2391,268 @@ impl<CS: CipherSuite> Drop for ServerLogin<CS> {
     }
 }
 
+impl<CS: CipherSuite> Zeroize for ClientPasswordChange<CS> {
+    fn zeroize(&mut self) {
+        self.registration.zeroize();
+        self.client_static_keypair.zeroize();
+        self.session_key.zeroize();
+        self.export_key.zeroize();
+        self.credentials.zeroize();
+    }
+}
+
+impl<CS: CipherSuite> Drop for ClientPasswordChange<CS> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 // Helper functions
 
 fn get_password_derived_key<G: GroupWithMapToCurve, SH: SlowHash<D>, D: Hash>(
     token: &oprf::Token<G>,
     beta: G,
+    slow_hash: Option<&SH>,
 ) -> Result<Vec<u8>, InternalPakeError> {
-    let oprf_output = oprf::finalize::<G, D>(&token.data, &token.blind, beta);
-    SH::hash(oprf_output)
+    let oprf_output = oprf::finalize_point::<G, D>(token, beta);
+    match slow_hash {
+        Some(slow_hash) => slow_hash.hash(oprf_output),
+        None => SH::default_params().hash(oprf_output),
+    }
+}
+
+// Derives the masking key and seals a new envelope from the OPRF output, as
+// done at the end of registration
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn seal_registration<CS: CipherSuite>(
+    envelope_nonce: &[u8],
+    token: &oprf::Token<CS::Group>,
+    beta: CS::Group,
+    server_s_pk: &PublicKey,
+    optional_ids: Option<Identifiers>,
+    slow_hash: Option<&CS::SlowHash>,
+    client_static_keypair: Option<&KeyPair<CS::Group>>,
+    credentials: &[u8],
+) -> Result<
+    (
+        RegistrationUpload<CS>,
+        GenericArray<u8, <CS::Hash as Digest>::OutputSize>,
+    ),
+    ProtocolError,
+> {
+    if credentials.len() > Envelope::<CS>::max_credentials_len() {
+        return Err(PakeError::SerializationError.into());
+    }
+
+    let password_derived_key =
+        get_password_derived_key::<CS::Group, CS::SlowHash, CS::Hash>(token, beta, slow_hash)?;
+
+    let h = Hkdf::<CS::Hash>::new(None, &password_derived_key);
+    let mut masking_key = vec![0u8; <CS::Hash as Digest>::OutputSize::to_usize()];
+    h.expand(STR_MASKING_KEY, &mut masking_key)
+        .map_err(|_| InternalPakeError::HkdfError)?;
+
+    let (envelope, client_s_pk, export_key) = Envelope::<CS>::seal(
+        envelope_nonce,
+        &password_derived_key,
+        server_s_pk,
+        optional_ids,
+        client_static_keypair,
+        credentials,
+    )?;
+
+    Ok((
+        RegistrationUpload {
+            envelope,
+            masking_key: GenericArray::clone_from_slice(&masking_key[..]),
+            client_s_pk,
+            slow_hash: Some(
+                slow_hash
+                    .cloned()
+                    .unwrap_or_else(CS::SlowHash::default_params),
+            ),
+            mac: None,
+        },
+        export_key,
+    ))
+}
+
+// Computes the MAC binding a registration upload to the session key of a
+// login, under a label telling what the upload is for, so that a MAC computed
+// for one purpose is not accepted for another. The MAC is computed over the
+// upload without its MAC
+fn session_key_mac<CS: CipherSuite>(
+    label: &[u8],
+    session_key: &[u8],
+    registration_upload: &RegistrationUpload<CS>,
+) -> Result<Hmac<CS::Hash>, InternalPakeError> {
+    let mut mac_key = vec![0u8; <CS::Hash as Digest>::OutputSize::to_usize()];
+    Hkdf::<CS::Hash>::new(None, session_key)
+        .expand(label, &mut mac_key)
+        .map_err(|_| InternalPakeError::HkdfError)?;
+    let mut mac =
+        Hmac::<CS::Hash>::new_varkey(&mac_key).map_err(|_| InternalPakeError::HmacError)?;
+    mac.update(&registration_upload.serialize());
+    Ok(mac)
+}
+
+// Computes the MAC authenticating a registration upload under the session
+// key of a prior login, which also covers the credential identifier under
+// which the upload is registered
+fn registration_upload_mac<CS: CipherSuite>(
+    session_key: &[u8],
+    credential_identifier: &[u8],
+    registration_upload: &RegistrationUpload<CS>,
+) -> Result<Hmac<CS::Hash>, InternalPakeError> {
+    let mut mac = session_key_mac::<CS>(
+        STR_REGISTRATION_UPLOAD_MAC,
+        session_key,
+        registration_upload,
+    )?;
+    mac.update(&serialize(credential_identifier, 2));
+    Ok(mac)
+}
+
+// Extends the hash of the login transcript up to the second key exchange
+// message with the CredentialFinalization, so that it covers the whole login
+fn finalize_transcript_hash<CS: CipherSuite>(
+    ke2_transcript_hash: &[u8],
+    credential_finalization: &[u8],
+) -> GenericArray<u8, <CS::Hash as Digest>::OutputSize> {
+    CS::Hash::new()
+        .chain(ke2_transcript_hash)
+        .chain(credential_finalization)
+        .finalize()
+}
+
+fn generate_oprf_seed<D: Hash, R: CryptoRng + RngCore>(
+    rng: &mut R,
+) -> GenericArray<u8, D::OutputSize> {
+    let mut seed = vec![0u8; D::OutputSize::to_usize()];
+    rng.fill_bytes(&mut seed);
+    GenericArray::clone_from_slice(&seed[..])
+}
+
+// Checks that the server's response carries a valid proof of the evaluation
+// of the OPRF if the client pinned a public OPRF key, and none otherwise
+fn verify_oprf_proof<CS: CipherSuite>(
+    token: &oprf::Token<CS::Group>,
+    beta: CS::Group,
+    proof: Option<&oprf::Proof<CS::Group>>,
+) -> Result<(), ProtocolError> {
+    match (token.public_key, proof) {
+        (None, None) => Ok(()),
+        (Some(public_key), Some(proof)) => Ok(oprf::verify_proof::<CS::Group, CS::Hash>(
+            public_key,
+            &[token.blinded_element::<CS::Hash>()?],
+            &[beta],
+            proof,
+        )?),
+        _ => Err(PakeError::InvalidOprfProofError.into()),
+    }
+}
+
+// Serializes the public OPRF key pinned by the client, which is empty in the
+// base mode of the OPRF
+fn serialize_oprf_public_key<G: Group>(public_key: &Option<G>) -> Vec<u8> {
+    match public_key {
+        Some(public_key) => serialize(&public_key.to_arr(), 2),
+        None => serialize(&[], 2),
+    }
+}
+
+fn deserialize_oprf_public_key<G: Group>(input: &[u8]) -> Result<Option<G>, ProtocolError> {
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let checked_slice = check_slice_size(input, G::ElemLen::to_usize(), "oprf_public_key")?;
+    let public_key = G::from_element_slice(GenericArray::from_slice(checked_slice))?;
+    // Throw an error if the identity group element is encountered
+    if public_key.is_identity() {
+        return Err(PakeError::IdentityGroupElementError.into());
+    }
+    Ok(Some(public_key))
 }
 
 fn oprf_key_from_seed<G: GroupWithMapToCurve, D: Hash>(
     oprf_seed: &GenericArray<u8, D::OutputSize>,
     credential_identifier: &[u8],
 ) -> Result<G::Scalar, InternalPakeError> {
-    let mut oprf_key_bytes = vec![0u8; <PrivateKey as SizedBytes>::Len::to_usize()];
-    Hkdf::<D>::from_prk(oprf_seed)
-        .map_err(|_| InternalPakeError::HkdfError)?
-        .expand(
-            &[credential_identifier, STR_OPRF_KEY].concat(),
-            &mut oprf_key_bytes,
-        )
-        .map_err(|_| InternalPakeError::HkdfError)?;
+    let hkdf = Hkdf::<D>::from_prk(oprf_seed).map_err(|_| InternalPakeError::HkdfError)?;
+    oprf_key_from_hkdf::<G, D>(&hkdf, credential_identifier)
+}
+
+fn oprf_key_from_hkdf<G: GroupWithMapToCurve, D: Hash>(
+    hkdf: &Hkdf<D>,
+    credential_identifier: &[u8],
+) -> Result<G::Scalar, InternalPakeError> {
+    let mut oprf_key_bytes = vec![0u8; G::ScalarLen::to_usize()];
+    hkdf.expand(
+        &[credential_identifier, STR_OPRF_KEY].concat(),
+        &mut oprf_key_bytes,
+    )
+    .map_err(|_| InternalPakeError::HkdfError)?;
     G::hash_to_scalar::<D>(&oprf_key_bytes[..], b"")
 }
 
+// The HKDF extracts set up for each of the OPRF seeds used by the requests of
+// a batch, from which the OPRF key of each credential is expanded
+struct OprfKeyDerivations<CS: CipherSuite> {
+    hkdfs: Vec<(u32, Hkdf<CS::Hash>)>,
+}
+
+impl<CS: CipherSuite> OprfKeyDerivations<CS> {
+    fn new() -> Self {
+        Self { hkdfs: Vec::new() }
+    }
+
+    fn oprf_key(
+        &mut self,
+        server_setup: &ServerSetup<CS>,
+        key_id: u32,
+        credential_identifier: &[u8],
+    ) -> Result<<CS::Group as Group>::Scalar, ProtocolError> {
+        let index = match self.hkdfs.iter().position(|(id, _)| *id == key_id) {
+            Some(index) => index,
+            None => {
+                let hkdf = Hkdf::<CS::Hash>::from_prk(server_setup.oprf_seed(key_id)?)
+                    .map_err(|_| InternalPakeError::HkdfError)?;
+                self.hkdfs.push((key_id, hkdf));
+                self.hkdfs.len() - 1
+            }
+        };
+        Ok(oprf_key_from_hkdf::<CS::Group, CS::Hash>(
+            &self.hkdfs[index].1,
+            credential_identifier,
+        )?)
+    }
+}
+
+// How the server evaluates the OPRF during a login
+enum OprfEvaluation<'a, CS: CipherSuite> {
+    // Under the OPRF key of the credential of the given identifier, derived
+    // from the seed of the ServerSetup
+    Derived(&'a mut OprfKeyDerivations<CS>, &'a [u8]),
+    // In the threshold mode, under the server's own share of the OPRF key,
+    // combined with the partial evaluations of other share holders, which are
+    // verified against their public shares
+    Threshold(
+        &'a OprfKeyShare<CS>,
+        &'a [PartialEvaluation<CS>],
+        &'a [PublicShare<CS>],
+    ),
+}
+
 fn mask_response<CS: CipherSuite>(
     masking_key: &[u8],
     masking_nonce: &[u8],
     server_s_pk: &PublicKey,
     envelope: &Envelope<CS>,
+    layout: &EnvelopeLayout,
 ) -> Result<Vec<u8>, ProtocolError> {
-    let mut xor_pad = vec![0u8; <PublicKey as SizedBytes>::Len::to_usize() + Envelope::<CS>::len()];
+    let plaintext = [&server_s_pk[..], &envelope.serialize_padded(layout)?].concat();
+
+    let mut xor_pad = vec![0u8; plaintext.len()];
     Hkdf::<CS::Hash>::from_prk(masking_key)
         .map_err(|_| InternalPakeError::HkdfError)?
         .expand(
@@ -928,8 +2661,6 @@ fn mask_response<CS: CipherSuite>(
         )
         .map_err(|_| InternalPakeError::HkdfError)?;
 
-    let plaintext = [&server_s_pk.to_arr()[..], &envelope.serialize()].concat();
-
     Ok(xor_pad
         .iter()
         .zip(plaintext.iter())
@@ -937,12 +2668,15 @@ fn mask_response<CS: CipherSuite>(
         .collect())
 }
 
-fn unmask_response<CS: CipherSuite>(
+pub(crate) fn unmask_response<CS: CipherSuite>(
     masking_key: &[u8],
     masking_nonce: &[u8],
     masked_response: &[u8],
 ) -> Result<(PublicKey, Envelope<CS>), ProtocolError> {
-    let mut xor_pad = vec![0u8; <PublicKey as SizedBytes>::Len::to_usize() + Envelope::<CS>::len()];
+    let key_len = <CS::Group as Group>::ElemLen::to_usize();
+    check_slice_size_atleast(masked_response, key_len, "masked_response")?;
+
+    let mut xor_pad = vec![0u8; masked_response.len()];
     Hkdf::<CS::Hash>::from_prk(masking_key)
         .map_err(|_| InternalPakeError::HkdfError)?
         .expand(
@@ -955,13 +2689,11 @@ fn unmask_response<CS: CipherSuite>(
         .zip(masked_response.iter())
         .map(|(&x1, &x2)| x1 ^ x2)
         .collect();
-    let key_len = <PublicKey as SizedBytes>::Len::to_usize();
-    let unchecked_server_s_pk =
-        PublicKey::from_arr(&GenericArray::clone_from_slice(&plaintext[..key_len]))?;
-    let envelope = Envelope::deserialize(&plaintext[key_len..])?;
+    let unchecked_server_s_pk = PublicKey::from_bytes(&plaintext[..key_len]);
+    let envelope = Envelope::deserialize_padded(&plaintext[key_len..])?;
 
     // Ensure that public key is valid
-    let server_s_pk = KeyPair::<CS::Group>::check_public_key(unchecked_server_s_pk)
+    let server_s_pk = KeyPair::check_static_public_key::<CS>(unchecked_server_s_pk)
         .map_err(|_| ProtocolError::VerificationError(PakeError::SerializationError))?;
 
     Ok((server_s_pk, envelope))
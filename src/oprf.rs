@@ -3,69 +3,454 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+//! An implementation of the base and verifiable modes of the oblivious
+//! pseudorandom function (OPRF) from
+//! <https://www.ietf.org/archive/id/draft-irtf-cfrg-voprf-06.txt>, which OPAQUE
+//! is built upon
+//!
+//! The OPRF can also be used on its own, for instance to check whether a
+//! password appears in a server's list of breached passwords, or for private
+//! set membership, without revealing the input to the server. The client
+//! [blind]s its input and sends the resulting [BlindedElement] to the server,
+//! which returns an [EvaluatedElement] computed with its OPRF key. The client
+//! then [finalize]s it into the output of the OPRF. These functions implement
+//! the base mode.
+//!
+//! In the verifiable mode, the client knows the public key of the server
+//! beforehand and [blind_verifiable]s its inputs against it. The server
+//! evaluates a batch of blinded elements at once with [evaluate_batch], which
+//! returns a single [Proof] covering the whole batch, and the client checks
+//! that proof as it finalizes the batch with [finalize_batch]. Proving the
+//! batch costs about as much as proving a single evaluation.
+//!
+//! ```
+//! use curve25519_dalek::ristretto::RistrettoPoint;
+//! use opaque_ke::{group::Group, oprf};
+//! use rand::rngs::OsRng;
+//! let mut rng = OsRng;
+//! // On the server
+//! let oprf_key = RistrettoPoint::random_nonzero_scalar(&mut rng);
+//! // On the client
+//! let (token, blinded_element) =
+//!     oprf::blind::<_, RistrettoPoint, sha2::Sha512>(b"hunter2", &mut rng)?;
+//! let message = blinded_element.serialize();
+//! // On the server
+//! let blinded_element = oprf::BlindedElement::<RistrettoPoint>::deserialize(&message)?;
+//! let message = oprf::evaluate(&blinded_element, &oprf_key).serialize();
+//! // On the client
+//! let evaluated_element = oprf::EvaluatedElement::<RistrettoPoint>::deserialize(&message)?;
+//! let output = oprf::finalize::<RistrettoPoint, sha2::Sha512>(&token, &evaluated_element);
+//! # Ok::<(), opaque_ke::errors::ProtocolError>(())
+//! ```
+
 use crate::{
-    errors::InternalPakeError, group::Group, hash::Hash, map_to_curve::GroupWithMapToCurve,
-    serialization::serialize,
+    errors::{utils::check_slice_size, InternalPakeError, PakeError, ProtocolError},
+    group::Group,
+    hash::Hash,
+    map_to_curve::GroupWithMapToCurve,
+    serialization::{i2osp, serialize},
 };
 use digest::Digest;
-use generic_array::GenericArray;
+use generic_array::{typenum::Unsigned, GenericArray};
 use rand::{CryptoRng, RngCore};
+use zeroize::Zeroize;
 
 /// Used to store the OPRF input and blinding factor
-#[derive(Clone)]
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
 pub struct Token<Grp: Group> {
     pub(crate) data: Vec<u8>,
     pub(crate) blind: Grp::Scalar,
+    // The public key of the server in the verifiable mode, against which
+    // the proof of the evaluation is verified
+    #[zeroize(skip)]
+    pub(crate) public_key: Option<Grp>,
+}
+
+impl<Grp: Group> Token<Grp> {
+    /// The mode of the OPRF, which is verifiable if the token holds the
+    /// public key of the server
+    pub(crate) fn mode(&self) -> Mode {
+        match self.public_key {
+            Some(_) => Mode::Verifiable,
+            None => Mode::Base,
+        }
+    }
+
+    /// Serialization into bytes, which contains the secret blinding factor
+    #[cfg(test)]
+    pub(crate) fn serialize(&self) -> Vec<u8> {
+        [&Grp::scalar_as_bytes(&self.blind)[..], &self.data].concat()
+    }
+
+    /// Deserialization from bytes
+    #[cfg(test)]
+    pub(crate) fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let scalar_len = Grp::ScalarLen::to_usize();
+        let checked_slice =
+            crate::errors::utils::check_slice_size_atleast(input, scalar_len, "token_bytes")?;
+        Ok(Self {
+            data: checked_slice[scalar_len..].to_vec(),
+            blind: Grp::from_scalar_slice(GenericArray::from_slice(&checked_slice[..scalar_len]))?,
+            public_key: None,
+        })
+    }
+}
+
+/// The blinded input of the client, sent to the server
+pub struct BlindedElement<G: Group> {
+    pub(crate) value: G,
+}
+
+/// The evaluation of the OPRF on a [BlindedElement], sent back to the client
+pub struct EvaluatedElement<G: Group> {
+    pub(crate) value: G,
+}
+
+macro_rules! impl_element_wrapper {
+    ($item:ident, $name:literal) => {
+        // Cannot be derived because it would require for G to be Clone.
+        impl<G: Group> Clone for $item<G> {
+            fn clone(&self) -> Self {
+                Self { value: self.value }
+            }
+        }
+
+        impl<G: Group> $item<G> {
+            /// Serialization into bytes
+            pub fn serialize(&self) -> Vec<u8> {
+                self.value.to_arr().to_vec()
+            }
+
+            /// Deserialization from bytes
+            pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+                let elem_len = G::ElemLen::to_usize();
+                let checked_slice = check_slice_size(input, elem_len, $name)?;
+                let value = G::from_element_slice(GenericArray::from_slice(checked_slice))?;
+                // Throw an error if the identity group element is encountered
+                if value.is_identity() {
+                    return Err(PakeError::IdentityGroupElementError.into());
+                }
+                Ok(Self { value })
+            }
+        }
+    };
 }
 
+impl_element_wrapper!(BlindedElement, "blinded_element_bytes");
+impl_element_wrapper!(EvaluatedElement, "evaluated_element_bytes");
+
 static STR_VOPRF: &[u8] = b"VOPRF06-HashToGroup-";
+static STR_VOPRF_HASH_TO_SCALAR: &[u8] = b"VOPRF06-HashToScalar-";
 static STR_VOPRF_FINALIZE: &[u8] = b"VOPRF06-Finalize-";
-static MODE_BASE: u8 = 0x00;
+static STR_VOPRF_SEED: &[u8] = b"VOPRF06-Seed-";
+static STR_VOPRF_COMPOSITE: &[u8] = b"VOPRF06-Composite-";
+static STR_VOPRF_CHALLENGE: &[u8] = b"VOPRF06-Challenge-";
+
+/// The mode of the OPRF, from which its context string is derived
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Mode {
+    Base = 0x00,
+    Verifiable = 0x01,
+}
+
+impl Mode {
+    fn context_string<G: GroupWithMapToCurve>(self) -> Vec<u8> {
+        G::get_context_string(self as u8)
+    }
+}
+
+/// A DLEQ proof, in the verifiable mode, that evaluations of the OPRF were
+/// computed with the private key corresponding to the server's public key
+pub struct Proof<G: Group> {
+    c: G::Scalar,
+    s: G::Scalar,
+}
+
+// Cannot be derived because it would require for G::Scalar to be Copy.
+impl<G: Group> Clone for Proof<G> {
+    fn clone(&self) -> Self {
+        Self {
+            c: self.c.clone(),
+            s: self.s.clone(),
+        }
+    }
+}
+
+impl<G: Group> Proof<G> {
+    pub(crate) fn len() -> usize {
+        2 * G::ScalarLen::to_usize()
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> Vec<u8> {
+        [G::scalar_as_bytes(&self.c), G::scalar_as_bytes(&self.s)].concat()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let scalar_len = G::ScalarLen::to_usize();
+        let checked_slice = check_slice_size(input, Self::len(), "oprf_proof")?;
 
-/// Computes the first step for the multiplicative blinding version of DH-OPRF. This
-/// message is sent from the client (who holds the input) to the server (who holds the OPRF key).
-/// The client can also pass in an optional "pepper" string to be mixed in with the input through
-/// an HKDF computation.
-pub(crate) fn blind<R: RngCore + CryptoRng, G: GroupWithMapToCurve, H: Hash>(
+        Ok(Self {
+            c: G::from_scalar_slice(GenericArray::from_slice(&checked_slice[..scalar_len]))?,
+            s: G::from_scalar_slice(GenericArray::from_slice(&checked_slice[scalar_len..]))?,
+        })
+    }
+}
+
+/// Computes the first step for the multiplicative blinding version of DH-OPRF. The
+/// [BlindedElement] is sent from the client (who holds the input) to the server (who holds
+/// the OPRF key), while the [Token] is kept by the client in order to finalize the OPRF.
+pub fn blind<R: RngCore + CryptoRng, G: GroupWithMapToCurve, H: Hash>(
+    input: &[u8],
+    blinding_factor_rng: &mut R,
+) -> Result<(Token<G>, BlindedElement<G>), ProtocolError> {
+    let (token, value) = blind_point::<R, G, H>(input, None, blinding_factor_rng)?;
+    Ok((token, BlindedElement { value }))
+}
+
+/// Computes the second step for the multiplicative blinding version of DH-OPRF. The
+/// [EvaluatedElement] is sent from the server (who holds the OPRF key) to the client.
+pub fn evaluate<G: Group>(
+    blinded_element: &BlindedElement<G>,
+    oprf_key: &G::Scalar,
+) -> EvaluatedElement<G> {
+    EvaluatedElement {
+        value: evaluate_point(blinded_element.value, oprf_key),
+    }
+}
+
+/// Computes the third step for the multiplicative blinding version of DH-OPRF, in which
+/// the client unblinds the server's [EvaluatedElement] and outputs the result of the OPRF
+/// on its input.
+pub fn finalize<G: GroupWithMapToCurve, H: Hash>(
+    token: &Token<G>,
+    evaluated_element: &EvaluatedElement<G>,
+) -> GenericArray<u8, <H as Digest>::OutputSize> {
+    finalize_point::<G, H>(token, evaluated_element.value)
+}
+
+/// Computes the first step in the verifiable mode, in which the client holds
+/// the `public_key` of the server and checks that the [EvaluatedElement]s it
+/// receives were computed with the corresponding OPRF key.
+pub fn blind_verifiable<R: RngCore + CryptoRng, G: GroupWithMapToCurve, H: Hash>(
+    input: &[u8],
+    public_key: &G,
+    blinding_factor_rng: &mut R,
+) -> Result<(Token<G>, BlindedElement<G>), ProtocolError> {
+    let (token, value) = blind_point::<R, G, H>(input, Some(*public_key), blinding_factor_rng)?;
+    Ok((token, BlindedElement { value }))
+}
+
+/// Computes the second step in the verifiable mode, for a batch of
+/// [BlindedElement]s received from one or several clients. Along with the
+/// [EvaluatedElement]s, in the same order, it returns a single [Proof] for
+/// the whole batch, which is sent to every client of the batch.
+pub fn evaluate_batch<R: RngCore + CryptoRng, G: GroupWithMapToCurve, H: Hash>(
+    rng: &mut R,
+    blinded_elements: &[BlindedElement<G>],
+    oprf_key: &G::Scalar,
+) -> Result<(Vec<EvaluatedElement<G>>, Proof<G>), ProtocolError> {
+    let blinded_points: Vec<G> = blinded_elements.iter().map(|x| x.value).collect();
+    let evaluated_points: Vec<G> = blinded_points
+        .iter()
+        .map(|&point| evaluate_point(point, oprf_key))
+        .collect();
+    let proof = generate_proof::<R, G, H>(rng, oprf_key, &blinded_points, &evaluated_points)?;
+    Ok((
+        evaluated_points
+            .into_iter()
+            .map(|value| EvaluatedElement { value })
+            .collect(),
+        proof,
+    ))
+}
+
+/// Computes the third step in the verifiable mode, for the [Token]s of a
+/// batch and the [EvaluatedElement]s returned for them, in the same order.
+/// The [Proof] is verified against the public key that the tokens were
+/// blinded with, which must be the same for all of them, before the outputs
+/// of the OPRF are returned.
+pub fn finalize_batch<G: GroupWithMapToCurve, H: Hash>(
+    tokens: &[Token<G>],
+    evaluated_elements: &[EvaluatedElement<G>],
+    proof: &Proof<G>,
+) -> Result<Vec<GenericArray<u8, <H as Digest>::OutputSize>>, ProtocolError> {
+    let public_key = match tokens.first().and_then(|token| token.public_key) {
+        Some(public_key) => public_key,
+        None => return Err(PakeError::InvalidOprfProofError.into()),
+    };
+    if tokens
+        .iter()
+        .any(|token| token.public_key.map(|other| other.to_arr()) != Some(public_key.to_arr()))
+    {
+        return Err(PakeError::InvalidOprfProofError.into());
+    }
+    let blinded_points = tokens
+        .iter()
+        .map(|token| token.blinded_element::<H>())
+        .collect::<Result<Vec<G>, InternalPakeError>>()?;
+    let evaluated_points: Vec<G> = evaluated_elements.iter().map(|x| x.value).collect();
+    verify_proof::<G, H>(public_key, &blinded_points, &evaluated_points, proof)?;
+    Ok(tokens
+        .iter()
+        .zip(evaluated_points)
+        .map(|(token, point)| finalize_point::<G, H>(token, point))
+        .collect())
+}
+
+/// Blinds the input in the base mode, or in the verifiable mode if the public
+/// key of the server is given
+pub(crate) fn blind_point<R: RngCore + CryptoRng, G: GroupWithMapToCurve, H: Hash>(
     input: &[u8],
+    public_key: Option<G>,
     blinding_factor_rng: &mut R,
 ) -> Result<(Token<G>, G), InternalPakeError> {
     // Choose a random scalar that must be non-zero
     let blind = G::random_nonzero_scalar(blinding_factor_rng);
-    let dst = [STR_VOPRF, &G::get_context_string(MODE_BASE)].concat();
-    let mapped_point = G::map_to_curve::<H>(input, &dst)?;
-    let blind_token = mapped_point * &blind;
-    Ok((
-        Token {
-            data: input.to_vec(),
-            blind,
-        },
-        blind_token,
-    ))
+    let token = Token {
+        data: input.to_vec(),
+        blind,
+        public_key,
+    };
+    let blind_token = token.blinded_element::<H>()?;
+    Ok((token, blind_token))
 }
 
-/// Computes the second step for the multiplicative blinding version of DH-OPRF. This
-/// message is sent from the server (who holds the OPRF key) to the client.
-pub(crate) fn evaluate<G: Group>(point: G, oprf_key: &G::Scalar) -> G {
+impl<G: GroupWithMapToCurve> Token<G> {
+    // Recomputes the blinded element sent to the server
+    pub(crate) fn blinded_element<H: Hash>(&self) -> Result<G, InternalPakeError> {
+        let dst = [STR_VOPRF, &self.mode().context_string::<G>()].concat();
+        Ok(G::map_to_curve::<H>(&self.data, &dst)? * &self.blind)
+    }
+}
+
+pub(crate) fn evaluate_point<G: Group>(point: G, oprf_key: &G::Scalar) -> G {
     point * oprf_key
 }
 
-/// Computes the third step for the multiplicative blinding version of DH-OPRF, in which
-/// the client unblinds the server's message.
-pub(crate) fn finalize<G: GroupWithMapToCurve, H: Hash>(
+/// Proves, in the verifiable mode, that each of the `evaluated_elements` was
+/// computed from the blinded element at the same position with `oprf_key`.
+/// A single proof covers all of the evaluations
+pub(crate) fn generate_proof<R: RngCore + CryptoRng, G: GroupWithMapToCurve, H: Hash>(
+    rng: &mut R,
+    oprf_key: &G::Scalar,
+    blinded_elements: &[G],
+    evaluated_elements: &[G],
+) -> Result<Proof<G>, InternalPakeError> {
+    let public_key = G::base_point() * oprf_key;
+    let (m, z) = compute_composites::<G, H>(public_key, blinded_elements, evaluated_elements)?;
+
+    let r = G::random_nonzero_scalar(rng);
+    let c = compute_challenge::<G, H>(public_key, m, z, G::base_point() * &r, m * &r)?;
+    let s = G::scalar_sub(&r, &G::scalar_mul(&c, oprf_key));
+
+    Ok(Proof { c, s })
+}
+
+/// Verifies a proof produced by [generate_proof] against the public key of
+/// the server
+pub(crate) fn verify_proof<G: GroupWithMapToCurve, H: Hash>(
+    public_key: G,
+    blinded_elements: &[G],
+    evaluated_elements: &[G],
+    proof: &Proof<G>,
+) -> Result<(), PakeError> {
+    let (m, z) = compute_composites::<G, H>(public_key, blinded_elements, evaluated_elements)?;
+
+    let t2 = (G::base_point() * &proof.s).add_element(&(public_key * &proof.c));
+    let t3 = (m * &proof.s).add_element(&(z * &proof.c));
+    let c = compute_challenge::<G, H>(public_key, m, z, t2, t3)?;
+
+    if G::scalar_as_bytes(&c) != G::scalar_as_bytes(&proof.c) {
+        return Err(PakeError::InvalidOprfProofError);
+    }
+    Ok(())
+}
+
+fn hash_to_scalar<G: GroupWithMapToCurve, H: Hash>(
     input: &[u8],
-    blind: &G::Scalar,
+) -> Result<G::Scalar, InternalPakeError> {
+    let dst = [
+        STR_VOPRF_HASH_TO_SCALAR,
+        &Mode::Verifiable.context_string::<G>(),
+    ]
+    .concat();
+    G::hash_to_scalar::<H>(input, &dst)
+}
+
+// Combines the blinded and evaluated elements into a single pair, with
+// weights derived from all of them and the public key
+fn compute_composites<G: GroupWithMapToCurve, H: Hash>(
+    public_key: G,
+    blinded_elements: &[G],
+    evaluated_elements: &[G],
+) -> Result<(G, G), InternalPakeError> {
+    if blinded_elements.is_empty() || blinded_elements.len() != evaluated_elements.len() {
+        return Err(InternalPakeError::InvalidByteSequence);
+    }
+    let context = Mode::Verifiable.context_string::<G>();
+    let seed_dst = [STR_VOPRF_SEED, &context].concat();
+    let composite_dst = [STR_VOPRF_COMPOSITE, &context].concat();
+
+    let seed = H::digest(&[serialize(&public_key.to_arr(), 2), serialize(&seed_dst, 2)].concat());
+    let mut composites: Option<(G, G)> = None;
+    for (i, (blinded_element, evaluated_element)) in
+        blinded_elements.iter().zip(evaluated_elements).enumerate()
+    {
+        let composite_input = [
+            serialize(&seed, 2),
+            i2osp(i, 2),
+            serialize(&blinded_element.to_arr(), 2),
+            serialize(&evaluated_element.to_arr(), 2),
+            serialize(&composite_dst, 2),
+        ]
+        .concat();
+        let d = hash_to_scalar::<G, H>(&composite_input)?;
+        let (m, z) = (*blinded_element * &d, *evaluated_element * &d);
+        composites = Some(match composites {
+            Some((m_acc, z_acc)) => (m_acc.add_element(&m), z_acc.add_element(&z)),
+            None => (m, z),
+        });
+    }
+    composites.ok_or(InternalPakeError::InvalidByteSequence)
+}
+
+fn compute_challenge<G: GroupWithMapToCurve, H: Hash>(
+    public_key: G,
+    m: G,
+    z: G,
+    t2: G,
+    t3: G,
+) -> Result<G::Scalar, InternalPakeError> {
+    let challenge_dst = [STR_VOPRF_CHALLENGE, &Mode::Verifiable.context_string::<G>()].concat();
+    let challenge_input = [
+        serialize(&public_key.to_arr(), 2),
+        serialize(&m.to_arr(), 2),
+        serialize(&z.to_arr(), 2),
+        serialize(&t2.to_arr(), 2),
+        serialize(&t3.to_arr(), 2),
+        serialize(&challenge_dst, 2),
+    ]
+    .concat();
+    hash_to_scalar::<G, H>(&challenge_input)
+}
+
+pub(crate) fn finalize_point<G: GroupWithMapToCurve, H: Hash>(
+    token: &Token<G>,
     evaluated_element: G,
 ) -> GenericArray<u8, <H as Digest>::OutputSize> {
-    let unblinded_element = evaluated_element * &G::scalar_invert(blind);
-    finalize_after_unblind::<G, H>(input, unblinded_element)
+    let unblinded_element = evaluated_element * &G::scalar_invert(&token.blind);
+    finalize_after_unblind::<G, H>(&token.data, unblinded_element, token.mode())
 }
 
 fn finalize_after_unblind<G: GroupWithMapToCurve, H: Hash>(
     input: &[u8],
     unblinded_element: G,
+    mode: Mode,
 ) -> GenericArray<u8, <H as Digest>::OutputSize> {
-    let finalize_dst = [STR_VOPRF_FINALIZE, &G::get_context_string(MODE_BASE)].concat();
+    let finalize_dst = [STR_VOPRF_FINALIZE, &mode.context_string::<G>()].concat();
     let hash_input = [
         serialize(input, 2),
         serialize(&unblinded_element.to_arr().to_vec(), 2),
@@ -75,37 +460,6 @@ fn finalize_after_unblind<G: GroupWithMapToCurve, H: Hash>(
     <H as Digest>::digest(&hash_input)
 }
 
-////////////////////////
-// Benchmarking shims //
-////////////////////////
-
-#[cfg(feature = "bench")]
-#[doc(hidden)]
-#[inline]
-pub fn blind_shim<R: RngCore + CryptoRng, G: GroupWithMapToCurve, H: Hash>(
-    input: &[u8],
-    blinding_factor_rng: &mut R,
-) -> Result<(Token<G>, G), InternalPakeError> {
-    blind::<R, G, H>(input, blinding_factor_rng)
-}
-
-#[cfg(feature = "bench")]
-#[doc(hidden)]
-#[inline]
-pub fn evaluate_shim<G: Group>(point: G, oprf_key: &G::Scalar) -> G {
-    evaluate(point, oprf_key)
-}
-
-#[cfg(feature = "bench")]
-#[doc(hidden)]
-#[inline]
-pub fn finalize_shim<G: GroupWithMapToCurve, H: Hash>(
-    token: &Token<G>,
-    point: G,
-) -> Result<GenericArray<u8, <H as Digest>::OutputSize>, InternalPakeError> {
-    Ok(finalize::<G, H>(&token.data, &token.blind, point))
-}
-
 ///////////
 // Tests //
 // ===== //
@@ -115,33 +469,33 @@ pub fn finalize_shim<G: GroupWithMapToCurve, H: Hash>(
 mod tests {
     use super::*;
     use crate::group::Group;
-    use curve25519_dalek::ristretto::RistrettoPoint;
+    use curve25519_dalek::{ristretto::RistrettoPoint, traits::Identity};
     use generic_array::{arr, GenericArray};
     use rand::rngs::OsRng;
     use sha2::Sha512;
 
     fn prf(input: &[u8], oprf_key: &[u8; 32]) -> GenericArray<u8, <Sha512 as Digest>::OutputSize> {
-        let dst = [STR_VOPRF, &RistrettoPoint::get_context_string(MODE_BASE)].concat();
+        let dst = [STR_VOPRF, &Mode::Base.context_string::<RistrettoPoint>()].concat();
         let point = RistrettoPoint::map_to_curve::<Sha512>(input, &dst).unwrap();
         let scalar =
             RistrettoPoint::from_scalar_slice(GenericArray::from_slice(&oprf_key[..])).unwrap();
         let res = point * scalar;
 
-        finalize_after_unblind::<RistrettoPoint, sha2::Sha512>(&input, res)
+        finalize_after_unblind::<RistrettoPoint, sha2::Sha512>(&input, res, Mode::Base)
     }
 
     #[test]
     fn oprf_retrieval() -> Result<(), InternalPakeError> {
         let input = b"hunter2";
         let mut rng = OsRng;
-        let (token, alpha) = blind::<_, RistrettoPoint, Sha512>(&input[..], &mut rng)?;
+        let (token, alpha) = blind_point::<_, RistrettoPoint, Sha512>(&input[..], None, &mut rng)?;
         let oprf_key_bytes = arr![
             u8; 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
             24, 25, 26, 27, 28, 29, 30, 31, 32,
         ];
         let oprf_key = RistrettoPoint::from_scalar_slice(&oprf_key_bytes)?;
-        let beta = evaluate::<RistrettoPoint>(alpha, &oprf_key);
-        let res = finalize::<RistrettoPoint, sha2::Sha512>(&token.data, &token.blind, beta);
+        let beta = evaluate_point::<RistrettoPoint>(alpha, &oprf_key);
+        let res = finalize_point::<RistrettoPoint, sha2::Sha512>(&token, beta);
         let res2 = prf(&input[..], &oprf_key.as_bytes());
         assert_eq!(res, res2);
         Ok(())
@@ -152,13 +506,201 @@ mod tests {
         let mut rng = OsRng;
         let mut input = vec![0u8; 64];
         rng.fill_bytes(&mut input);
-        let (token, alpha) = blind::<_, RistrettoPoint, sha2::Sha512>(&input, &mut rng).unwrap();
-        let res = finalize::<RistrettoPoint, sha2::Sha512>(&token.data, &token.blind, alpha);
+        let (token, alpha) =
+            blind_point::<_, RistrettoPoint, sha2::Sha512>(&input, None, &mut rng).unwrap();
+        let res = finalize_point::<RistrettoPoint, sha2::Sha512>(&token, alpha);
 
-        let dst = [STR_VOPRF, &RistrettoPoint::get_context_string(MODE_BASE)].concat();
+        let dst = [STR_VOPRF, &Mode::Base.context_string::<RistrettoPoint>()].concat();
         let point = RistrettoPoint::map_to_curve::<Sha512>(&input, &dst).unwrap();
-        let res2 = finalize_after_unblind::<RistrettoPoint, sha2::Sha512>(&input, point);
+        let res2 =
+            finalize_after_unblind::<RistrettoPoint, sha2::Sha512>(&input, point, Mode::Base);
 
         assert_eq!(res, res2);
     }
+
+    #[test]
+    fn oprf_verifiable_mode_context() -> Result<(), InternalPakeError> {
+        let mut rng = OsRng;
+        let oprf_key = RistrettoPoint::random_nonzero_scalar(&mut rng);
+        let public_key = RistrettoPoint::base_point() * oprf_key;
+        let (token, alpha) = blind_point::<_, RistrettoPoint, Sha512>(b"hunter2", None, &mut rng)?;
+        let (verifiable_token, verifiable_alpha) =
+            blind_point::<_, RistrettoPoint, Sha512>(b"hunter2", Some(public_key), &mut rng)?;
+        assert_eq!(token.mode(), Mode::Base);
+        assert_eq!(verifiable_token.mode(), Mode::Verifiable);
+
+        // Both the blinding and the finalization depend on the mode
+        let unblinded = |token: &Token<RistrettoPoint>, alpha: RistrettoPoint| {
+            alpha * RistrettoPoint::scalar_invert(&token.blind)
+        };
+        assert_ne!(
+            unblinded(&token, alpha),
+            unblinded(&verifiable_token, verifiable_alpha)
+        );
+        let output = finalize_point::<RistrettoPoint, Sha512>(
+            &verifiable_token,
+            evaluate_point(verifiable_alpha, &oprf_key),
+        );
+        let other_mode_output = finalize_after_unblind::<RistrettoPoint, Sha512>(
+            b"hunter2",
+            unblinded(&verifiable_token, verifiable_alpha) * oprf_key,
+            Mode::Base,
+        );
+        assert_ne!(output, other_mode_output);
+        Ok(())
+    }
+
+    #[test]
+    fn oprf_proof_verification() -> Result<(), ProtocolError> {
+        let mut rng = OsRng;
+        let oprf_key = RistrettoPoint::random_nonzero_scalar(&mut rng);
+        let public_key = RistrettoPoint::base_point() * oprf_key;
+        let (token, alpha) =
+            blind_point::<_, RistrettoPoint, Sha512>(b"hunter2", Some(public_key), &mut rng)?;
+        assert_eq!(token.blinded_element::<Sha512>()?, alpha);
+        let beta = evaluate_point::<RistrettoPoint>(alpha, &oprf_key);
+        let proof =
+            generate_proof::<_, RistrettoPoint, Sha512>(&mut rng, &oprf_key, &[alpha], &[beta])?;
+        verify_proof::<RistrettoPoint, Sha512>(public_key, &[alpha], &[beta], &proof)?;
+        let proof = Proof::<RistrettoPoint>::deserialize(&proof.serialize())?;
+        verify_proof::<RistrettoPoint, Sha512>(public_key, &[alpha], &[beta], &proof)?;
+
+        // An evaluation under another key is rejected, even with a valid
+        // proof for that key
+        let other_key = RistrettoPoint::random_nonzero_scalar(&mut rng);
+        let other_beta = evaluate_point::<RistrettoPoint>(alpha, &other_key);
+        assert!(verify_proof::<RistrettoPoint, Sha512>(
+            public_key,
+            &[alpha],
+            &[other_beta],
+            &proof
+        )
+        .is_err());
+        let other_proof = generate_proof::<_, RistrettoPoint, Sha512>(
+            &mut rng,
+            &other_key,
+            &[alpha],
+            &[other_beta],
+        )?;
+        assert!(verify_proof::<RistrettoPoint, Sha512>(
+            public_key,
+            &[alpha],
+            &[other_beta],
+            &other_proof
+        )
+        .is_err());
+
+        // A single proof covers several evaluations
+        let (_, other_alpha) =
+            blind_point::<_, RistrettoPoint, Sha512>(b"hunter3", Some(public_key), &mut rng)?;
+        let alphas = [alpha, other_alpha];
+        let betas = [
+            beta,
+            evaluate_point::<RistrettoPoint>(other_alpha, &oprf_key),
+        ];
+        let proof =
+            generate_proof::<_, RistrettoPoint, Sha512>(&mut rng, &oprf_key, &alphas, &betas)?;
+        verify_proof::<RistrettoPoint, Sha512>(public_key, &alphas, &betas, &proof)?;
+        assert!(verify_proof::<RistrettoPoint, Sha512>(
+            public_key,
+            &alphas,
+            &[betas[1], betas[0]],
+            &proof
+        )
+        .is_err());
+        assert!(verify_proof::<RistrettoPoint, Sha512>(public_key, &[], &[], &proof).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn oprf_public_api() -> Result<(), ProtocolError> {
+        let mut rng = OsRng;
+        let oprf_key = RistrettoPoint::random_nonzero_scalar(&mut rng);
+        let (token, blinded_element) = blind::<_, RistrettoPoint, Sha512>(b"hunter2", &mut rng)?;
+        let token = Token::<RistrettoPoint>::deserialize(&token.serialize())?;
+        let blinded_element =
+            BlindedElement::<RistrettoPoint>::deserialize(&blinded_element.serialize())?;
+        let evaluated_element = evaluate(&blinded_element, &oprf_key);
+        let evaluated_element =
+            EvaluatedElement::<RistrettoPoint>::deserialize(&evaluated_element.serialize())?;
+        let output = finalize::<RistrettoPoint, Sha512>(&token, &evaluated_element);
+        assert_eq!(output, prf(b"hunter2", &oprf_key.to_bytes()));
+
+        assert!(BlindedElement::<RistrettoPoint>::deserialize(
+            &RistrettoPoint::identity().to_arr()
+        )
+        .is_err());
+        assert!(EvaluatedElement::<RistrettoPoint>::deserialize(&[0u8; 31]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn oprf_public_api_batch() -> Result<(), ProtocolError> {
+        let mut rng = OsRng;
+        let oprf_key = RistrettoPoint::random_nonzero_scalar(&mut rng);
+        let public_key = RistrettoPoint::base_point() * oprf_key;
+        let inputs: [&[u8]; 3] = [b"hunter2", b"hunter3", b"correct horse"];
+        let mut tokens = Vec::new();
+        let mut blinded_elements = Vec::new();
+        for input in inputs.iter() {
+            let (token, blinded_element) =
+                blind_verifiable::<_, RistrettoPoint, Sha512>(input, &public_key, &mut rng)?;
+            tokens.push(token);
+            blinded_elements.push(blinded_element);
+        }
+
+        let (evaluated_elements, proof) =
+            evaluate_batch::<_, RistrettoPoint, Sha512>(&mut rng, &blinded_elements, &oprf_key)?;
+        let proof = Proof::<RistrettoPoint>::deserialize(&proof.serialize())?;
+        let outputs =
+            finalize_batch::<RistrettoPoint, Sha512>(&tokens, &evaluated_elements, &proof)?;
+        assert_eq!(outputs.len(), inputs.len());
+
+        // The outputs are those of the OPRF in the verifiable mode
+        for (token, output) in tokens.iter().zip(&outputs) {
+            let unblinded_element = token.blinded_element::<Sha512>()?
+                * RistrettoPoint::scalar_invert(&token.blind)
+                * oprf_key;
+            assert_eq!(
+                output,
+                &finalize_after_unblind::<RistrettoPoint, Sha512>(
+                    &token.data,
+                    unblinded_element,
+                    Mode::Verifiable
+                )
+            );
+        }
+
+        // A subset of the batch does not verify against the proof of the
+        // whole batch
+        assert!(finalize_batch::<RistrettoPoint, Sha512>(
+            &tokens[..2],
+            &evaluated_elements[..2],
+            &proof
+        )
+        .is_err());
+
+        // Tokens of the base mode, or blinded against another public key,
+        // are rejected
+        let (base_token, _) = blind::<_, RistrettoPoint, Sha512>(b"hunter2", &mut rng)?;
+        let other_public_key = RistrettoPoint::base_point() * oprf_key * oprf_key;
+        let (other_token, _) =
+            blind_verifiable::<_, RistrettoPoint, Sha512>(b"hunter2", &other_public_key, &mut rng)?;
+        for token in [base_token, other_token].iter() {
+            let mut other_tokens = tokens.clone();
+            other_tokens[0] = token.clone();
+            assert!(match finalize_batch::<RistrettoPoint, Sha512>(
+                &other_tokens,
+                &evaluated_elements,
+                &proof
+            ) {
+                Err(ProtocolError::VerificationError(PakeError::InvalidOprfProofError)) => true,
+                _ => false,
+            });
+        }
+
+        // An empty batch cannot be evaluated
+        assert!(evaluate_batch::<_, RistrettoPoint, Sha512>(&mut rng, &[], &oprf_key).is_err());
+        Ok(())
+    }
 }
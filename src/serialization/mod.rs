@@ -5,6 +5,9 @@
 
 use crate::errors::PakeError;
 
+// The maximum length of a field which is length-prefixed with two bytes
+pub(crate) const MAX_FIELD_LEN: usize = 0xffff;
+
 // Corresponds to the I2OSP() function from RFC8017
 pub(crate) fn i2osp(input: usize, length: usize) -> Vec<u8> {
     if length <= std::mem::size_of::<usize>() {
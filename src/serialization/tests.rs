@@ -5,21 +5,20 @@
 
 use crate::{
     ciphersuite::CipherSuite,
-    envelope::{Envelope, InnerEnvelopeMode},
+    envelope::{Envelope, EnvelopeLayout, InnerEnvelopeMode, END_OF_ENVELOPE},
     errors::*,
     group::Group,
     key_exchange::{
         traits::{FromBytes, KeyExchange, ToBytes},
         tripledh::{NonceLen, TripleDH},
     },
-    keypair::{KeyPair, PublicKey},
+    keypair::KeyPair,
     serialization::{i2osp, os2ip, serialize},
     *,
 };
 
 use curve25519_dalek::{ristretto::RistrettoPoint, traits::Identity};
 use generic_array::typenum::Unsigned;
-use generic_bytes::SizedBytes;
 use proptest::{collection::vec, prelude::*};
 use rand::{rngs::OsRng, RngCore};
 
@@ -31,6 +30,17 @@ impl CipherSuite for Default {
     type KeyExchange = TripleDH;
     type Hash = sha2::Sha512;
     type SlowHash = crate::slow_hash::NoOpHash;
+    const ID: u16 = 0x0001;
+}
+
+// Shares the group of Default, but is told apart by its identifier
+struct OtherSuite;
+impl CipherSuite for OtherSuite {
+    type Group = RistrettoPoint;
+    type KeyExchange = TripleDH;
+    type Hash = sha2::Sha512;
+    type SlowHash = crate::slow_hash::NoOpHash;
+    const ID: u16 = 0xff01;
 }
 
 const HASH_SIZE: usize = 64; // Because of SHA512
@@ -56,11 +66,19 @@ fn client_registration_roundtrip() {
     let mut rng = OsRng;
     let sc = <RistrettoPoint as Group>::random_nonzero_scalar(&mut rng);
 
-    // serialization order: scalar, password
-    let bytes: Vec<u8> = [&sc.as_bytes()[..], &pw[..]].concat();
+    // serialization order: scalar, public OPRF key, password
+    let bytes: Vec<u8> = [&sc.as_bytes()[..], &serialize(&[], 2), &pw[..]].concat();
     let reg = ClientRegistration::<Default>::deserialize(&bytes[..]).unwrap();
     let reg_bytes = reg.serialize();
     assert_eq!(reg_bytes, bytes);
+
+    // In the verifiable mode of the OPRF, the public key pinned by the client
+    let oprf_public_key = random_ristretto_point().to_arr().to_vec();
+    let bytes: Vec<u8> = [&sc.as_bytes()[..], &serialize(&oprf_public_key, 2), &pw[..]].concat();
+    let reg = ClientRegistration::<Default>::deserialize(&bytes[..]).unwrap();
+    assert_eq!(reg.serialize(), bytes);
+    let bytes: Vec<u8> = [&sc.as_bytes()[..], &serialize(&oprf_public_key[1..], 2)].concat();
+    assert!(ClientRegistration::<Default>::deserialize(&bytes[..]).is_err());
 }
 
 #[test]
@@ -78,14 +96,49 @@ fn server_registration_roundtrip() {
     mock_envelope_bytes.extend_from_slice(&[0; MAC_SIZE]); // length-MAC_SIZE hmac
 
     let mock_client_kp = KeyPair::<<Default as CipherSuite>::Group>::generate_random(&mut rng);
-    // serialization order: oprf_key, public key, envelope
+    // serialization order: version, suite id, OPRF key ID, server key ID,
+    // slow hash parameters, public key, masking key, envelope
     let mut bytes = Vec::<u8>::new();
-    bytes.extend_from_slice(&mock_client_kp.public().to_arr());
+    bytes.push(3);
+    bytes.extend_from_slice(&i2osp(Default::ID as usize, 2));
+    bytes.extend_from_slice(&i2osp(7, 4));
+    bytes.extend_from_slice(&i2osp(5, 4));
+    bytes.extend_from_slice(&serialize(&[], 2));
+    bytes.extend_from_slice(&mock_client_kp.public().to_vec());
     bytes.extend_from_slice(&masking_key);
     bytes.extend_from_slice(&mock_envelope_bytes);
     let reg = ServerRegistration::<Default>::deserialize(&bytes[..]).unwrap();
     let reg_bytes = reg.serialize();
     assert_eq!(reg_bytes, bytes);
+    assert_eq!(reg.oprf_key_id(), 7);
+    assert_eq!(reg.server_key_id(), 5);
+
+    // A record from another ciphersuite is rejected
+    match ServerRegistration::<OtherSuite>::deserialize(&bytes[..]) {
+        Err(ProtocolError::CipherSuiteMismatchError { expected, actual }) => {
+            assert_eq!(expected, OtherSuite::ID);
+            assert_eq!(actual, Default::ID);
+        }
+        _ => panic!("Expected a ciphersuite mismatch"),
+    }
+
+    // Records predating the key IDs are read with the key ID 0
+    let mut legacy_bytes = bytes.clone();
+    legacy_bytes[0] = 2;
+    legacy_bytes.drain(7..11);
+    let legacy_reg = ServerRegistration::<Default>::deserialize(&legacy_bytes[..]).unwrap();
+    assert_eq!(legacy_reg.oprf_key_id(), 7);
+    assert_eq!(legacy_reg.server_key_id(), 0);
+    legacy_bytes[0] = 1;
+    legacy_bytes.drain(3..7);
+    let legacy_reg = ServerRegistration::<Default>::deserialize(&legacy_bytes[..]).unwrap();
+    assert_eq!(legacy_reg.oprf_key_id(), 0);
+    assert_eq!(legacy_reg.server_key_id(), 0);
+    assert_eq!(legacy_reg.serialize()[11..], bytes[11..]);
+
+    // And a record with an unknown version is rejected
+    bytes[0] = 4;
+    assert!(ServerRegistration::<Default>::deserialize(&bytes[..]).is_err());
 }
 
 #[test]
@@ -118,7 +171,7 @@ fn registration_response_roundtrip() {
     let beta_bytes = pt.to_arr();
     let mut rng = OsRng;
     let skp = KeyPair::<<Default as CipherSuite>::Group>::generate_random(&mut rng);
-    let pubkey_bytes = skp.public().to_arr();
+    let pubkey_bytes = skp.public().to_vec();
 
     let mut input = Vec::new();
     input.extend_from_slice(beta_bytes.as_slice());
@@ -144,7 +197,7 @@ fn registration_response_roundtrip() {
 fn registration_upload_roundtrip() {
     let mut rng = OsRng;
     let skp = KeyPair::<<Default as CipherSuite>::Group>::generate_random(&mut rng);
-    let pubkey_bytes = skp.public().to_arr();
+    let pubkey_bytes = skp.public().to_vec();
 
     let mut key = [0u8; 32];
     rng.fill_bytes(&mut key);
@@ -154,9 +207,15 @@ fn registration_upload_roundtrip() {
     let mut masking_key = vec![0u8; <sha2::Sha512 as Digest>::OutputSize::to_usize()];
     rng.fill_bytes(&mut masking_key);
 
-    let (envelope, _) =
-        Envelope::<Default>::seal_raw(&key, &nonce, &pubkey_bytes, InnerEnvelopeMode::Internal)
-            .unwrap();
+    let (envelope, _) = Envelope::<Default>::seal_raw(
+        &key,
+        &nonce,
+        &[],
+        &[],
+        &pubkey_bytes,
+        InnerEnvelopeMode::Internal,
+    )
+    .unwrap();
     let envelope_bytes = envelope.serialize();
 
     let mut input = Vec::new();
@@ -167,6 +226,112 @@ fn registration_upload_roundtrip() {
     let r3 = RegistrationUpload::<Default>::deserialize(&input[..]).unwrap();
     let r3_bytes = r3.serialize();
     assert_eq!(input, r3_bytes);
+
+    // An envelope in the external mode is followed by the mode and the
+    // encrypted private key
+    let encrypted_private_key = skp.private().to_vec();
+    let (envelope, _) = Envelope::<Default>::seal_raw(
+        &key,
+        &nonce,
+        &encrypted_private_key,
+        &[],
+        &pubkey_bytes,
+        InnerEnvelopeMode::External,
+    )
+    .unwrap();
+    let envelope_bytes = envelope.serialize();
+    assert_eq!(
+        envelope_bytes,
+        [
+            &envelope_bytes[..Envelope::<Default>::len()],
+            &[2],
+            &encrypted_private_key[..]
+        ]
+        .concat()
+    );
+    let input = [&pubkey_bytes[..], &masking_key[..], &envelope_bytes].concat();
+    let r3 = RegistrationUpload::<Default>::deserialize(&input[..]).unwrap();
+    assert_eq!(input, r3.serialize());
+    assert!(RegistrationUpload::<Default>::deserialize(&input[..input.len() - 1]).is_err());
+
+    // Credentials are length-prefixed and follow the mode, which is then also
+    // serialized in the internal mode
+    let (envelope, _) = Envelope::<Default>::seal_raw(
+        &key,
+        &nonce,
+        &[],
+        b"credentials",
+        &pubkey_bytes,
+        InnerEnvelopeMode::Internal,
+    )
+    .unwrap();
+    let envelope_bytes = envelope.serialize();
+    assert_eq!(
+        envelope_bytes,
+        [
+            &envelope_bytes[..Envelope::<Default>::len()],
+            &[1],
+            &serialize(b"credentials", 2)
+        ]
+        .concat()
+    );
+    let input = [&pubkey_bytes[..], &masking_key[..], &envelope_bytes].concat();
+    let r3 = RegistrationUpload::<Default>::deserialize(&input[..]).unwrap();
+    assert_eq!(input, r3.serialize());
+    let input = [
+        &pubkey_bytes[..],
+        &masking_key[..],
+        &envelope_bytes[..Envelope::<Default>::len() + 1],
+    ]
+    .concat();
+    assert!(RegistrationUpload::<Default>::deserialize(&input[..]).is_err());
+
+    // The parameters of the slow hashing function, which are empty for
+    // NoOpHash, and the MAC of an authenticated registration follow the
+    // envelope in any mode, after a marker which cannot start the contents
+    // of the envelope
+    let mut mac = vec![0u8; <sha2::Sha512 as Digest>::OutputSize::to_usize()];
+    rng.fill_bytes(&mut mac);
+    for (encrypted_private_key, credentials, mode) in vec![
+        (&[][..], &[][..], InnerEnvelopeMode::Internal),
+        (
+            &encrypted_private_key[..],
+            &[][..],
+            InnerEnvelopeMode::External,
+        ),
+        (&[][..], &b"credentials"[..], InnerEnvelopeMode::Internal),
+        (
+            &encrypted_private_key[..],
+            &b"credentials"[..],
+            InnerEnvelopeMode::External,
+        ),
+    ] {
+        let (envelope, _) = Envelope::<Default>::seal_raw(
+            &key,
+            &nonce,
+            encrypted_private_key,
+            credentials,
+            &pubkey_bytes,
+            mode,
+        )
+        .unwrap();
+        let input = [
+            &pubkey_bytes[..],
+            &masking_key[..],
+            &envelope.serialize(),
+            &[END_OF_ENVELOPE, 0, 0],
+            &mac[..],
+        ]
+        .concat();
+        let r3 = RegistrationUpload::<Default>::deserialize(&input[..]).unwrap();
+        assert_eq!(input, r3.serialize());
+        assert!(RegistrationUpload::<Default>::deserialize(&input[..input.len() - 1]).is_err());
+        assert!(RegistrationUpload::<Default>::deserialize(&[&input[..], &[0]].concat()).is_err());
+        // The marker is only serialized if something follows it
+        assert!(
+            RegistrationUpload::<Default>::deserialize(&input[..input.len() - mac.len()]).is_err()
+        );
+    }
 }
 
 #[test]
@@ -212,7 +377,7 @@ fn credential_response_roundtrip() {
     rng.fill_bytes(&mut masking_nonce);
 
     let mut masked_response =
-        vec![0u8; <PublicKey as SizedBytes>::Len::to_usize() + Envelope::<Default>::len()];
+        vec![0u8; <RistrettoPoint as Group>::ElemLen::to_usize() + Envelope::<Default>::len()];
     rng.fill_bytes(&mut masked_response);
 
     let server_e_kp = KeyPair::<<Default as CipherSuite>::Group>::generate_random(&mut rng);
@@ -233,6 +398,38 @@ fn credential_response_roundtrip() {
     let l2_bytes = l2.serialize();
     assert_eq!(input, l2_bytes);
 
+    // The application payload of the server follows the message
+    let input_with_payload = [&input[..], &[2], &serialize(b"payload", 2)].concat();
+    let l2 = CredentialResponse::<Default>::deserialize(&input_with_payload).unwrap();
+    assert_eq!(l2.payload, b"payload");
+    assert_eq!(input_with_payload, l2.serialize());
+
+    // The masked response stays in place, with the length fixed by the
+    // envelope layout of the server
+    let layout = EnvelopeLayout {
+        external_mode: true,
+        credentials_len: 4,
+    };
+    let mut long_masked_response = vec![
+        0u8;
+        <RistrettoPoint as Group>::ElemLen::to_usize()
+            + Envelope::<Default>::padded_len(&layout)
+    ];
+    rng.fill_bytes(&mut long_masked_response);
+    let long_input = [
+        &pt_bytes[..],
+        &masking_nonce,
+        &long_masked_response,
+        &ke2m[..],
+    ]
+    .concat();
+    let l2 = CredentialResponse::<Default>::deserialize_with_layout(&long_input, &layout).unwrap();
+    assert_eq!(l2.masked_response, long_masked_response);
+    assert_eq!(long_input, l2.serialize());
+
+    // Unknown trailing fields are rejected
+    assert!(CredentialResponse::<Default>::deserialize(&[&input[..], &[4]].concat()).is_err());
+
     // Assert that identity group element is rejected
     let identity = RistrettoPoint::identity();
     let identity_bytes = identity.to_arr().to_vec();
@@ -262,6 +459,20 @@ fn login_third_message_roundtrip() {
     let l3 = CredentialFinalization::<Default>::deserialize(&input).unwrap();
     let l3_bytes = l3.serialize();
     assert_eq!(input, l3_bytes);
+
+    // The application payload of the client follows the message, under a tag
+    // telling whether it is encrypted
+    for &(tag, encrypted) in &[(2, false), (3, true)] {
+        let input_with_payload = [&input[..], &[tag], &serialize(b"payload", 2)].concat();
+        let l3 = CredentialFinalization::<Default>::deserialize(&input_with_payload).unwrap();
+        assert_eq!(l3.payload, b"payload");
+        assert_eq!(l3.payload_encrypted, encrypted);
+        assert_eq!(input_with_payload, l3.serialize());
+    }
+
+    // An empty payload is never serialized
+    let input_with_empty_payload = [&input[..], &[2], &serialize(b"", 2)].concat();
+    assert!(CredentialFinalization::<Default>::deserialize(&input_with_empty_payload).is_err());
 }
 
 #[test]
@@ -275,11 +486,13 @@ fn client_login_roundtrip() {
     rng.fill_bytes(&mut client_nonce);
 
     let serialized_credential_request = b"serialized credential_request".to_vec();
-    let l1_data = [client_e_kp.private().to_arr().to_vec(), client_nonce].concat();
+    let l1_data = [client_e_kp.private().to_vec(), client_nonce].concat();
 
-    // serialization order: scalar, credential_request, ke1_state, password
+    // serialization order: scalar, public OPRF key, credential_request,
+    // ke1_state, password
     let bytes: Vec<u8> = [
         &sc.as_bytes()[..],
+        &serialize(&[], 2),
         &serialize(&serialized_credential_request, 2),
         &serialize(&l1_data, 2),
         &pw[..],
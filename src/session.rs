@@ -0,0 +1,127 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Derivation of application keys from the session key of a login
+
+use crate::{
+    ciphersuite::CipherSuite,
+    errors::{InternalPakeError, ProtocolError},
+    key_exchange::tripledh::hkdf_expand_label,
+};
+use digest::Digest;
+use generic_array::typenum::Unsigned;
+use std::marker::PhantomData;
+use zeroize::Zeroize;
+
+static STR_EXPORTER: &[u8] = b"Exporter";
+
+// The labels are prefixed with "OPAQUE-" and length-prefixed with one byte
+const MAX_LABEL_LEN: usize = 255 - 7;
+
+/// The session key established by a login, from which the client and the
+/// server can export any number of keys for their application, such as
+/// encryption, MAC or resumption keys. The exported keys follow the
+/// exporters of TLS 1.3: each one is derived from a secret specific to its
+/// label, and is bound to a context. The session key is borrowed from the
+/// result of the login, which zeroizes it when dropped
+pub struct SessionKeys<'a, CS: CipherSuite> {
+    session_key: &'a [u8],
+    _cs: PhantomData<CS>,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for SessionKeys<'_, CS> {
+    fn clone(&self) -> Self {
+        Self {
+            session_key: self.session_key,
+            _cs: PhantomData,
+        }
+    }
+}
+
+impl<'a, CS: CipherSuite> SessionKeys<'a, CS> {
+    pub(crate) fn new(session_key: &'a [u8]) -> Self {
+        Self {
+            session_key,
+            _cs: PhantomData,
+        }
+    }
+
+    /// Returns the session key itself
+    pub fn session_key(&self) -> &'a [u8] {
+        self.session_key
+    }
+
+    /// Exports a key of the given length, which is determined by its label
+    /// and by the context it is used in, and is independent from the keys
+    /// exported with any other label or context. The label is at most 248
+    /// bytes long, and the length at most 255 times the output size of the
+    /// hash function of the ciphersuite
+    pub fn export(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        length: usize,
+    ) -> Result<Vec<u8>, ProtocolError> {
+        let hash_len = <CS::Hash as Digest>::OutputSize::to_usize();
+        if label.len() > MAX_LABEL_LEN || length > 255 * hash_len {
+            return Err(InternalPakeError::HkdfError.into());
+        }
+
+        let mut secret = hkdf_expand_label::<CS::Hash>(
+            self.session_key,
+            label,
+            &CS::Hash::digest(b""),
+            hash_len,
+        )?;
+        let result = hkdf_expand_label::<CS::Hash>(
+            &secret,
+            STR_EXPORTER,
+            &CS::Hash::digest(context),
+            length,
+        );
+        secret.zeroize();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{key_exchange::tripledh::TripleDH, slow_hash::NoOpHash};
+    use curve25519_dalek::ristretto::RistrettoPoint;
+
+    struct Default;
+    impl CipherSuite for Default {
+        type Group = RistrettoPoint;
+        type KeyExchange = TripleDH;
+        type Hash = sha2::Sha512;
+        type SlowHash = NoOpHash;
+        const ID: u16 = 0x0001;
+    }
+
+    #[test]
+    fn export_separates_labels_and_contexts() -> Result<(), ProtocolError> {
+        let session_keys = SessionKeys::<Default>::new(&[7u8; 64]);
+        let key = session_keys.export(b"encryption", b"context", 32)?;
+        assert_eq!(key.len(), 32);
+        assert_eq!(key, session_keys.export(b"encryption", b"context", 32)?);
+        assert_ne!(key, session_keys.export(b"mac", b"context", 32)?);
+        assert_ne!(key, session_keys.export(b"encryption", b"other", 32)?);
+        assert_ne!(
+            key,
+            SessionKeys::<Default>::new(&[8u8; 64]).export(b"encryption", b"context", 32)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn export_rejects_invalid_lengths() {
+        let session_keys = SessionKeys::<Default>::new(&[7u8; 64]);
+        assert!(session_keys.export(&[0u8; 249], b"", 32).is_err());
+        assert!(session_keys.export(b"label", b"", 255 * 64 + 1).is_err());
+        assert!(session_keys.export(&[0u8; 248], b"", 255 * 64).is_ok());
+    }
+}
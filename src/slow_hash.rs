@@ -5,25 +5,64 @@
 
 //! Trait specifying a slow hashing function
 
+#[cfg(any(feature = "slow-hash", feature = "slow-hash-argon2"))]
+use crate::errors::utils::check_slice_size;
+#[cfg(feature = "slow-hash-argon2")]
+use crate::{errors::utils::check_slice_size_atleast, serialization::serialize};
 use crate::{errors::InternalPakeError, hash::Hash};
 use digest::Digest;
-#[cfg(feature = "slow-hash")]
+#[cfg(any(feature = "slow-hash", feature = "slow-hash-argon2"))]
 use generic_array::typenum::Unsigned;
 use generic_array::GenericArray;
+#[cfg(any(feature = "slow-hash", feature = "slow-hash-argon2"))]
+use std::convert::TryInto;
+
+/// Used for the slow hashing function in OPAQUE. Implementors hold the cost
+/// parameters of the function, so that they can be chosen at runtime
+pub trait SlowHash<D: Hash>: Clone + Sized {
+    /// The parameters used when none are specified by the caller
+    fn default_params() -> Self;
+
+    /// Serializes the parameters, so that they can be stored along with a
+    /// password file
+    fn serialize(&self) -> Vec<u8>;
+
+    /// Deserializes parameters produced by `serialize`
+    fn deserialize(input: &[u8]) -> Result<Self, InternalPakeError>;
 
-/// Used for the slow hashing function in OPAQUE
-pub trait SlowHash<D: Hash> {
     /// Computes the slow hashing function
     fn hash(
+        &self,
         input: GenericArray<u8, <D as Digest>::OutputSize>,
     ) -> Result<Vec<u8>, InternalPakeError>;
 }
 
 /// A no-op hash which simply returns its input
+#[derive(Clone, Copy, Default)]
 pub struct NoOpHash;
 
 impl<D: Hash> SlowHash<D> for NoOpHash {
+    fn default_params() -> Self {
+        Self
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn deserialize(input: &[u8]) -> Result<Self, InternalPakeError> {
+        if !input.is_empty() {
+            return Err(InternalPakeError::SizeError {
+                name: "no_op_hash_params",
+                len: 0,
+                actual_len: input.len(),
+            });
+        }
+        Ok(Self)
+    }
+
     fn hash(
+        &self,
         input: GenericArray<u8, <D as Digest>::OutputSize>,
     ) -> Result<Vec<u8>, InternalPakeError> {
         Ok(input.to_vec())
@@ -37,17 +76,178 @@ const DEFAULT_SCRYPT_R: u32 = 8u32;
 #[cfg(feature = "slow-hash")]
 const DEFAULT_SCRYPT_P: u32 = 1u32;
 
+/// The scrypt memory-hard function
 #[cfg(feature = "slow-hash")]
-impl<D: Hash> SlowHash<D> for scrypt::ScryptParams {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scrypt {
+    log_n: u8,
+    r: u32,
+    p: u32,
+}
+
+#[cfg(feature = "slow-hash")]
+impl Scrypt {
+    /// Specifies the log2 of the cost parameter N, the block size r and the
+    /// degree of parallelism p
+    pub fn new(log_n: u8, r: u32, p: u32) -> Result<Self, InternalPakeError> {
+        scrypt::ScryptParams::new(log_n, r, p).map_err(|_| InternalPakeError::SlowHashError)?;
+        Ok(Self { log_n, r, p })
+    }
+}
+
+#[cfg(feature = "slow-hash")]
+impl Default for Scrypt {
+    fn default() -> Self {
+        Self {
+            log_n: DEFAULT_SCRYPT_LOG_N,
+            r: DEFAULT_SCRYPT_R,
+            p: DEFAULT_SCRYPT_P,
+        }
+    }
+}
+
+#[cfg(feature = "slow-hash")]
+impl<D: Hash> SlowHash<D> for Scrypt {
+    fn default_params() -> Self {
+        Self::default()
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        [
+            &[self.log_n][..],
+            &self.r.to_be_bytes(),
+            &self.p.to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    fn deserialize(input: &[u8]) -> Result<Self, InternalPakeError> {
+        let checked_slice = check_slice_size(input, 9, "scrypt_params")?;
+        Self::new(
+            checked_slice[0],
+            u32::from_be_bytes(checked_slice[1..5].try_into().unwrap()),
+            u32::from_be_bytes(checked_slice[5..].try_into().unwrap()),
+        )
+    }
+
     fn hash(
+        &self,
         input: GenericArray<u8, <D as Digest>::OutputSize>,
     ) -> Result<Vec<u8>, InternalPakeError> {
-        let params =
-            scrypt::ScryptParams::new(DEFAULT_SCRYPT_LOG_N, DEFAULT_SCRYPT_R, DEFAULT_SCRYPT_P)
-                .map_err(|_| InternalPakeError::SlowHashError)?;
+        let params = scrypt::ScryptParams::new(self.log_n, self.r, self.p)
+            .map_err(|_| InternalPakeError::SlowHashError)?;
         let mut output = vec![0u8; <D as Digest>::OutputSize::to_usize()];
         scrypt::scrypt(&input, &[], &params, &mut output)
             .map_err(|_| InternalPakeError::SlowHashError)?;
         Ok(output)
     }
 }
+
+// The salt of the default parameters, which deployments can replace with
+// their own through Argon2id::new
+#[cfg(feature = "slow-hash-argon2")]
+const DEFAULT_ARGON2ID_SALT: &[u8] = b"OPAQUE-Argon2id-Salt";
+// The salt is serialized with a two-byte length prefix
+#[cfg(feature = "slow-hash-argon2")]
+const MAX_ARGON2ID_SALT_LEN: usize = 0xffff;
+
+/// The Argon2id memory-hard function, as recommended by the OPAQUE draft
+#[cfg(feature = "slow-hash-argon2")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Argon2id {
+    memory_cost: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: Vec<u8>,
+}
+
+#[cfg(feature = "slow-hash-argon2")]
+impl Argon2id {
+    /// Specifies the memory size in KiB, the number of iterations, the degree
+    /// of parallelism and the salt, which must be between 8 and 65535 bytes
+    /// long
+    pub fn new(
+        memory_cost: u32,
+        iterations: u32,
+        parallelism: u32,
+        salt: &[u8],
+    ) -> Result<Self, InternalPakeError> {
+        argon2::Params::new(memory_cost, iterations, parallelism, None)
+            .map_err(|_| InternalPakeError::SlowHashError)?;
+        if salt.len() < argon2::MIN_SALT_LEN || salt.len() > MAX_ARGON2ID_SALT_LEN {
+            return Err(InternalPakeError::SlowHashError);
+        }
+        Ok(Self {
+            memory_cost,
+            iterations,
+            parallelism,
+            salt: salt.to_vec(),
+        })
+    }
+}
+
+/// The second recommended option of
+/// <https://www.rfc-editor.org/rfc/rfc9106.html#section-4>, with 64 MiB of
+/// memory, 3 iterations and 4 lanes
+#[cfg(feature = "slow-hash-argon2")]
+impl Default for Argon2id {
+    fn default() -> Self {
+        Self {
+            memory_cost: 1 << 16,
+            iterations: 3,
+            parallelism: 4,
+            salt: DEFAULT_ARGON2ID_SALT.to_vec(),
+        }
+    }
+}
+
+#[cfg(feature = "slow-hash-argon2")]
+impl<D: Hash> SlowHash<D> for Argon2id {
+    fn default_params() -> Self {
+        Self::default()
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        [
+            &self.memory_cost.to_be_bytes()[..],
+            &self.iterations.to_be_bytes(),
+            &self.parallelism.to_be_bytes(),
+            &serialize(&self.salt, 2),
+        ]
+        .concat()
+    }
+
+    fn deserialize(input: &[u8]) -> Result<Self, InternalPakeError> {
+        let checked_slice = check_slice_size_atleast(input, 14, "argon2id_params")?;
+        let salt_len = u16::from_be_bytes(checked_slice[12..14].try_into().unwrap()) as usize;
+        let salt = check_slice_size(&checked_slice[14..], salt_len, "argon2id_salt")?;
+        Self::new(
+            u32::from_be_bytes(checked_slice[..4].try_into().unwrap()),
+            u32::from_be_bytes(checked_slice[4..8].try_into().unwrap()),
+            u32::from_be_bytes(checked_slice[8..12].try_into().unwrap()),
+            salt,
+        )
+    }
+
+    fn hash(
+        &self,
+        input: GenericArray<u8, <D as Digest>::OutputSize>,
+    ) -> Result<Vec<u8>, InternalPakeError> {
+        let output_len = <D as Digest>::OutputSize::to_usize();
+        let params = argon2::Params::new(
+            self.memory_cost,
+            self.iterations,
+            self.parallelism,
+            Some(output_len),
+        )
+        .map_err(|_| InternalPakeError::SlowHashError)?;
+        let argon2 =
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+        let mut output = vec![0u8; output_len];
+        argon2
+            .hash_password_into(&input, &self.salt, &mut output)
+            .map_err(|_| InternalPakeError::SlowHashError)?;
+        Ok(output)
+    }
+}
@@ -5,20 +5,29 @@
 
 #![allow(unsafe_code)]
 
+#[cfg(feature = "ml-kem")]
+use crate::key_exchange::{
+    hybrid::{HybridTripleDH, Kem},
+    ml_kem::MlKem768,
+};
 use crate::{
     ciphersuite::CipherSuite,
     errors::*,
     group::Group,
-    key_exchange::tripledh::{NonceLen, TripleDH},
+    key_exchange::{
+        hmqv::HMQV,
+        traits::{AsBytePointers, KeyExchange},
+        tripledh::{NonceLen, TripleDH},
+    },
     keypair::KeyPair,
     opaque::*,
-    slow_hash::NoOpHash,
+    slow_hash::{NoOpHash, SlowHash},
     tests::mock_rng::CycleRng,
     *,
 };
 use curve25519_dalek::{ristretto::RistrettoPoint, traits::Identity};
+use digest::Digest;
 use generic_array::typenum::Unsigned;
-use generic_bytes::SizedBytes;
 use rand::{rngs::OsRng, RngCore};
 use serde_json::Value;
 use std::slice::from_raw_parts;
@@ -33,6 +42,93 @@ impl CipherSuite for RistrettoSha5123dhNoSlowHash {
     type KeyExchange = TripleDH;
     type Hash = sha2::Sha512;
     type SlowHash = NoOpHash;
+    const ID: u16 = 0x0001;
+}
+
+struct RistrettoSha512HmqvNoSlowHash;
+impl CipherSuite for RistrettoSha512HmqvNoSlowHash {
+    type Group = RistrettoPoint;
+    type KeyExchange = HMQV;
+    type Hash = sha2::Sha512;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0002;
+}
+
+#[cfg(feature = "ml-kem")]
+struct RistrettoSha512HybridNoSlowHash;
+#[cfg(feature = "ml-kem")]
+impl CipherSuite for RistrettoSha512HybridNoSlowHash {
+    type Group = RistrettoPoint;
+    type KeyExchange = HybridTripleDH<MlKem768>;
+    type Hash = sha2::Sha512;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0003;
+}
+
+#[cfg(feature = "p256")]
+struct P256Sha2563dhNoSlowHash;
+#[cfg(feature = "p256")]
+impl CipherSuite for P256Sha2563dhNoSlowHash {
+    type Group = p256_::ProjectivePoint;
+    type KeyExchange = TripleDH;
+    type Hash = sha2::Sha256;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0101;
+}
+
+#[cfg(feature = "p256")]
+struct P256Sha256HmqvNoSlowHash;
+#[cfg(feature = "p256")]
+impl CipherSuite for P256Sha256HmqvNoSlowHash {
+    type Group = p256_::ProjectivePoint;
+    type KeyExchange = HMQV;
+    type Hash = sha2::Sha256;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0102;
+}
+
+#[cfg(feature = "slow-hash-argon2")]
+struct RistrettoSha5123dhArgon2;
+#[cfg(feature = "slow-hash-argon2")]
+impl CipherSuite for RistrettoSha5123dhArgon2 {
+    type Group = RistrettoPoint;
+    type KeyExchange = TripleDH;
+    type Hash = sha2::Sha512;
+    type SlowHash = crate::slow_hash::Argon2id;
+    const ID: u16 = 0x0005;
+}
+
+#[cfg(feature = "p384")]
+struct P384Sha3843dhNoSlowHash;
+#[cfg(feature = "p384")]
+impl CipherSuite for P384Sha3843dhNoSlowHash {
+    type Group = p384_::ProjectivePoint;
+    type KeyExchange = TripleDH;
+    type Hash = sha2::Sha384;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0201;
+}
+
+#[cfg(feature = "p521")]
+struct P521Sha5123dhNoSlowHash;
+#[cfg(feature = "p521")]
+impl CipherSuite for P521Sha5123dhNoSlowHash {
+    type Group = p521_::ProjectivePoint;
+    type KeyExchange = TripleDH;
+    type Hash = sha2::Sha512;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0301;
+}
+
+#[cfg(feature = "sigma")]
+struct RistrettoSha512SigmaNoSlowHash;
+#[cfg(feature = "sigma")]
+impl CipherSuite for RistrettoSha512SigmaNoSlowHash {
+    type Group = RistrettoPoint;
+    type KeyExchange = crate::key_exchange::sigma::Sigma;
+    type Hash = sha2::Sha512;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0004;
 }
 
 pub struct TestVectorParameters {
@@ -101,10 +197,10 @@ static TEST_VECTOR: &str = r#"
     "credential_request": "0cdc7df1cca989b56917c95127e59ec8f05bda7c606cb45e714bfa582b429832e56a024c1d89f05ff245b98ba097cdfdcc5c181b3d5e9d52100d421d3160f80a5a513aecfa17dab422221a980819c680aea9a49947c7c0caca94fc61dcb4632c",
     "credential_response": "e88418f5a9145287062e50b060e6f6790583ec8646430af1bff0a2729bf20d1b2b49d01802a69aacdad4979c503b96d08f44e4c67eaf82bbf6e71c6bb5473aa8718337df372fbb0de1beb29e2f4e6a2419858326ffe3f2a24172cca25e6344edd7db031cac3e206218eda4555d816f341c428317a4d37ed63441a278f78185b202b675b620e6f35056964d400c311cad23a1e6b0d9a91837d9d0021280bf0facf422961c96cffea530a24eb2486d4fa91adadaf7ac9a17d35b329b2add32e368a4b66443250a0cc39ad9baae6ada72c243ddee53b712eb48933993230c13500f2896e6f69e8610ced17584f34c09d872300bac6c99b8157392517ab9e9ed1f4aa163f8040d899cc77cf1f0ca2c4be6aef1616288cd3a6ac21989bdfc07bc4e94a284cf4c588583b2361195feab1ddcd390defde6282db2edc3eb535ede66404b",
     "credential_finalization": "2f8c71675d7db1b32ed3daaa7f15fc353f6af536ab1199e41e43ece9871d8b69336b8c84c4906810bb87c1a0407bd5f5d780c7d10a1c94016103639e507cf6d0",
-    "client_registration_state": "08f845725404c823f477eb1e8f79dab63fdbbb2110a6c360fc98a4d2720e9d0a70617373776f7264",
-    "client_login_state": "08f845725404c823f477eb1e8f79dab63fdbbb2110a6c360fc98a4d2720e9d0a00600cdc7df1cca989b56917c95127e59ec8f05bda7c606cb45e714bfa582b429832e56a024c1d89f05ff245b98ba097cdfdcc5c181b3d5e9d52100d421d3160f80a5a513aecfa17dab422221a980819c680aea9a49947c7c0caca94fc61dcb4632c00409f42ca864614d4175e1540e4c56fe18362cb56b778dccf6b0a9446a23735dc03e56a024c1d89f05ff245b98ba097cdfdcc5c181b3d5e9d52100d421d3160f80a70617373776f7264",
-    "server_login_state": "a62f305635e341c151f5e51b89307940031337a0ad8f1369ddec9b672dc31f35d59be00eb66d77bda0079d6eda94809c863da359fef3a636704ae3fa1c9b9b2d18eb9b193528fbb392a5eab5da8068b7c276c8fe00814213ddd70d02157902bebfce850b403aaa4c99f8dbd5ff50d4ad3e703fb564a3fc474861e3f69d7c9a90037d3dbf36f215082644d5c5bc91e138f9665e7bc538f4bc70f97c91dfcd029b1c027b03dc99137478b3570d9da27922b88a8784f1c2f07cd04a0db04246531a",
-    "password_file": "d6f1486284e595707ae341a4d083d454477933b1bcf770bfc4087127c0a8e844e833f76e997aef5b46d2108811667183d08f0cc0a8465dac277287591cac1e42933ed23a2c9476cfa939854a40fc746c21606535b19f0a48cf8cc565f7c3e6df60a33dd8e1970aa3d2ed09c03ad0380e0cf628a669d3b7d030d3fea0dd7f5c0654e4188e55b7fe2eed8a7aee79ae6cfefabab86e7b7822f05bc422ac7e7a9acb968001b3dc5ead255a2d7599a7be60aa97ebed89808db20faa445e912f7df2da",
+    "client_registration_state": "08f845725404c823f477eb1e8f79dab63fdbbb2110a6c360fc98a4d2720e9d0a000070617373776f7264",
+    "client_login_state": "08f845725404c823f477eb1e8f79dab63fdbbb2110a6c360fc98a4d2720e9d0a000000600cdc7df1cca989b56917c95127e59ec8f05bda7c606cb45e714bfa582b429832e56a024c1d89f05ff245b98ba097cdfdcc5c181b3d5e9d52100d421d3160f80a5a513aecfa17dab422221a980819c680aea9a49947c7c0caca94fc61dcb4632c00409f42ca864614d4175e1540e4c56fe18362cb56b778dccf6b0a9446a23735dc03e56a024c1d89f05ff245b98ba097cdfdcc5c181b3d5e9d52100d421d3160f80a70617373776f7264",
+    "server_login_state": "001b0000000000000000000000000e637265644964656e746966696572000018eb9b193528fbb392a5eab5da8068b7c276c8fe00814213ddd70d02157902bebfce850b403aaa4c99f8dbd5ff50d4ad3e703fb564a3fc474861e3f69d7c9a90d6f1486284e595707ae341a4d083d454477933b1bcf770bfc4087127c0a8e84400036964550003696453a62f305635e341c151f5e51b89307940031337a0ad8f1369ddec9b672dc31f35d59be00eb66d77bda0079d6eda94809c863da359fef3a636704ae3fa1c9b9b2d18eb9b193528fbb392a5eab5da8068b7c276c8fe00814213ddd70d02157902bebfce850b403aaa4c99f8dbd5ff50d4ad3e703fb564a3fc474861e3f69d7c9a90037d3dbf36f215082644d5c5bc91e138f9665e7bc538f4bc70f97c91dfcd029b1c027b03dc99137478b3570d9da27922b88a8784f1c2f07cd04a0db04246531a",
+    "password_file": "03000100000000000000000000d6f1486284e595707ae341a4d083d454477933b1bcf770bfc4087127c0a8e844e833f76e997aef5b46d2108811667183d08f0cc0a8465dac277287591cac1e42933ed23a2c9476cfa939854a40fc746c21606535b19f0a48cf8cc565f7c3e6df60a33dd8e1970aa3d2ed09c03ad0380e0cf628a669d3b7d030d3fea0dd7f5c0654e4188e55b7fe2eed8a7aee79ae6cfefabab86e7b7822f05bc422ac7e7a9acb968001b3dc5ead255a2d7599a7be60aa97ebed89808db20faa445e912f7df2da",
     "export_key": "f1abeb7ab0a43ff1924b59d744053b271d999f341eedc740f1f62d785d19bec939479e5e39f2ec25f5ef712ecd10a085653ad1ed9049092cb2a3d44d6cc205ba",
     "session_key": "037d3dbf36f215082644d5c5bc91e138f9665e7bc538f4bc70f97c91dfcd029b1c027b03dc99137478b3570d9da27922b88a8784f1c2f07cd04a0db04246531a"
 }
@@ -289,7 +385,7 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
     let id_s = b"idS";
     let password = b"password";
     let context = b"context";
-    let mut oprf_seed = [0u8; 64];
+    let mut oprf_seed = vec![0u8; <CS::Hash as Digest>::OutputSize::to_usize()];
     rng.fill_bytes(&mut oprf_seed);
     let mut masking_nonce = [0u8; 64];
     rng.fill_bytes(&mut masking_nonce);
@@ -302,7 +398,7 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
 
     let fake_sk: Vec<u8> = fake_kp.private().to_vec();
     let server_setup = ServerSetup::<CS>::deserialize(
-        &[&oprf_seed, &server_s_kp.private().to_arr()[..], &fake_sk].concat(),
+        &[&oprf_seed, &server_s_kp.private().to_vec()[..], &fake_sk].concat(),
     )
     .unwrap();
 
@@ -326,6 +422,7 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
     let client_registration_state = client_registration_start_result.state.serialize().to_vec();
 
     let server_registration_start_result = ServerRegistration::<CS>::start(
+        &mut rng,
         &server_setup,
         client_registration_start_result.message,
         &credential_identifier[..],
@@ -337,7 +434,7 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
         .to_vec();
 
     let mut client_s_sk_and_nonce: Vec<u8> = Vec::new();
-    client_s_sk_and_nonce.extend_from_slice(&client_s_kp.private().to_arr());
+    client_s_sk_and_nonce.extend_from_slice(&client_s_kp.private().to_vec());
     client_s_sk_and_nonce.extend_from_slice(&envelope_nonce);
 
     let mut finish_registration_rng = CycleRng::new(client_s_sk_and_nonce);
@@ -346,8 +443,12 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
         .finish(
             &mut finish_registration_rng,
             server_registration_start_result.message,
-            ClientRegistrationFinishParameters::WithIdentifiers(
-                Identifiers::ClientAndServerIdentifiers(id_u.to_vec(), id_s.to_vec()),
+            ClientRegistrationFinishParameters::new(
+                Some(Identifiers::ClientAndServerIdentifiers(
+                    id_u.to_vec(),
+                    id_s.to_vec(),
+                )),
+                None,
             ),
         )
         .unwrap();
@@ -356,12 +457,16 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
         .serialize()
         .to_vec();
 
-    let password_file = ServerRegistration::finish(client_registration_finish_result.message);
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )
+    .unwrap();
     let password_file_bytes = password_file.serialize();
 
     let mut client_login_start: Vec<u8> = Vec::new();
     client_login_start.extend_from_slice(&blinding_factor_bytes);
-    client_login_start.extend_from_slice(&client_e_kp.private().to_arr());
+    client_login_start.extend_from_slice(&client_e_kp.private().to_vec());
     client_login_start.extend_from_slice(&client_nonce);
 
     let mut client_login_start_rng = CycleRng::new(client_login_start);
@@ -373,7 +478,7 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
     let mut server_e_sk_and_nonce_rng = CycleRng::new(
         [
             masking_nonce.to_vec(),
-            server_e_kp.private().to_arr().to_vec(),
+            server_e_kp.private().to_vec(),
             server_nonce.to_vec(),
         ]
         .concat(),
@@ -384,9 +489,12 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
         Some(password_file),
         client_login_start_result.message,
         credential_identifier,
-        ServerLoginStartParameters::WithContextAndIdentifiers(
-            context.to_vec(),
-            Identifiers::ClientAndServerIdentifiers(id_u.to_vec(), id_s.to_vec()),
+        ServerLoginStartParameters::new(
+            Some(context.to_vec()),
+            Some(Identifiers::ClientAndServerIdentifiers(
+                id_u.to_vec(),
+                id_s.to_vec(),
+            )),
         ),
     )
     .unwrap();
@@ -397,23 +505,27 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
         .state
         .finish(
             server_login_start_result.message,
-            ClientLoginFinishParameters::WithContextAndIdentifiers(
-                context.to_vec(),
-                Identifiers::ClientAndServerIdentifiers(id_u.to_vec(), id_s.to_vec()),
+            ClientLoginFinishParameters::new(
+                Some(context.to_vec()),
+                Some(Identifiers::ClientAndServerIdentifiers(
+                    id_u.to_vec(),
+                    id_s.to_vec(),
+                )),
+                None,
             ),
         )
         .unwrap();
     let credential_finalization_bytes = client_login_finish_result.message.serialize();
 
     TestVectorParameters {
-        client_s_pk: client_s_kp.public().to_arr().to_vec(),
-        client_s_sk: client_s_kp.private().to_arr().to_vec(),
-        client_e_pk: client_e_kp.public().to_arr().to_vec(),
-        client_e_sk: client_e_kp.private().to_arr().to_vec(),
-        server_s_pk: server_s_kp.public().to_arr().to_vec(),
-        server_s_sk: server_s_kp.private().to_arr().to_vec(),
-        server_e_pk: server_e_kp.public().to_arr().to_vec(),
-        server_e_sk: server_e_kp.private().to_arr().to_vec(),
+        client_s_pk: client_s_kp.public().to_vec(),
+        client_s_sk: client_s_kp.private().to_vec(),
+        client_e_pk: client_e_kp.public().to_vec(),
+        client_e_sk: client_e_kp.private().to_vec(),
+        server_s_pk: server_s_kp.public().to_vec(),
+        server_s_sk: server_s_kp.private().to_vec(),
+        server_e_pk: server_e_kp.public().to_vec(),
+        server_e_sk: server_e_kp.private().to_vec(),
         fake_sk,
         credential_identifier: credential_identifier.to_vec(),
         id_u: id_u.to_vec(),
@@ -436,7 +548,7 @@ fn generate_parameters<CS: CipherSuite>() -> TestVectorParameters {
         client_registration_state,
         client_login_state,
         server_login_state,
-        session_key: client_login_finish_result.session_key,
+        session_key: client_login_finish_result.session_key.to_vec(),
         export_key: client_registration_finish_result.export_key.to_vec(),
     }
 }
@@ -515,6 +627,7 @@ fn test_registration_response() -> Result<(), ProtocolError> {
 
     let server_registration_start_result =
         ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut OsRng,
             &server_setup,
             RegistrationRequest::deserialize(&parameters.registration_request[..])?,
             &parameters.credential_identifier,
@@ -539,8 +652,12 @@ fn test_registration_upload() -> Result<(), ProtocolError> {
     .finish(
         &mut finish_registration_rng,
         RegistrationResponse::deserialize(&parameters.registration_response[..])?,
-        ClientRegistrationFinishParameters::WithIdentifiers(
-            Identifiers::ClientAndServerIdentifiers(parameters.id_u, parameters.id_s),
+        ClientRegistrationFinishParameters::new(
+            Some(Identifiers::ClientAndServerIdentifiers(
+                parameters.id_u,
+                parameters.id_s,
+            )),
+            None,
         ),
     )?;
 
@@ -560,11 +677,18 @@ fn test_registration_upload() -> Result<(), ProtocolError> {
 fn test_password_file() -> Result<(), ProtocolError> {
     let parameters = populate_test_vectors(&serde_json::from_str(TEST_VECTOR).unwrap());
 
-    let password_file = ServerRegistration::finish(RegistrationUpload::<
-        RistrettoSha5123dhNoSlowHash,
-    >::deserialize(
-        &parameters.registration_upload[..]
-    )?);
+    let password_file = ServerRegistration::finish(
+        RegistrationUpload::<RistrettoSha5123dhNoSlowHash>::deserialize(
+            &parameters.registration_upload[..],
+        )?,
+        // The key IDs of a new ServerSetup
+        RegistrationKeyIds {
+            oprf_key_id: 0,
+            server_key_id: 0,
+            envelope_layout: EnvelopeLayout::default(),
+            credential_identifier: Vec::new(),
+        },
+    )?;
 
     assert_eq!(
         hex::encode(parameters.password_file),
@@ -630,9 +754,12 @@ fn test_credential_response() -> Result<(), ProtocolError> {
             &parameters.credential_request[..],
         )?,
         &parameters.credential_identifier,
-        ServerLoginStartParameters::WithContextAndIdentifiers(
-            parameters.context,
-            Identifiers::ClientAndServerIdentifiers(parameters.id_u, parameters.id_s),
+        ServerLoginStartParameters::new(
+            Some(parameters.context),
+            Some(Identifiers::ClientAndServerIdentifiers(
+                parameters.id_u,
+                parameters.id_s,
+            )),
         ),
     )?;
     assert_eq!(
@@ -657,15 +784,19 @@ fn test_credential_finalization() -> Result<(), ProtocolError> {
         CredentialResponse::<RistrettoSha5123dhNoSlowHash>::deserialize(
             &parameters.credential_response[..],
         )?,
-        ClientLoginFinishParameters::WithContextAndIdentifiers(
-            parameters.context,
-            Identifiers::ClientAndServerIdentifiers(parameters.id_u, parameters.id_s),
+        ClientLoginFinishParameters::new(
+            Some(parameters.context),
+            Some(Identifiers::ClientAndServerIdentifiers(
+                parameters.id_u,
+                parameters.id_s,
+            )),
+            None,
         ),
     )?;
 
     assert_eq!(
         hex::encode(&parameters.server_s_pk),
-        hex::encode(&client_login_finish_result.server_s_pk.to_arr().to_vec())
+        hex::encode(&client_login_finish_result.server_s_pk.to_vec())
     );
     assert_eq!(
         hex::encode(&parameters.session_key),
@@ -690,9 +821,10 @@ fn test_server_login_finish() -> Result<(), ProtocolError> {
     let server_login_result = ServerLogin::<RistrettoSha5123dhNoSlowHash>::deserialize(
         &parameters.server_login_state[..],
     )?
-    .finish(CredentialFinalization::deserialize(
-        &parameters.credential_finalization[..],
-    )?)?;
+    .finish(
+        CredentialFinalization::deserialize(&parameters.credential_finalization[..])?,
+        ServerLoginFinishParameters::default(),
+    )?;
 
     assert_eq!(
         hex::encode(parameters.session_key),
@@ -702,34 +834,42 @@ fn test_server_login_finish() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-fn test_complete_flow(
+fn test_complete_flow<CS: CipherSuite>(
+    registration_password: &[u8],
+    login_password: &[u8],
+) -> Result<(), ProtocolError> {
+    test_complete_flow_with_slow_hash::<CS>(registration_password, login_password, None, None)
+}
+
+fn test_complete_flow_with_slow_hash<CS: CipherSuite>(
     registration_password: &[u8],
     login_password: &[u8],
+    registration_slow_hash: Option<&CS::SlowHash>,
+    login_slow_hash: Option<&CS::SlowHash>,
 ) -> Result<(), ProtocolError> {
     let credential_identifier = b"credentialIdentifier";
     let mut client_rng = OsRng;
     let mut server_rng = OsRng;
-    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
+    let server_setup = ServerSetup::<CS>::new(&mut server_rng);
     let client_registration_start_result =
-        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &mut client_rng,
-            registration_password,
-        )?;
-    let server_registration_start_result =
-        ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &server_setup,
-            client_registration_start_result.message,
-            credential_identifier,
-        )?;
+        ClientRegistration::<CS>::start(&mut client_rng, registration_password)?;
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        &mut server_rng,
+        &server_setup,
+        client_registration_start_result.message,
+        credential_identifier,
+    )?;
     let client_registration_finish_result = client_registration_start_result.state.finish(
         &mut client_rng,
         server_registration_start_result.message,
-        ClientRegistrationFinishParameters::default(),
+        ClientRegistrationFinishParameters::new(None, registration_slow_hash),
     )?;
-    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
-    let client_login_start_result =
-        ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(&mut client_rng, login_password)?;
-    let server_login_start_result = ServerLogin::<RistrettoSha5123dhNoSlowHash>::start(
+    let p_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+    let client_login_start_result = ClientLogin::<CS>::start(&mut client_rng, login_password)?;
+    let server_login_start_result = ServerLogin::<CS>::start(
         &mut server_rng,
         &server_setup,
         Some(p_file),
@@ -740,14 +880,15 @@ fn test_complete_flow(
 
     let client_login_result = client_login_start_result.state.finish(
         server_login_start_result.message,
-        ClientLoginFinishParameters::default(),
+        ClientLoginFinishParameters::new(None, None, login_slow_hash),
     );
 
     if hex::encode(registration_password) == hex::encode(login_password) {
         let client_login_finish_result = client_login_result?;
-        let server_login_finish_result = server_login_start_result
-            .state
-            .finish(client_login_finish_result.message)?;
+        let server_login_finish_result = server_login_start_result.state.finish(
+            client_login_finish_result.message,
+            ServerLoginFinishParameters::default(),
+        )?;
 
         assert_eq!(
             hex::encode(&server_login_finish_result.session_key),
@@ -769,128 +910,247 @@ fn test_complete_flow(
 
 #[test]
 fn test_complete_flow_success() -> Result<(), ProtocolError> {
-    test_complete_flow(b"good password", b"good password")
+    test_complete_flow::<RistrettoSha5123dhNoSlowHash>(b"good password", b"good password")
 }
 
 #[test]
 fn test_complete_flow_fail() -> Result<(), ProtocolError> {
-    test_complete_flow(b"good password", b"bad password")
+    test_complete_flow::<RistrettoSha5123dhNoSlowHash>(b"good password", b"bad password")
 }
 
-// Zeroize tests
+#[cfg(feature = "slow-hash-argon2")]
+#[test]
+fn test_argon2_complete_flow_success() -> Result<(), ProtocolError> {
+    // Kept small so that the tests run quickly
+    let argon2 = crate::slow_hash::Argon2id::new(64, 2, 2, b"argon2id salt")?;
+    test_complete_flow_with_slow_hash::<RistrettoSha5123dhArgon2>(
+        b"good password",
+        b"good password",
+        Some(&argon2),
+        Some(&argon2),
+    )
+}
 
+#[cfg(feature = "slow-hash-argon2")]
 #[test]
-fn test_zeroize_client_registration_start() -> Result<(), ProtocolError> {
-    let mut client_rng = OsRng;
-    let client_registration_start_result =
-        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &mut client_rng,
-            STR_PASSWORD.as_bytes(),
+fn test_argon2_complete_flow_fail() -> Result<(), ProtocolError> {
+    let argon2 = crate::slow_hash::Argon2id::new(64, 2, 2, b"argon2id salt")?;
+    test_complete_flow_with_slow_hash::<RistrettoSha5123dhArgon2>(
+        b"good password",
+        b"bad password",
+        Some(&argon2),
+        Some(&argon2),
+    )
+}
+
+#[cfg(feature = "slow-hash-argon2")]
+#[test]
+fn test_argon2_different_cost_levels() -> Result<(), ProtocolError> {
+    let low_cost = crate::slow_hash::Argon2id::new(64, 1, 1, b"argon2id salt")?;
+    let high_cost = crate::slow_hash::Argon2id::new(128, 2, 2, b"argon2id salt")?;
+
+    // Registrations made at either cost level can log in with the same level
+    for slow_hash in [&low_cost, &high_cost].iter().copied() {
+        test_complete_flow_with_slow_hash::<RistrettoSha5123dhArgon2>(
+            b"good password",
+            b"good password",
+            Some(slow_hash),
+            Some(slow_hash),
         )?;
+    }
 
-    let mut state = client_registration_start_result.state;
-    let ptrs = state.as_byte_ptrs();
-    state.zeroize();
+    // But logging in with a different cost level than the one used during
+    // registration fails
+    assert!(
+        match test_complete_flow_with_slow_hash::<RistrettoSha5123dhArgon2>(
+            b"good password",
+            b"good password",
+            Some(&low_cost),
+            Some(&high_cost),
+        ) {
+            Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+            _ => false,
+        }
+    );
 
-    for (ptr, len) in ptrs {
-        let bytes = unsafe { from_raw_parts(ptr, len) };
-        assert!(bytes.iter().all(|&x| x == 0));
-    }
+    Ok(())
+}
+
+#[cfg(feature = "slow-hash-argon2")]
+#[test]
+fn test_argon2_different_salts() -> Result<(), ProtocolError> {
+    let argon2 = crate::slow_hash::Argon2id::new(64, 1, 1, b"argon2id salt")?;
+    let other_salt = crate::slow_hash::Argon2id::new(64, 1, 1, b"other argon2id salt")?;
+    assert!(
+        match test_complete_flow_with_slow_hash::<RistrettoSha5123dhArgon2>(
+            b"good password",
+            b"good password",
+            Some(&argon2),
+            Some(&other_salt),
+        ) {
+            Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+            _ => false,
+        }
+    );
 
+    // Salts shorter than eight bytes are rejected
+    assert!(crate::slow_hash::Argon2id::new(64, 1, 1, b"salt").is_err());
     Ok(())
 }
 
+#[cfg(feature = "slow-hash-argon2")]
 #[test]
-fn test_zeroize_client_registration_finish() -> Result<(), ProtocolError> {
-    let mut client_rng = OsRng;
-    let mut server_rng = OsRng;
-    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
+fn test_argon2_password_file_records_params() -> Result<(), ProtocolError> {
+    let argon2 = crate::slow_hash::Argon2id::new(64, 2, 2, b"argon2id salt")?;
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhArgon2>::new(&mut rng);
     let client_registration_start_result =
-        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &mut client_rng,
-            STR_PASSWORD.as_bytes(),
-        )?;
-    let server_registration_start_result =
-        ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &server_setup,
-            client_registration_start_result.message,
-            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
-        )?;
+        ClientRegistration::<RistrettoSha5123dhArgon2>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
     let client_registration_finish_result = client_registration_start_result.state.finish(
-        &mut client_rng,
+        &mut rng,
         server_registration_start_result.message,
-        ClientRegistrationFinishParameters::default(),
+        ClientRegistrationFinishParameters::new(None, Some(&argon2)),
+    )?;
+    // The parameters reach the server through the registration upload
+    let password_file = ServerRegistration::finish(
+        RegistrationUpload::<RistrettoSha5123dhArgon2>::deserialize(
+            &client_registration_finish_result.message.serialize(),
+        )?,
+        server_registration_start_result.key_ids,
     )?;
 
-    let mut state = client_registration_finish_result.state;
-    let ptrs = state.as_byte_ptrs();
-    state.zeroize();
-
-    for (ptr, len) in ptrs {
-        let bytes = unsafe { from_raw_parts(ptr, len) };
-        assert!(bytes.iter().all(|&x| x == 0));
-    }
+    let password_file =
+        ServerRegistration::<RistrettoSha5123dhArgon2>::deserialize(&password_file.serialize())?;
+    assert_eq!(password_file.slow_hash(), &argon2);
 
     Ok(())
 }
 
-#[test]
-fn test_zeroize_server_registration_finish() -> Result<(), ProtocolError> {
+fn test_password_file_upgrade<CS: CipherSuite>(
+    old_slow_hash: &CS::SlowHash,
+    new_slow_hash: &CS::SlowHash,
+) -> Result<(), ProtocolError> {
     let mut client_rng = OsRng;
     let mut server_rng = OsRng;
-    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
+    let mut server_setup = ServerSetup::<CS>::new(&mut server_rng);
+    server_setup.set_envelope_credentials_len(b"recovery code".len())?;
     let client_registration_start_result =
-        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &mut client_rng,
-            STR_PASSWORD.as_bytes(),
-        )?;
-    let server_registration_start_result =
-        ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &server_setup,
-            client_registration_start_result.message,
-            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
-        )?;
+        ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        &mut server_rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
     let client_registration_finish_result = client_registration_start_result.state.finish(
         &mut client_rng,
         server_registration_start_result.message,
-        ClientRegistrationFinishParameters::default(),
+        ClientRegistrationFinishParameters {
+            credentials: Some(b"recovery code"),
+            ..ClientRegistrationFinishParameters::new(None, Some(old_slow_hash))
+        },
+    )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
     )?;
-    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
 
-    let mut state = p_file;
-    let ptrs = state.as_byte_ptrs();
-    state.zeroize();
+    // Log in with the old parameters, requesting an upgrade to the new ones
+    let client_login_start_result =
+        ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish_with_upgrade(
+        &mut client_rng,
+        server_login_start_result.message,
+        ClientLoginFinishParameters::new(None, None, Some(old_slow_hash)),
+        new_slow_hash,
+    )?;
+    let password_file_upgrade = PasswordFileUpgrade::<CS>::deserialize(
+        &client_login_finish_result
+            .password_file_upgrade
+            .unwrap()
+            .serialize(),
+    )?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::new(Some(password_file_upgrade)),
+    )?;
+    let password_file = ServerRegistration::<CS>::deserialize(
+        &server_login_finish_result
+            .password_file
+            .unwrap()
+            .serialize(),
+    )?;
+    assert_eq!(
+        password_file.slow_hash().serialize(),
+        new_slow_hash.serialize()
+    );
 
-    for (ptr, len) in ptrs {
-        let bytes = unsafe { from_raw_parts(ptr, len) };
-        assert!(bytes.iter().all(|&x| x == 0));
-    }
+    // The upgraded password file can then be used with the new parameters
+    let client_login_start_result =
+        ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let upgraded_login_finish_result = client_login_start_result.state.finish(
+        server_login_start_result.message,
+        ClientLoginFinishParameters::new(None, None, Some(new_slow_hash)),
+    )?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        upgraded_login_finish_result.message,
+        ServerLoginFinishParameters::default(),
+    )?;
+
+    assert_eq!(
+        hex::encode(&server_login_finish_result.session_key),
+        hex::encode(&upgraded_login_finish_result.session_key)
+    );
+    assert_eq!(
+        hex::encode(client_login_finish_result.upgrade_export_key.unwrap()),
+        hex::encode(upgraded_login_finish_result.export_key)
+    );
+    // The credentials carry over to the upgraded password file
+    assert_eq!(
+        &upgraded_login_finish_result.credentials[..],
+        b"recovery code"
+    );
 
     Ok(())
 }
 
 #[test]
-fn test_zeroize_client_login_start() -> Result<(), ProtocolError> {
-    let mut client_rng = OsRng;
-    let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
-        &mut client_rng,
-        STR_PASSWORD.as_bytes(),
-    )?;
-
-    let mut state = client_login_start_result.state;
-    let ptrs = state.as_byte_ptrs();
-    state.zeroize();
-
-    for (ptr, len) in ptrs {
-        let bytes = unsafe { from_raw_parts(ptr, len) };
-        assert!(bytes.iter().all(|&x| x == 0));
-    }
+fn test_password_file_upgrade_success() -> Result<(), ProtocolError> {
+    test_password_file_upgrade::<RistrettoSha5123dhNoSlowHash>(&NoOpHash, &NoOpHash)
+}
 
-    Ok(())
+#[cfg(feature = "slow-hash-argon2")]
+#[test]
+fn test_argon2_password_file_upgrade() -> Result<(), ProtocolError> {
+    let low_cost = crate::slow_hash::Argon2id::new(64, 1, 1, b"argon2id salt")?;
+    let high_cost = crate::slow_hash::Argon2id::new(128, 2, 2, b"argon2id salt")?;
+    test_password_file_upgrade::<RistrettoSha5123dhArgon2>(&low_cost, &high_cost)
 }
 
 #[test]
-fn test_zeroize_server_login_start() -> Result<(), ProtocolError> {
+fn test_password_file_upgrade_tampered() -> Result<(), ProtocolError> {
     let mut client_rng = OsRng;
     let mut server_rng = OsRng;
     let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
@@ -899,57 +1159,2464 @@ fn test_zeroize_server_login_start() -> Result<(), ProtocolError> {
             &mut client_rng,
             STR_PASSWORD.as_bytes(),
         )?;
-    let server_registration_start_result =
-        ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &server_setup,
-            client_registration_start_result.message,
-            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
-        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut server_rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
     let client_registration_finish_result = client_registration_start_result.state.finish(
         &mut client_rng,
         server_registration_start_result.message,
         ClientRegistrationFinishParameters::default(),
     )?;
-    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
     let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
         &mut client_rng,
         STR_PASSWORD.as_bytes(),
     )?;
-    let server_login_start_result = ServerLogin::<RistrettoSha5123dhNoSlowHash>::start(
+    let server_login_start_result = ServerLogin::start(
         &mut server_rng,
         &server_setup,
-        Some(p_file),
+        Some(password_file),
         client_login_start_result.message,
         STR_CREDENTIAL_IDENTIFIER.as_bytes(),
         ServerLoginStartParameters::default(),
     )?;
+    let client_login_finish_result = client_login_start_result.state.finish_with_upgrade(
+        &mut client_rng,
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+        &NoOpHash,
+    )?;
 
-    let mut state = server_login_start_result.state;
-    let ptrs = state.as_byte_ptrs();
-    state.zeroize();
-
-    for (ptr, len) in ptrs {
-        let bytes = unsafe { from_raw_parts(ptr, len) };
-        assert!(bytes.iter().all(|&x| x == 0));
-    }
+    // Flip a bit of the new registration, keeping the original MAC
+    let mut upgrade_bytes = client_login_finish_result
+        .password_file_upgrade
+        .unwrap()
+        .serialize();
+    let last = upgrade_bytes.len() - 1;
+    upgrade_bytes[last] ^= 1;
+    let password_file_upgrade = PasswordFileUpgrade::deserialize(&upgrade_bytes)?;
+
+    assert!(match server_login_start_result.state.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::new(Some(password_file_upgrade)),
+    ) {
+        Err(ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError)) => true,
+        _ => false,
+    });
 
     Ok(())
 }
 
-#[test]
-fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
+// Logs in while exchanging application payloads, which are tampered with in
+// transit if requested, and returns the results of the client and the server
+#[allow(clippy::type_complexity)]
+fn payload_login<CS: CipherSuite>(
+    verifiable_oprf: bool,
+    server_payload: &[u8],
+    client_payload: &[u8],
+    encrypt_client_payload: bool,
+    tamper_server_payload: bool,
+    tamper_client_payload: bool,
+) -> Result<(ClientLoginFinishResult<CS>, ServerLoginFinishResult<CS>), ProtocolError> {
     let mut client_rng = OsRng;
     let mut server_rng = OsRng;
-    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
-    let client_registration_start_result =
-        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+    let mut server_setup = ServerSetup::<CS>::new(&mut server_rng);
+    server_setup.set_verifiable_oprf(verifiable_oprf);
+    let oprf_public_key = server_setup.oprf_public_key(
+        server_setup.oprf_key_id(),
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_start_result = if verifiable_oprf {
+        ClientRegistration::<CS>::start_verifiable(
             &mut client_rng,
             STR_PASSWORD.as_bytes(),
-        )?;
-    let server_registration_start_result =
-        ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
-            &server_setup,
-            client_registration_start_result.message,
+            &oprf_public_key,
+        )?
+    } else {
+        ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?
+    };
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        &mut server_rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    let client_login_start_result = if verifiable_oprf {
+        ClientLogin::<CS>::start_verifiable(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            &oprf_public_key,
+        )?
+    } else {
+        ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?
+    };
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters {
+            payload: Some(server_payload.to_vec()),
+            ..ServerLoginStartParameters::new(Some(b"context".to_vec()), None)
+        },
+    )?;
+
+    // The payloads are the last fields of the messages
+    let mut credential_response_bytes = server_login_start_result.message.serialize();
+    if tamper_server_payload {
+        let last = credential_response_bytes.len() - 1;
+        credential_response_bytes[last] ^= 1;
+    }
+    let client_login_finish_result = client_login_start_result.state.finish(
+        CredentialResponse::deserialize(&credential_response_bytes)?,
+        ClientLoginFinishParameters {
+            context: Some(b"context".to_vec()),
+            payload: Some(client_payload.to_vec()),
+            encrypt_payload: encrypt_client_payload,
+            ..ClientLoginFinishParameters::default()
+        },
+    )?;
+
+    let mut credential_finalization_bytes = client_login_finish_result.message.serialize();
+    // The payload of the client is only sent in plaintext if it is not
+    // encrypted
+    if !client_payload.is_empty() {
+        let contains_client_payload = credential_finalization_bytes
+            .windows(client_payload.len())
+            .any(|window| window == client_payload);
+        assert_eq!(contains_client_payload, !encrypt_client_payload);
+    }
+    if tamper_client_payload {
+        let last = credential_finalization_bytes.len() - 1;
+        credential_finalization_bytes[last] ^= 1;
+    }
+    let server_login_finish_result = server_login_start_result.state.finish(
+        CredentialFinalization::deserialize(&credential_finalization_bytes)?,
+        ServerLoginFinishParameters::default(),
+    )?;
+
+    Ok((client_login_finish_result, server_login_finish_result))
+}
+
+#[test]
+fn test_login_payloads() -> Result<(), ProtocolError> {
+    for &verifiable_oprf in &[false, true] {
+        for &encrypt_client_payload in &[false, true] {
+            let (client_login_finish_result, server_login_finish_result) =
+                payload_login::<RistrettoSha5123dhNoSlowHash>(
+                    verifiable_oprf,
+                    b"server policy",
+                    b"client device",
+                    encrypt_client_payload,
+                    false,
+                    false,
+                )?;
+            assert_eq!(client_login_finish_result.server_payload, b"server policy");
+            assert_eq!(server_login_finish_result.client_payload, b"client device");
+            assert_eq!(
+                client_login_finish_result.session_key,
+                server_login_finish_result.session_key
+            );
+            assert_eq!(
+                client_login_finish_result.transcript_hash,
+                server_login_finish_result.transcript_hash
+            );
+        }
+    }
+
+    // An encrypted payload longer than a single expansion of the pad
+    let long_payload = vec![0x5a; 20000];
+    let (_, server_login_finish_result) = payload_login::<RistrettoSha5123dhNoSlowHash>(
+        false,
+        b"",
+        &long_payload,
+        true,
+        false,
+        false,
+    )?;
+    assert_eq!(server_login_finish_result.client_payload, long_payload);
+
+    // Only one of the parties sends a payload
+    let (client_login_finish_result, server_login_finish_result) =
+        payload_login::<RistrettoSha512HmqvNoSlowHash>(
+            false,
+            b"",
+            b"client device",
+            true,
+            false,
+            false,
+        )?;
+    assert!(client_login_finish_result.server_payload.is_empty());
+    assert_eq!(server_login_finish_result.client_payload, b"client device");
+
+    Ok(())
+}
+
+#[test]
+fn test_session_keys_export() -> Result<(), ProtocolError> {
+    let (client_login_finish_result, server_login_finish_result) =
+        payload_login::<RistrettoSha5123dhNoSlowHash>(false, b"", b"", false, false, false)?;
+    let client_session_keys = client_login_finish_result.session_keys();
+    let server_session_keys = server_login_finish_result.session_keys();
+    assert_eq!(
+        client_session_keys.session_key(),
+        &server_login_finish_result.session_key[..]
+    );
+
+    for &(label, length) in &[(&b"encryption"[..], 32), (b"mac", 64), (b"resumption", 100)] {
+        let client_key = client_session_keys.export(label, b"context", length)?;
+        assert_eq!(client_key.len(), length);
+        assert_eq!(
+            client_key,
+            server_session_keys.export(label, b"context", length)?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_login_payloads_tampered() {
+    assert!(match payload_login::<RistrettoSha5123dhNoSlowHash>(
+        false,
+        b"server policy",
+        b"client device",
+        false,
+        true,
+        false,
+    ) {
+        Err(ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError)) => true,
+        _ => false,
+    });
+    // The payload of the client is covered by the third key exchange message,
+    // whether it is encrypted or not
+    for &encrypt_client_payload in &[false, true] {
+        assert!(match payload_login::<RistrettoSha5123dhNoSlowHash>(
+            true,
+            b"server policy",
+            b"client device",
+            encrypt_client_payload,
+            false,
+            true,
+        ) {
+            Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+            _ => false,
+        });
+    }
+}
+
+#[test]
+fn test_server_login_finish_metadata() -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut server_rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+    )?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let server_login = ServerLogin::<RistrettoSha5123dhNoSlowHash>::deserialize(
+        &server_login_start_result.state.serialize(),
+    )?;
+
+    let client_login_finish_result = client_login_start_result.state.finish(
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::default(),
+    )?;
+
+    assert_eq!(
+        client_login_finish_result.transcript_hash,
+        server_login_finish_result.transcript_hash
+    );
+    // The identities default to the static public keys
+    assert_eq!(
+        server_login_finish_result.id_u,
+        server_login_finish_result.client_s_pk.to_vec()
+    );
+    assert_eq!(
+        server_login_finish_result.id_s,
+        server_setup.keypair().public().to_vec()
+    );
+
+    Ok(())
+}
+
+// Logs in with the given channel bindings on the server and client sides,
+// along with a context and application payloads
+fn channel_binding_login(
+    server_channel_binding: &[u8],
+    client_channel_binding: &[u8],
+) -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut server_rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+    )?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters {
+            payload: Some(b"server policy".to_vec()),
+            channel_binding: Some(server_channel_binding.to_vec()),
+            ..ServerLoginStartParameters::new(Some(b"context".to_vec()), None)
+        },
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        server_login_start_result.message,
+        ClientLoginFinishParameters {
+            context: Some(b"context".to_vec()),
+            payload: Some(b"client device".to_vec()),
+            channel_binding: Some(client_channel_binding.to_vec()),
+            ..ClientLoginFinishParameters::default()
+        },
+    )?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::default(),
+    )?;
+    assert_eq!(
+        hex::encode(&server_login_finish_result.session_key),
+        hex::encode(&client_login_finish_result.session_key)
+    );
+    assert_eq!(client_login_finish_result.server_payload, b"server policy");
+    assert_eq!(server_login_finish_result.client_payload, b"client device");
+
+    Ok(())
+}
+
+#[test]
+fn test_channel_binding() -> Result<(), ProtocolError> {
+    channel_binding_login(b"tls exporter", b"tls exporter")?;
+
+    for (server_channel_binding, client_channel_binding) in &[
+        (&b"tls exporter"[..], &b"other exporter"[..]),
+        (&b"tls exporter"[..], &b""[..]),
+        (&b""[..], &b"tls exporter"[..]),
+    ] {
+        assert!(
+            match channel_binding_login(server_channel_binding, client_channel_binding) {
+                Err(ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError)) =>
+                    true,
+                _ => false,
+            }
+        );
+    }
+
+    Ok(())
+}
+
+// Logs in with the given password file, or with a new registration if none is
+// given, and returns the password file along with the server's static public
+// key as seen by the client
+fn register_and_login(
+    server_setup: &ServerSetup<RistrettoSha5123dhNoSlowHash>,
+    password_file: Option<ServerRegistration<RistrettoSha5123dhNoSlowHash>>,
+) -> Result<(ServerRegistration<RistrettoSha5123dhNoSlowHash>, Vec<u8>), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let password_file = match password_file {
+        Some(password_file) => password_file,
+        None => {
+            let client_registration_start_result = ClientRegistration::<
+                RistrettoSha5123dhNoSlowHash,
+            >::start(
+                &mut client_rng, STR_PASSWORD.as_bytes()
+            )?;
+            let server_registration_start_result = ServerRegistration::start(
+                &mut server_rng,
+                server_setup,
+                client_registration_start_result.message,
+                STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            )?;
+            // The key IDs can be stored between the two steps
+            let key_ids = RegistrationKeyIds::deserialize(
+                &server_registration_start_result.key_ids.serialize(),
+            )?;
+            let client_registration_finish_result = client_registration_start_result.state.finish(
+                &mut client_rng,
+                server_registration_start_result.message,
+                ClientRegistrationFinishParameters::default(),
+            )?;
+            ServerRegistration::finish(client_registration_finish_result.message, key_ids)?
+        }
+    };
+
+    let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+    )?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        server_setup,
+        Some(password_file.clone()),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::default(),
+    )?;
+    assert_eq!(
+        hex::encode(&server_login_finish_result.session_key),
+        hex::encode(&client_login_finish_result.session_key)
+    );
+
+    Ok((
+        password_file,
+        client_login_finish_result.server_s_pk.to_vec(),
+    ))
+}
+
+#[test]
+fn test_oprf_seed_rotation() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    let (old_password_file, _) = register_and_login(&server_setup, None)?;
+    assert_eq!(old_password_file.oprf_key_id(), 0);
+
+    // After a rotation, new registrations use the new seed while existing
+    // password files can still be used to log in
+    let new_key_id = server_setup.rotate_oprf_seed(&mut rng);
+    assert_eq!(new_key_id, 1);
+    let server_setup =
+        ServerSetup::<RistrettoSha5123dhNoSlowHash>::deserialize(&server_setup.serialize())?;
+    assert_eq!(server_setup.oprf_key_id(), new_key_id);
+    assert_eq!(server_setup.previous_oprf_key_ids(), vec![0]);
+
+    let (new_password_file, _) = register_and_login(&server_setup, None)?;
+    assert_eq!(new_password_file.oprf_key_id(), new_key_id);
+    register_and_login(&server_setup, Some(old_password_file.clone()))?;
+
+    // Once the previous seed is removed, only the new password file remains
+    // usable
+    let mut server_setup = server_setup;
+    server_setup.remove_oprf_seed(0)?;
+    register_and_login(&server_setup, Some(new_password_file))?;
+    assert!(
+        match register_and_login(&server_setup, Some(old_password_file)) {
+            Err(ProtocolError::UnknownOprfKeyIdError(0)) => true,
+            _ => false,
+        }
+    );
+
+    // The current seed cannot be removed
+    assert!(server_setup.remove_oprf_seed(new_key_id).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_server_keypair_rotation() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    let old_server_s_pk = server_setup.keypair().public().to_vec();
+    let (old_password_file, server_s_pk) = register_and_login(&server_setup, None)?;
+    assert_eq!(old_password_file.server_key_id(), 0);
+    assert_eq!(server_s_pk, old_server_s_pk);
+
+    // During the overlap window, each password file is used with the keypair
+    // it was registered with, which the client can observe
+    let new_key_id = server_setup.rotate_keypair(&mut rng);
+    assert_eq!(new_key_id, 1);
+    let server_setup =
+        ServerSetup::<RistrettoSha5123dhNoSlowHash>::deserialize(&server_setup.serialize())?;
+    assert_eq!(server_setup.server_key_id(), new_key_id);
+    assert_eq!(server_setup.previous_server_key_ids(), vec![0]);
+    let new_server_s_pk = server_setup.keypair().public().to_vec();
+    assert_ne!(new_server_s_pk, old_server_s_pk);
+
+    let (new_password_file, server_s_pk) = register_and_login(&server_setup, None)?;
+    assert_eq!(new_password_file.server_key_id(), new_key_id);
+    assert_eq!(server_s_pk, new_server_s_pk);
+    let (_, server_s_pk) = register_and_login(&server_setup, Some(old_password_file.clone()))?;
+    assert_eq!(server_s_pk, old_server_s_pk);
+
+    // Closing the window makes the password files registered under the
+    // previous keypair unusable
+    let mut server_setup = server_setup;
+    server_setup.remove_keypair(0)?;
+    register_and_login(&server_setup, Some(new_password_file))?;
+    assert!(
+        match register_and_login(&server_setup, Some(old_password_file)) {
+            Err(ProtocolError::UnknownServerKeyIdError(0)) => true,
+            _ => false,
+        }
+    );
+    assert!(server_setup.remove_keypair(new_key_id).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_server_setup_versions() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    let (password_file, _) = register_and_login(&server_setup, None)?;
+    let bytes = server_setup.serialize();
+
+    // A server setup serialized without a version, which only holds the OPRF
+    // seed and the keypairs, uses the key ID 0 for both
+    let setup_len = <sha2::Sha512 as Digest>::OutputSize::to_usize()
+        + 2 * <RistrettoPoint as Group>::ScalarLen::to_usize();
+    let unversioned_server_setup =
+        ServerSetup::<RistrettoSha5123dhNoSlowHash>::deserialize(&bytes[1..1 + setup_len])?;
+    assert_eq!(unversioned_server_setup.oprf_key_id(), 0);
+    assert_eq!(unversioned_server_setup.server_key_id(), 0);
+    register_and_login(&unversioned_server_setup, Some(password_file))?;
+
+    // Unknown versions and truncated server setups are rejected
+    let mut unknown_version_bytes = bytes.clone();
+    unknown_version_bytes[0] += 1;
+    assert!(
+        ServerSetup::<RistrettoSha5123dhNoSlowHash>::deserialize(&unknown_version_bytes).is_err()
+    );
+    assert!(
+        ServerSetup::<RistrettoSha5123dhNoSlowHash>::deserialize(&bytes[..bytes.len() - 1])
+            .is_err()
+    );
+
+    Ok(())
+}
+
+// Runs a login against a server whose OPRF evaluation is replaced by the
+// partial evaluations of the given key shares
+fn threshold_login(
+    server_setup: &ServerSetup<RistrettoSha5123dhNoSlowHash>,
+    password_file: &ServerRegistration<RistrettoSha5123dhNoSlowHash>,
+    oprf_key_share: &threshold::OprfKeyShare<RistrettoSha5123dhNoSlowHash>,
+    other_shares: &[&threshold::OprfKeyShare<RistrettoSha5123dhNoSlowHash>],
+    public_shares: &[threshold::PublicShare<RistrettoSha5123dhNoSlowHash>],
+) -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+    )?;
+    let partial_evaluations = other_shares
+        .iter()
+        .map(|share| {
+            share.evaluate_credential_request(&mut server_rng, &client_login_start_result.message)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let server_login_start_result = ServerLogin::start_threshold(
+        &mut server_rng,
+        server_setup,
+        Some(password_file.clone()),
+        client_login_start_result.message,
+        oprf_key_share,
+        &partial_evaluations,
+        public_shares,
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::default(),
+    )?;
+    assert_eq!(
+        hex::encode(&server_login_finish_result.session_key),
+        hex::encode(&client_login_finish_result.session_key)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_threshold_oprf() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    let shares = threshold::OprfKeyShare::<RistrettoSha5123dhNoSlowHash>::generate(&mut rng, 3, 5)?;
+    let shares: Vec<_> = shares
+        .iter()
+        .map(|share| threshold::OprfKeyShare::deserialize(&share.serialize()))
+        .collect::<Result<_, _>>()?;
+    let public_shares: Vec<_> = shares.iter().map(|share| share.public_share()).collect();
+
+    // Registration by the holder of the first share, with the next two share
+    // holders
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let partial_evaluations = shares[1..3]
+        .iter()
+        .map(|share| {
+            threshold::PartialEvaluation::deserialize(
+                &share
+                    .evaluate_registration_request(
+                        &mut rng,
+                        &client_registration_start_result.message,
+                    )?
+                    .serialize(),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let server_registration_start_result = ServerRegistration::start_threshold(
+        &server_setup,
+        client_registration_start_result.message,
+        &shares[0],
+        &partial_evaluations,
+        &public_shares,
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    // Any subset of at least three share holders can be used to log in
+    threshold_login(
+        &server_setup,
+        &password_file,
+        &shares[0],
+        &[&shares[1], &shares[2]],
+        &public_shares,
+    )?;
+    threshold_login(
+        &server_setup,
+        &password_file,
+        &shares[0],
+        &[&shares[4], &shares[3]],
+        &public_shares,
+    )?;
+    threshold_login(
+        &server_setup,
+        &password_file,
+        &shares[3],
+        &[&shares[4], &shares[1]],
+        &public_shares,
+    )?;
+    threshold_login(
+        &server_setup,
+        &password_file,
+        &shares[0],
+        &shares[1..].iter().collect::<Vec<_>>(),
+        &public_shares,
+    )?;
+
+    // Too few share holders, or the OPRF key derived from the server's seed,
+    // do not suffice, and a share cannot be counted twice
+    assert!(match threshold_login(
+        &server_setup,
+        &password_file,
+        &shares[0],
+        &[&shares[4]],
+        &public_shares
+    ) {
+        Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+        _ => false,
+    });
+    assert!(
+        match register_and_login(&server_setup, Some(password_file.clone())) {
+            Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+            _ => false,
+        }
+    );
+    assert!(threshold_login(
+        &server_setup,
+        &password_file,
+        &shares[0],
+        &[&shares[0], &shares[1], &shares[2]],
+        &public_shares
+    )
+    .is_err());
+
+    // A partial evaluation with a share of another key is rejected before
+    // being combined, as is the threshold mode in the verifiable mode
+    let other_shares =
+        threshold::OprfKeyShare::<RistrettoSha5123dhNoSlowHash>::generate(&mut rng, 3, 5)?;
+    assert!(match threshold_login(
+        &server_setup,
+        &password_file,
+        &shares[0],
+        &[&shares[1], &other_shares[2]],
+        &public_shares
+    ) {
+        Err(ProtocolError::InvalidPartialEvaluationError(3)) => true,
+        _ => false,
+    });
+    let mut verifiable_server_setup = server_setup.clone();
+    verifiable_server_setup.set_verifiable_oprf(true);
+    assert!(match threshold_login(
+        &verifiable_server_setup,
+        &password_file,
+        &shares[0],
+        &[&shares[1], &shares[2]],
+        &public_shares
+    ) {
+        Err(ProtocolError::VerifiableThresholdError) => true,
+        _ => false,
+    });
+
+    Ok(())
+}
+
+// Runs a login, in the verifiable mode of the OPRF if the client pins the given
+// public OPRF key
+fn verifiable_oprf_login(
+    server_setup: &ServerSetup<RistrettoSha5123dhNoSlowHash>,
+    password_file: &ServerRegistration<RistrettoSha5123dhNoSlowHash>,
+    oprf_public_key: Option<&[u8]>,
+    tamper: bool,
+) -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let client_login_start_result = match oprf_public_key {
+        Some(oprf_public_key) => ClientLogin::<RistrettoSha5123dhNoSlowHash>::start_verifiable(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+            oprf_public_key,
+        )?,
+        None => ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+        )?,
+    };
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        server_setup,
+        Some(password_file.clone()),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let mut credential_response_bytes = server_login_start_result.message.serialize();
+    if tamper {
+        // Flips a bit of the challenge of the proof, near the end of the
+        // message
+        let last = credential_response_bytes.len() - 33;
+        credential_response_bytes[last] ^= 1;
+    }
+    // The pinned key is kept in the serialized state of the client
+    let client_login_finish_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::deserialize(
+        &client_login_start_result.state.serialize(),
+    )?
+    .finish(
+        CredentialResponse::deserialize(&credential_response_bytes)?,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::default(),
+    )?;
+    assert_eq!(
+        hex::encode(&server_login_finish_result.session_key),
+        hex::encode(&client_login_finish_result.session_key)
+    );
+    Ok(())
+}
+
+fn is_invalid_oprf_proof<T>(result: Result<T, ProtocolError>) -> bool {
+    match result {
+        Err(ProtocolError::VerificationError(PakeError::InvalidOprfProofError)) => true,
+        _ => false,
+    }
+}
+
+#[test]
+fn test_verifiable_oprf() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    let (base_password_file, _) = register_and_login(&server_setup, None)?;
+    verifiable_oprf_login(&server_setup, &base_password_file, None, false)?;
+
+    server_setup.set_verifiable_oprf(true);
+    let mut server_setup =
+        ServerSetup::<RistrettoSha5123dhNoSlowHash>::deserialize(&server_setup.serialize())?;
+    assert!(server_setup.verifiable_oprf());
+    let oprf_public_key = server_setup
+        .oprf_public_key(
+            server_setup.oprf_key_id(),
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?
+        .to_vec();
+
+    // A proof is useless without a key pinned by the client, which rejects it
+    assert!(is_invalid_oprf_proof(verifiable_oprf_login(
+        &server_setup,
+        &base_password_file,
+        None,
+        false
+    )));
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    assert!(is_invalid_oprf_proof(
+        client_registration_start_result.state.finish(
+            &mut rng,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters::default(),
+        )
+    ));
+
+    // The proof is verified during registration
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start_verifiable(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            &oprf_public_key,
+        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::deserialize(
+            &client_registration_start_result.state.serialize(),
+        )?
+        .finish(
+            &mut rng,
+            RegistrationResponse::deserialize(
+                &server_registration_start_result.message.serialize(),
+            )?,
+            ClientRegistrationFinishParameters::default(),
+        )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    // And during login
+    verifiable_oprf_login(&server_setup, &password_file, Some(&oprf_public_key), false)?;
+    let other_public_key = RistrettoPoint::base_point().to_arr().to_vec();
+    assert!(is_invalid_oprf_proof(verifiable_oprf_login(
+        &server_setup,
+        &password_file,
+        Some(&other_public_key),
+        false
+    )));
+    assert!(is_invalid_oprf_proof(verifiable_oprf_login(
+        &server_setup,
+        &password_file,
+        Some(&oprf_public_key),
+        true
+    )));
+    assert!(
+        ClientLogin::<RistrettoSha5123dhNoSlowHash>::start_verifiable(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+            &[]
+        )
+        .is_err()
+    );
+
+    // The output of the OPRF depends on its mode, so that a password file
+    // registered in the base mode cannot be used in the verifiable mode
+    assert!(match verifiable_oprf_login(
+        &server_setup,
+        &base_password_file,
+        Some(&oprf_public_key),
+        false
+    ) {
+        Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+        _ => false,
+    });
+
+    // The public OPRF key of a password file is looked up by its key ID
+    server_setup.rotate_oprf_seed(&mut rng);
+    assert_eq!(
+        server_setup
+            .oprf_public_key(
+                password_file.oprf_key_id(),
+                STR_CREDENTIAL_IDENTIFIER.as_bytes()
+            )?
+            .to_vec(),
+        oprf_public_key
+    );
+    assert_ne!(
+        server_setup
+            .oprf_public_key(
+                server_setup.oprf_key_id(),
+                STR_CREDENTIAL_IDENTIFIER.as_bytes()
+            )?
+            .to_vec(),
+        oprf_public_key
+    );
+    verifiable_oprf_login(&server_setup, &password_file, Some(&oprf_public_key), false)?;
+
+    // A client pinning a key rejects a server in the base mode
+    server_setup.set_verifiable_oprf(false);
+    assert!(is_invalid_oprf_proof(verifiable_oprf_login(
+        &server_setup,
+        &password_file,
+        Some(&oprf_public_key),
+        false
+    )));
+
+    Ok(())
+}
+
+fn envelope_login<CS: CipherSuite>(
+    server_setup: &ServerSetup<CS>,
+    password_file: &ServerRegistration<CS>,
+    password: &[u8],
+) -> Result<(ClientLoginFinishResult<CS>, ServerLoginFinishResult<CS>), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let client_login_start_result = ClientLogin::<CS>::start(&mut client_rng, password)?;
+    let server_login_start_result = ServerLogin::start(
+        &mut server_rng,
+        server_setup,
+        Some(password_file.clone()),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        CredentialResponse::deserialize_with_layout(
+            &server_login_start_result.message.serialize(),
+            &server_setup.envelope_layout(),
+        )?,
+        ClientLoginFinishParameters::default(),
+    )?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        client_login_finish_result.message.clone(),
+        ServerLoginFinishParameters::default(),
+    )?;
+    Ok((client_login_finish_result, server_login_finish_result))
+}
+
+fn envelope_registration<CS: CipherSuite>(
+    server_setup: &ServerSetup<CS>,
+    client_static_keypair: Option<&KeyPair<CS::Group>>,
+    credentials: Option<&[u8]>,
+) -> Result<ServerRegistration<CS>, ProtocolError> {
+    let client_registration_start_result =
+        ClientRegistration::<CS>::start(&mut OsRng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut OsRng,
+        server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut OsRng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters {
+            client_static_keypair,
+            credentials,
+            ..ClientRegistrationFinishParameters::default()
+        },
+    )?;
+    ServerRegistration::finish(
+        client_registration_finish_result.message,
+        RegistrationKeyIds::deserialize(&server_registration_start_result.key_ids.serialize())?,
+    )
+}
+
+fn is_envelope_layout_mismatch<T>(result: Result<T, ProtocolError>) -> bool {
+    match result {
+        Err(ProtocolError::EnvelopeLayoutMismatchError) => true,
+        _ => false,
+    }
+}
+
+fn credential_response_len<CS: CipherSuite>(
+    server_setup: &ServerSetup<CS>,
+    password_file: Option<ServerRegistration<CS>>,
+) -> Result<usize, ProtocolError> {
+    let client_login_start_result = ClientLogin::<CS>::start(&mut OsRng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut OsRng,
+        server_setup,
+        password_file,
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    Ok(server_login_start_result.message.serialize().len())
+}
+
+fn test_external_envelope_mode<CS: CipherSuite>() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let mut server_setup = ServerSetup::<CS>::new(&mut rng);
+    server_setup.set_external_envelope_mode(true);
+    let mut server_setup = ServerSetup::<CS>::deserialize(&server_setup.serialize())?;
+    assert!(server_setup.external_envelope_mode());
+    let client_static_keypair = KeyPair::<CS::Group>::generate_random(&mut rng);
+
+    let client_registration_start_result =
+        ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters {
+            client_static_keypair: Some(&client_static_keypair),
+            ..ClientRegistrationFinishParameters::default()
+        },
+    )?;
+    let password_file = ServerRegistration::finish(
+        RegistrationUpload::<CS>::deserialize(
+            &client_registration_finish_result.message.serialize(),
+        )?,
+        server_registration_start_result.key_ids,
+    )?;
+    let password_file = ServerRegistration::<CS>::deserialize(&password_file.serialize())?;
+
+    // The client recovers the keypair it registered with
+    let (client_login_finish_result, server_login_finish_result) =
+        envelope_login(&server_setup, &password_file, STR_PASSWORD.as_bytes())?;
+    assert_eq!(
+        client_login_finish_result.session_key,
+        server_login_finish_result.session_key
+    );
+    assert_eq!(
+        client_login_finish_result.export_key,
+        client_registration_finish_result.export_key
+    );
+    let expected_client_s_pk =
+        KeyPair::<CS::Group>::from_private_key_slice_static::<CS>(client_static_keypair.private())?
+            .public()
+            .to_vec();
+    assert_eq!(
+        server_login_finish_result.client_s_pk.to_vec(),
+        expected_client_s_pk
+    );
+
+    assert!(
+        match envelope_login(&server_setup, &password_file, b"wrong password") {
+            Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+            _ => false,
+        }
+    );
+
+    // A client in the internal mode logs in to the same server
+    let client_registration_start_result =
+        ClientRegistration::<CS>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let internal_password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+    let (client_login_finish_result, server_login_finish_result) = envelope_login(
+        &server_setup,
+        &internal_password_file,
+        STR_PASSWORD.as_bytes(),
+    )?;
+    assert_eq!(
+        client_login_finish_result.session_key,
+        server_login_finish_result.session_key
+    );
+
+    // The responses for clients in either mode and for an unregistered client
+    // have the same length
+    let registered_len = credential_response_len(&server_setup, Some(password_file.clone()))?;
+    assert_eq!(
+        credential_response_len(&server_setup, Some(internal_password_file))?,
+        registered_len
+    );
+    assert_eq!(
+        credential_response_len(&server_setup, None)?,
+        registered_len
+    );
+
+    // A server which is not in the external envelope mode neither accepts
+    // nor answers for a password file in this mode
+    server_setup.set_external_envelope_mode(false);
+    assert!(is_envelope_layout_mismatch(envelope_registration(
+        &server_setup,
+        Some(&client_static_keypair),
+        None
+    )));
+    assert!(credential_response_len(&server_setup, Some(password_file)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_ristretto_external_envelope_mode() -> Result<(), ProtocolError> {
+    test_external_envelope_mode::<RistrettoSha5123dhNoSlowHash>()
+}
+
+#[cfg(feature = "sigma")]
+#[test]
+fn test_sigma_external_envelope_mode() -> Result<(), ProtocolError> {
+    test_external_envelope_mode::<RistrettoSha512SigmaNoSlowHash>()
+}
+
+#[test]
+fn test_envelope_credentials() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let client_static_keypair = KeyPair::<RistrettoPoint>::generate_random(&mut rng);
+    let credentials = b"wrapped vault key";
+
+    for &external_mode in &[false, true] {
+        let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+        server_setup.set_external_envelope_mode(external_mode);
+        server_setup.set_envelope_credentials_len(credentials.len())?;
+        let server_setup =
+            ServerSetup::<RistrettoSha5123dhNoSlowHash>::deserialize(&server_setup.serialize())?;
+        assert_eq!(server_setup.envelope_credentials_len(), credentials.len());
+        let client_registration_start_result =
+            ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+                &mut rng,
+                STR_PASSWORD.as_bytes(),
+            )?;
+        let server_registration_start_result = ServerRegistration::start(
+            &mut rng,
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters {
+                client_static_keypair: if external_mode {
+                    Some(&client_static_keypair)
+                } else {
+                    None
+                },
+                credentials: Some(credentials),
+                ..ClientRegistrationFinishParameters::default()
+            },
+        )?;
+        let password_file = ServerRegistration::finish(
+            RegistrationUpload::<RistrettoSha5123dhNoSlowHash>::deserialize(
+                &client_registration_finish_result.message.serialize(),
+            )?,
+            server_registration_start_result.key_ids,
+        )?;
+        let password_file = ServerRegistration::<RistrettoSha5123dhNoSlowHash>::deserialize(
+            &password_file.serialize(),
+        )?;
+
+        let (client_login_finish_result, server_login_finish_result) =
+            envelope_login(&server_setup, &password_file, STR_PASSWORD.as_bytes())?;
+        assert_eq!(&client_login_finish_result.credentials[..], credentials);
+        assert_eq!(
+            client_login_finish_result.session_key,
+            server_login_finish_result.session_key
+        );
+        assert!(
+            match envelope_login(&server_setup, &password_file, b"wrong password") {
+                Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+                _ => false,
+            }
+        );
+
+        // The responses for a registered and an unregistered client have the
+        // same length
+        assert_eq!(
+            credential_response_len(&server_setup, None)?,
+            credential_response_len(&server_setup, Some(password_file.clone()))?
+        );
+
+        // Uploads carrying credentials of another length, or none, are
+        // rejected
+        let keypair = if external_mode {
+            Some(&client_static_keypair)
+        } else {
+            None
+        };
+        assert!(is_envelope_layout_mismatch(envelope_registration(
+            &server_setup,
+            keypair,
+            Some(b"wrapped vault key!")
+        )));
+        assert!(is_envelope_layout_mismatch(envelope_registration(
+            &server_setup,
+            keypair,
+            None
+        )));
+
+        // A server which does not expect credentials cannot answer for a
+        // password file carrying them
+        let mut server_setup = server_setup;
+        server_setup.set_envelope_credentials_len(0)?;
+        assert!(credential_response_len(&server_setup, Some(password_file)).is_err());
+    }
+
+    // Credentials too long for the masked response are rejected
+    let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    assert!(server_setup.set_envelope_credentials_len(0x4000).is_err());
+
+    Ok(())
+}
+
+#[allow(clippy::type_complexity)]
+fn account_login(
+    server_setup: &ServerSetup<RistrettoSha5123dhNoSlowHash>,
+    account: &ServerAccount<RistrettoSha5123dhNoSlowHash>,
+    alias: &[u8],
+    password: &[u8],
+) -> Result<
+    (
+        ServerLoginStartResult<RistrettoSha5123dhNoSlowHash>,
+        Result<ClientLoginFinishResult<RistrettoSha5123dhNoSlowHash>, ProtocolError>,
+    ),
+    ProtocolError,
+> {
+    let client_login_start_result =
+        ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(&mut OsRng, password)?;
+    let server_login_start_result = ServerLogin::start_for_account(
+        &mut OsRng,
+        server_setup,
+        Some(account),
+        alias,
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_login_finish_result = client_login_start_result.state.finish(
+        CredentialResponse::deserialize_with_layout(
+            &server_login_start_result.message.serialize(),
+            &server_setup.envelope_layout(),
+        )?,
+        ClientLoginFinishParameters::default(),
+    );
+    Ok((server_login_start_result, client_login_finish_result))
+}
+
+#[test]
+fn test_password_aliases() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    server_setup.set_external_envelope_mode(true);
+    let client_static_keypair = KeyPair::<RistrettoPoint>::generate_random(&mut rng);
+    let aliases: [(&[u8], &[u8]); 2] = [
+        (b"primary", STR_PASSWORD.as_bytes()),
+        (b"break-glass", b"correct horse battery staple"),
+    ];
+
+    let mut account = ServerAccount::<RistrettoSha5123dhNoSlowHash>::new();
+    for &(alias, password) in &aliases {
+        let credential_identifier =
+            credential_identifier(STR_CREDENTIAL_IDENTIFIER.as_bytes(), alias)?;
+        let client_registration_start_result =
+            ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(&mut rng, password)?;
+        let server_registration_start_result = ServerRegistration::start(
+            &mut rng,
+            &server_setup,
+            client_registration_start_result.message,
+            &credential_identifier,
+        )?;
+        let client_registration_finish_result = client_registration_start_result.state.finish(
+            &mut rng,
+            server_registration_start_result.message,
+            ClientRegistrationFinishParameters {
+                client_static_keypair: Some(&client_static_keypair),
+                ..ClientRegistrationFinishParameters::default()
+            },
+        )?;
+        account.insert(
+            alias,
+            ServerRegistration::finish(
+                RegistrationUpload::<RistrettoSha5123dhNoSlowHash>::deserialize(
+                    &client_registration_finish_result.message.serialize(),
+                )?,
+                server_registration_start_result.key_ids,
+            )?,
+        )?;
+    }
+    let account = ServerAccount::<RistrettoSha5123dhNoSlowHash>::deserialize(&account.serialize())?;
+    assert_eq!(account.len(), aliases.len());
+
+    // Each alias opens the same client static keypair under its own password
+    let mut registered_len = 0;
+    for &(alias, password) in &aliases {
+        let (server_login_start_result, client_login_finish_result) =
+            account_login(&server_setup, &account, alias, password)?;
+        registered_len = server_login_start_result.message.serialize().len();
+        let server_login_finish_result = server_login_start_result.state.finish(
+            client_login_finish_result?.message,
+            ServerLoginFinishParameters::default(),
+        )?;
+        assert_eq!(
+            server_login_finish_result.client_s_pk,
+            *client_static_keypair.public()
+        );
+    }
+
+    // The password of one alias does not open another
+    let (_, client_login_finish_result) =
+        account_login(&server_setup, &account, b"primary", aliases[1].1)?;
+    assert!(match client_login_finish_result {
+        Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+        _ => false,
+    });
+
+    // An alias which the account does not hold gets a dummy response of the
+    // same length
+    let (server_login_start_result, client_login_finish_result) =
+        account_login(&server_setup, &account, b"spare", STR_PASSWORD.as_bytes())?;
+    assert_eq!(
+        server_login_start_result.message.serialize().len(),
+        registered_len
+    );
+    assert!(client_login_finish_result.is_err());
+
+    // A credential registered with another client static keypair is rejected
+    let mut account = account;
+    assert!(
+        match account.insert(b"spare", envelope_registration(&server_setup, None, None)?) {
+            Err(ProtocolError::VerificationError(
+                PakeError::IncompatibleClientStaticPublicKeyError,
+            )) => true,
+            _ => false,
+        }
+    );
+    assert_eq!(account.len(), aliases.len());
+
+    Ok(())
+}
+
+// Logs in with the current password while starting a change to the new one
+fn password_change_login<CS: CipherSuite>(
+    server_setup: &ServerSetup<CS>,
+    password_file: &ServerRegistration<CS>,
+    password: &[u8],
+    new_password: &[u8],
+) -> Result<
+    (
+        ClientPasswordChangeStartResult<CS>,
+        ServerLoginFinishResult<CS>,
+    ),
+    ProtocolError,
+> {
+    let client_login_start_result = ClientLogin::<CS>::start(&mut OsRng, password)?;
+    let server_login_start_result = ServerLogin::start(
+        &mut OsRng,
+        server_setup,
+        Some(password_file.clone()),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    let client_password_change_start_result = ClientPasswordChange::start(
+        &mut OsRng,
+        client_login_start_result.state,
+        CredentialResponse::deserialize_with_layout(
+            &server_login_start_result.message.serialize(),
+            &server_setup.envelope_layout(),
+        )?,
+        ClientLoginFinishParameters::default(),
+        new_password,
+    )?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        client_password_change_start_result
+            .login_result
+            .message
+            .clone(),
+        ServerLoginFinishParameters::default(),
+    )?;
+    Ok((
+        client_password_change_start_result,
+        server_login_finish_result,
+    ))
+}
+
+#[test]
+fn test_password_change() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let new_password = b"correct horse battery staple";
+    let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    server_setup.set_external_envelope_mode(true);
+    server_setup.set_envelope_credentials_len(b"recovery code".len())?;
+    let client_static_keypair = KeyPair::<RistrettoPoint>::generate_random(&mut rng);
+    let password_file = envelope_registration(
+        &server_setup,
+        Some(&client_static_keypair),
+        Some(b"old recovery!"),
+    )?;
+
+    // The new password is registered under the OPRF seed of the existing
+    // password file, even once it has been rotated
+    server_setup.rotate_oprf_seed(&mut rng);
+    let (client_password_change_start_result, server_login_finish_result) = password_change_login(
+        &server_setup,
+        &password_file,
+        STR_PASSWORD.as_bytes(),
+        new_password,
+    )?;
+    let client_login_finish_result = client_password_change_start_result.login_result;
+    let server_registration_start_result = ServerRegistration::start_password_change(
+        &mut rng,
+        &server_setup,
+        RegistrationRequest::deserialize(&client_password_change_start_result.message.serialize())?,
+        &server_login_finish_result,
+    )?;
+    let client_password_change_finish_result = client_password_change_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters {
+            credentials: Some(b"recovery code"),
+            ..ClientRegistrationFinishParameters::default()
+        },
+    )?;
+    assert_eq!(
+        client_password_change_finish_result.old_export_key,
+        client_login_finish_result.export_key
+    );
+    let password_change_bytes = client_password_change_finish_result.message.serialize();
+    let key_ids =
+        RegistrationKeyIds::deserialize(&server_registration_start_result.key_ids.serialize())?;
+    let new_password_file = ServerRegistration::finish_password_change(
+        PasswordFileUpgrade::deserialize(&password_change_bytes)?,
+        &server_login_finish_result,
+        key_ids.clone(),
+    )?;
+    assert_eq!(new_password_file.oprf_key_id(), password_file.oprf_key_id());
+
+    // The new password opens the same static keypair, under the new export
+    // key
+    let (new_client_login_finish_result, new_server_login_finish_result) =
+        envelope_login(&server_setup, &new_password_file, new_password)?;
+    assert_eq!(
+        new_client_login_finish_result.export_key,
+        client_password_change_finish_result.export_key
+    );
+    assert_ne!(
+        new_client_login_finish_result.export_key,
+        client_login_finish_result.export_key
+    );
+    assert_eq!(
+        new_server_login_finish_result.client_s_pk,
+        server_login_finish_result.client_s_pk
+    );
+    assert_eq!(
+        &new_client_login_finish_result.credentials[..],
+        b"recovery code"
+    );
+    assert!(
+        match envelope_login(&server_setup, &new_password_file, STR_PASSWORD.as_bytes()) {
+            Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+            _ => false,
+        }
+    );
+
+    // The password change is only accepted for the login it follows
+    let (_, other_server_login_finish_result) =
+        envelope_login(&server_setup, &password_file, STR_PASSWORD.as_bytes())?;
+    assert!(match ServerRegistration::finish_password_change(
+        PasswordFileUpgrade::deserialize(&password_change_bytes)?,
+        &other_server_login_finish_result,
+        key_ids.clone(),
+    ) {
+        Err(ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError)) => true,
+        _ => false,
+    });
+    let mut tampered_bytes = password_change_bytes.clone();
+    let last = tampered_bytes.len() - 1;
+    tampered_bytes[last] ^= 1;
+    assert!(ServerRegistration::finish_password_change(
+        PasswordFileUpgrade::deserialize(&tampered_bytes)?,
+        &server_login_finish_result,
+        key_ids,
+    )
+    .is_err());
+
+    // Nor is it accepted if the registration was started under another
+    // credential identifier, or under the current OPRF seed
+    for credential_identifier in &[
+        &b"another credential"[..],
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    ] {
+        let client_registration_start_result =
+            ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(&mut rng, new_password)?;
+        let server_registration_start_result = ServerRegistration::start(
+            &mut rng,
+            &server_setup,
+            client_registration_start_result.message,
+            credential_identifier,
+        )?;
+        assert!(match ServerRegistration::finish_password_change(
+            PasswordFileUpgrade::deserialize(&password_change_bytes)?,
+            &server_login_finish_result,
+            server_registration_start_result.key_ids,
+        ) {
+            Err(ProtocolError::PasswordChangeMismatchError) => true,
+            _ => false,
+        });
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_password_change_internal_mode() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let new_password = b"correct horse battery staple";
+    let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    server_setup.set_external_envelope_mode(true);
+    let password_file = envelope_registration(&server_setup, None, None)?;
+
+    let (client_password_change_start_result, server_login_finish_result) = password_change_login(
+        &server_setup,
+        &password_file,
+        STR_PASSWORD.as_bytes(),
+        new_password,
+    )?;
+    let server_registration_start_result = ServerRegistration::start_password_change(
+        &mut rng,
+        &server_setup,
+        client_password_change_start_result.message,
+        &server_login_finish_result,
+    )?;
+    let client_password_change_finish_result = client_password_change_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let new_password_file = ServerRegistration::finish_password_change(
+        client_password_change_finish_result.message,
+        &server_login_finish_result,
+        server_registration_start_result.key_ids,
+    )?;
+
+    // The password file stays in the internal mode, in which the static
+    // keypair of the client is derived from the new password
+    assert!(!new_password_file.upload.envelope.is_external_mode());
+    let (new_client_login_finish_result, new_server_login_finish_result) =
+        envelope_login(&server_setup, &new_password_file, new_password)?;
+    assert_eq!(
+        new_client_login_finish_result.export_key,
+        client_password_change_finish_result.export_key
+    );
+    assert_ne!(
+        new_server_login_finish_result.client_s_pk,
+        server_login_finish_result.client_s_pk
+    );
+
+    Ok(())
+}
+
+fn authenticated_registration_upload(
+    server_setup: &ServerSetup<RistrettoSha5123dhNoSlowHash>,
+    credential_identifier: &[u8],
+    session_key: Option<&[u8]>,
+) -> Result<(Vec<u8>, RegistrationKeyIds), ProtocolError> {
+    let mut rng = OsRng;
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        server_setup,
+        client_registration_start_result.message,
+        credential_identifier,
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters {
+            session_key,
+            credential_identifier: Some(credential_identifier),
+            ..ClientRegistrationFinishParameters::default()
+        },
+    )?;
+    Ok((
+        client_registration_finish_result.message.serialize(),
+        server_registration_start_result.key_ids,
+    ))
+}
+
+#[test]
+fn test_authenticated_registration() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    let (password_file, _) = register_and_login(&server_setup, None)?;
+    let (client_login_finish_result, server_login_finish_result) =
+        envelope_login(&server_setup, &password_file, STR_PASSWORD.as_bytes())?;
+    let (_, other_server_login_finish_result) =
+        envelope_login(&server_setup, &password_file, STR_PASSWORD.as_bytes())?;
+
+    let (upload_bytes, key_ids) = authenticated_registration_upload(
+        &server_setup,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        Some(&client_login_finish_result.session_key),
+    )?;
+    let (plain_upload_bytes, _) = authenticated_registration_upload(
+        &server_setup,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        None,
+    )?;
+    let finish_authenticated =
+        |upload_bytes: &[u8], server_login_finish_result: &ServerLoginFinishResult<_>| {
+            ServerRegistration::finish_authenticated(
+                RegistrationUpload::<RistrettoSha5123dhNoSlowHash>::deserialize(upload_bytes)?,
+                key_ids.clone(),
+                server_login_finish_result,
+            )
+        };
+
+    // The registration is accepted under the session key of the login, and
+    // its MAC is not kept in the password file
+    let new_password_file = finish_authenticated(&upload_bytes, &server_login_finish_result)?;
+    assert_eq!(
+        new_password_file.serialize().len(),
+        password_file.serialize().len()
+    );
+    let (new_client_login_finish_result, _) =
+        envelope_login(&server_setup, &new_password_file, STR_PASSWORD.as_bytes())?;
+    assert_ne!(
+        new_client_login_finish_result.export_key,
+        client_login_finish_result.export_key
+    );
+    assert_eq!(
+        ServerRegistration::finish(
+            RegistrationUpload::<RistrettoSha5123dhNoSlowHash>::deserialize(&upload_bytes)?,
+            key_ids.clone(),
+        )?
+        .serialize(),
+        new_password_file.serialize()
+    );
+
+    // It is rejected under another session key, without a MAC, or with a
+    // tampered MAC
+    let mut tampered_bytes = upload_bytes.clone();
+    let last = tampered_bytes.len() - 1;
+    tampered_bytes[last] ^= 1;
+    for &(upload_bytes, server_login_finish_result) in &[
+        (&upload_bytes, &other_server_login_finish_result),
+        (&plain_upload_bytes, &server_login_finish_result),
+        (&tampered_bytes, &server_login_finish_result),
+    ] {
+        assert!(
+            match finish_authenticated(upload_bytes, server_login_finish_result) {
+                Err(ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError)) =>
+                    true,
+                _ => false,
+            }
+        );
+    }
+
+    // The MAC covers the credential identifier, so that the session key of a
+    // login for one credential does not authenticate a registration for
+    // another
+    let (other_upload_bytes, other_key_ids) = authenticated_registration_upload(
+        &server_setup,
+        b"another credential",
+        Some(&client_login_finish_result.session_key),
+    )?;
+    assert!(match ServerRegistration::finish_authenticated(
+        RegistrationUpload::<RistrettoSha5123dhNoSlowHash>::deserialize(&other_upload_bytes)?,
+        key_ids,
+        &server_login_finish_result,
+    ) {
+        Err(ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError)) => true,
+        _ => false,
+    });
+    assert!(match ServerRegistration::finish_authenticated(
+        RegistrationUpload::<RistrettoSha5123dhNoSlowHash>::deserialize(&other_upload_bytes)?,
+        other_key_ids,
+        &server_login_finish_result,
+    ) {
+        Err(ProtocolError::CredentialIdentifierMismatchError) => true,
+        _ => false,
+    });
+
+    Ok(())
+}
+
+#[test]
+fn test_server_login_batch() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let mut server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    let (old_password_file, _) = register_and_login(&server_setup, None)?;
+    server_setup.rotate_oprf_seed(&mut rng);
+    let (new_password_file, _) = register_and_login(&server_setup, None)?;
+
+    // The batch mixes password files under both OPRF seeds with an
+    // unregistered client
+    let password_files = vec![
+        Some(old_password_file.clone()),
+        Some(new_password_file.clone()),
+        None,
+        Some(old_password_file.clone()),
+    ];
+    let mut client_states = Vec::new();
+    let mut requests = Vec::new();
+    for password_file in password_files {
+        let client_login_start_result =
+            ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+        client_states.push(client_login_start_result.state);
+        requests.push(ServerLoginStartRequest {
+            message: client_login_start_result.message,
+            credential_identifier: STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+            password_file,
+            params: ServerLoginStartParameters::default(),
+        });
+    }
+    let server_login_start_results = ServerLogin::start_batch(&mut rng, &server_setup, requests);
+    assert_eq!(server_login_start_results.len(), client_states.len());
+
+    for (i, (client_state, server_login_start_result)) in client_states
+        .into_iter()
+        .zip(server_login_start_results)
+        .enumerate()
+    {
+        let server_login_start_result = server_login_start_result?;
+        let client_login_finish_result = client_state.finish(
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        );
+        if i == 2 {
+            assert!(match client_login_finish_result {
+                Err(ProtocolError::VerificationError(PakeError::InvalidLoginError)) => true,
+                _ => false,
+            });
+            continue;
+        }
+        let client_login_finish_result = client_login_finish_result?;
+        let server_login_finish_result = server_login_start_result.state.finish(
+            client_login_finish_result.message,
+            ServerLoginFinishParameters::default(),
+        )?;
+        assert_eq!(
+            hex::encode(&server_login_finish_result.session_key),
+            hex::encode(&client_login_finish_result.session_key)
+        );
+    }
+
+    // A failing request does not affect the rest of the batch
+    server_setup.remove_oprf_seed(0)?;
+    let requests = vec![old_password_file, new_password_file]
+        .into_iter()
+        .map(|password_file| {
+            Ok(ServerLoginStartRequest {
+                message: ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
+                    &mut rng,
+                    STR_PASSWORD.as_bytes(),
+                )?
+                .message,
+                credential_identifier: STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+                password_file: Some(password_file),
+                params: ServerLoginStartParameters::default(),
+            })
+        })
+        .collect::<Result<Vec<_>, ProtocolError>>()?;
+    let server_login_start_results = ServerLogin::start_batch(&mut rng, &server_setup, requests);
+    assert!(match server_login_start_results[0] {
+        Err(ProtocolError::UnknownOprfKeyIdError(0)) => true,
+        _ => false,
+    });
+    assert!(server_login_start_results[1].is_ok());
+
+    Ok(())
+}
+
+#[cfg(feature = "p256")]
+#[test]
+fn test_p256_complete_flow_success() -> Result<(), ProtocolError> {
+    test_complete_flow::<P256Sha2563dhNoSlowHash>(b"good password", b"good password")
+}
+
+#[cfg(feature = "p256")]
+#[test]
+fn test_p256_complete_flow_fail() -> Result<(), ProtocolError> {
+    test_complete_flow::<P256Sha2563dhNoSlowHash>(b"good password", b"bad password")
+}
+
+#[cfg(feature = "p256")]
+#[test]
+fn test_p256_generate_parameters() {
+    // Exercises serialization of every message and state with the
+    // P-256 sizes
+    let parameters = generate_parameters::<P256Sha2563dhNoSlowHash>();
+    assert_eq!(parameters.client_s_pk.len(), 33);
+    assert_eq!(parameters.client_s_sk.len(), 32);
+}
+
+#[cfg(feature = "p384")]
+#[test]
+fn test_p384_complete_flow_success() -> Result<(), ProtocolError> {
+    test_complete_flow::<P384Sha3843dhNoSlowHash>(b"good password", b"good password")
+}
+
+#[cfg(feature = "p384")]
+#[test]
+fn test_p384_complete_flow_fail() -> Result<(), ProtocolError> {
+    test_complete_flow::<P384Sha3843dhNoSlowHash>(b"good password", b"bad password")
+}
+
+#[cfg(feature = "p384")]
+#[test]
+fn test_p384_generate_parameters() {
+    let parameters = generate_parameters::<P384Sha3843dhNoSlowHash>();
+    assert_eq!(parameters.client_s_pk.len(), 49);
+    assert_eq!(parameters.client_s_sk.len(), 48);
+}
+
+#[cfg(feature = "p521")]
+#[test]
+fn test_p521_complete_flow_success() -> Result<(), ProtocolError> {
+    test_complete_flow::<P521Sha5123dhNoSlowHash>(b"good password", b"good password")
+}
+
+#[cfg(feature = "p521")]
+#[test]
+fn test_p521_complete_flow_fail() -> Result<(), ProtocolError> {
+    test_complete_flow::<P521Sha5123dhNoSlowHash>(b"good password", b"bad password")
+}
+
+#[cfg(feature = "p521")]
+#[test]
+fn test_p521_generate_parameters() {
+    let parameters = generate_parameters::<P521Sha5123dhNoSlowHash>();
+    assert_eq!(parameters.client_s_pk.len(), 67);
+    assert_eq!(parameters.client_s_sk.len(), 66);
+}
+
+#[test]
+fn test_hmqv_complete_flow_success() -> Result<(), ProtocolError> {
+    test_complete_flow::<RistrettoSha512HmqvNoSlowHash>(b"good password", b"good password")
+}
+
+#[test]
+fn test_hmqv_complete_flow_fail() -> Result<(), ProtocolError> {
+    test_complete_flow::<RistrettoSha512HmqvNoSlowHash>(b"good password", b"bad password")
+}
+
+#[test]
+fn test_hmqv_generate_parameters() {
+    let parameters = generate_parameters::<RistrettoSha512HmqvNoSlowHash>();
+    assert_eq!(parameters.client_s_pk.len(), 32);
+    assert_eq!(parameters.client_s_sk.len(), 32);
+}
+
+#[cfg(feature = "ml-kem")]
+#[test]
+fn test_hybrid_complete_flow_success() -> Result<(), ProtocolError> {
+    test_complete_flow::<RistrettoSha512HybridNoSlowHash>(b"good password", b"good password")
+}
+
+#[cfg(feature = "ml-kem")]
+#[test]
+fn test_hybrid_complete_flow_fail() -> Result<(), ProtocolError> {
+    test_complete_flow::<RistrettoSha512HybridNoSlowHash>(b"good password", b"bad password")
+}
+
+#[cfg(feature = "ml-kem")]
+#[test]
+fn test_hybrid_generate_parameters() {
+    let parameters = generate_parameters::<RistrettoSha512HybridNoSlowHash>();
+    assert_eq!(parameters.client_s_pk.len(), 32);
+    assert_eq!(parameters.client_s_sk.len(), 32);
+}
+
+#[cfg(feature = "ml-kem")]
+#[test]
+fn test_hybrid_tampered_ciphertext() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha512HybridNoSlowHash>::new(&mut rng);
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha512HybridNoSlowHash>::start(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    let client_login_start_result =
+        ClientLogin::<RistrettoSha512HybridNoSlowHash>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+
+    // Flip a bit of the KEM ciphertext, which precedes the MAC at the end of
+    // the credential response, so that it is implicitly rejected
+    let mut credential_response_bytes = server_login_start_result.message.serialize();
+    let mac_len =
+        <<RistrettoSha512HybridNoSlowHash as CipherSuite>::Hash as Digest>::OutputSize::to_usize();
+    let ciphertext_len = <MlKem768 as Kem>::CiphertextLen::to_usize();
+    let ciphertext_start = credential_response_bytes.len() - mac_len - ciphertext_len;
+    credential_response_bytes[ciphertext_start] ^= 1;
+    let credential_response = CredentialResponse::<RistrettoSha512HybridNoSlowHash>::deserialize(
+        &credential_response_bytes,
+    )?;
+
+    let client_login_finish_result = client_login_start_result
+        .state
+        .finish(credential_response, ClientLoginFinishParameters::default());
+    assert!(match client_login_finish_result {
+        Err(ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError)) => true,
+        _ => false,
+    });
+
+    Ok(())
+}
+
+#[cfg(feature = "ml-kem")]
+#[test]
+fn test_hybrid_invalid_ke1_message() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let client_login_start_result =
+        ClientLogin::<RistrettoSha512HybridNoSlowHash>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let credential_request_bytes = client_login_start_result.message.serialize();
+    assert!(
+        CredentialRequest::<RistrettoSha512HybridNoSlowHash>::deserialize(
+            &credential_request_bytes
+        )
+        .is_ok()
+    );
+
+    // The blinded element and the client nonce precede the ephemeral public
+    // key of the client, which is followed by the encapsulation key
+    let client_e_pk_start = 32 + NonceLen::to_usize();
+    let encapsulation_key_start = client_e_pk_start + 32;
+
+    // A non-canonical encoding of a ristretto255 element
+    let mut invalid_client_e_pk = credential_request_bytes.clone();
+    invalid_client_e_pk[client_e_pk_start..encapsulation_key_start].copy_from_slice(&[0xff; 32]);
+    assert!(
+        CredentialRequest::<RistrettoSha512HybridNoSlowHash>::deserialize(&invalid_client_e_pk)
+            .is_err()
+    );
+
+    // A coefficient of the encapsulation key which is not reduced modulo q
+    let mut invalid_encapsulation_key = credential_request_bytes.clone();
+    invalid_encapsulation_key[encapsulation_key_start] = 0xff;
+    invalid_encapsulation_key[encapsulation_key_start + 1] |= 0x0f;
+    assert!(
+        CredentialRequest::<RistrettoSha512HybridNoSlowHash>::deserialize(
+            &invalid_encapsulation_key
+        )
+        .is_err()
+    );
+
+    assert!(
+        CredentialRequest::<RistrettoSha512HybridNoSlowHash>::deserialize(
+            &credential_request_bytes[..credential_request_bytes.len() - 1]
+        )
+        .is_err()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "p256")]
+#[test]
+fn test_p256_hmqv_complete_flow_success() -> Result<(), ProtocolError> {
+    test_complete_flow::<P256Sha256HmqvNoSlowHash>(b"good password", b"good password")
+}
+
+#[cfg(feature = "p256")]
+#[test]
+fn test_p256_hmqv_complete_flow_fail() -> Result<(), ProtocolError> {
+    test_complete_flow::<P256Sha256HmqvNoSlowHash>(b"good password", b"bad password")
+}
+
+#[cfg(feature = "sigma")]
+#[test]
+fn test_sigma_complete_flow_success() -> Result<(), ProtocolError> {
+    test_complete_flow::<RistrettoSha512SigmaNoSlowHash>(b"good password", b"good password")
+}
+
+#[cfg(feature = "sigma")]
+#[test]
+fn test_sigma_complete_flow_fail() -> Result<(), ProtocolError> {
+    test_complete_flow::<RistrettoSha512SigmaNoSlowHash>(b"good password", b"bad password")
+}
+
+#[cfg(feature = "sigma")]
+#[test]
+fn test_sigma_generate_parameters() {
+    let parameters = generate_parameters::<RistrettoSha512SigmaNoSlowHash>();
+    assert_eq!(parameters.client_s_pk.len(), 32);
+    assert_eq!(parameters.client_s_sk.len(), 32);
+}
+
+#[cfg(feature = "sigma")]
+#[test]
+fn test_sigma_static_keys() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha512SigmaNoSlowHash>::new(&mut rng);
+    let mut seed = [0u8; 32];
+    hkdf::Hkdf::<sha2::Sha512>::new(None, server_setup.keypair().private())
+        .expand(b"OPAQUE-SIGMA-SigningKey", &mut seed)
+        .unwrap();
+    let signing_key = ed25519_dalek::SecretKey::from_bytes(&seed).unwrap();
+    assert_eq!(
+        ed25519_dalek::PublicKey::from(&signing_key).as_bytes()[..],
+        server_setup.keypair().public()[..]
+    );
+
+    // The private key is not used as an Ed25519 secret key as is
+    let raw_signing_key =
+        ed25519_dalek::SecretKey::from_bytes(server_setup.keypair().private()).unwrap();
+    assert_ne!(
+        ed25519_dalek::PublicKey::from(&raw_signing_key).as_bytes()[..],
+        server_setup.keypair().public()[..]
+    );
+
+    // The static keys survive a serialization roundtrip of the server setup
+    let server_setup =
+        ServerSetup::<RistrettoSha512SigmaNoSlowHash>::deserialize(&server_setup.serialize())?;
+    assert_eq!(
+        ed25519_dalek::PublicKey::from(&signing_key).as_bytes()[..],
+        server_setup.keypair().public()[..]
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sigma")]
+#[test]
+fn test_sigma_tampered_signature() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha512SigmaNoSlowHash>::new(&mut rng);
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha512SigmaNoSlowHash>::start(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    let client_login_start_result =
+        ClientLogin::<RistrettoSha512SigmaNoSlowHash>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+
+    // Flip a bit of the server signature, which precedes the MAC at the end
+    // of the credential response
+    let mut credential_response_bytes = server_login_start_result.message.serialize();
+    let signature_index = credential_response_bytes.len()
+        - <<RistrettoSha512SigmaNoSlowHash as CipherSuite>::Hash as Digest>::OutputSize::to_usize()
+        - 1;
+    credential_response_bytes[signature_index] ^= 1;
+    let credential_response = CredentialResponse::<RistrettoSha512SigmaNoSlowHash>::deserialize(
+        &credential_response_bytes,
+    )?;
+
+    let client_login_finish_result = client_login_start_result
+        .state
+        .finish(credential_response, ClientLoginFinishParameters::default());
+    assert!(match client_login_finish_result {
+        Err(ProtocolError::VerificationError(PakeError::KeyExchangeMacValidationError)) => true,
+        _ => false,
+    });
+
+    Ok(())
+}
+
+#[test]
+fn test_record_kind() -> Result<(), ProtocolError> {
+    let mut rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut rng);
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result = ServerRegistration::start(
+        &mut rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let password_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    // A registered client
+    let client_login_start_result =
+        ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        Some(password_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    assert_eq!(
+        server_login_start_result.state.record_kind(),
+        RecordKind::Registered
+    );
+    let registered_message_len = server_login_start_result.message.serialize().len();
+    let client_login_finish_result = client_login_start_result.state.finish(
+        server_login_start_result.message,
+        ClientLoginFinishParameters::default(),
+    )?;
+    server_login_start_result.state.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::default(),
+    )?;
+
+    // An unregistered client, whose credential response cannot be told apart
+    let client_login_start_result =
+        ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(&mut rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::start(
+        &mut rng,
+        &server_setup,
+        None,
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+    assert_eq!(
+        server_login_start_result.state.record_kind(),
+        RecordKind::Dummy
+    );
+    assert_eq!(
+        server_login_start_result.message.serialize().len(),
+        registered_message_len
+    );
+    let state = ServerLogin::<RistrettoSha5123dhNoSlowHash>::deserialize(
+        &server_login_start_result.state.serialize(),
+    )?;
+    assert_eq!(state.record_kind(), RecordKind::Dummy);
+    assert!(client_login_start_result
+        .state
+        .finish(
+            server_login_start_result.message,
+            ClientLoginFinishParameters::default(),
+        )
+        .is_err());
+
+    Ok(())
+}
+
+// Zeroize tests
+
+#[test]
+fn test_zeroize_client_registration_start() -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+
+    let mut state = client_registration_start_result.state;
+    let ptrs = state.as_byte_ptrs();
+    state.zeroize();
+
+    for (ptr, len) in ptrs {
+        let bytes = unsafe { from_raw_parts(ptr, len) };
+        assert!(bytes.iter().all(|&x| x == 0));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_client_registration_finish() -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result =
+        ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut server_rng,
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+
+    let mut state = client_registration_finish_result.state;
+    let ptrs = state.as_byte_ptrs();
+    state.zeroize();
+
+    for (ptr, len) in ptrs {
+        let bytes = unsafe { from_raw_parts(ptr, len) };
+        assert!(bytes.iter().all(|&x| x == 0));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_server_registration_finish() -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result =
+        ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut server_rng,
+            &server_setup,
+            client_registration_start_result.message,
+            STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let p_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+
+    let mut state = p_file;
+    let ptrs = state.as_byte_ptrs();
+    state.zeroize();
+
+    for (ptr, len) in ptrs {
+        let bytes = unsafe { from_raw_parts(ptr, len) };
+        assert!(bytes.iter().all(|&x| x == 0));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_client_login_start() -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
+        &mut client_rng,
+        STR_PASSWORD.as_bytes(),
+    )?;
+
+    let mut state = client_login_start_result.state;
+    let ptrs = state.as_byte_ptrs();
+    state.zeroize();
+
+    for (ptr, len) in ptrs {
+        let bytes = unsafe { from_raw_parts(ptr, len) };
+        assert!(bytes.iter().all(|&x| x == 0));
+    }
+
+    Ok(())
+}
+
+fn zeroize_server_login_start<CS: CipherSuite>() -> Result<(), ProtocolError>
+where
+    <CS::KeyExchange as KeyExchange<CS::Hash, CS::Group>>::KE2State: AsBytePointers,
+{
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<CS>::new(&mut server_rng);
+    let client_registration_start_result =
+        ClientRegistration::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_registration_start_result = ServerRegistration::<CS>::start(
+        &mut server_rng,
+        &server_setup,
+        client_registration_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+    )?;
+    let client_registration_finish_result = client_registration_start_result.state.finish(
+        &mut client_rng,
+        server_registration_start_result.message,
+        ClientRegistrationFinishParameters::default(),
+    )?;
+    let p_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
+    let client_login_start_result =
+        ClientLogin::<CS>::start(&mut client_rng, STR_PASSWORD.as_bytes())?;
+    let server_login_start_result = ServerLogin::<CS>::start(
+        &mut server_rng,
+        &server_setup,
+        Some(p_file),
+        client_login_start_result.message,
+        STR_CREDENTIAL_IDENTIFIER.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
+
+    let mut state = server_login_start_result.state;
+    let ptrs = state.as_byte_ptrs();
+    state.zeroize();
+
+    for (ptr, len) in ptrs {
+        let bytes = unsafe { from_raw_parts(ptr, len) };
+        assert!(bytes.iter().all(|&x| x == 0));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_zeroize_server_login_start() -> Result<(), ProtocolError> {
+    zeroize_server_login_start::<RistrettoSha5123dhNoSlowHash>()
+}
+
+#[test]
+fn test_zeroize_hmqv_server_login_start() -> Result<(), ProtocolError> {
+    zeroize_server_login_start::<RistrettoSha512HmqvNoSlowHash>()
+}
+
+#[test]
+fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
+    let mut client_rng = OsRng;
+    let mut server_rng = OsRng;
+    let server_setup = ServerSetup::<RistrettoSha5123dhNoSlowHash>::new(&mut server_rng);
+    let client_registration_start_result =
+        ClientRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut client_rng,
+            STR_PASSWORD.as_bytes(),
+        )?;
+    let server_registration_start_result =
+        ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut server_rng,
+            &server_setup,
+            client_registration_start_result.message,
             STR_CREDENTIAL_IDENTIFIER.as_bytes(),
         )?;
     let client_registration_finish_result = client_registration_start_result.state.finish(
@@ -957,7 +3624,10 @@ fn test_zeroize_client_login_finish() -> Result<(), ProtocolError> {
         server_registration_start_result.message,
         ClientRegistrationFinishParameters::default(),
     )?;
-    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+    let p_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
     let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
         &mut client_rng,
         STR_PASSWORD.as_bytes(),
@@ -999,6 +3669,7 @@ fn test_zeroize_server_login_finish() -> Result<(), ProtocolError> {
         )?;
     let server_registration_start_result =
         ServerRegistration::<RistrettoSha5123dhNoSlowHash>::start(
+            &mut server_rng,
             &server_setup,
             client_registration_start_result.message,
             STR_CREDENTIAL_IDENTIFIER.as_bytes(),
@@ -1008,7 +3679,10 @@ fn test_zeroize_server_login_finish() -> Result<(), ProtocolError> {
         server_registration_start_result.message,
         ClientRegistrationFinishParameters::default(),
     )?;
-    let p_file = ServerRegistration::finish(client_registration_finish_result.message);
+    let p_file = ServerRegistration::finish(
+        client_registration_finish_result.message,
+        server_registration_start_result.key_ids,
+    )?;
     let client_login_start_result = ClientLogin::<RistrettoSha5123dhNoSlowHash>::start(
         &mut client_rng,
         STR_PASSWORD.as_bytes(),
@@ -1025,9 +3699,10 @@ fn test_zeroize_server_login_finish() -> Result<(), ProtocolError> {
         server_login_start_result.message,
         ClientLoginFinishParameters::default(),
     )?;
-    let server_login_finish_result = server_login_start_result
-        .state
-        .finish(client_login_finish_result.message)?;
+    let server_login_finish_result = server_login_start_result.state.finish(
+        client_login_finish_result.message,
+        ServerLoginFinishParameters::default(),
+    )?;
 
     let mut state = server_login_finish_result.state;
     let ptrs = state.as_byte_ptrs();
@@ -1041,6 +3716,25 @@ fn test_zeroize_server_login_finish() -> Result<(), ProtocolError> {
     Ok(())
 }
 
+#[test]
+fn test_unmask_short_response() {
+    let masking_key = [0u8; 64];
+    let masking_nonce = [0u8; 32];
+
+    for len in [0, 16, 31].iter() {
+        assert!(matches!(
+            unmask_response::<RistrettoSha5123dhNoSlowHash>(
+                &masking_key,
+                &masking_nonce,
+                &vec![0u8; *len]
+            ),
+            Err(ProtocolError::VerificationError(PakeError::CryptoError(
+                InternalPakeError::SizeError { .. }
+            )))
+        ));
+    }
+}
+
 #[test]
 fn test_scalar_always_nonzero() -> Result<(), ProtocolError> {
     // Start out with a bunch of zeros to force resampling of scalar
@@ -0,0 +1,193 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+use crate::{
+    errors::*,
+    key_exchange::{hybrid::Kem, ml_kem::MlKem768},
+    tests::mock_rng::CycleRng,
+};
+use generic_array::GenericArray;
+#[allow(deprecated)]
+use ml_kem_::{array::Array, ml_kem_768::DecapsulationKey, Decapsulate, ExpandedKeyEncoding};
+use serde_json::Value;
+
+// The ML-KEM-768 test groups of the NIST ACVP-Server vector sets for FIPS 203
+// (vsId 42), found in gen-val/json-files/ML-KEM-keyGen-FIPS203 (tgId 2) and
+// gen-val/json-files/ML-KEM-encapDecap-FIPS203 (tgIds 2 and 5) of
+// https://github.com/usnistgov/ACVP-Server. The decapsulation cases all use
+// the decapsulation key of tgId 5, and those whose reason is "modify
+// ciphertext" exercise the implicit rejection
+
+static ML_KEM_768_KEY_GEN: &[&str] = &[
+    r#"
+    {
+        "tcId": "26",
+        "d": "e34a701c4c87582f42264ee422d3c684d97611f2523efe0c998af05056d693dc",
+        "z": "a85768f3486bd32a01bf9a8f21ea938e648eae4e5448c34c3eb88820b159eedd",
+        "ek": "6d14a071f7cc452558d5e71a7b087062ecb1386844588246126402b1fa1637733cd5f60cc84bcb646a7892614d7c51b1c7f1a2799132f13427dc482158da254470a59e00a4e49686fdc077559367270c2153f11007592c9c4310cf8a12c6a8713bd6bb51f3124f989ba0d54073cc242e0968780b875a869efb851586b9a868a384b9e6821b201b932c455369a739ec22569c977c212b381871813656af5b567ef893b584624c863a259000f17b254b98b185097c50ebb68b244342e05d4de520125b8e1033b1436093ace7ce8e71b458d525673363045a3b3eea9455428a398705a42327adb3774b7057f42b017ec0739a983f19e8214d09195fa24d2d571db73c19a6f8460e50830d415f627b88e94a7b153791a0c0c7e9484c74d53c714889f0e321b6660a532a5bc0e557fbca35e29bc611200ed3c633077a4d873c5cc67006b753bf6d6b7af6ca402ab618236c0affbc801f8222fbc36ce0984e2b18c944bbcbef03b1e1361c1f44b0d734afb1566cff8744da8b9943d6b45a3c09030702ca201ffe20cb7ec5b0d4149ee2c28e8b23374f471b57150d0ec9336261a2d5cb84a3acacc4289473a4c0abc617c9abc178734434c82e1685588a5c2ea2678f6b3c2228733130c466e5b86ef491153e48662247b875d201020b566b81b64d839ab4633baa8ace202baab4496297f9807adbbb1e332c6f8022b2a18cfdd4a82530b6d3f007c3353898d966cc2c21cb4244bd00443f209870acc42bc33068c724ec17223619c1093cca6aeb29500664d1225036b4b81091906969481f1c723c140b9d6c168f5b64bea69c5fd6385df7364b8723bcc85e038c7e464a900d68a2127818994217aec8bdb39a970a9963de93688e2ac82abcc22fb9277ba22009e878381a38163901c7d4c85019538d35caae9c41af8c929ee20bb08ca619e72c2f2262c1c9938572551ac02dc9268fbcc35d79011c3c090ad40a4f111c9be55c427eb796c1932d8673579af1b4c638b0944489012a2559a3b02481b01ac30ba8960f80c0c2b3947d36a12c080498bee448716c973416c8242804a3da099ee137b0ba90fe4a5c6a89200276a0cfb643ec2c56a2d708d7b4373e44c1502a763a600586e6cda6273897d44448287dc2e602dc39200bf6166236559fd12a60892aeb153dd651bb469910b4b34669f91da8654d1eb72eb6e02800b3b0a7d0a48c836854d3a83e65569cb7230bb44f3f143a6dec5f2c39ab90f274f2088bd3d6a6fca0070273bedc84777fb52e3c558b0ae06183d5a48d452f68e15207f861627aca14279630f82ec3a0ca078633b600afa79743a600215be5637458ce2ce8aff5a08eb5017b2c766577479f8dc6bf9f5cc75089932161b96cea406620aedb630407f7687ebbb4814c7981637a48a90de68031e062a7af7612b4f5c7a6da86bd136529e64295a5613ea73bd3d4448cb81f243135c0a660beb9c17e651def469a7d90a15d3481090bcbf227012328941fa46f39c5006ad93d458aa6add655862b418c3094f551460df2153a5810a7da74f0614c2588be49dc6f5e88154642bd1d3762563326433507156a57c57694bdd26e7a246feb723aed67b04887c8e476b48cab59e5362f26a9ef50c2bc80ba146226216fe62968a60d04e8c170d741c7a2b0e1abdac968",
+        "dk": "98a1b2da4a65cfb5845ea7311e6a06db731f1590c41ee74ba10782715b35a3102df637872be65bab37a1de2511d703c70247b35ef27435485024d93fd9e77c43804f371749ba00b20a8c5c588bc9abe068aeaaa938517ebfe53b6b663282903dcd189736d7296816c733a1c77c6375e5397c0f189bbfe47643a61f58f8a3c6911be4611a8c7bc050021163d0a404dc14065748ff29be60d2b9fdcc8ffd98c587f38c67115786464bdb342b17e897d64617cbfb117973a5458977a7d7617a1b4d83ba03c611138a4673b1eb34b078033f97cffe80c146a26943f842b976327bf1cbc60119525bb9a3c03493349000dd8f51ba21a2e92361762324600e0c13aaa6cb69bfb24276483f6b02421259b7585263c1a028d682c508bbc2801a56e98b8f620b0483d79b5ad8585ac0a475bac77865194196338791b7985a05d109395cca8932722a91950d37e12b891420a52b62cbfa815df6174ce00e68bca75d4838ca280f713c7e6924afd95baa0d01ada637b158347034c0ab1a7183331a820acbcb83193a1a94c8f7e384aed0c35ed3cb3397bb638086e7a35a6408a3a4b90ce953707c19bc46c3b2da3b2ee32319c56b928032b5ed1256d0753d341423e9db139de7714ff075caf58fd9f57d1a54019b5926406830dae29a875302a81256f4d6cf5e74034ea614bf70c2764b20c9589cdb5c25761a04e58292907c578a94a35836bee3112dc2c3ae2192c9deaa304b29c7fea1bdf47b3b6bcba2c0e55c9cdb6de7149e9cb17917718f12c8032de1ade0648d405519c70719becc701845cf9f4b912fe71983ca34f9018c7ca7bb2f6c5d7f8c5b297359ec75209c2543ff11c4244977c5969524ec454d44c323fcca94acac273a0ec49b4a8a585bce7a5b305c04c3506422580357016a850c3f7ee17205a77b291c7731c9836c02aee5406f63c6a07a214382aa15336c05d1045588107645ea7de6870fc0e55e1540974301c42ec14105518680f688abe4ce453738fe471b87fc31f5c68a39e68af51b0240b90e0364b04bac43d6fb68ab65ae028b62bd683b7d28ad38806bee725b5b2416a8d79c16ec2a99ea4a8d92a2f5052e67f97352289761c5c39fc5c742e9c0a740ca59fc0182f709d01b5187f00063daab397596eea4a31bdbcbd4c1bb0c55be7c6850fda9326b353e288c5013226c3c3923a791609e8002e73a5f7b6bb4a877b1fdf53bb2bab3dd424d31bbb448e609a66b0e343c286e8760312b6d37aa5201d21f53503d88389adca21c70fb6c0fc9c69d6616c9ea3780e35565c0c97c15179c95343ecc5e1c2a24de4699f6875ea2fa2dd3e357bc43914795207e026b850a2237950c108a512fc88c22488112607088185fb0e09c2c4197a83687266bab2e583e21c40f4cc008fe652804d8223f1520a90b0d5385c7553cc767c58d120ccd3ef5b5d1a6cd7bc00dff1321b2f2c432b64efb8a3f5d0064b3f34293026c851c2ded68b9dff4a28f6a8d225535e0477084430cffda0ac0552f9a212785b749913a06fa2274c0d15bad325458d323ef6bae13c0010d525c1d5269973ac29bda7c983746918ba0e002588e30375d78329e6b8ba8c4462a692fb6083842b8c8c92c60f252726d14a071f7cc452558d5e71a7b087062ecb1386844588246126402b1fa1637733cd5f60cc84bcb646a7892614d7c51b1c7f1a2799132f13427dc482158da254470a59e00a4e49686fdc077559367270c2153f11007592c9c4310cf8a12c6a8713bd6bb51f3124f989ba0d54073cc242e0968780b875a869efb851586b9a868a384b9e6821b201b932c455369a739ec22569c977c212b381871813656af5b567ef893b584624c863a259000f17b254b98b185097c50ebb68b244342e05d4de520125b8e1033b1436093ace7ce8e71b458d525673363045a3b3eea9455428a398705a42327adb3774b7057f42b017ec0739a983f19e8214d09195fa24d2d571db73c19a6f8460e50830d415f627b88e94a7b153791a0c0c7e9484c74d53c714889f0e321b6660a532a5bc0e557fbca35e29bc611200ed3c633077a4d873c5cc67006b753bf6d6b7af6ca402ab618236c0affbc801f8222fbc36ce0984e2b18c944bbcbef03b1e1361c1f44b0d734afb1566cff8744da8b9943d6b45a3c09030702ca201ffe20cb7ec5b0d4149ee2c28e8b23374f471b57150d0ec9336261a2d5cb84a3acacc4289473a4c0abc617c9abc178734434c82e1685588a5c2ea2678f6b3c2228733130c466e5b86ef491153e48662247b875d201020b566b81b64d839ab4633baa8ace202baab4496297f9807adbbb1e332c6f8022b2a18cfdd4a82530b6d3f007c3353898d966cc2c21cb4244bd00443f209870acc42bc33068c724ec17223619c1093cca6aeb29500664d1225036b4b81091906969481f1c723c140b9d6c168f5b64bea69c5fd6385df7364b8723bcc85e038c7e464a900d68a2127818994217aec8bdb39a970a9963de93688e2ac82abcc22fb9277ba22009e878381a38163901c7d4c85019538d35caae9c41af8c929ee20bb08ca619e72c2f2262c1c9938572551ac02dc9268fbcc35d79011c3c090ad40a4f111c9be55c427eb796c1932d8673579af1b4c638b0944489012a2559a3b02481b01ac30ba8960f80c0c2b3947d36a12c080498bee448716c973416c8242804a3da099ee137b0ba90fe4a5c6a89200276a0cfb643ec2c56a2d708d7b4373e44c1502a763a600586e6cda6273897d44448287dc2e602dc39200bf6166236559fd12a60892aeb153dd651bb469910b4b34669f91da8654d1eb72eb6e02800b3b0a7d0a48c836854d3a83e65569cb7230bb44f3f143a6dec5f2c39ab90f274f2088bd3d6a6fca0070273bedc84777fb52e3c558b0ae06183d5a48d452f68e15207f861627aca14279630f82ec3a0ca078633b600afa79743a600215be5637458ce2ce8aff5a08eb5017b2c766577479f8dc6bf9f5cc75089932161b96cea406620aedb630407f7687ebbb4814c7981637a48a90de68031e062a7af7612b4f5c7a6da86bd136529e64295a5613ea73bd3d4448cb81f243135c0a660beb9c17e651def469a7d90a15d3481090bcbf227012328941fa46f39c5006ad93d458aa6add655862b418c3094f551460df2153a5810a7da74f0614c2588be49dc6f5e88154642bd1d3762563326433507156a57c57694bdd26e7a246feb723aed67b04887c8e476b48cab59e5362f26a9ef50c2bc80ba146226216fe62968a60d04e8c170d741c7a2b0e1abdac968e29020839d052fa372585627f8b59ee312ae414c979d825f06a6929a79625718a85768f3486bd32a01bf9a8f21ea938e648eae4e5448c34c3eb88820b159eedd"
+    }
+    "#,
+    r#"
+    {
+        "tcId": "27",
+        "d": "444f032dd19ae7518c4b35b0732a41dc567845aba8bd7b04a9c413a0cf2de0b5",
+        "z": "df0f282411f4a071489a8f618e2ae5aef40131cac5233d6d731522720c2feb1c",
+        "ek": "5cc523b2d908c45907a6694a665195171a5b2fb583a5c240cadca8f0e83e46b14052c9620d3b7ef386ce8b9a5e873b65693b0d341c6eb2d10ce5e937cfb8c4c9134401babfeebbaecf47113a34b9c6e011bdc78a54f2b7bf36a5ffd27563d7443f2109f02a64c421411ddb2d1404a86f793a2de62cdc560bfd6604d4b6330ba6aa621414e8c12dc71c25652abaf36b875de1978dd209ab53b885206c3a1b4f8b4a0670c087cda9cda7997437155659255c2d024822a448ce5157cf5b6e4c495a949960886a902c79591120117c4a73ce7b380c661851e1ca9ef1973d8a9d2a191b938c4110259c4227b600ba7ec9b033bb0300715032836573382445435a743ca61e923b18adec7cfaf10ade908e582560ee91aca012942319b4888109e55aa738a7bcf777c92b4b09a50a1c043c982c2c2357f73c1687b35bd123fc905e1a719353466a42b915dbf1a1750339bf0923419681e4531d97e2160ad896db056570570510fb711169af2de0cba51c5f5056242965ad429301e7020ae0141f845833a3fba0b192426c001a7147c2926805cd86725442cadc2636bb769dcde46d1bd12d30f4695593b5753870ef796fb2f3a53f283d5828b77cb75d5de1ba25357c290a957fd501aee0ae59d7ae97833b0bb640f781a08bd256c79117c220bdd83280a0069b29a645720096d297a2e5245439268c0ed01f75a939978372b9e05d93da899c10bf6cdb18698c46ebe00bf90730e2ea393014461dec6c87f17b2ee16c13b8507c6009bee074f17367a5fc3067a28b7d804c32860ede650e6fe85cf6e301d1b1647323199ca296abc54d2811507572b5dff92b54e3786d130938417624775d8534b0102b6b8006803ddb376eb830d1ca80e717bb7f260a5ca4a56bfc5da790151725942ae7c42b2b9e385b4e0f995d4402161070b73a6bb0cdb77ef11b1286d75e315635e719088dc7909d026b198ac93bb4b6fe395843a4428f75c0c1448c605a8caba0b8cd19ce465764b523628b3334e3885d68d5089e1a3045840c36a73aefe7b93ab357fd8a46d7547a8efb243e4953e67ca72cfa0b77835768aa0cd2d976820a97bc21c7033084ad45c0bf6b483aca8a485641eb55a47be36abceb96143ba90c515d5be8513bb994cfa88ff4b3600e34c1e656877606b6280384a0f481458044c47732fa9b58195a5dfb48636e1558c56a43cb6941dee5aeb1e27b89a7121be166879b62bc01619a9abe840cc678e028e9bc71ce233fd9db8816294d71f1a080101912920534750dde692f782bac4d4481a0900e6bb952ada798ee06232c200f57f76a914617914b7398a0433cd7a11b5ac09789034f39338ce567e3e7aefe35b0c3b85d21506e8886587670761af9bad3261daf22cbfc664604234b3b784ea001cc6702b9222545cfdb2965eb54678780ee3c9cc134cd2e655908d6bdf460bee364c66d5accf4b492ade9a0f3eb31995badde4628b67165ff6014d848541035cda46949ec1c12ff492726a7214d1c7273fb85d5484e5a178751b56e3fb163d13a53c7b3038e09b847a8c06ff9b42e8c345cc95aac1a09660ac1fc7a146e7845ab83390871655e604c4c009ee924ae107b61bc3664f488ac60783a1c346bd18c56ced3f03bc1b1e4075e9785f235ebc5ce6621414e77d52cec3b2e",
+        "dk": "657004a34b4ea6b278bdc1bc94a997d86b206f88875a934042732cfaf8b3a0141fdd815f2203bd92ac478a9033126a8478fbb6453aae005c03f60444163066ee922781d08dfb1508f547555b3027a2f75f28401a7d69a09669ac8309c3d4e4b49b214c4c76b3e4c26ced4940a325885c71883881b6c18c57bf22cb4484674a738988708fb7ec68855a96ef033b4a877038612b7b14bb3dca791dc5cc7c85614a694d0672cb5656ca51c7b3ce11abe1f4b790800fe7f47f97d640141702b147a3a6d99279b258cae7899c353a66f6af3c53c4a632beb545b65a2724ef06cd05978e3ee20bf264a0335b21fc2137c71161a8a3aaa1a6afabd023f58c0c393630e41561568c6669c2683b0b493a60a42889a178acc3289bb135c891d89698c38aae187c6e3db16335fa61bf70c6d496b5251bcefa9a1c95980e3810c0059c62e8838f1b0b46b4c5a2fea19e790b2eb4c8c3a164c8bf5c89c2812e982b0f3da0cde958a26bd03a38c562cc67b2c07509e6742cb44c04320aa87c23c3e3a7506f26afe94523d1b05280ba53b4abb8c5717422d071396c6b7733a09b11ce1e6b2280f1c9215913fba6522f90c009c0988caac61721993ae73dd71a551ed8431c1a8d286857455624842c4cfa80b9143ccebf930aa1e738eff1a46efcc0d766b7e4ac39ad508d6cb9891deb61b0aac5fb9385e1d0682f786ca37c3df1a38bdfc1162e975eb604163752cac6c47e3bd909c53726c6d084188904ca98c743c9b5d700cbe4a809f1756dcf4c65c5a6b7a7f2725595a0c89c26381c218004b1a275701b50586a327652390fb68868cfe8084067abc53a9a2cecc72bc625ca7751ec158f35e791008543eb202ae258c588e69e695425b9ba4fe0082ecc530ebfab41db23cfa8c2a63aab11d179c91a712062536c4ff1c205287296b001121436c5f813747350c9ab63cec0ccf7dab3e642210517155228910c729bc9b24b138b85ed9a4678b2b4c67a73282842ea66cc458c706bf4a591bbcbbd370e09c937e396b76fe4a3b56b4cf638a5ce055cb63c1275d53b4197493a1a4309a4ccdadc3ad1f47a5e8c5c89235321028ef158094a6385c4e010d6f8ccf1c627bcb3600544b276d2ac9cc91d4bd5ad75dbcc8e7b7a981680212b5a3d395f8aa1cf2b0a23ebb63bddc5185be53a6c1410d0d96889a74265e3b34f4477fdf5b680d793f35c7a372b25a1f47c5875b34b80aca2c25a0de69d58e71856c55e37a79bc7376898c45bdad66fd0a554d8f9bd69a525baa4bf40b0aefdec66ea329acf7b44d33c4fa248734f516bb0a69ff751a3e3d95975dc4e25194cd6f88e7264352628af45b38a3434951ff99cbaea812c04c354227431b01ccf2b5955b59bbb5a2bf382227d71631c541af888232ef733a085aa1d14493c063b64e8bb28e3b7d0686ce8f942eec58734525dbac07159627863d97f7c198c50e9ab10e54979c394e90395e6a793c882cba9d56179b75f11799709577f149cc93ea3a764c610eae641f8fa2801a22b5686b335117c3c7b3d74986f70384a26a33b323787b7888cf873be39411829d69d6e2ca2279971ae27660b5224d21015440844c457b6b9f2c50d19580489c63ae0612d423a5cc523b2d908c45907a6694a665195171a5b2fb583a5c240cadca8f0e83e46b14052c9620d3b7ef386ce8b9a5e873b65693b0d341c6eb2d10ce5e937cfb8c4c9134401babfeebbaecf47113a34b9c6e011bdc78a54f2b7bf36a5ffd27563d7443f2109f02a64c421411ddb2d1404a86f793a2de62cdc560bfd6604d4b6330ba6aa621414e8c12dc71c25652abaf36b875de1978dd209ab53b885206c3a1b4f8b4a0670c087cda9cda7997437155659255c2d024822a448ce5157cf5b6e4c495a949960886a902c79591120117c4a73ce7b380c661851e1ca9ef1973d8a9d2a191b938c4110259c4227b600ba7ec9b033bb0300715032836573382445435a743ca61e923b18adec7cfaf10ade908e582560ee91aca012942319b4888109e55aa738a7bcf777c92b4b09a50a1c043c982c2c2357f73c1687b35bd123fc905e1a719353466a42b915dbf1a1750339bf0923419681e4531d97e2160ad896db056570570510fb711169af2de0cba51c5f5056242965ad429301e7020ae0141f845833a3fba0b192426c001a7147c2926805cd86725442cadc2636bb769dcde46d1bd12d30f4695593b5753870ef796fb2f3a53f283d5828b77cb75d5de1ba25357c290a957fd501aee0ae59d7ae97833b0bb640f781a08bd256c79117c220bdd83280a0069b29a645720096d297a2e5245439268c0ed01f75a939978372b9e05d93da899c10bf6cdb18698c46ebe00bf90730e2ea393014461dec6c87f17b2ee16c13b8507c6009bee074f17367a5fc3067a28b7d804c32860ede650e6fe85cf6e301d1b1647323199ca296abc54d2811507572b5dff92b54e3786d130938417624775d8534b0102b6b8006803ddb376eb830d1ca80e717bb7f260a5ca4a56bfc5da790151725942ae7c42b2b9e385b4e0f995d4402161070b73a6bb0cdb77ef11b1286d75e315635e719088dc7909d026b198ac93bb4b6fe395843a4428f75c0c1448c605a8caba0b8cd19ce465764b523628b3334e3885d68d5089e1a3045840c36a73aefe7b93ab357fd8a46d7547a8efb243e4953e67ca72cfa0b77835768aa0cd2d976820a97bc21c7033084ad45c0bf6b483aca8a485641eb55a47be36abceb96143ba90c515d5be8513bb994cfa88ff4b3600e34c1e656877606b6280384a0f481458044c47732fa9b58195a5dfb48636e1558c56a43cb6941dee5aeb1e27b89a7121be166879b62bc01619a9abe840cc678e028e9bc71ce233fd9db8816294d71f1a080101912920534750dde692f782bac4d4481a0900e6bb952ada798ee06232c200f57f76a914617914b7398a0433cd7a11b5ac09789034f39338ce567e3e7aefe35b0c3b85d21506e8886587670761af9bad3261daf22cbfc664604234b3b784ea001cc6702b9222545cfdb2965eb54678780ee3c9cc134cd2e655908d6bdf460bee364c66d5accf4b492ade9a0f3eb31995badde4628b67165ff6014d848541035cda46949ec1c12ff492726a7214d1c7273fb85d5484e5a178751b56e3fb163d13a53c7b3038e09b847a8c06ff9b42e8c345cc95aac1a09660ac1fc7a146e7845ab83390871655e604c4c009ee924ae107b61bc3664f488ac60783a1c346bd18c56ced3f03bc1b1e4075e9785f235ebc5ce6621414e77d52cec3b2ebba283f4c993a010081e2cc571d97234472cc9858d199cf0d6e6b9bd720c2665df0f282411f4a071489a8f618e2ae5aef40131cac5233d6d731522720c2feb1c"
+    }
+    "#,
+];
+
+static ML_KEM_768_ENCAPSULATION: &[&str] = &[
+    r#"
+    {
+        "tcId": "26",
+        "ek": "89d2cb65f94dcbfc890efc7d0e5a7a38344d1641a3d0b024d50797a5f23c3a18b3101a1269069f43a842bacc098a8821271c673db1beb33034e4d7774d16635c7c2c3c2763453538bc1632e1851591a51642974e5928abb8e55fe55612f9b141aff015545394b2092e590970ec29a7b7e7aa1fb4493bf7cb731906c2a5cb49e6614859064e19b8fa26af51c44b5e7535bfdac072b646d3ea490d277f0d97ced47395fed91e8f2bce0e3ca122c2025f74067ab928a822b35653a74f06757629afb1a1caf237100ea935e793c8f58a71b3d6ae2c8658b10150d4a38f572a0d49d28ae89451d338326fdb3b4350036c1081117740edb86b12081c5c1223dbb5660d5b3cb3787d481849304c68be875466f14ee5495c2bd795ae412d09002d65b8719b90cba3603ac4958ea03cc138c86f7851593125334701b677f82f4952a4c93b5b4c134bb42a857fd15c650864a6aa94eb691c0b691be4684c1f5b7490467fc01b1d1fda4dda35c4ecc231bc73a6fef42c99d34eb82a4d014987b3e386910c62679a118f3c5bd9f467e4162042424357db92ef484a4a1798c1257e870a30cb20aaa0335d83314fe0aa7e63a862648041a72a6321523220b1ace9bb701b21ac1253cb812c15575a9085eabeade73a4ae76e6a7b158a20586d78a5ac620a5c9abcc9c043350a73656b0abe822da5e0ba76045fad75401d7a3b703791b7e99261710f86b72421d240a347638377205a152c794130a4e047742b888303bddc309116764de7424cebea6db65348ac537e01a9cc56ea667d5aa87ac9aaa4317d262c10143050b8d07a728ca633c13e468abcead372c77b8ecf3b986b98c1e55860b2b4216766ad874c35ed7205068739230220b5a2317d102c598356f168acbe80608de4c9a710b8dd07078cd7c671058af1b0b8304a314f7b29be78a933c7b9294424954a1bf8bc745de86198659e0e1225a910726074969c39a97c19240601a46e013dcdcb677a8cbd2c95a40629c256f24a328951df57502ab30772cc7e5b850027c8551781ce4985bdacf6b865c104e8a4bc65c41694d456b7169e45ab3d7acabeafe23ad6a7b94d1979a2f4c1cae7cd77d681d290b5d8e451bfdcccf5310b9d12a88ec29b10255d5e17a192670aa9731c5ca67ec784c502781be8527d6fc003c6701b3632284b40307a527c7620377feb0b73f722c9e3cd4dec64876b93ab5b7cfc4a657f852b659282864384f442b22e8a21109387b8b47585fc680d0ba45c7a8b1d7274bda57845d100d0f42a3b74628773351fd7ac305b2497639be90b3f4f71a6aa3561eecc6a691bb5cb3914d8634ca1e1af543c049a8c6e868c51f0423bd2d5ae09b79e57c27f3fe3ae2b26a441babfc6718ce8c05b4fe793b910b8fbcbbe7f1013242b40e0514d0bdc5c88bac594c794ce5122fbf34896819147b928381587963b0b90034aa07a10be176e01c80ad6a4b71b10af4241400a2a4cbbc05961a15ec1474ed51a3cc6d35800679a462809caa3ab4f7094cd6610b4a700cba939e7eac93e38c99755908727619ed76a34e53c4fa25bfc97008206697dd145e5b9188e5b014e941681e15fe3e132b8a3903474148ba28b987111c9bcb3989bbbc671c581b44a492845f288e62196e471fed3c39c1bbddb0837d0d4706b0922c4",
+        "m": "2ce74ad291133518fe60c7df5d251b9d82add48462ff505c6e547e949e6b6bf7",
+        "c": "56b42d593aab8e8773bd92d76eabddf3b1546f8326f57a7b773764b6c0dd30470f68dff82e0dca92509274ecfe83a954735fde6e14676daaa3680c30d524f4efa79ed6a1f9ed7e1c00560e8683538c3105ab931be0d2b249b38cb9b13af5ceaf7887a59dba16688a7f28de0b14d19f391eb41832a56479416ccf94e997390ed7878eeaff49328a70e0ab5fce6c63c09b35f4e45994de615b88bb722f70e87d2bbd72ae71e1ee9008e459d8e743039a8ddeb874fce5301a2f8c0ee8c2fee7a4ee68b5ed6a6d9ab74f98bb3ba0fe89e82bd5a525c5e8790f818ccc605877d46c8bdb5c337b025bb840ff471896e43bfa99d73dbe31805c27a43e57f0618b3ae522a4644e0d4e4c1c548489431be558f3bfc50e16617e110dd7af9a6fd83e3fbb68c304d15f6cb700d61d7aa915a6751ea3ba80223e654132a20999a43bf408592730b9a9499636c09fa729f9cb1f9d3442f47357a2b9cf15d3103b9bf396c23088f118ede346b5c03891cfa5d517cef8471322e7e31087c4b036abad784bff72a9b11fa198facbcb91f067feaf76fcfe5327c1070b3da6988400756760d2d1f060298f1683d51e3616e98c51c9c03aa42f2e633651a47ad3cc2ab4a852ae0c4b04b4e1c3dd944445a2b12b4f42a6435105c04122fc3587afe409a00b308d63c5dd8163654504eedbb7b5329577c35fbeb3f463872cac28142b3c12a740ec6ea7ce9ad78c6fc8fe1b4df5fc55c1667f31f2312da07799dc870a478608549fedafe021f1cf2984180364e90ad98d845652aa3cdd7a8eb09f5e51423fab42a7b7bb4d514864be8d71297e9c3b17a993f0ae62e8ef52637bd1b885bd9b6ab727854d703d8dc478f96cb81fce4c60383ac01fcf0f971d4c8f352b7a82e218652f2c106ca92ae686bacfcef5d327347a97a9b375d67341552bc2c538778e0f9801823ccdfcd1eaaded55b18c9757e3f212b2889d3857db51f981d16185fd0f900853a75005e3020a8b95b7d8f2f2631c70d78a957c7a62e1b3719070acd1fd480c25b83847da027b6ebbc2eec2df22c87f9b46d5d7baf156b53cee929572b92c4784c4e829f3446a1ffe47f99decd0436029ddebd3ed8e87e5e73d123dbe8a4ddacf2abde87f33ae2b621c0ec5d5cad1259deec2aeff6088f04f27a20338b5762543e5100899a4cbfb7b3ca456b3a19b83a4c432230c23e1c7f107c4cb112152f1c0f30da0bb33f4f11f47eea43872bafa84ae22256d708e0604dade4b2a4dde8cccf11930e13553934ae3ece52f3d7ccc00287377879fe6b8ece7ef79423507c9da339559c20de1c51955999bae47401dc3cdfaa1b256d09c7db9fc8698bfcefa7302d56fbcde1fbaaa1c653454e6fd3d84e4f79a931c681cbb6cb462b10dae112bdfb7f65c7fdf6e5fc594ec3a474a94bd97e6ec81f71c230bf70ca0f13ce3dffbd9ff9804efd8f37a4d3629b43a8f55544ebc5ac0abd9a33d79699068346a0f1a3a96e115a5d80be165b562d082984d5aacc3a2301981a6418f8ba7d7b0d7ca5875c6",
+        "k": "2696d28e9c61c2a01ce9b1608dcb9d292785a0cd58efb7fe13b1de95f0db55b3"
+    }
+    "#,
+    r#"
+    {
+        "tcId": "27",
+        "ek": "f5841d6aea683fdba16308bdab828dddd7735b8b7a0dac6a57eb5134b91d8d6cbd989580411144e1fb5a6a559a7056376210a8284742d22a5881c5214c90023fc910d5d02a869087557900273bb875420b5717cd0b23064aa820cdf372f3e4778d70aeb5d02b6182c4d37110d782b6e80303332697b4c610a384a0c632c0d9484a1d3b5ea921525bec5755c839df942f24a027db50b2d760066d10a117bc9a1b65c448cb9acf3b4f644316e8941c449803f6851a74d832a739b2c0ea9258c7258e98bd3e833d879a6845ec4ecc44b6fa699388135f5e4830f2625e9fa5cc982c578b2593d350b06288a854d3349c24586d3aa2e68726a873b1e5aaa3b22671d8c69aeb180718cb456b942e4b6678e620a00bca310c722ddd499ead9c6b66666a3de39a45d7af0bbb7ab6a0beaf8bbcbba17b1d097abb09a70e410352d2084423ac53ecbb4c196021f01e662a60c68b3bf48a5f0864a25577912f52620ce6347bd27ff68a17d4b92cd7d01b89e3487a5bc2859781f3ebb8b5b4c2d682636c486a000a576a4b63affc05082b5abe3cc0b37b1e586c2107d97157e325a067bb86453414a15594a510dcfb2fe1a0074483120fb83440db1b8c3b41e36364f92056083cb9cf91b39f28cf00f6ad098aa10fdb4b4d9b64ed1338e0d5b7a5169c3d8c0184b19966e54272f765c0337bbd307f8c97369a7a87da44a5bf468db8a9aa5ea598f885ab50174b0f9025a4eb53d2323d202a05265331fd836df8e02b4595458551abed8a3875b83bf976942372cb37296c813acd2c27b41a5514b66ab25759009db38a9d0473d5b7a9a7d6795f1188a079b1792a01141347af2194ca681055d36e954c02d6935bba7c2ef7f4b5e47c8b0a0069f29575e863967ce4c53105230472172fb79e69089d5a7bcaa95784bfa279efe67da145308baaa1a5a303757946c2866b4841660a99c1968b8f7de799abd71806eb9f091397c1cc4171152a6afc36bd733fc6c53545361ab6258cb45c9f1331baea85be4558935984c081f73e4b377e0251ca7c396bbbb81d271bb9f0589e1be3218b0b5840372253aa80a5db79e11199c0832b2433880b68bd84fc02aa3cbbec205ebbc7b050967b4dfb11e2fa63bcf6b7656a8028ab607cb084c21747ed573a055166f82215d7201d5d439a19f584f470b4272962c137b38545309547cec25b09c96459ab7b4da69c8d7b9277bbc4b5568813da904141a011d9b45ac1f181273149f3c46f45ca9735221b97cb528e8ab59c5711a57c603f7a91803254e8cc4a37d84d1f6535e5a791a50145e1e073430810b3ab79df4053538c7db4826a1b428a84553bb881a23507385271b32f854706bb2d3e884e7b391985b39b7ba373071455187b3dd7da75f6988bbd6bc39ef2808c245aec9c024ca16546a16f63831a7b6797951a40894a5e38422f30b87e70355ccbe960b216592d0073f1240c21bb109ae76c9de5b7835bc08ac6601c314a82232fa6f6896bd7834f0254bf112602022844f0cba9fc3d2e3a58edd56ddc498adc9a03fcb43ca138640f85397fd5731f537d6bdc3ac76563d6516f1cf24f84b7c957635defbbb70071621c8b2585380a63660ef2cb6ca5910bad42a1b621cab8c26780d4251dfd1c6370ef12193c3cef0223187a4557bc08f4add382",
+        "m": "76d04f481e68b2f901ecab58b6369a2cc31a9dcced82a1bbd426be0aee266aee",
+        "c": "be483938dac565b129658d168d494e522b52d031de7fcc2fc6d52bdce3f649ab140ece5b25486b5f85d43ed6d85f6bbdc4141dcfa6c03f680c7b6d51484b461f700e207e2e281070dd48aed510a64e6849c462705ae29c566e6f2461f90387daa3108fe9372a2b8d11cc2cd6ca20d9d1cebc31c12b3daf01f9cb67a4db488daf1760a48a29bb4e25a26752ff161b94dfc82a9773a8e5b9f761da751fbba982feab1a7fa3460cf669d5b8b3bef8eda6310009ee7130478222fbcc59cccc248fba6384db7bf5d3b553c8ed134135f09deca3877c9c4b22a478f892317841de917e642b966906886358b09e8761e98eed4ec8309c578502c070e7c4e43cf2ffddf1e4ced37762fc8d5d5c65348fdf01a0cc85314c022040982b94f4cc7fb565eb00c218cc61740062f896e992038f58d02b170dc903bb665b2a6cd724e201c17e646816e2ad528baa20c43bc8ecc090f644256aa22fa3365820fe7c8aa5d168d67a21785d4bb2beee4fd3943fe351a0e94aacf9a5b4859ea97f3a5aecd213169356876b756137697f4c40a567cd960aa0436e61986407b2b88839fa226966271004c1445e057f932bbde1274757a55f2ac8846ff770b1565c746814276487a9d3e454f5fab0d77c82723a114bde9882911a02192da811d9b3dd2b2c7255c15e3346d6ed745c28a1f3c7bf4ce2df9213e6fab9ce90d7941c86e5eba1cd90c9d12b94274d2d2c3af727690a425ba8df2527b26071d5a4c969ea61b646773810513a1aef7f7e6ad5c5922569611ce5e94b674069c7914eb0ccb3dd03842a9c32302efd8caf9a1e4094339d7e857c994fb30c01d7f116ef66d8a502267848e38b080f0e5206da26549fc7ec8f3d713f1241a09941cd7ea71dd86044f909a0d8c67361996d12e2d42c16e08ca7f789df296c00393bfc83e47aa8130454f78de07149d4fbcb304810bedf462542b4b24a1a1d0a9f2b5b8706431287ba88b026e329e8865ab4f0aad74d849f34945edf6b3719e8103b110404a8fbc300592807851c442b506295b2fc76a600a0f9c3b3d796cdcd3c27b10feb1bbbb462bbce0bdd33292cd873d2396b0924bddf8da7408c4e680956dad992e45925e9721985d4547bbe2684f4d4fd220fa87773447bf7a620f979fd529d86d2753f0e77c498e02b1eb55812d9e19ee6c99a61543eef1c124716448fddb46eb2d460179148da2f01aa91c9b9b04a350a63d98b8ceb6005a39734c8f3cf9094d650812e1707caaa98ec35d4acfe425c48e4d8a1bf190da3438684a27564255c8e5d1a97033f87077429711128bdf396deb75e304376fab9cc33eba906d3804819534817ea309e3c260f9697f55bf4aa5c08a8a59eab27bfca0c2301434d7b490312cfb5095bf9948e3554e5409aa74ea7bfefb9bc7ca61fac565f2f7384f5832c2c29fc9f5d1ebab56612c6696dc93ff21db4dcd87f09705ee062db948f68c6d5f7d1886059c87604089adada5db49ea2bf3c3813a71018f1f559b2d72e35a013e3d9cbfda480b43e616b9c7a",
+        "k": "44263624052c18e3aa23310697414499f1c0eae45a1060d84eeb65fcdbcb5733"
+    }
+    "#,
+];
+
+static ML_KEM_768_DECAPSULATION_KEY: &str = "1e4ac87b1a692a529fdbbab93374c57d110b10f2b1ddebac0d196b7ba631b8e9293028a8f379888c422dc8d32bbf226010c2c1ec73189080456b0564b258b0f23131bc79c8e8c11cef3938b243c5ce9c0edd37c8f9d29877dbbb615b9b5ac3c948487e467196a9143efbc7cedb64b45d4acda2666cbc2804f2c8662e128f6a9969ec15bc0b9351f6f96346aa7abc743a14fa030e37a2e7597bddfc5a22f9cedaf8614832527210b26f024c7f6c0dcf551e97a4858764c321d1834ad51d75bb246d277237b7bd41dc4362d063f4298292272d01011780b79856b296c4e946658b79603197c9b2a99ec66acb06ce2f69b5a5a61e9bd06ad443ceb0c74ed65345a903b614e81368aac2b3d2a79ca8ccaa1c3b88fb82a36632860b3f7950833fd0212ec96ede4ab6f5a0bda3ec6060a658f9457f6cc87c6b620c1a1451987486e496612a101d0e9c20577c571edb5282608bf4e1ac926c0db1c82a504a799d89885ca6252bd5b1c183af701392a407c05b848c2a3016c40613f02a449b3c7926da067a533116506840097510460bbfd36073dcb0bfa009b36a9123eaa68f835f74a01b00d2097835964df521ce9210789c30b7f06e5844b444c53322396e4799baf6a88af7315860d0192d48c2c0da6b5ba64325543acdf5900e8bc477ab05820072d463affed097e062bd78c99d12b385131a241b708865b4190af69ea0a64db71448a60829369c7555198e438c9abc310bc70101913bb12faa5beef975841617c847cd6b336f877987753822020b92c4cc97055c9b1e0b128bf11f505005b6ab0e627795a20609efa991e598b80f37b1c6a1c3a1e9aee7028f77570ab2139128a00108c50eb305cdb8f9a603a6b078413f6f9b14c6d82b5199ce59d887902a281a027b717495fe12672a127bbf9b256c43720d7c160b281c12757da135b1933352be4ab67e40248afc318e2370c3b8208e695bdf337459b9acbfe5b487f76e9b4b4001d6cf90ca8c699a174d42972dc733f33389fdf59a1daba81d834955027334185ad02c76cf294846ca9294ba0ed66741ddec791cab34196ac5657c5a78321b56c33306b5102397a5c09c3508f76b48282459f81d0c72a43f737bc2f12f45422628b67db51ac1424276a6c08c3f7615665bbb8e928148a270f991bcf365a90f87c30687b68809c91f231813b866bea82e30374d80aa0c02973437498a53b14bf6b6ca1ed76ab8a20d54a083f4a26b7c038d81967640c20bf4431e71dacce8577b21240e494c31f2d877daf4924fd39d82d6167fbcc1f9c5a259f843e30987ccc4bce7493a2404b5e44387f707425781b743fb555685584e2557cc038b1a9b3f4043121f5472eb2b96e5941fec011ceea50791636c6abc26c1377ee3b5146fc7c85cb335b1e795eec2033ee44b9aa90685245ef7b4436c000e66bc8bcbf1cdb803ac1421b1fdb266d5291c8310373a8a3ce9562ab197953871ab99f382cc5aa9c0f273d1dca55d2712853871e1a83cb3b85450f76d3f3c42bab5505f7212fdb6b8b7f6029972a8f3751e4c94c1108b02d6ac79f8d938f05a1b2c229b14b42b31b01a364017e59578c6b033833774cb9b570f9086b722903b375446b495d8a29bf80751877a80fb724a0210c3e1692f397c2f1ddc2e6ba17af81b92acfabef5f7573cb493d184027b718238c89a3549b8905b28a83362867c082d3019d3ca70700731ceb73e8472c1a3a093361c5fea6a7d40955d07a41b64e50081a361b604cc518447c8e25765ab7d68b243275207af8ca6564a4cb1e94199dba1878c59bec809ab48b2f211badc6a1998d9c7227c1303f469d46a9c7e5303f98aba67569ae8227c16ba1fb3244466a25e7f823671810cc26206feb29c7e2a1a91959eeb03a98252a4f7412674eb9a4b277e1f2595fca64033b41b40330812e9735b7c607501cd8183a22afc3392553744f33c4d202526945c6d78a60e201a16987a6fa59d94464b56506556784824a07058f57320e76c825b9347f2936f4a0e5cdaa18cf8833945ae312a36b5f5a3810aac82381fdae4cb9c6831d8eb8abab850416443d739086b1c326fc2a3975704e396a59680c3b5f360f5480d2b62169cd94ca71b37bc5878ba2985e068ba050b2ce50726d4b4451b77aaa8676eae094982210192197b1e92a27f59868b78867887b9a70c32af84630aa908814379e6519150ba16439b5e2b0603d06aa6674557f5b0983e5cb6a97596069b01bb3128c416680657204fd07640392e16b19f337a99a304844e1aa474e9c799062971f672268960f5a82f950070bbe9c2a71950a3785bdf0b8440255ed63928d257845168b1eccc4191325aa76645719b28ebd89302dc6723c786df5217b243099ca78238e57e64692f206b177abc259660395cd7860fb35a16f6b2fe6548c85ab66330c517fa74cdf3cb49d26b1181901af775a1e180813b6a24c456829b5c38104ece43c76a437a6a33b6fc6c5e65c8a89466c1425485b29b9e1854368afca353e143d0a90a6c6c9e7fdb62a606856b5614f12b64b796020c3534c3605cfdc73b86714f411850228a28b8f4b49e663416c84f7e381f6af1071343bf9d39b45439240cc03897295fea080b14bb2d8119a880e164495c61bebc7139c11857c85e1750338d6343913706a507c9566464cd2837cf914d1a3c35e89b235c6ab7ed078bed234757c02ef6993d4a273cb8150528da4d76708177e9425546c83e147039766603b30da6268f4598a53194240a2832a3d67533b5056f9aaac61b4b17b9a2693aa0d58891e6cc56cdd772410900c405af20b903797c64876915c37b8487a1449ce924cd345c29a36e08238f7a157cc7e516ab5ba73c8063f726bb5a0a0319e57127438c7fc601c99ccaae4c1a83726fdcb5045ed1a82a985ea995396d77272c66ce493289f6110910f37c2741ce47026a6f8261999c6482572b1693912ef12eebea7acf9234fb409f2a6090e6b0bfd895469d0b2a921bb723f87a33ea5465ab90f514b67698c0768b6ca498b022c512fa0875f054aa2265867e31c0e522651e024a07d60dd9f633166921f4126bc2b6aa01cc15a09b85bff8218c5aae95bc1ffb26ae5a137670f04910ca9d7241b6660c394c5455917746a26682fb71a432ea9530e839bdeb07433004f45a0ddaa0b24e3a566a540815f281e3fc259ac6cbc0acb8d62268b603bc676ab415c474bb94873e4487ae31a4e3845c79901550890ee8784eef904fee62ba8c5f952c68413052e0a7e3388bb8ff0ad602ae3ea14d9df6dd5e4cc6a381a41da5c137ecc49df587e178eaf47702ec623780691a3233f69f12bd9c9b9637c51378ad71a831055277254cc63c5ad4cb76b4ab82e5fca135e8d26a6b3a89fa5b6f";
+
+static ML_KEM_768_DECAPSULATION: &[&str] = &[
+    r#"
+    {
+        "tcId": "86",
+        "reason": "modify ciphertext",
+        "c": "74a26c7d27146a22c7eab420134e973799cec1da2df61ae0fa7905a3a47485a063076bfa22d6e4fe5059de0a32e38f11abd63f990e91bd0e3a5bc6e710dfe5dc0f6d4a18147ebc2e2d9b179374d83692c53efbd45f28a2a928c2494f903576c410eb1773895ebeadb119960eebda9c3c710795a6d9b781fc58b30d08107f4e20944a382afb079f31d21724f2c26e6a53412f0a908be7586f2b3d6d7c1dea0270e98aa209244bd88ed68aae01432342ba5f49e015cb476b5b78d15ea77a354cc9e9fd07137d8760be42fd4746c62c02028e7b405ddc95df3d021921cfeddb3d961b957eca302a263dab2dc117beb3e79efacfcf936dfc09fc0d19c358d724fa381ea06ca067c384e944302c3907ab15a1da4b41352692add59b061541f07eff25ec42f46e1a0e370cad06ff3fd997d4d2c5648af762231b382d0593401936cba21551a2ae30d8e8effcf43916b83138bb5e610364429879fa9cdd5b7d3cf2feabaa1dc8d50ce69402e21103e795df7074d1fcf65f8a4e18986d5417780602c63be5a044863384bd3d8ffb685eac567ed8349dcf2ceb702b7375b145729998049d13e2cd466cf2231b9d3a20018ee908f8514a6c6a89df7232f91fcd84b81ebc8bc539e9a37a4324755564be1bf4fa1fb4571e0abbc9b52f9d090c33be599de6c8532c7cb7ec8b4e2d3c07505280e99923865903ffd18bc13b9c8164aa1eae84e38d3f57fdb8801785f105a6a8574bd2fe9bf305848e525330bc2d24f0257e47a4950f433a9233e8cdeba81dbae7d8c1a06d01f70de6ef663207d84952827bab3d451cbea0990007fbdb4240fe899a706f7c1563e05c70be9d575189ef83e0cf76195f6652491cce04f1ce2092170a92e0dd7301246a4c44fc0b4ee6aaa63fc7027840abd2ec25f654589738cd38b9e10b975cfb6c1d2eb4da97736998f84fdddd810d72da3c5ab13507420ddbfaa4f7750c1fae9c7dfb30f40a12aea689fc78da900020e3abb32a364d5c6b3c7544a1b5734a41e95c8314b448cd0b738d829af772a8f81c51adba2d85f326c8f5d6961cf12d44a9bedea00d1df5b48f429b1ce0c15ea5f5bc10b017247ba2c6be922b0563b8e9698677cb6c45ccf2081bf84219d2904c11ff92199f8aefad62d8608e200802c5a07202cc820e9e520e31bf36a83002eca4018b0b3a398801562aa86c77ab0d50a8fbc3768b0a643b97e7f9072168de29b8175999c9aa48d301a3f0303172e9c7d4f16329d5ca9d42397c3982e10c9da42de88bd6c2ab91c1e71e778e58bb8f801f207a88a9b47f9c687afbba34eda6d2899e4fa0008aa2b539711753dc7c07f614e814f683d6c037562ae1fbbe6d7d5fa54b7a6d9451e11b01aaccc3bf2ed64742dd100e0eab2df6cccf937b6d5981eca0e01f3245cf26a72ad1adf066c8f5430d72f509963a657d85e554c14e26e8bec5d5f3ab998c9b29f16b04747d80749b30e51fd2a7f690c22f9986aaf6358d6fab8ded54971b32641de2b258590eeaa6bf1f32324a7c4c983f49466d86",
+        "k": "3d23b10df232a180786f61261e85278251746580bebca6acbad60aef6952be69"
+    }
+    "#,
+    r#"
+    {
+        "tcId": "88",
+        "reason": "no modification",
+        "c": "a5c81c76c24305e1ce5d8135d41523682e9ee6d7b40ad41df1f37c9b17dce78076019a6b0b7c95c9be7af29507b2d5a6987c8ee3259190855243e6e56f5620608c52d96fab103a8700fba1a87dca6078118a0871762c9534c0c0c3978c91c3a01f0f608dcf757815438fe8957c8a859183b1b6721a0865bebc799d4e5c0e7bd3eae4858e6ab6a2e7658ed80d4ed158b036b93fa03afa6ae3136cf3d693c911bcc75905e5b0cb2865b9e9884522a77777613e53111d5a1c7d3dab734ceb03657ae0c89763e99471054776bae7d51b0e73a5bb35aec30ff6bc93684916fef1162586452f426653e2ca844d5744307ff9aeb287a6447783b21a0e939c81421d631f5dcb452e51ed34e3dad1cf504e0a3b0f4711a8dc6499d1691d109569336ce1558a4c0a464e2087ea8f9e3b18f747ef61f4576aeb42b17cadb7f0fd84da8e3a6f471d95edfa65be9e6c9f6ae756a22a4f1a5c543c26ba7bad88e16d5f5b7e12e2d4ca34b3a64d17f87ccfc4ff8c5e4f53752a077c68721e8cc817f9ff24876170ff2af89fa95855a5b1de347c07fddbcfe7264aa5ed6401491561d831538f852b0ed7b9e8ebaffc060284f22d2baee56fa9f6d01432a115a2d6a64c38ae0a50ba362fb57b53e3e855b83ce8c42274045599f65fa6a8921d85f94ed230b516712db6fd2ff28b3a3371d9be058ae75c2fa591b7ec3c3daa1f7642bc26c324c08090607e6662154db37cf747967a1f9fc29089f570ebe60eeef89fd24481028c85aef1dc3b09f22cd3691bbbb821c7a8a0f35ad12be1dd199b977048f3d48c16bb2ca94cecb8928770d5bb329a0327e0b286faa1c65281031a31c84f2edc9c04d475ed4e128e51efa97d0148cba6c95f674c589f301c265bed708e9ad8da3c5cecbdeeed35ef1e253132ba89920d786b88230b013bcf2dc92d6b157afa8da8592cd0743d4982be60d7c2d5c472ab9fa7f4cc3d12b0ebaf0abe555c75805426844dd9428643f84406a1b8d6faedfd8ae6e73a72772a2159acabd972aeb6f7de091ac5fdd7f49a3dc6641cdf62446b4b04a31f73b80a62f80a404a8cb18ce3e65480ef7b52bf0091117e5d08eae1b0aabb72e6dffff76f6e44bbd7ea570d6604bc2e74318bafa315a38861aa1b21afb2a53f2614f1d640075984ae62e2fca1d1b4db369f15705ce7d4df8ae98264501051c0def21d645d49625af02ca428d9f0c2cd9fbaeeab97e8e9151662b6992b4c99ab1b925d08920363373f76d3fdf0828caa69c8b1bdc6f521df641cf1c8a4e7ef0c23289a4e2cf18acebbe4c1e68369bd5235120142ecdd1a73811e2e533a647d7aee16daa03b683639dcf1e1f1e71cfaed48f69aec3e831733da19cebec1ddbf71cbae0800f2f6d64a096ec495d62f4344f7aa5621b322353a795aa099ea3a070272d053d4653a20cf210eaaf12cae6023d8e5118df04b384a44d1edb91c44989ef7ee57f2bf81a24bdc76807da967ee6525410c5c485067efc3d39a9ad42cc753baa59a1fd28af35c00d18a406a28fc79ba",
+        "k": "dc5b8888bc1eba5c1969c21164ea43e22e7ac0cd012a2f26cb8c487e69ef7ce4"
+    }
+    "#,
+    r#"
+    {
+        "tcId": "89",
+        "reason": "modify ciphertext",
+        "c": "0baf0f6e91ecae3199f4921631891a14c13b418b53384992da3a8dada7deffb9e1e5f559d27344b60be81ecd01cab1e316573d571ed46f59248f4023db0282207e730549cdb60e793e4cd17ac6f2800e2d1ffb83477a6fe1d73992682123ea730c63269db13088d6da46d086ccea2176398eac663270b8b2f337a55e19f4c500de066b5441794c2d0ccadfe5abde7d93fd7d6468bc4f925633366d9316788b90b110a4d99485e7e578537a267744fb266a4f243fa02e3a81da67ed477923b36b37be21dda21eb51dca1f0ce41652145f4c542b2e5c922617033608246bbe2b5250a368804abdb2ef6c31c491ce3dd852aeabf6eef1530f4c99286b4b595d57cf3a99580b59aaa2c55e080b5230ea19cf2701d21a37fefd6f9709657a21add063ecbc197b5ad068be502a2e090d83f4156b671e46617be6d6a17d0425fac565c4a0e48966e9d900cb2c2b0d296e0baa9d6c5e0514cd78834053058a97d3ddf81529079858737440812670e818c9891681d350ecec93dae389d534a5c78f01811917061cac0003d2bea390eb63fa0fe9babcd7ff302d4b66567b2bfa67b20f962847d010aa4193cbe9f8cc1b14f8b237c22675b298a8376dfb6037bf7cea36bdead5b505111f67730824b4964815d00f63ee98b9bea0f2f47cc007d5606ed7f967cb15ccd4afbc99881cfd297bdc2a509ed3cb320df58dc4a5bcd1cb100b9d6418cb8e0f40def293da2370ca729b0fab071fa6aeb0f3f5d1925ab2df732f98ddbff23d5411e4921a1c506f2f93251e822c4cf83998b000fe65ed386f5745b1d4d91ad9f98b45e713c8d944409e9d354f42fdb9749a5107c8831562e683498c55e1475e552ac10858ab9867bf8003fb88b3b09f6e8ad8e94ce82e342b1780d68ec8565fc0684ab6c798bf09fa65be62c37a0862abfe99d7dbe1431b4cfe007b7ec7930b14f6d161bdcaae2217d69d9fdbb4f882b9f464f8642acd9ba018b93a8e3a965194accd96e661cf0cf4a2662076e20e8bc319693f1953dab93feb9bcad666832df42f250fadbcfaf742d68642021bd6ffd97720c3e5ab86d82ce8b14c0289dbf51b50c13cfcec12a3922dcd2de8473329aeb23580b22f9c36b4f06d6579751be0593120f808f0e145d94d1ddbbe1d489b744cf6c35964c3dd96d95fb693543c69766877da80bde8acdf62c366d0a4a553187461f671376f7e70f554965d57760cdf5c6f6366e33b3bfb550cc1f93d98d250f90d7d36bc01581c49417546bf6bba9d10d41c0a008855f321547bdd5a6cfa2a2516f71415b5bc2d5fa1b9b79fdc7f2b78aa113375ec1717f0f273bd8cbef59139518a4e8a67db4d071257000336bb07497f72faac2c1fc0f553b2eba53475f466a2b36afe0b72b4342e995c544e6e14ff7d327f80e7ac6f65190045f380b5978f50e33272484626266125a39da08b46256624ce34223bb17299b8b8162753812f2644c9a13c51430b02abd188dd1a4547c920ba27cdaf145bdebc6f45eee3f2f55553010f7b35ac63a3c7c61c",
+        "k": "dcbeb5e4e8b14bd3031d5916ba03258119a5dacdac850cb483bd7aa80b7038d8"
+    }
+    "#,
+    r#"
+    {
+        "tcId": "90",
+        "reason": "no modification",
+        "c": "2513de1e55ed0e862614587fe47f308c90a1f426470ca1293bddf7b9ddd6c368dc152f45c71354904ed48e15a1cb449b4c45d0f201ed5c7d3a047a72f080265d66c47d39469097eeeabbaa3b07ed1f1aeab80c7d24552fa8889c674a5d4840289de6b0fa9a222e693708d1f252dfe8b993956883c07067c1c0844ef0beb49f63534d21d471d6b727ffc59477f9e89e5beb2af0cbeb052f003414da4070008753cfc0c6d0fa9d1c15388fe5886eadd3474f28e4682c0e01784a037dc3799330ea380767b0d0b6edfc9730e04d1039548a6f83889098522ebab684da6fe26a4a6891d86d40fcd9a24f743d74b23b1596810727c81bb3f9f3badfae9997949ee0e24987fa182a00d73dceadf667e90e5ae76a1f83a91fcea78c96269f0c9501f1d4ce682506a7ea89302a1480e18cdc1f6d57b5312eaf808895b20897e9a782f916cd75b4981da1381f14eb1ec248b27f0e6966a0cd75414a735928b2120615d88fa57af5c40e61750f0a0f8e605747e7c32d5a23f14124969c072e949c8475e3108d689d2d20797fe14618811e9a497fd26b9e71355852d4b36340b61695e3745f8d07644ac6e2c18b3fc276d4d19db69a7cf26086f172e2bce1618a740a0c739fd504f72c2a72adb5564bc85dab4c9ce790d78d14d3bd242df04106d96ce7c3b392cced9b99df359fd51f306cbcbd5b46b8487cd7b7edd3c5c02965c84630da1b6b8b317fe55f7c79e05cdac9e863023daf470e9c3fb8c01fdf3aedf2193bfa69a806e2e70151abcf96d31cf6a317c059ca8c7d456a8e5ebaa6c1283a319f188aaa80d8301e321754e5fb4e0b25594b01bc5f82ff25b064c766424d658459efd7a20b65db181811e6d5a4bd153f7066bd7757d2d417d21f83d7c4cb6a0703a42032f0fd198d9d8b0f91b359fbe908432c3286e1ef9d601702157efbab68e0e7136bfc90d26bd8a9a7018de4c4bf05ce465f917d20a4f221a4ee78813a1e8a117c8470929701ccc201a85e7f18b6bc96fe80b1e074661525d3fd0ce2565ab11155dafe4d3410328d6dbb4dd99a84fe96283d32322522b88b3aa2a11c0324b1d5556ef408d37b0df802d163fe38d7c38916a26810bd175d22762353c3175dc6040c899e07a339cd4ddbd4d5549e02c0d691263936a9f63111412b60aa9f57486334e40b2bc1b8eaa487a094e45c3f77f72ea741ce225ecbe2b5e4a1fc080070a658fdf9e2b388722855267b30d94b63c3ed35d475b7eb22e3d2462aba9cf2a86b738ebb270ab29708a2614a557e33a620b507286e5d4ca57e2ceedb9965ff1c3e1777f980cdfb1445bbe0b6acba0216980f962fbfabe265b3adfe8641088287468827ae601b6a165deed39c0e8773bf2046bbf63634bdbcaf98358d25fde475781733dde8c6d6383d13b6d48ff1b65e2ff13aaa9cccfc3c626935c5270f9e23a71a87cf2bd793cb175d23ea5fbd82c18a1822428c32db9e31b94be3144abb00f5acaaa431c17386719c3ff47c38720b1ab01889dad877badc9fc716f648fc8b551f",
+        "k": "2c37c49e94df715b3c09e63a39e04db8d26bd2b9072c9b21076bdfc0b608534c"
+    }
+    "#,
+];
+
+fn decode(values: &Value, key: &str) -> Option<Vec<u8>> {
+    values[key].as_str().and_then(|s| hex::decode(s).ok())
+}
+
+// Tests d, z -> ek, dk
+#[test]
+#[allow(deprecated)]
+fn test_ml_kem_768_key_gen() {
+    for tv in ML_KEM_768_KEY_GEN {
+        let values: Value = serde_json::from_str(tv).unwrap();
+        let seed = [decode(&values, "d").unwrap(), decode(&values, "z").unwrap()].concat();
+
+        let mut rng = CycleRng::new(seed.to_vec());
+        let (decapsulation_key, encapsulation_key) = MlKem768::generate_keypair(&mut rng);
+        assert_eq!(seed, decapsulation_key.to_vec());
+        assert_eq!(decode(&values, "ek").unwrap(), encapsulation_key.to_vec());
+
+        // The decapsulation key is kept as its seed, which expands to dk
+        let expanded = DecapsulationKey::from_seed(Array::from_fn(|i| seed[i])).to_expanded_bytes();
+        assert_eq!(decode(&values, "dk").unwrap(), expanded.to_vec());
+    }
+}
+
+// Tests ek, m -> c, k
+#[test]
+fn test_ml_kem_768_encapsulation() -> Result<(), ProtocolError> {
+    for tv in ML_KEM_768_ENCAPSULATION {
+        let values: Value = serde_json::from_str(tv).unwrap();
+        let encapsulation_key = GenericArray::clone_from_slice(&decode(&values, "ek").unwrap());
+
+        let mut rng = CycleRng::new(decode(&values, "m").unwrap());
+        let (ciphertext, shared_secret) = MlKem768::encapsulate(&mut rng, &encapsulation_key)?;
+        assert_eq!(decode(&values, "c").unwrap(), ciphertext.to_vec());
+        assert_eq!(decode(&values, "k").unwrap(), shared_secret.to_vec());
+    }
+    Ok(())
+}
+
+// Tests dk, c -> k
+#[test]
+#[allow(deprecated)]
+fn test_ml_kem_768_decapsulation() {
+    let decapsulation_key = DecapsulationKey::from_expanded(&Array::clone_from_slice(
+        &hex::decode(ML_KEM_768_DECAPSULATION_KEY).unwrap()[..],
+    ))
+    .unwrap();
+    for tv in ML_KEM_768_DECAPSULATION {
+        let values: Value = serde_json::from_str(tv).unwrap();
+        let ciphertext = Array::clone_from_slice(&decode(&values, "c").unwrap()[..]);
+        assert_eq!(
+            decode(&values, "k").unwrap(),
+            decapsulation_key.decapsulate(&ciphertext).to_vec()
+        );
+    }
+}
+
+#[test]
+fn test_ml_kem_768_round_trip() -> Result<(), ProtocolError> {
+    let values: Value = serde_json::from_str(ML_KEM_768_KEY_GEN[0]).unwrap();
+    let seed = [decode(&values, "d").unwrap(), decode(&values, "z").unwrap()].concat();
+    let mut rng = CycleRng::new(seed);
+    let (decapsulation_key, encapsulation_key) = MlKem768::generate_keypair(&mut rng);
+
+    let mut rng = CycleRng::new(vec![7u8; 32]);
+    let (mut ciphertext, shared_secret) = MlKem768::encapsulate(&mut rng, &encapsulation_key)?;
+    assert_eq!(
+        shared_secret,
+        MlKem768::decapsulate(&decapsulation_key, &ciphertext)?
+    );
+
+    // A modified ciphertext is implicitly rejected
+    ciphertext[0] ^= 1;
+    assert_ne!(
+        shared_secret,
+        MlKem768::decapsulate(&decapsulation_key, &ciphertext)?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_ml_kem_768_invalid_encapsulation_key() {
+    let values: Value = serde_json::from_str(ML_KEM_768_ENCAPSULATION[0]).unwrap();
+    let mut encapsulation_key = GenericArray::clone_from_slice(&decode(&values, "ek").unwrap());
+    assert!(MlKem768::check_encapsulation_key(&encapsulation_key).is_ok());
+
+    // An encapsulation key with a coefficient which is not reduced modulo q
+    encapsulation_key[0] = 0xff;
+    encapsulation_key[1] |= 0x0f;
+    assert!(MlKem768::check_encapsulation_key(&encapsulation_key).is_err());
+    let mut rng = CycleRng::new(decode(&values, "m").unwrap());
+    assert!(MlKem768::encapsulate(&mut rng, &encapsulation_key).is_err());
+}
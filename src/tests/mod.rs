@@ -4,6 +4,8 @@
 // LICENSE file in the root directory of this source tree.
 
 mod full_test;
+#[cfg(feature = "ml-kem")]
+mod ml_kem_test_vectors;
 pub mod mock_rng;
 mod opaque_test_vectors;
 mod voprf_test_vectors;
@@ -4,12 +4,13 @@
 // LICENSE file in the root directory of this source tree.
 
 use crate::{
-    ciphersuite::CipherSuite, errors::*, key_exchange::tripledh::TripleDH, keypair::PrivateKey,
-    opaque::*, slow_hash::NoOpHash, tests::mock_rng::CycleRng, *,
+    ciphersuite::CipherSuite, errors::*, group::Group, key_exchange::tripledh::TripleDH, opaque::*,
+    slow_hash::NoOpHash, tests::mock_rng::CycleRng, *,
 };
 use curve25519_dalek::ristretto::RistrettoPoint;
+use digest::Digest;
 use generic_array::typenum::Unsigned;
-use generic_bytes::SizedBytes;
+use rand::rngs::OsRng;
 use serde_json::Value;
 
 // Tests
@@ -21,6 +22,51 @@ impl CipherSuite for Ristretto255Sha512NoSlowHash {
     type KeyExchange = TripleDH;
     type Hash = sha2::Sha512;
     type SlowHash = NoOpHash;
+    const ID: u16 = 0x0001;
+}
+
+#[cfg(feature = "p256")]
+struct P256Sha256NoSlowHash;
+#[cfg(feature = "p256")]
+impl CipherSuite for P256Sha256NoSlowHash {
+    type Group = p256_::ProjectivePoint;
+    type KeyExchange = TripleDH;
+    type Hash = sha2::Sha256;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0101;
+}
+
+#[cfg(feature = "p384")]
+struct P384Sha384NoSlowHash;
+#[cfg(feature = "p384")]
+impl CipherSuite for P384Sha384NoSlowHash {
+    type Group = p384_::ProjectivePoint;
+    type KeyExchange = TripleDH;
+    type Hash = sha2::Sha384;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0201;
+}
+
+#[cfg(feature = "p521")]
+struct P521Sha512NoSlowHash;
+#[cfg(feature = "p521")]
+impl CipherSuite for P521Sha512NoSlowHash {
+    type Group = p521_::ProjectivePoint;
+    type KeyExchange = TripleDH;
+    type Hash = sha2::Sha512;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0301;
+}
+
+#[cfg(feature = "sigma")]
+struct Ristretto255Sha512SigmaNoSlowHash;
+#[cfg(feature = "sigma")]
+impl CipherSuite for Ristretto255Sha512SigmaNoSlowHash {
+    type Group = RistrettoPoint;
+    type KeyExchange = crate::key_exchange::sigma::Sigma;
+    type Hash = sha2::Sha512;
+    type SlowHash = NoOpHash;
+    const ID: u16 = 0x0004;
 }
 
 #[derive(PartialEq)]
@@ -381,6 +427,1253 @@ b57aec04e64593007f98a405
 ~~~
 "#];
 
+// Generated by an independent implementation of the draft, which reproduces
+// its ristretto255 vectors above, with the P-256 suite of the OPRF
+#[cfg(feature = "p256")]
+static P256_TEST_VECTORS: &[&str] = &[
+    r#"
+## OPAQUE-3DH P-256 Test Vector 1
+
+### Configuration
+
+~~~
+OPRF: 0003
+Hash: SHA256
+MHF: Identity
+KDF: HKDF-SHA256
+MAC: HMAC-SHA256
+EnvelopeMode: 01
+Group: P256_XMD:SHA-256_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 32
+Npk: 33
+Nsk: 32
+Nm: 32
+Nx: 32
+Nok: 32
+~~~
+
+### Input Values
+
+~~~
+oprf_seed: 4ba30f12bc6e4409ef9476b895dadc643b3db6f8512fd336012aa645e18
+7120c
+credential_identifier: 31323334
+password: 436f7272656374486f72736542617474657279537461706c65
+envelope_nonce: 17c1f097d113421f8de86372d5ea1a3195e21304f564ca8c112eb5
+647f409525
+masking_nonce: be76c081c3992771eb434947564b1134d7c8f4aa08d3fe9477d9114
+437d26bfd
+server_private_key: 013b64cbf9c844b301bd2dd9ab89070776ee89e51775115ee6
+bc4b1a8c200e35
+server_public_key: 02b59757bc943ad102e35a24bb01161ee5e6b2b59c092041383
+efb9ad29a167efb
+server_nonce: f6801c448f125c2609e032263a0c2bf207a2f49f3bd07bcd45fe9abd
+577c7f63
+client_nonce: a52c6efde50ed3c5eaa86fe7d5c7ec1432ed67a23dc8efdfa6e80c91
+0df582ef
+server_keyshare: 03a069ec6cf6628fdb35a41131a20341f67bef28e1fee462f0e7f
+3a0e660d36d06
+client_keyshare: 020f2db09cf5b89af6050f1f0d91481a1c3ccee51d29ff4bce448
+e5b5adb32536a
+server_private_keyshare: e8e07732d8737f4e3ca560d1af8703c1af36a7867424d
+d7e264dc1dab2eced41
+client_private_keyshare: 0a42438f583f593841ce45cfe5cea6ebbf6a964f7a6ec
+0c7b60283a17ce2eb67
+blind_registration: 926a86a0a24ad89f03af46c0f9011710acd4e587b5a1d10e20
+372260f832724b
+blind_login: 9238a4e4429125d6789c70ada34927909ca602daf7c8743825d2d9b25
+f74d235
+oprf_key: 412bfc7792d285fc88f34ebda1089107cfeb8fb7cbaa2149ec34df4d014d
+bfb3
+~~~
+
+### Intermediate Values
+
+~~~
+client_public_key: 03efbf65aea583e97e0ad20b9e8631795ba1437e7254e2a3b88
+4aac0fba9e5b298
+auth_key: ad7dcd5d27b26a609676800e0c2966ba1ab31f045557ec0fca477899d0b1
+6916
+randomized_pwd: 107cef99d980c30177770cb27d4f2119cff9951c5a7777cff7782a
+a6c8f75246
+envelope: 17c1f097d113421f8de86372d5ea1a3195e21304f564ca8c112eb5647f40
+9525e2136b2d36b6d76198baff39b5b392fe68db6b61594690a4ae555868b133f4f9
+handshake_secret: 405e78861cfea69e6940948ffdae13511b6b11e2e5cd6c8227df
+35f22beac7a7
+server_mac_key: acc235406c1cd6f8aae84f804dbd8e1f8c7449ffa5b3151feb3609
+c5e739c9f3
+client_mac_key: 0087459ff9eec2070da55a3aba60375ed04a05ce9fedfd72e7b2f9
+5f9cfa4efc
+~~~
+
+### Output Values
+
+~~~
+registration_request: 03c23b188a36764c3ef436ae89ea5bd16237df7d09b2b77a
+21ffb8bc57d08e74ad
+registration_response: 023c71995f09bbccac7744aac5da8f323352ff6101f5455
+33a4f97eb9ced23959a02b59757bc943ad102e35a24bb01161ee5e6b2b59c092041383
+efb9ad29a167efb
+registration_upload: 03efbf65aea583e97e0ad20b9e8631795ba1437e7254e2a3b
+884aac0fba9e5b298a431cb800a602625696b9dc78a8ee2b4c7b95741a4e30a61856a5
+1dab3dbb6aa17c1f097d113421f8de86372d5ea1a3195e21304f564ca8c112eb5647f4
+09525e2136b2d36b6d76198baff39b5b392fe68db6b61594690a4ae555868b133f4f9
+KE1: 03f36a5ad0dacde8aa6f7f71c2e8f60abc54bb795f2e7a197f58965674f68e8eb
+ca52c6efde50ed3c5eaa86fe7d5c7ec1432ed67a23dc8efdfa6e80c910df582ef020f2
+db09cf5b89af6050f1f0d91481a1c3ccee51d29ff4bce448e5b5adb32536a
+KE2: 038e4c4e60170d21096cac859cee28f824c3d4343d8c0d23c1add5b3d780e08d3
+abe76c081c3992771eb434947564b1134d7c8f4aa08d3fe9477d9114437d26bfdf0e54
+38007ca50a3e66c59916dff5edc03cfb4ab0756651c65c5830468a1f3e0c6ec9b861bc
+ee21d4f9fb605d15ee05385278b01aa3e53a7c5db58f1bef68030b54287d390267c8c4
+5c43a79146f43a71d6356ffb17646314c578c0ee41f83cd16f6801c448f125c2609e03
+2263a0c2bf207a2f49f3bd07bcd45fe9abd577c7f6303a069ec6cf6628fdb35a41131a
+20341f67bef28e1fee462f0e7f3a0e660d36d0685f712fdea23699721cd00c5b741134
+182123624ebb8c1ee42706854afd1683c
+KE3: 5efaf9d5670ca42481030b597a3f56632299deb42074b330d4e2c4392dc22457
+export_key: 66b463fc92d5bf48d47a766e51e92615ce2f7856d02f08ff4e64336050
+60782a
+session_key: a2ae9c6e3585a96a15a615bb8b5f142e81abca2e605b2452041d67bad
+17a64ca
+~~~
+"#,
+    r#"
+## OPAQUE-3DH P-256 Test Vector 2
+
+### Configuration
+
+~~~
+OPRF: 0003
+Hash: SHA256
+MHF: Identity
+KDF: HKDF-SHA256
+MAC: HMAC-SHA256
+EnvelopeMode: 01
+Group: P256_XMD:SHA-256_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 32
+Npk: 33
+Nsk: 32
+Nm: 32
+Nx: 32
+Nok: 32
+~~~
+
+### Input Values
+
+~~~
+client_identity: 616c696365
+server_identity: 626f62
+oprf_seed: d50a1fa54114e2c1d7546eca2b41ef0a46b7e68bcfd71c671ecf37c3027
+a49ed
+credential_identifier: 31323334
+password: 436f7272656374486f72736542617474657279537461706c65
+envelope_nonce: 4b5d763ef75f25abbe04f7c374ad9697c1ba42dd85b77da2474c2c
+f5752d35e9
+masking_nonce: c553822b34af07aa428c8894cfe4ec30f3aae2d59621f6ba2d562e2
+378045860
+server_private_key: 8c245ddb9a0908bb0ab06ecf60a0ff4ac6c413381ed64342b3
+ef03f2a181f262
+server_public_key: 026f70548a605c40012bd6bf30a2cef0c8c5bd12dba0bf3d57b
+9af3e0fedf2c87c
+server_nonce: caafe19cdf330f0fcd01a68252a589f6dd916928e2a75506303b87a1
+1e7a5b98
+client_nonce: 7b10515a36f85ccf674c01c4c242b9b378720fc696e18d7acdc2a674
+19d96a56
+server_keyshare: 0242549ed37dba443559c0e6a4ba92efdea06391c4adfdc43e64f
+8775e515b6561
+client_keyshare: 03f27790f74946b32ee2ad61a690ff8222a56f8c6b844fc2eaae2
+a5c6fb741df9b
+server_private_keyshare: 2085a644e24832d2a7f5c7f36c60f714e9bb750736272
+515cf833caefe319a89
+client_private_keyshare: 91cdf2aedacb72407e081028786e79bc7220102a8dca1
+1fc435b2a7552d8feb2
+blind_registration: 3f184d06a913e0ffe293287eca4ee60ea5d18c5fa44096e193
+3abc21decfd478
+blind_login: 7bac9e04d97351656a8f9582e8107bfc69d33be37e7cea911f6a784c5
+d21d634
+oprf_key: 1b5bc905cf34607afd59f0eb97240d191c490b29d6e6b8b3d26ff3c4d1f1
+e451
+~~~
+
+### Intermediate Values
+
+~~~
+client_public_key: 024a706191332ba437422a68d5ea6809d9837bd66fe79814c63
+d803f61b69c8530
+auth_key: 235db39d7ce151a1a49c4910f9a4eed09441a7ce451fe71fce8d9153a285
+546e
+randomized_pwd: f9ab0c36272282cdade5cae70ec1cc47e52a9adca195e31280799a
+529bc4c891
+envelope: 4b5d763ef75f25abbe04f7c374ad9697c1ba42dd85b77da2474c2cf5752d
+35e9e07463533664f9440518426fb30dd289cf70c8d308499b725a89c421114fd5d6
+handshake_secret: a6733f134fdcc43dcec889f0292c56f9360143f3036c4ebb4873
+f7a78a42f1ef
+server_mac_key: 8c009b3fdd29074f0eaa23e34a3238fa86964ba6284685f2934cc7
+5966ed32d1
+client_mac_key: 9da9113e97659d652c7dbfc8a4c9bc7e59e521a4e924034e11a99b
+70cf84ea23
+~~~
+
+### Output Values
+
+~~~
+registration_request: 03bf9be6670554cd8cf689e9095d20de15a4667b461eb403
+7089a3ab8675245106
+registration_response: 03ded785a1c62768452d7d329cb757096125afd176e855b
+19dd7b4e6b5b1262b00026f70548a605c40012bd6bf30a2cef0c8c5bd12dba0bf3d57b
+9af3e0fedf2c87c
+registration_upload: 024a706191332ba437422a68d5ea6809d9837bd66fe79814c
+63d803f61b69c8530ee61af6af9bf54c653ae34ba5dfe0e9fce24da6311683f39b0b81
+eacc8d2bda04b5d763ef75f25abbe04f7c374ad9697c1ba42dd85b77da2474c2cf5752
+d35e9e07463533664f9440518426fb30dd289cf70c8d308499b725a89c421114fd5d6
+KE1: 03c959a346803b9c053261c0dbf158e347cf3419596274c0d648404cab62ebb6a
+e7b10515a36f85ccf674c01c4c242b9b378720fc696e18d7acdc2a67419d96a5603f27
+790f74946b32ee2ad61a690ff8222a56f8c6b844fc2eaae2a5c6fb741df9b
+KE2: 0299eb9a0c72b79e5f2edad6501db81319df2379d02e1d9f1317f41739c13fe76
+0c553822b34af07aa428c8894cfe4ec30f3aae2d59621f6ba2d562e23780458608ca30
+d65e256fb94f98271ea07402b9d78baef6999a0f9f4f30402aea7b0c2384bf564f16f1
+cc376c8308be4592101c89c3304f26095bd0bd00c7da3f503ae46f2cbef41324a9e0b2
+01319be588cba82906d8552744f8be102e0f2a6e0413eea5ecaafe19cdf330f0fcd01a
+68252a589f6dd916928e2a75506303b87a11e7a5b980242549ed37dba443559c0e6a4b
+a92efdea06391c4adfdc43e64f8775e515b65619a481a09f1de9dd407b7de2f0d1bf7b
+08f1e9b831e21e6eb5a988bc3d9858e65
+KE3: 40de9681f4aace9a11dd4234c1d11996f1d388a942711fad693dc2985da06499
+export_key: a236021925bfcd5080bb10e2a1f5dee163ac11a096fa91e71dde243e62
+8df9ba
+session_key: 4914ad4065f5ddf031ca4aa176ded2902ec69639543f77fb7d0079c69
+eb39aec
+~~~
+"#,
+];
+
+#[cfg(feature = "p256")]
+static P256_FAKE_TEST_VECTORS: &[&str] = &[r#"
+### OPAQUE-3DH P-256 Fake Test Vector 1
+
+#### Configuration
+
+~~~
+OPRF: 0003
+Hash: SHA256
+MHF: Identity
+KDF: HKDF-SHA256
+MAC: HMAC-SHA256
+EnvelopeMode: 01
+Group: P256_XMD:SHA-256_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 32
+Npk: 33
+Nsk: 32
+Nm: 32
+Nx: 32
+Nok: 32
+~~~
+
+#### Input Values
+
+~~~
+client_identity: 616c696365
+server_identity: 626f62
+oprf_seed: c47507568bc49c0688b274eeb3ce44c2589c8f603891227ceaf2cd5139a
+bbe8e
+credential_identifier: 31323334
+masking_nonce: d550ee0f507174cf34df8ca8ca020bcb8cf3bdae1ec021de11a1e98
+24819f973
+client_private_key: 540f3a0733a2314d94da3894ec233c80138cb717cb0b1b980f
+c595a1ec3d8de6
+client_public_key: 0367c545263164df1b926b84e585b7a29674e1d68d1df547895
+f9e4315ef9b90b8
+server_private_key: 676f32008dab3a83431f4f124354973b72f7f2c5fc7da50f08
+44950242acf5e3
+server_public_key: 0397c62aac0d93625087bad565f3ed9a7485626c0af14c5925b
+523d7209716fb9c
+server_nonce: 417c308c4310293d9c3ad612dc416a57dddd2b0d48591c79ee2ff94f
+40cef640
+server_keyshare: 0397c394f4518ce8a27134f93ab4be05102e9c22559345a78613e
+5f2b1adcf95de
+server_private_keyshare: 310779321fa2107820fbf3c44ccd4b82661a31ac29e7f
+83a4bc51e718543fc99
+masking_key: d73132d6d3343529b6a5837666228e67ec37382bf024b60a502c82105
+6484999
+KE1: 03c959a346803b9c053261c0dbf158e347cf3419596274c0d648404cab62ebb6a
+e7b10515a36f85ccf674c01c4c242b9b378720fc696e18d7acdc2a67419d96a5603f27
+790f74946b32ee2ad61a690ff8222a56f8c6b844fc2eaae2a5c6fb741df9b
+~~~
+
+#### Output Values
+
+~~~
+KE2: 029ffe8a5182275e88b3d9b0707859e15f44f762af986b9d7847b39662e255b1a
+2d550ee0f507174cf34df8ca8ca020bcb8cf3bdae1ec021de11a1e9824819f9735d1c0
+a99821ce80ddb1759998bbfc5d4c3c733058f04af1616e640438534a7e7b3161edd715
+d5d227a87aa5d150a1b488a1893f6e8bbd046648a6ae85aa65ab84be9b76255551605a
+e8efa6d8d9ef70b9fef9cc9ab8f34a3823ea8b7fdff7880a8417c308c4310293d9c3ad
+612dc416a57dddd2b0d48591c79ee2ff94f40cef6400397c394f4518ce8a27134f93ab
+4be05102e9c22559345a78613e5f2b1adcf95de32d59845a760dff86d93e098172bf84
+9054e588c88a70b4bd20150252bf672d8
+~~~
+"#];
+
+// Generated by an independent implementation of the draft, which reproduces
+// its ristretto255 vectors above, with the P-384 suite of the OPRF
+#[cfg(feature = "p384")]
+static P384_TEST_VECTORS: &[&str] = &[
+    r#"
+## OPAQUE-3DH P-384 Test Vector 1
+
+### Configuration
+
+~~~
+OPRF: 0004
+Hash: SHA384
+MHF: Identity
+KDF: HKDF-SHA384
+MAC: HMAC-SHA384
+EnvelopeMode: 01
+Group: P384_XMD:SHA-384_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 48
+Npk: 49
+Nsk: 48
+Nm: 48
+Nx: 48
+Nok: 48
+~~~
+
+### Input Values
+
+~~~
+oprf_seed: ae0aa481db18d09bfe3587c89f32b730c436e5191305b844097cec7be43
+60ed0fac45efd803df7281d5466a9c00bcde1
+credential_identifier: 31323334
+password: 436f7272656374486f72736542617474657279537461706c65
+envelope_nonce: 107c2813bddeef0be6682aa4359a59727b4cdc3ad88d7c59d6ed2d
+62b053ab69
+masking_nonce: 6caa04bd37cdfb5aa62f4045a2437e0cb5be746a5e40978798f5490
+88136f953
+server_private_key: 045db79d177c9dcf7428a48293ba8237553f37615998df6e75
+1fcda0e4f7bd7ea5080d25f86c1a975f1b07096b7b229e
+server_public_key: 0218b35b6f654764f82cfd3b8b7850fddcb71881fa30a81cbf9
+cff96ee1bc4e653caf6f5960821c5128df5e10980de49a2
+server_nonce: d84857ba1ab57cb7eea9402163d615777b41495a11213e64dc41f831
+23107d0f
+client_nonce: 273d98a0537d08680f92dfe90f6786e1e9c89035e18ae1c56e4c69e2
+b76173df
+server_keyshare: 031ddac460bec37060eeba5fd03d695d7284b39b52f6c968a2497
+d7b47e3f1341bdbf1624a0f9fd6d20a11813b709adb1a
+client_keyshare: 03fd1c2afcf544868182f2db1503604e478657ce8c5798cf46be9
+d9caca0929b96c11e247e0136f8a529c00a7f2ff6faf8
+server_private_keyshare: 4439f9a359f187871bd95bdb868c7ddf466b8c1caaf56
+d890ec25e99f8aaf56ff43043d0feff91ccdf9a41c2044d7dfa
+client_private_keyshare: 3e88d675ea2aecafe4185d2f69f818f21d3123b1eb7be
+a508d736004786aac742b22835d5b4d3cecc70523bb5df08ff5
+blind_registration: b88dfa954fa7077c91b3ae6d05d109dd7b31dc347a423a3d61
+d7bd0f3cbaba7ad92f3c337ed5e3126c7023e8c991cf27
+blind_login: 312b7dbe7015e586b0e9eebed7f284e408bbb451ee1808297c83c6ca0
+e3299d20f4cb00fe9ba799cbee26e1dfcb21e9c
+oprf_key: 37595f0543f41bb3cba9ba98cc8c75109dec62bd4bb347b3d54aa18d769f
+6fcd4734be51bebf5441e5c16080fe51816d
+~~~
+
+### Intermediate Values
+
+~~~
+client_public_key: 03938ca2f621250e3512e21044544a919ff9c6a3bf28c0ad4e6
+172621592fcb8a045c105bc0bfbba7fa1d003f098a2d1b0
+auth_key: afdb710719f69d0632e97da1a02ced708ac6fc47d50e87a29732da03e5c4
+b65cfc26809f721535c0632cd8d33170cfb9
+randomized_pwd: 4f100b52989298a7762d01437c775716c7c42ada6b526877e40c0c
+cb4b60ed4b3d178a8b1befc03f63ed42de3207dff2
+envelope: 107c2813bddeef0be6682aa4359a59727b4cdc3ad88d7c59d6ed2d62b053
+ab69799d7f8c218d33935c8ad455664d8a989d0bf14152699dc6276c956b02f88c5a35
+09b0c4b0369b1e07383d9ffd602411
+handshake_secret: 75d0b0e3d7297d599a6d8253778117aea450363a0445a762499f
+9caef9f833ee1857de61b19888f7a298f0f6ac30c9fd
+server_mac_key: 07a3d9813674ecb1f2b639e0bd15af8b04c522e23943317291052b
+27d3526fc0dd9dee1519db69f313ce97aa2b02300a
+client_mac_key: 35d15ed3dc179e3b529bed9d4ece756034d11a7eba2545f7ddba37
+d38e5165504a0579cb1e7313ef88978a12519831da
+~~~
+
+### Output Values
+
+~~~
+registration_request: 03ac5e929461768bbb5117cd9cc9865259a9a2ee418cd983
+c423a444367e3543130cc5f57e24d5789fa17d53636cf0be31
+registration_response: 026929ed9ae4c3129614500e3d1f5f49434b0e19ddb4328
+3ef12428a3c0ad5de42328c9d1e36a2fd85e2f6e9b22c9e71940218b35b6f654764f82
+cfd3b8b7850fddcb71881fa30a81cbf9cff96ee1bc4e653caf6f5960821c5128df5e10
+980de49a2
+registration_upload: 03938ca2f621250e3512e21044544a919ff9c6a3bf28c0ad4
+e6172621592fcb8a045c105bc0bfbba7fa1d003f098a2d1b0c95ddd661d17132eaf03f
+653547d6e1c465edc0d7340d6e970c4c917ad7fb43739bab9455aeacdbcb7cac347334
+bbded107c2813bddeef0be6682aa4359a59727b4cdc3ad88d7c59d6ed2d62b053ab697
+99d7f8c218d33935c8ad455664d8a989d0bf14152699dc6276c956b02f88c5a3509b0c
+4b0369b1e07383d9ffd602411
+KE1: 0292a9ee64bdadc26ab7f7d145ec01120b5ac5e40c78afad7d5589190cb7d0849
+6d9c139b40971ecde5a0175f2adfc64df273d98a0537d08680f92dfe90f6786e1e9c89
+035e18ae1c56e4c69e2b76173df03fd1c2afcf544868182f2db1503604e478657ce8c5
+798cf46be9d9caca0929b96c11e247e0136f8a529c00a7f2ff6faf8
+KE2: 02e40a813c66c28101d5df8d3b492717b7a27816322bb563da8c5893889dae8b0
+d53d1e1de111424a77ec3f403b687397b6caa04bd37cdfb5aa62f4045a2437e0cb5be7
+46a5e40978798f549088136f953ec6e8d9c2799babce2a6c5752101074cd134ddd03a3
+658295590fa375cbe0b5c44e19f6cf7a7fa8dc0a774afe6c2838141ed117105cdb7a76
+a6065f35a61f34a3501924a1d9e820c0cf03134f75ae3be41a291ef7f4cd9b01ce50a7
+09490b3d46eed2f5f24661b8d56e7e7b8d6b78b41f662418e1d3f740a2af7da0d67b6f
+e7bdfd84857ba1ab57cb7eea9402163d615777b41495a11213e64dc41f83123107d0f0
+31ddac460bec37060eeba5fd03d695d7284b39b52f6c968a2497d7b47e3f1341bdbf16
+24a0f9fd6d20a11813b709adb1a4d8feaa1d92414649a269773681d6413cc8a0998eee
+87dde088215166ddc0da9683b2a7749b11d0d14e7eb7cc831412d
+KE3: 1a7a0b74032e7352ed17062591ccc058f47e0194215465351b46252d2c68b1875
+654cec8e8d2eede22419148c1134068
+export_key: 03866a991eb2ab7c1670bd8a912a6ba6d145a58eee41e057554fc286aa
+6c6e255a78198393d6f41fb425f2b5437db6ea
+session_key: 0b0bb4f90472558830deea397d57ae13933c4a39fb3deca85afa64728
+e9ccd9c5fb6f11d383891413279f8ad48786564
+~~~
+"#,
+    r#"
+## OPAQUE-3DH P-384 Test Vector 2
+
+### Configuration
+
+~~~
+OPRF: 0004
+Hash: SHA384
+MHF: Identity
+KDF: HKDF-SHA384
+MAC: HMAC-SHA384
+EnvelopeMode: 01
+Group: P384_XMD:SHA-384_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 48
+Npk: 49
+Nsk: 48
+Nm: 48
+Nx: 48
+Nok: 48
+~~~
+
+### Input Values
+
+~~~
+client_identity: 616c696365
+server_identity: 626f62
+oprf_seed: 888a7e6c3089076a7cfcad528e425f3a45f15a0267a56011a865e5bb00d
+111da422486127a7a1dc5476f14fe29458834
+credential_identifier: 31323334
+password: 436f7272656374486f72736542617474657279537461706c65
+envelope_nonce: 3bcdb4c01eecd317798b98ab54bf83dfbaa57228ac8f4d44f6d754
+e09085f390
+masking_nonce: 74224efe2e741f7262a985111f6ab31a152a4c5c0e5fcfe20bb15ad
+fffd7f103
+server_private_key: 272cef01cfb75b25a00969d0af2184bc81f6f3c5f477eace91
+19ad25834d33ddaddaf2753b3312efe1ad57581c0d1072
+server_public_key: 036171a2ca0b2feb05dff575285926be824f9f84b98aa1debc2
+f4b24416016924e8a035bfcdd528fb6882938abfb67fc68
+server_nonce: 0057732af82afaf6fa969f48ccc1d631646fecb7422c1c4821ac9c33
+129246e9
+client_nonce: c75858d7c3765680b938be3d92c65436b4b28faa413ab29e9a1120b9
+1a01780f
+server_keyshare: 03259bf237cb5637076d2d3181cd7eaeb9c2e812d92983e10ae4e
+cba67b3b101e0db1ae68850537407bedf3aab564f2620
+client_keyshare: 0222840983b68e9161e260de13616795939d2b60b22fb1467ea79
+f73ab1e3bbadd78ba7aa32131c126d8edaade08601fee
+server_private_keyshare: cd09e52e11697ec2299ee8663a265ea952c4a3bd1b1a8
+9b2bcb2d3beacb3a7253fb8d9cb97e84ed402088da4ec6f6753
+client_private_keyshare: ec245a79ca2c64d31586f20de99bddf80364b9d4f0acb
+8776c18123dcf907f41f2054772d7cebe8d65fe6e9cd3b499f8
+blind_registration: 00bee3ce746a3dc410600d445e82c093dd7f6c493e445fb9aa
+e7d158756e2d7b6f5a827a61b433ae5fac565a66110533
+blind_login: 0b3266b249c2f4d948ab1e4af9e1a68e190bb0a35a1bd4cd0cc5912e7
+2c179bfff1070a3da34ac4a461349fd67d4c448
+oprf_key: 9716d29a1953b2dc764d5b3e3862b001aae6cd167d64b7b380293ee7554b
+724bbac99e2ffdaf33f929ed395648d1ec27
+~~~
+
+### Intermediate Values
+
+~~~
+client_public_key: 02d2056f774cb965f4dfae6aae4fe4b8825c41b23befe7a8aac
+97e8d2c91e7c7fe2b69807577a1e8e8d7ae012358045c59
+auth_key: b18e8b21ddcd9c954f628691a1795f219fedeaff69f3e55ada0e60bb83fe
+9f605dcd7dccbbc0fe41246d3397858a1b9c
+randomized_pwd: b126c80d03116637ac07939d7f8dfa15437b77be85fd14aa75161c
+b7badbf1ed5f241375267b02064fce50b82f2eb77c
+envelope: 3bcdb4c01eecd317798b98ab54bf83dfbaa57228ac8f4d44f6d754e09085
+f390ab457df7f46a8c28aca6c5b6b92fc47347f810e14f42e5a29262d58f8c8e685db4
+1a2f9768b98cbbef0d32d0c4048c40
+handshake_secret: 02819f00138a8c621d59ee9a324d18a04650ad935f86fdd82297
+c78a1ab1ee7c0bc285640b0bdd39eee09a51a2b7737e
+server_mac_key: 23a993267a97040a98ea739b08d77582335d270abfb1904ee3720c
+d4a2669799b616fbda1361afc50fdf218d37da927c
+client_mac_key: f1f75759fbf739f626e57e877b0ab4460919187d99e3e0cc34b885
+5a4f7c62ca193de13fdcca2d9e8c85235a86ff72f6
+~~~
+
+### Output Values
+
+~~~
+registration_request: 026c7ea1513fca4b2b8ccc357f6877d20aa42e43b5b2382d
+8fc3b15606970d25b0f1f42de8b0a5b190f7cc173b79ca6a9c
+registration_response: 02b6c043a1d289ef25528d71e5ab781388afbe38d7ed8c2
+702db3ff6326079f12ee8a7c3a75d1638678d6e1e551afce171036171a2ca0b2feb05d
+ff575285926be824f9f84b98aa1debc2f4b24416016924e8a035bfcdd528fb6882938a
+bfb67fc68
+registration_upload: 02d2056f774cb965f4dfae6aae4fe4b8825c41b23befe7a8a
+ac97e8d2c91e7c7fe2b69807577a1e8e8d7ae012358045c5930539537fdf1a52ceb46c
+93f07a808bbc7837f0524433116cbf5372fcb929586d67ecd3cd0a929e6f4f5c30f562
+082733bcdb4c01eecd317798b98ab54bf83dfbaa57228ac8f4d44f6d754e09085f390a
+b457df7f46a8c28aca6c5b6b92fc47347f810e14f42e5a29262d58f8c8e685db41a2f9
+768b98cbbef0d32d0c4048c40
+KE1: 0223e775859ebefebd90c06c30f0f489ad530afae6a1a54375b0acd5dfd9fcafd
+55ded4b8af5a99336ae94b647d220911ec75858d7c3765680b938be3d92c65436b4b28
+faa413ab29e9a1120b91a01780f0222840983b68e9161e260de13616795939d2b60b22
+fb1467ea79f73ab1e3bbadd78ba7aa32131c126d8edaade08601fee
+KE2: 02c20bd08663669e6286b2bd05ee6822156e19b66b6420d771b6a3fa0567cd0fa
+feaccf69b96f39af6368dbb43e1a0d7e674224efe2e741f7262a985111f6ab31a152a4
+c5c0e5fcfe20bb15adfffd7f103feacdee9a056ad1c0633a6072421b2f0add396694d3
+7db661a4682126fa407ca0d6522ef5d3045d56cf8d9085355319ec1aab1428865e83a7
+3571e9c4808b04c8c539db89edcc8847444b766c2a153fa3c6e24e91d701c1cf0fc4a3
+8cdaa7ff821d41abdac71f38739088ee0df65116c88f94e8d754e4a76e2099f7df1623
+bbde30057732af82afaf6fa969f48ccc1d631646fecb7422c1c4821ac9c33129246e90
+3259bf237cb5637076d2d3181cd7eaeb9c2e812d92983e10ae4ecba67b3b101e0db1ae
+68850537407bedf3aab564f262055ac43b9254814576605922a6626c4f9f28b1c27f47
+45de57f32eae517481aad85370caddadfe295938ebea31fbafa20
+KE3: 830dd50b969550993b69f57f13e37f94270eb61e0a28493f47023e0725955fc89
+4fe08c4b76d8d4ebd9718f6c0b176d8
+export_key: 2e417f3498368e5c5b36e568b422077695a66e1811bf3b225071e77615
+1d9d387883e2ac5a13e1bca663935b8ecd15d5
+session_key: 2a409900799a9954fbc8af169642822a046b1cfaad9096e6e14fc222b
+8a25a03ef7d81d383e47f143d79e9f0d780e5e6
+~~~
+"#,
+];
+
+#[cfg(feature = "p384")]
+static P384_FAKE_TEST_VECTORS: &[&str] = &[r#"
+### OPAQUE-3DH P-384 Fake Test Vector 1
+
+#### Configuration
+
+~~~
+OPRF: 0004
+Hash: SHA384
+MHF: Identity
+KDF: HKDF-SHA384
+MAC: HMAC-SHA384
+EnvelopeMode: 01
+Group: P384_XMD:SHA-384_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 48
+Npk: 49
+Nsk: 48
+Nm: 48
+Nx: 48
+Nok: 48
+~~~
+
+#### Input Values
+
+~~~
+client_identity: 616c696365
+server_identity: 626f62
+oprf_seed: e94c87f9203ca3d02e2bf387861ba13bfa23980155c62d1d5e8b071511f
+dccafcd4c0099cf7e6bcb22c4db0c2d0fbf0e
+credential_identifier: 31323334
+masking_nonce: 6df87df7b86ea0396b76ad2a955d164402cf06218e644225d19c796
+d60b42500
+client_private_key: f1ec7e89faf3623e448112efd3b3a9083bd6db5204d5ecc566
+7ddde1b28937008c089d65960e071f693be50a1d356307
+client_public_key: 02b0525c4221125e35e4c408e2aec5ac735c0f1ab3d6ce53a76
+cee8f38df3f377a9438304081cce5347bda4fe650cfaac6
+server_private_key: f389b4f8d67e8ef279f1bb5ad1eb782f300977f224e38087d8
+4a8110802389f8a026b13ab73a617aa1ede897e50279cf
+server_public_key: 02d076f2aec4215ff27c7500ae19a879efd76f0690e4ef34536
+4bde0b55f0a572ade616522d1338741d5b9a3455726790a
+server_nonce: 32cfae301b0382446728fbbd89c28e073eed37fb6b94a9f6314fd556
+c99e0505
+server_keyshare: 02e1906e143ea106f12215fd38929b26ddfdc9f58d2faf69ae29a
+912db91a37040dbcdd248141bd29eff3262f640e8e759
+server_private_keyshare: b2f5dee1684ebf706187e5e12c7ae26d50ac4e696a3ae
+d9d4d5291fc7398329f76f8a4c857abeba7c070e50b530e0806
+masking_key: 611e8152f391afaa775fbcf00531cf1b26332658dc41cfc8c0f94dd10
+6d35ac883fb9528e65968b2320ad26ee233c4d2
+KE1: 0223e775859ebefebd90c06c30f0f489ad530afae6a1a54375b0acd5dfd9fcafd
+55ded4b8af5a99336ae94b647d220911ec75858d7c3765680b938be3d92c65436b4b28
+faa413ab29e9a1120b91a01780f0222840983b68e9161e260de13616795939d2b60b22
+fb1467ea79f73ab1e3bbadd78ba7aa32131c126d8edaade08601fee
+~~~
+
+#### Output Values
+
+~~~
+KE2: 03136cf279c48ac541bafe11c89a2260979bc58b93317afb2e95c096e57a298f2
+bd04a104f822233f872f6be6ddaf8114d6df87df7b86ea0396b76ad2a955d164402cf0
+6218e644225d19c796d60b42500552f23c23a2b13bbb96c4bc49ea14c862fafaefd6fc
+d1b8ecf3198ce9c83a526ea5dcebb7c15980a6d321671223761d84a9b61530323258ba
+890689a32e8d40806e90ad6560c5c0a7a4bca926cc5656a5448f5a8901924a1fbc8cd7
+4fe87a4cab2ef111e43dcffd63b404e91a21d28dce59d829eac094e7453f1304dbd818
+6821732cfae301b0382446728fbbd89c28e073eed37fb6b94a9f6314fd556c99e05050
+2e1906e143ea106f12215fd38929b26ddfdc9f58d2faf69ae29a912db91a37040dbcdd
+248141bd29eff3262f640e8e759d5eaedb72c88467031071529ad7c63f3dcf318f43d4
+9eeecb296130183241310c3083b3e45cffa6abc111d744816d572
+~~~
+"#];
+
+// Generated by an independent implementation of the draft, which reproduces
+// its ristretto255 vectors above, with the P-521 suite of the OPRF
+#[cfg(feature = "p521")]
+static P521_TEST_VECTORS: &[&str] = &[
+    r#"
+## OPAQUE-3DH P-521 Test Vector 1
+
+### Configuration
+
+~~~
+OPRF: 0005
+Hash: SHA512
+MHF: Identity
+KDF: HKDF-SHA512
+MAC: HMAC-SHA512
+EnvelopeMode: 01
+Group: P521_XMD:SHA-512_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 64
+Npk: 67
+Nsk: 66
+Nm: 64
+Nx: 64
+Nok: 66
+~~~
+
+### Input Values
+
+~~~
+oprf_seed: dd5c4a0605e590d2d1d44d220d6a44d8a4b51f2752ba2a860cca5addc56
+f7a98c078389faa959ae71dd01097964a70e7a6243b3cf12fc3e9cd50864c8cf517a1
+credential_identifier: 31323334
+password: 436f7272656374486f72736542617474657279537461706c65
+envelope_nonce: fec414f3a3b481a4f1b5554ef59fe0c4f9ab28b89550c81171642c
+3ce179ddff
+masking_nonce: e26d8fe6fb4fef60c87ce8a387dc3c7629b1ff0dd6a0cfd4f778c58
+af5c66a78
+server_private_key: 004ea31f2c950ad613a613ad755c7e3c62e5f8e08f700619d7
+8cc4a609322e486d07a3977cfc0b7597166c6609900ace0fbec0a7fdd88a5475299800
+cd8228e6ba97
+server_public_key: 0201f0b86640f019248a27bfdfaffe25ed153933e2c00aae93d
+2720c7b8bf43b982d167e7872456f65a8a86dce705a44ed33615d5ffe75fa4f46d9aa3
+48a30e977da08
+server_nonce: aac17d9cc92f07de8632ce28d8db2572708f841695f7d487dda2ee01
+c709520f
+client_nonce: 2ed94c410f52e6f589473d6710dd0e8508886f0a63ca74fd53633a1f
+5f78c9fb
+server_keyshare: 0201f9ee97479002a6cff321386a9a77dc1bc2383e2ce50c7f4b9
+d89dbcf8e92ea18da08a35d9ef886ee1f6c042566ab822b152f98767ebefdecfcf0edb
+d61094d8505
+client_keyshare: 0301e8c309f2f0ef377a6949e1299af9041b5b321e88848abd3e4
+01418da37363ec16864304fae0dce3af0f2d291e63661f17e6914745dc2839cb2aaadc
+947d814c148
+server_private_keyshare: 018e66d8fc4de7f1d33815525e1e4672da21532a46848
+2dc85f1f6e20c9b6bce85eccfad8540d299e296a12fe694a925f573ac6b7ece22f7a93
+234cccb68a063b473
+client_private_keyshare: 00306ce6f5a4e5d417d15f80f7ceebb4c655c84c36673
+6398cf6eed03523879e39d839e71ac6000deb7dc0183a3200db9ca81225d21ba9f742a
+abe45e15cffae81d7
+blind_registration: 01502f5f4f50f9c25dc4f90f52890c256b0843d1c77ab04162
+92c6a2dca951e6234b90cdafb95c7d801c16f7365966d39e22b0f85ca57ba4e2a108bd
+a97f5dac973d
+blind_login: 010ce651618c3dce742548a88e2d7f58af1f45d397c196c84a31439f7
+60abbf55cd22028351eaf07bd21b4af7bf071ee64717bcb1c00bd70449e16a30a9e3cb
+2ecb5
+oprf_key: 013f15a45b7d52e791ec2c02ade001a01331b1e4676a005b515ff19dd2d2
+1e7a50bad741a0ebfd163fb8d4459627a243769b9a7df335371c2d94e86ef5a96c8b1c
+f0
+~~~
+
+### Intermediate Values
+
+~~~
+client_public_key: 020021fc069a55092f2e02daf507e21407de11cf756be980f5f
+8f5bc4c41d488f02cbfb8355e28a15b6aae25a8cb34590afe2698091ed64b8af45e8aa
+8b2bcb016ba62
+auth_key: 416c589bc8042306c89166dfc07022f21679172d8aa487e96b044af2613c
+054fef474433acfce3b54c654f27af64f4184b72ec1a629e0c5dd2d198ae6856dc92
+randomized_pwd: c71b75d2f17aacf273bc5ec69e7a64bd851038d12260638eb4ec61
+1bb46a01529f80710ca976c53ea61ac2457a57374ffbb3c21a5e01ee67188317794640
+aedd
+envelope: fec414f3a3b481a4f1b5554ef59fe0c4f9ab28b89550c81171642c3ce179
+ddff0276beedc3f1aebc2f74641e342c8554d638ed60e6ba3b1a5f8cefbdd90fbcd9dc
+5d00ef2bb95e29c854a7c13bcc9a80ef4073965f6bf5aa462e7ed0197959bd
+handshake_secret: 2cb8a153ac32848db7f6cee4fdb2fb387411e7cbe0b754fb9780
+1994fd0c795e50011dba849b9448c83941845597daa2bc65cd2bd19e222459eaff7f9b
+78ea14
+server_mac_key: 15a0b33b659f24c3dea377d6ec0504aa3c148116d02e658261b299
+a2ec198424c341759096b498ba8656a740fa4c719ef2923cddd5fd27d0a51dbf1ad77f
+b471
+client_mac_key: 554d2e64294c316e042a8e69a311ad165fbb1b855ce1ee7af7773c
+a031fa4bee7044ba1e7da3a783caab69b027f146efbb09cd0476c93a16983bb503d544
+53c6
+~~~
+
+### Output Values
+
+~~~
+registration_request: 02004f81d575518357ce0a4a328b14051a70509ac13bed87
+950f35fdc93882c05e991900507cfaee24588a6c8aca39414a439d81ea2cfd4bd2a275
+13323707a4485171
+registration_response: 0300491eb4506a236c360003e86b0dade606044adfacb83
+b0a753c228f160d6fe6c92f42ebd212479a239e1c8470d5875d4fa01367972692faa46
+f297daf2f83b089b30201f0b86640f019248a27bfdfaffe25ed153933e2c00aae93d27
+20c7b8bf43b982d167e7872456f65a8a86dce705a44ed33615d5ffe75fa4f46d9aa348
+a30e977da08
+registration_upload: 020021fc069a55092f2e02daf507e21407de11cf756be980f
+5f8f5bc4c41d488f02cbfb8355e28a15b6aae25a8cb34590afe2698091ed64b8af45e8
+aa8b2bcb016ba627c517274387329e08cd3982ffb18d1afb4b846e6d5f6305d67a907b
+0deffdc184794474393900094920d8984bc5fb3dd19f884e57a7e6ca4a1c80a26410a9
+083fec414f3a3b481a4f1b5554ef59fe0c4f9ab28b89550c81171642c3ce179ddff027
+6beedc3f1aebc2f74641e342c8554d638ed60e6ba3b1a5f8cefbdd90fbcd9dc5d00ef2
+bb95e29c854a7c13bcc9a80ef4073965f6bf5aa462e7ed0197959bd
+KE1: 03019ae9b211b1a691fbde8ee98956af6e0ffa595877d5b46dcd99c8b8112dfcc
+59c728399957ea14e1a61221e2200c3000804e46a6a3f15ec1fdeb3d86f8eef82586c2
+ed94c410f52e6f589473d6710dd0e8508886f0a63ca74fd53633a1f5f78c9fb0301e8c
+309f2f0ef377a6949e1299af9041b5b321e88848abd3e401418da37363ec16864304fa
+e0dce3af0f2d291e63661f17e6914745dc2839cb2aaadc947d814c148
+KE2: 02019a88e5d3adabfe7d6fd229747a30bfa4aacb5696f00cb123375c6c5559b99
+f4887dd19c71cfd039e7e3d984b5315d8cbd78dbdd8c5e365f1bf2569ff69cb991e3de
+26d8fe6fb4fef60c87ce8a387dc3c7629b1ff0dd6a0cfd4f778c58af5c66a78638d655
+df91d8d5f66fa98e60ca197b354a479312ecdd82065bf69d2682489a91b9387b0ac761
+70e3344e77e0bd3cbe724a71036acee1ced17b2c6cb1744e9f305b552008883557bd29
+20d880c446b56ea5b61a53f7a06bb4a25b296d45058e1bd9a52da8d4111e70c0a8284d
+5124698d29daf783fb9f9b8ce9d84c1f63bb48d490d0843b7a26f3755579ec31416989
+c35f6597ec839634d303f49a59ffd679ef538e6aac17d9cc92f07de8632ce28d8db257
+2708f841695f7d487dda2ee01c709520f0201f9ee97479002a6cff321386a9a77dc1bc
+2383e2ce50c7f4b9d89dbcf8e92ea18da08a35d9ef886ee1f6c042566ab822b152f987
+67ebefdecfcf0edbd61094d8505b5f518ad56babede7db111b5835ff6272652310cc63
+9a6a0e530de66bc2ec68882cda99761e00f89abd13b0f228d91fe03cde54f823982efe
+ef1a9849ab9cbd9
+KE3: 9f33b0b56faade42adf30aee63cdb8520862403a60515ae828cc30e78440a550d
+1984c598717017560685d8665bb24f94d69f41d89ed82b1f9b84b2b66c54959
+export_key: 2228dc25e86970a9c8b05d6ffef9bbc9e5bddba923b898760b2496ab6d
+5c4c6394f282768fb09d978b224f2af4b5d3ad84dc42a996ac96be4eb81195bd3d4ff5
+session_key: c3fdf1d4c2eeb063a35f37053d9ae41b04257288836c5a36f9b8d8a22
+d057284b020b343585c616f5fffc9ff9a14865b95465554386c2b16c63d8c9bb23ce26
+f
+~~~
+"#,
+    r#"
+## OPAQUE-3DH P-521 Test Vector 2
+
+### Configuration
+
+~~~
+OPRF: 0005
+Hash: SHA512
+MHF: Identity
+KDF: HKDF-SHA512
+MAC: HMAC-SHA512
+EnvelopeMode: 01
+Group: P521_XMD:SHA-512_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 64
+Npk: 67
+Nsk: 66
+Nm: 64
+Nx: 64
+Nok: 66
+~~~
+
+### Input Values
+
+~~~
+client_identity: 616c696365
+server_identity: 626f62
+oprf_seed: c2012702bbfc18393852af92ee4d2155a3c53d4d8c298cacb7b7c1110d0
+bc0e9b724b0df0da3fc3f36d2a6cd601bc16974ad7c9b2e7b28e70a0bda06d8840e5d
+credential_identifier: 31323334
+password: 436f7272656374486f72736542617474657279537461706c65
+envelope_nonce: 4e1c220bd2cbfd6fdb6e6ed540a098a4bd5d07bab2c189670893ed
+cdb18d723e
+masking_nonce: 143ed60ac1e3bb7d6c76928ecf3aefb47fbf88e8287261590d954a5
+61e0ed3c1
+server_private_key: 018da6364b6c679e88f9b5f98a977798bdd1c3aef5c445440c
+27d9360f6bf80cb652c8f05e614cdf4e4ad3032db0348ab71d2bac03249d12842b054f
+acba7c0d41e4
+server_public_key: 0300797b35dc6e2495092f162eb73002fe24d12ef8b3febb642
+f1240c90cdae3c9a79123baa785e8dea6d97b53b75533910d9a4517cdfd5e21134454c
+0023c60f0ce39
+server_nonce: 1c5593f45785e3d24218508e12a9ea4ffdfc4a6fb90b7ce1a0b84e18
+1869ccca
+client_nonce: de681f8ca738b5c18675ab50c98d5565b6ad31db64a83b4dfc6ab503
+ee16df9a
+server_keyshare: 020061013606c82012fd0834a73b9d857c0a8e6f3a27c15268fde
+f8f9c0cb339ba1f947111fc16e36bace2327ae782092ef7c700d1be5ec36eaa4d876e3
+6c8ca399b92
+client_keyshare: 03008f5db287e4a92f68d50c7e4ea17d8921b08318ef1cf2980b5
+cf33186f3916b97d3b37d2956ce60cd85d8b92d55259fbf708e73d68fd602ff46f50fc
+4a27c4f1642
+server_private_keyshare: 00a1f4668847ef85ec2085581a2215f91c9eacbabb3bd
+9f958e5e12f0360972fd84c263eba78ad68b7ca522e044d7e3ac27b248b71ea92114e2
+a58112731e668541a
+client_private_keyshare: 00f07e48f39f3f4923299cdffbe27fe249b9938247534
+44a0c1c479f043f120b6f5f6bb8637425a88a76153b4f5ff516d6531a6ad5cbbf815b5
+b4a4bb80a674d7729
+blind_registration: 01e3a95331d831ae87f6ce035608a70c9206840876aea531e9
+0caa89b5453fc291f5b3e5fcb593725d96ee05421685b4d326a8d3fcb2778d035fa303
+6ac091d71115
+blind_login: 013f246b37931ae7d89c5a49c2ffc3cf62e2802d3bd56c60bd918a5b7
+03aefd59a44fde37c2c8c9f88fb737eaa67c94e31e7d1194266423846b90b3be295a6e
+ede01
+oprf_key: 0081d1085f0b6036464771a484378a4a7709939f41eba6aa777a9e128704
+14f900ce835463c7cf8dd08b7ef242b905aead676c877b39bfe6b5a9b500a4215c62a2
+28
+~~~
+
+### Intermediate Values
+
+~~~
+client_public_key: 0300821fd45e787244210b2cd8c93cda66b61023e8e64c1ba39
+61f6313dbca8ef00c208dd1f4100154ffed57fe83c1e2cbf975bcc2064478fadf47757
+869d61b977af5
+auth_key: 6ebe63e261d8488d5c5ce96dbe20dc06a18e50aa1d3d9a6ba62bcc1489b9
+4940f7fb002428bc656ed48364b10ae6fa68375be12338b56eb9279b0b82baf709d8
+randomized_pwd: fef6ed01fd6a5a44375f0ed7ca4432b271be0ccff270d4fd2f0d6a
+2fb9aa5350000e8fe49364cc8e6f83bdbabd8a6c8de20fe0099d4449d988c7f9fc9706
+edcf
+envelope: 4e1c220bd2cbfd6fdb6e6ed540a098a4bd5d07bab2c189670893edcdb18d
+723ef4e08a8d6a4083069eb56bc662b4f797b2062da5aa75c76e6e790a09bc863773c1
+6498929a3ef96f65660416d05b07dc358b13fe5a350ff0f247ec77938a26b9
+handshake_secret: ab0fbb7e18c6c06547481f9b1380a989af62fbdabc9922f1a0b7
+70896e4afeb9cfcbc21a8cadc5eee5cf877d63a4b7ae1d9a77bfd1ee2e3af73aff64b9
+b68894
+server_mac_key: 728ebe3b233a5f2757fbbedd5b58764b3b0a3218ce2845cf5fdbe3
+a9eb7c718a9cea34b793f4cf70a62f1eee6aca5ee42bb5c7fedd3fc6e2482ef59a07a6
+888d
+client_mac_key: e3339b2e63ee2c8766d1052c959c79e9f0c2d73e3438c462b50d38
+bded77e6062d8cd96452ad88095b5177419c9070c03b172caeba399ce2e16a7dfa4474
+86c7
+~~~
+
+### Output Values
+
+~~~
+registration_request: 0201f6f6ece2ef97dd6acace023f99d777701edfac62e0d3
+cdb73ece6a43ea055e18822b56bff3f3ce9daa343252f23baa42ae86f078c83dc1f021
+ac76d00ed2751324
+registration_response: 0201f97ac692629c063b72ff9d2b8354c7bae50fc01cd6e
+4fda1373f058a4c2ffe57133d9fa73b74eca5f9e1a4f2f1976a820052fb6ed616671c9
+0e67bf7b9cda543e80300797b35dc6e2495092f162eb73002fe24d12ef8b3febb642f1
+240c90cdae3c9a79123baa785e8dea6d97b53b75533910d9a4517cdfd5e21134454c00
+23c60f0ce39
+registration_upload: 0300821fd45e787244210b2cd8c93cda66b61023e8e64c1ba
+3961f6313dbca8ef00c208dd1f4100154ffed57fe83c1e2cbf975bcc2064478fadf477
+57869d61b977af5c4190a97e73723c40114c64a48a44cf31af9777e4574234f4dafca6
+7bec21c7304b3673cf114258c026afe315735da8027e68dfc2bb57c55d3c90e03d130d
+4d14e1c220bd2cbfd6fdb6e6ed540a098a4bd5d07bab2c189670893edcdb18d723ef4e
+08a8d6a4083069eb56bc662b4f797b2062da5aa75c76e6e790a09bc863773c16498929
+a3ef96f65660416d05b07dc358b13fe5a350ff0f247ec77938a26b9
+KE1: 0200ec2cee68294246cc592f16672e32717c7fb0a417973e3e63ba60a4e1bbaf6
+3d515eab041e2e07cf5d3cbe7c40874cd6780db90ef1bf1a679d04a5453c59fa21c24d
+e681f8ca738b5c18675ab50c98d5565b6ad31db64a83b4dfc6ab503ee16df9a03008f5
+db287e4a92f68d50c7e4ea17d8921b08318ef1cf2980b5cf33186f3916b97d3b37d295
+6ce60cd85d8b92d55259fbf708e73d68fd602ff46f50fc4a27c4f1642
+KE2: 0200c8ffc2f2dc87108e1ef9193b5f1e4ccb0ff0909e14ce6cafd51e16b71d7c5
+78ee455df8b6ba25c3bef6125f0c6bca14d9fcabe01727721f405986253bb58e62c601
+43ed60ac1e3bb7d6c76928ecf3aefb47fbf88e8287261590d954a561e0ed3c155af1a1
+cf98d8882e77e695e35fd5238494911942699f4a1b2aece8514acd07af8dc7c3dce582
+e937d6eeee04b85a3dde3b166c3b3ae461d4e127226144af29e10904b7300da7bf8e79
+3bac4eda5038157a984682ce6c9b25b0b1436ed69e35485f6b00722c463923409eabdf
+3bc1b567cf197c7b1c05fa4ae68cca1aee479e1dc806e967be0c4f052b02363b920501
+140d3b27c4323c9587c6752e4c737c5e7b9a4a31c5593f45785e3d24218508e12a9ea4
+ffdfc4a6fb90b7ce1a0b84e181869ccca020061013606c82012fd0834a73b9d857c0a8
+e6f3a27c15268fdef8f9c0cb339ba1f947111fc16e36bace2327ae782092ef7c700d1b
+e5ec36eaa4d876e36c8ca399b92874a163cc2190471184a122a47fb4558d85b217f8f2
+698ac2755a8901e63c24cee3764174ef145fa2f8b5a5eb6d52562a6fadf52b96fdf9b5
+3fbcd8fd7bd73f0
+KE3: d2cbb0d8a088c72c19e6a4e32e2dcf507a193f80523c1aabeef1ca2d57d33a998
+34bff4b71cf780007f441920447f3ca8bae8b8583fa76709381b291b1783275
+export_key: 702b92a175952367aaf35d61afc68c1ba059bcbd1e190934066bc035df
+a7b6f63384d2768f89f16f18cb16dcf166a25dc9acd60bfabb6aa378df62a42a5067ee
+session_key: c7e93fb1298a00febee3e998321734f7784ac4d690171709718303426
+7ea0e1a449cbf70e30d1586e1e08677f6e65b4ae5756ea7c33704da48b47de942e940f
+d
+~~~
+"#,
+];
+
+#[cfg(feature = "p521")]
+static P521_FAKE_TEST_VECTORS: &[&str] = &[r#"
+### OPAQUE-3DH P-521 Fake Test Vector 1
+
+#### Configuration
+
+~~~
+OPRF: 0005
+Hash: SHA512
+MHF: Identity
+KDF: HKDF-SHA512
+MAC: HMAC-SHA512
+EnvelopeMode: 01
+Group: P521_XMD:SHA-512_SSWU_RO_
+Context: 4f50415155452d504f43
+Nh: 64
+Npk: 67
+Nsk: 66
+Nm: 64
+Nx: 64
+Nok: 66
+~~~
+
+#### Input Values
+
+~~~
+client_identity: 616c696365
+server_identity: 626f62
+oprf_seed: 336ebfae49781723d5955c86c3cf796874ff48dc1ebb951ab66d6d17a9b
+1064118e468c6a7e01372585a8376ff630d0801ccaecd377ec572b01435812847d50d
+credential_identifier: 31323334
+masking_nonce: 478ee986a1b774d0fdecaa9e19dad32ab30734ace36ceb3e1e3f05b
+995a12057
+client_private_key: 00df4800c72577f652140f4fa5b38edff728fc0ca302f41563
+5eb6c43f38aa85b2747c496080d2822879d33571893bf07f3b52dafdbfb74f0f2a739e
+1b1780dda605
+client_public_key: 0300be95e27e091af95fcb4eaf55af36d2019fb37d39a84285b
+88d1cf8f121996c427b1ac952dfb0812925414c8b57ed587c3610e7981ded6d642af4d
+3526d642061ec
+server_private_key: 016e7f305c4322e4b73ef4345da47de1014a7dd55f3fd60f93
+66b845a127a190cb64835acc5f11cc1298012a81b66cf77d8e2581746dbe0c41aadbfa
+0cfaad920f1b
+server_public_key: 0200b521174a9f7d17d1758e6c1ba363b9254268a4fec7f01dd
+d7fa1ebd689890cb4efc83bda63333406d44edc94e74c9a80e292da5ce8e6189a33dc9
+a97fbffb62adc
+server_nonce: dc32acc089e3cc6f36b295ddb178ee30aa018c5d0a8b9a67d61380cb
+c4ad4d41
+server_keyshare: 0300ead12a7491781d4cade2141a41ce6492c34e23c27a6fd672e
+66fabbd377a8488ef782fd48ce8ae7ae2dccd643934a5d69b23fa3fff95ea5645b4615
+64578320a8c
+server_private_keyshare: 00a217c4abdf1825b95436eea3058ce79baf5bc44cdc8
+dcdfb9816a3c7c2000734d0ed9111aef4404bdb7831188c5d52b6dc27cda243c4547a4
+071c2823a1e4456ce
+masking_key: db35c57a754e6f0e61b1b1d28f57d01676a56ad6b7dc5d2e38f2a6961
+19d74f7ee2060ddf29b354e4f011a30f4532dccb1da9a4336e8f32ce7d2ff2b43ac936
+7
+KE1: 0200ec2cee68294246cc592f16672e32717c7fb0a417973e3e63ba60a4e1bbaf6
+3d515eab041e2e07cf5d3cbe7c40874cd6780db90ef1bf1a679d04a5453c59fa21c24d
+e681f8ca738b5c18675ab50c98d5565b6ad31db64a83b4dfc6ab503ee16df9a03008f5
+db287e4a92f68d50c7e4ea17d8921b08318ef1cf2980b5cf33186f3916b97d3b37d295
+6ce60cd85d8b92d55259fbf708e73d68fd602ff46f50fc4a27c4f1642
+~~~
+
+#### Output Values
+
+~~~
+KE2: 0300f53485da5ae480ad910c43e5b3e75ce9c1bf140c9b161d4ff85bea82732ba
+89fdd2cf48d08cc0a3f497998a40c5d703dca509d6af700ffbf89e415fc482db5f1824
+78ee986a1b774d0fdecaa9e19dad32ab30734ace36ceb3e1e3f05b995a12057f544773
+dbb6b3da6b7ce0549dd2a0e2b54996c6f903559d9879274df05fb88f7cfe9908d2c8e7
+44ae6f14e97234a943fc7a56785dc49e5d853efd4b06b3f2f5c9dbcb50dac1e091276e
+e7e788397fcdbd7ffb7cc21533addae404aa0d15714a0690363b1979630fc49fe10b65
+8079cf3f85ccc859d10cdb0fd8047c1aea1145b219ceb69a668d37abf8011ee9444f59
+7cc099d1fa45a13d832449c473b730474a4b0f4dc32acc089e3cc6f36b295ddb178ee3
+0aa018c5d0a8b9a67d61380cbc4ad4d410300ead12a7491781d4cade2141a41ce6492c
+34e23c27a6fd672e66fabbd377a8488ef782fd48ce8ae7ae2dccd643934a5d69b23fa3
+fff95ea5645b461564578320a8c1183859963543577c26d370921e404e16e8550c71a6
+96d72012c7e5e46403c13eb24b5c9d31fcdf014e6002e9ca31c002d4cff07ffefa7dd4
+f9e2136380fec4d
+~~~
+"#];
+
+// Generated by an independent implementation of the draft, which reproduces
+// its ristretto255 vectors above, with the SIGMA-I key exchange of
+// key_exchange::sigma in place of 3DH
+#[cfg(feature = "sigma")]
+static SIGMA_TEST_VECTORS: &[&str] = &[
+    r#"
+## OPAQUE-SIGMA ristretto255 Test Vector 1
+
+### Configuration
+
+~~~
+OPRF: 0001
+Hash: SHA512
+MHF: Identity
+KDF: HKDF-SHA512
+MAC: HMAC-SHA512
+EnvelopeMode: 01
+Group: ristretto255
+Context: 4f50415155452d504f43
+Nh: 64
+Npk: 32
+Nsk: 32
+Nm: 64
+Nx: 64
+Nok: 32
+~~~
+
+### Input Values
+
+~~~
+oprf_seed: 6ed797cef0c904ecc00fd8e5c4a83f773e13f4b9cbcc1fe11b7de522d86
+0e006daaccc5fd1a62a62622656c26e58b2c8599e1af6004ff5f9ba57e9e759e16b18
+credential_identifier: 31323334
+password: 436f7272656374486f72736542617474657279537461706c65
+envelope_nonce: b5fa400e6d6c46d2f377b1b955ddd1719d9e1512f4d94ab62d3dfb
+d2ee0ee9f9
+masking_nonce: c4fc2206f68a3c052c5a043bcef19d0f025a4c1a11d0f9e3dcc37e7
+2c7ba0acf
+server_private_key: 708315571c90faef694403d28c176cc26dcbe3b5512802387e
+3ad7433a741501
+server_public_key: 933903e735966bb72c134a3624102afb16713744d4cf9aa9346
+fc352c01a6b33
+server_nonce: a865090c18faaa163f795744d142cf80d289b393fc16f27b080f65f6
+ba8cbec1
+client_nonce: 20cf913923cce89f357b4447a4ff3a5a1e4e38d7be8c1e1bdb8f86ca
+66c04b81
+server_keyshare: 10af9d07927a4eaaebd9e1dabaacfabfd1df45fcf2603100e8e14
+1f720b4df6c
+client_keyshare: e6ba3a0591ad67765ecd076c0575d63c2bdfd1d9bbf06f299c285
+29282bc2a7a
+server_private_keyshare: 38ddfecd06bda4975ef3910c9a7d4ea78a0cfd523812c
+271f4e14ec80cc76908
+client_private_keyshare: 7988f097ab610889ee57ba8a12f540876ad306c040d30
+2e875e41d3a14d1f902
+blind_registration: 36543cc6efc9599ccf3ccf487db2c478fbca0561069a922e64
+3df7f19c522305
+blind_login: 0c0927295e28c3f23765460bb9c39970e40586bf0b0b9e437c047f874
+ca55809
+oprf_key: 4ba6b872baa132b2e4e965858142b4ab5e9e41fb7f570f0dbe953e8dbd02
+110b
+~~~
+
+### Intermediate Values
+
+~~~
+client_public_key: 96f628e336b3076ab7ecd4d81b3a4750c53a660a86ccde20f5c
+64b3dbf269858
+~~~
+
+### Output Values
+
+~~~
+registration_request: e0f2f86e9e2769d611da2f381f9bd5b62840a7ce87d8d2e9
+e34f0844952abb1b
+registration_response: b87826359e4556c38cc47550de7a4129bcaf4b944184a5d
+7f91e0b6949217b50933903e735966bb72c134a3624102afb16713744d4cf9aa9346fc
+352c01a6b33
+registration_upload: 96f628e336b3076ab7ecd4d81b3a4750c53a660a86ccde20f
+5c64b3dbf269858d3ea6aeb528121aaa662d0ddedc02df81b77ba53a7e6e1432f4c47d
+1a601712f74b1ca126a1f34b36ecc7cab8b51e66a72bf963776ab05269be12a013202e
+bb8b5fa400e6d6c46d2f377b1b955ddd1719d9e1512f4d94ab62d3dfbd2ee0ee9f985c
+ce77b3fe50935c333b51ad0b5baa5b8ccc9649997a60e420380fa8113c8133b84e15a9
+f34b4e2c8b581335e1ae505baa322bf8d7c1ac7f74dbb2bc68967a1
+KE1: 5a51fcf626f34c2bf42f9b7131bda854ffc5031b9c5d33dc7f83732ae0ac84372
+0cf913923cce89f357b4447a4ff3a5a1e4e38d7be8c1e1bdb8f86ca66c04b81e6ba3a0
+591ad67765ecd076c0575d63c2bdfd1d9bbf06f299c28529282bc2a7a
+KE2: 525d529f7d3087b0883a034173823f1d68702fa84aa6934d82326a13b2846e0ac
+4fc2206f68a3c052c5a043bcef19d0f025a4c1a11d0f9e3dcc37e72c7ba0acf66cf17b
+8afba3cab64e2c779875759ac47fbc87da5f5b261e789becbd95a1618dafb04798efae
+410351a4089e590810466e90667ae9f9687a6a8bdcf0480bb7235ffa2b53b7c98e0059
+fbc2b8042acf5d5e2d1c2069cb25935356f3e5221e2b617f6a6b0d9e56418818d3fbef
+438dd2d12fee5d3902e28738267f1e6725ab42ba865090c18faaa163f795744d142cf8
+0d289b393fc16f27b080f65f6ba8cbec110af9d07927a4eaaebd9e1dabaacfabfd1df4
+5fcf2603100e8e141f720b4df6cd6e653ca7333e4cf041c3a6eee7d06f840b9abbcb90
+7e5872f7f8059a7637d896b9e3b690171f48ed92865f99143c7aee765ac2effef0de50
+0c3ac417ce03b0fa970d7f5003cdc5bf8cac8ddf5104f3a879a262d45307ecef7e4272
+482d4edaa2230e8682097daee1236352f333b3c7baedcca3cb781a9582fc8f84c955ed
+c11
+KE3: 6951a5c8f85a8fa6d84c3685ce3c69d3e03561e3a860d3ed6f25fc8edf6ad1b1a
+67f8a2e50ec20639189571e44cdf5d8678ff8e702027a0acb33c7596947000302bd77f
+d27be76e186d4de745dcca469a650210bc175bc18d45fa3111b96cdaccd5c8d816f08f
+568e8806e7816bdcbfb8313756159a59a1d006f58219a7e957e
+export_key: 26cc4a0dcae3009396feceb7021008bf74e1854df9d248787364dec85a
+98dfb4384ce85e6744144a3a703fd05be610ee124b29c800abc145bb2d2247041ad485
+session_key: f8b1a2dc39b4d24c71106334062ce7a51babec32db44b7bf10d57bfb2
+774768a98fe133136e34fe822c00f42a337687aee51e91d8fbb2f201e551b5477ed4d4
+b
+~~~
+"#,
+    r#"
+## OPAQUE-SIGMA ristretto255 Test Vector 2
+
+### Configuration
+
+~~~
+OPRF: 0001
+Hash: SHA512
+MHF: Identity
+KDF: HKDF-SHA512
+MAC: HMAC-SHA512
+EnvelopeMode: 01
+Group: ristretto255
+Context: 4f50415155452d504f43
+Nh: 64
+Npk: 32
+Nsk: 32
+Nm: 64
+Nx: 64
+Nok: 32
+~~~
+
+### Input Values
+
+~~~
+client_identity: 616c696365
+server_identity: 626f62
+oprf_seed: 61b7eb8bbbeec7a978d0ce575ea32f81f9544c6bbe1561e205135c45a19
+5d7b6ecb11d31f3f4549da62305e1e59117756d763d71fc8df5e18cd6807359437586
+credential_identifier: 31323334
+password: 436f7272656374486f72736542617474657279537461706c65
+envelope_nonce: c1be3f34f3dd43d7ff58f1c7ea57eed5f228cca32e7ac861219c20
+3a58b92247
+masking_nonce: 0e84443cbe070170fa84b76a0869c3a53aa1cd2f584709595a61bb9
+ce834ec2a
+server_private_key: 3a98204d455d5564623402aac9085352ec206a08dc73e9b886
+cd8394c334d206
+server_public_key: 05da13bed6c4f7062fb9201029f66bf4a77ae16ccc4c46c332e
+3a8ab07dd7877
+server_nonce: 28aba2a05ae38aa60b5d68d4c1365866a09129813b32b1c355190542
+e2835727
+client_nonce: f4b9ed1a0453503ec810eae9b05d99fc293d5ab3226068c442ee686b
+608861a0
+server_keyshare: baa003b8fd349165b31810138df4d1b2193c35aa8ff25069777f6
+7a8e7c7c362
+client_keyshare: 70cb17702797977e682cfc85e0fe3668253048a2d04509ed37532
+e338da8502e
+server_private_keyshare: 110f4425ddc3c0735f748f490c0ec383baf193414af02
+a8c364c7a34b9d1a105
+client_private_keyshare: e2994fbbe258e065720e6352306175439f1b5f6b48aae
+36af5ce3a00e7ec2e08
+blind_registration: c73339df9b1a170b097322ee74bf50a4c99941da73f97fa19f
+a4b657fee47a0e
+blind_login: ce3613415080fa834d606d6aa7d86ce3adb9b2f85f1224ac9b44dc06f
+3cf5203
+oprf_key: 38df8efc39463aa795f039483b35c44209b3fcab8b840e784bb78308fb1d
+9d03
+~~~
+
+### Intermediate Values
+
+~~~
+client_public_key: 6a170a4d2999d9f9a10d37f65bebcb0fe48b49e59538ed7d57c
+a5357f3fc7e0d
+~~~
+
+### Output Values
+
+~~~
+registration_request: d0098990092e17cef73c76a504e0e64d05935f869feef59a
+51cfc840fe7b3825
+registration_response: 3cabc531d5e52cb19ed82e64e6a055bd980b437dc5258ce
+5495c485d03f7c92c05da13bed6c4f7062fb9201029f66bf4a77ae16ccc4c46c332e3a
+8ab07dd7877
+registration_upload: 6a170a4d2999d9f9a10d37f65bebcb0fe48b49e59538ed7d5
+7ca5357f3fc7e0d592e863c80115127cf4f2dfdf7e42e20c7932c18cbeb1f6b34525ba
+63110c5eff1645ae4df0cb3414205f9d974ea00bf5287e3e058a39e381e7d77a93d776
+13bc1be3f34f3dd43d7ff58f1c7ea57eed5f228cca32e7ac861219c203a58b92247e19
+e3567f31d7d54566172dd349ba8fb6c2c806306459a94f82d0d2f819e3a61ea6162af4
+5a15acc140cc93ca5a33e976f825935b4ffbdffdf442801d01c0895
+KE1: ae18834395e5c0aaf3c425c3f23354427cf73c6167ce41a6226840a84ebe0e11f
+4b9ed1a0453503ec810eae9b05d99fc293d5ab3226068c442ee686b608861a070cb177
+02797977e682cfc85e0fe3668253048a2d04509ed37532e338da8502e
+KE2: 001a3eb3c8952f1161098289fc2bb436cc5c221d16923c5c21ba9dcd8cb565340
+e84443cbe070170fa84b76a0869c3a53aa1cd2f584709595a61bb9ce834ec2af762a1c
+343932f73219db60876d9587e825173c506bc2d677c184ddeb6c2e6fc5a4e33e6ac3e2
+6865f6a2fd1ac22d4594fa97a338b64730dec9de6bc3f2fb6abc4fea130a1afd0fd49c
+0acfe8e109bf0339ea7b8955e73e04e406492caba5dc75290b7fb1d87356c4a7d49eed
+d9eb33f21f250357a2756d323f493185456114128aba2a05ae38aa60b5d68d4c136586
+6a09129813b32b1c355190542e2835727baa003b8fd349165b31810138df4d1b2193c3
+5aa8ff25069777f67a8e7c7c362344307da1b1dea929e7563d8986efb94e00ab6fea67
+c68944ef4d4a40e350c75b61988380fe1a8773e790b63f3a09bafd38aec09344efa75d
+dfc19f19c071c077f8b70f28d702a85f1b378e81fe4e2baced422328876588237f4ab1
+6b044addf976757a27c2651d0d061e2e2b8d2d9c7331efdb3918bea57b09f0799d9d8e
+737
+KE3: d68d47369b91350aca91e167630487fbfa861ebfdede474fb66d84ad6dca348f4
+4a701b79e0aabfbdf07c1d03285b3bf93c37ad1e551db998f0210b071fed6057fca859
+a8555a6ae19464caaf9831cd25dba5c376b5373b9279df8b2045774024eaa956a3fcd9
+09f8cbdad20e2893bcefaa2b2882fcaa1e6094cf03b72e3fa8d
+export_key: 6928711a1680a48ec780d04fddf8ec302549698803444c30a47da16915
+cc9f0225d7205e2954743e48df72cdd50577314734ec7bf455cbf6757db396446a4ddb
+session_key: 2a08efebd0cfc9e26bdf756e7bc874b6e7f1f4a89ea7a214b54d3dfb2
+3e0c3d8e4381befb5d07875ae99b8c29351d80a3ea71a1b714bac02616796cdbba2f41
+2
+~~~
+"#,
+];
+
+#[cfg(feature = "sigma")]
+static SIGMA_FAKE_TEST_VECTORS: &[&str] = &[r#"
+### OPAQUE-SIGMA ristretto255 Fake Test Vector 1
+
+#### Configuration
+
+~~~
+OPRF: 0001
+Hash: SHA512
+MHF: Identity
+KDF: HKDF-SHA512
+MAC: HMAC-SHA512
+EnvelopeMode: 01
+Group: ristretto255
+Context: 4f50415155452d504f43
+Nh: 64
+Npk: 32
+Nsk: 32
+Nm: 64
+Nx: 64
+Nok: 32
+~~~
+
+#### Input Values
+
+~~~
+client_identity: 616c696365
+server_identity: 626f62
+oprf_seed: 40c3ced61aff1bff994e7618306e3d06746af0d2fa40f1cb38a368065c3
+052721e735fbe1e2847665d04dfc058634bae903604f24f6a7e79fd1833d053a75837
+credential_identifier: 31323334
+masking_nonce: b0da2a617883700fc04b2d00e8d75fbb68265b248aa67454b3d416b
+236b8ebbf
+client_private_key: ae2a64748777975e4dd678f1c1c63a60a693768efe4ae82f03
+b47d42e004af07
+client_public_key: a7b47073894d7a489f02257c7ab12affff84e48ed932b5a276e
+dd655c846175d
+server_private_key: f710a16c965c6682855fdd6fdc4629599e1ab73fda58433f9f
+2889b31944e105
+server_public_key: 4e1147f62683ba53e1e276b1c5d24f945a2abb5937f11cf82ab
+cc382d34e0f83
+server_nonce: 3847e124b2444a88b9e5d9894e19ea08447faafe436e1872f8cc1b25
+0ea6b9fc
+server_keyshare: 5ad992de775025fd95d9bdfd462cb9e1d7cf68dd98ef5364ccd7f
+6e0ec383d5a
+server_private_keyshare: 4eabddb4fddb7da5b3725318d99f2a9b232cc903f082c
+04067b5bfdac001700a
+masking_key: c210f3d2dbf478807fa15b4d12cd836e2b6c85105f78a9e7bff900666
+ae0c36f07f43ce06d108a88f80910c5266da281e3482827b47fb9491f69df741e5d048
+a
+KE1: ae18834395e5c0aaf3c425c3f23354427cf73c6167ce41a6226840a84ebe0e11f
+4b9ed1a0453503ec810eae9b05d99fc293d5ab3226068c442ee686b608861a070cb177
+02797977e682cfc85e0fe3668253048a2d04509ed37532e338da8502e
+~~~
+
+#### Output Values
+
+~~~
+KE2: 0a02e23ee1cc12143247f5667acf692b12a27b7caaac5103e033483e0388f337b
+0da2a617883700fc04b2d00e8d75fbb68265b248aa67454b3d416b236b8ebbfaf4ea44
+80752ed264dcd0d20a91c0e27ac1e6aedb4afae597c778f37a5b4343c05219788de372
+ad1cc33ee99d9c4aa7e2ea1af5333c654d5060fa5abf119f45bcebfc756bb1f46788f0
+098abb7a8d7143593e5ac96ea1ceff5cecaffc7fce010b7ff3a9eadee905bad347f629
+6ca44e4d34e7a98b3b31549355a0c7db97b3c963847e124b2444a88b9e5d9894e19ea0
+8447faafe436e1872f8cc1b250ea6b9fc5ad992de775025fd95d9bdfd462cb9e1d7cf6
+8dd98ef5364ccd7f6e0ec383d5a3b81e0851e7348eae5efa9b44c39d1b0df436524a14
+74223d7d86c544d64cfd736f3f90534d0be4d5ad1786cbb8f9685d78e12e901a1a0b56
+4448a4a7b68720c75ea5ac0d4f48b587fe115811c79a962b0ea7bc201f4c6e070d3194
+fd3ef11b0e342b0f9a9d4d14500443d6c1aa689bc3e1cf7d997a7c8229ebdf424cc19a
+958
+~~~
+"#];
+
 macro_rules! parse {
     ( $v:ident, $s:expr ) => {
         parse_default!($v, $s, vec![])
@@ -397,9 +1690,13 @@ macro_rules! parse_default {
 }
 
 macro_rules! rfc_to_params {
-    ( $v:ident ) => {
+    ( $cs:ty, $v:ident ) => {
         $v.iter()
-            .map(|x| populate_test_vectors(&serde_json::from_str(rfc_to_json(x).as_str()).unwrap()))
+            .map(|x| {
+                populate_test_vectors::<$cs>(
+                    &serde_json::from_str(rfc_to_json(x).as_str()).unwrap(),
+                )
+            })
             .collect::<Vec<TestVectorParameters>>()
     };
 }
@@ -440,14 +1737,18 @@ fn decode(values: &Value, key: &str) -> Option<Vec<u8>> {
         .and_then(|s| hex::decode(&s.to_string()).ok())
 }
 
-fn populate_test_vectors(values: &Value) -> TestVectorParameters {
+fn populate_test_vectors<CS: CipherSuite>(values: &Value) -> TestVectorParameters {
     TestVectorParameters {
         dummy_private_key: parse_default!(
             values,
             "client_private_key",
-            vec![0u8; <PrivateKey as SizedBytes>::Len::to_usize()]
+            vec![0u8; <CS::Group as Group>::ScalarLen::to_usize()]
+        ),
+        dummy_masking_key: parse_default!(
+            values,
+            "masking_key",
+            vec![0u8; <CS::Hash as Digest>::OutputSize::to_usize()]
         ),
-        dummy_masking_key: parse_default!(values, "masking_key", vec![0u8; 64]),
         context: parse!(values, "Context"),
         envelope_mode: match values["EnvelopeMode"].as_str() {
             Some("01") => EnvelopeMode::Base,
@@ -485,10 +1786,23 @@ fn populate_test_vectors(values: &Value) -> TestVectorParameters {
     }
 }
 
-fn get_password_file_bytes(parameters: &TestVectorParameters) -> Result<Vec<u8>, ProtocolError> {
-    let password_file = ServerRegistration::<Ristretto255Sha512NoSlowHash>::finish(
+fn get_password_file_bytes<CS: CipherSuite>(
+    parameters: &TestVectorParameters,
+) -> Result<Vec<u8>, ProtocolError> {
+    let password_file = ServerRegistration::<CS>::finish(
         RegistrationUpload::deserialize(&parameters.registration_upload[..]).unwrap(),
-    );
+        // The key IDs of a new ServerSetup, which accepts envelopes in either
+        // mode
+        RegistrationKeyIds {
+            oprf_key_id: 0,
+            server_key_id: 0,
+            envelope_layout: EnvelopeLayout {
+                external_mode: true,
+                credentials_len: 0,
+            },
+            credential_identifier: Vec::new(),
+        },
+    )?;
 
     Ok(password_file.serialize())
 }
@@ -505,15 +1819,11 @@ fn parse_identifiers(
     }
 }
 
-#[test]
-fn test_registration_request() -> Result<(), ProtocolError> {
-    for parameters in rfc_to_params!(TEST_VECTORS) {
+fn registration_request<CS: CipherSuite>(test_vectors: &[&str]) -> Result<(), ProtocolError> {
+    for parameters in rfc_to_params!(CS, test_vectors) {
         let mut rng = CycleRng::new(parameters.blind_registration.to_vec());
         let client_registration_start_result =
-            ClientRegistration::<Ristretto255Sha512NoSlowHash>::start(
-                &mut rng,
-                &parameters.password,
-            )?;
+            ClientRegistration::<CS>::start(&mut rng, &parameters.password)?;
         assert_eq!(
             hex::encode(&parameters.registration_request),
             hex::encode(client_registration_start_result.message.serialize())
@@ -522,10 +1832,9 @@ fn test_registration_request() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-#[test]
-fn test_registration_response() -> Result<(), ProtocolError> {
-    for parameters in rfc_to_params!(TEST_VECTORS) {
-        let server_setup = ServerSetup::<Ristretto255Sha512NoSlowHash>::deserialize(
+fn registration_response<CS: CipherSuite>(test_vectors: &[&str]) -> Result<(), ProtocolError> {
+    for parameters in rfc_to_params!(CS, test_vectors) {
+        let server_setup = ServerSetup::<CS>::deserialize(
             &[
                 &parameters.oprf_seed[..],
                 &parameters.server_private_key[..],
@@ -533,12 +1842,12 @@ fn test_registration_response() -> Result<(), ProtocolError> {
             ]
             .concat(),
         )?;
-        let server_registration_start_result =
-            ServerRegistration::<Ristretto255Sha512NoSlowHash>::start(
-                &server_setup,
-                RegistrationRequest::deserialize(&parameters.registration_request[..]).unwrap(),
-                &parameters.credential_identifier,
-            )?;
+        let server_registration_start_result = ServerRegistration::<CS>::start(
+            &mut OsRng,
+            &server_setup,
+            RegistrationRequest::deserialize(&parameters.registration_request[..]).unwrap(),
+            &parameters.credential_identifier,
+        )?;
         assert_eq!(
             hex::encode(parameters.registration_response),
             hex::encode(server_registration_start_result.message.serialize())
@@ -547,24 +1856,20 @@ fn test_registration_response() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-#[test]
-fn test_registration_upload() -> Result<(), ProtocolError> {
-    for parameters in rfc_to_params!(TEST_VECTORS) {
+fn registration_upload<CS: CipherSuite>(test_vectors: &[&str]) -> Result<(), ProtocolError> {
+    for parameters in rfc_to_params!(CS, test_vectors) {
         let mut rng = CycleRng::new(parameters.blind_registration.to_vec());
         let client_registration_start_result =
-            ClientRegistration::<Ristretto255Sha512NoSlowHash>::start(
-                &mut rng,
-                &parameters.password,
-            )?;
+            ClientRegistration::<CS>::start(&mut rng, &parameters.password)?;
 
         let mut finish_registration_rng = CycleRng::new(parameters.envelope_nonce);
         let result = client_registration_start_result.state.finish(
             &mut finish_registration_rng,
             RegistrationResponse::deserialize(&parameters.registration_response[..]).unwrap(),
-            match parse_identifiers(parameters.client_identity, parameters.server_identity) {
-                None => ClientRegistrationFinishParameters::Default,
-                Some(ids) => ClientRegistrationFinishParameters::WithIdentifiers(ids),
-            },
+            ClientRegistrationFinishParameters::new(
+                parse_identifiers(parameters.client_identity, parameters.server_identity),
+                None,
+            ),
         )?;
 
         assert_eq!(
@@ -580,9 +1885,8 @@ fn test_registration_upload() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-#[test]
-fn test_ke1() -> Result<(), ProtocolError> {
-    for parameters in rfc_to_params!(TEST_VECTORS) {
+fn ke1<CS: CipherSuite>(test_vectors: &[&str]) -> Result<(), ProtocolError> {
+    for parameters in rfc_to_params!(CS, test_vectors) {
         let client_login_start = [
             parameters.blind_login,
             parameters.client_private_keyshare,
@@ -590,10 +1894,8 @@ fn test_ke1() -> Result<(), ProtocolError> {
         ]
         .concat();
         let mut client_login_start_rng = CycleRng::new(client_login_start);
-        let client_login_start_result = ClientLogin::<Ristretto255Sha512NoSlowHash>::start(
-            &mut client_login_start_rng,
-            &parameters.password,
-        )?;
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut client_login_start_rng, &parameters.password)?;
         assert_eq!(
             hex::encode(&parameters.KE1),
             hex::encode(client_login_start_result.message.serialize())
@@ -602,10 +1904,9 @@ fn test_ke1() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-#[test]
-fn test_ke2() -> Result<(), ProtocolError> {
-    for parameters in rfc_to_params!(TEST_VECTORS) {
-        let server_setup = ServerSetup::<Ristretto255Sha512NoSlowHash>::deserialize(
+fn ke2<CS: CipherSuite>(test_vectors: &[&str]) -> Result<(), ProtocolError> {
+    for parameters in rfc_to_params!(CS, test_vectors) {
+        let server_setup = ServerSetup::<CS>::deserialize(
             &[
                 &parameters.oprf_seed[..],
                 &parameters.server_private_key[..],
@@ -614,8 +1915,8 @@ fn test_ke2() -> Result<(), ProtocolError> {
             .concat(),
         )?;
 
-        let record = ServerRegistration::<Ristretto255Sha512NoSlowHash>::deserialize(
-            &get_password_file_bytes(&parameters)?[..],
+        let record = ServerRegistration::<CS>::deserialize(
+            &get_password_file_bytes::<CS>(&parameters)?[..],
         )?;
 
         let mut server_private_keyshare_and_nonce_rng = CycleRng::new(
@@ -626,20 +1927,16 @@ fn test_ke2() -> Result<(), ProtocolError> {
             ]
             .concat(),
         );
-        let server_login_start_result = ServerLogin::<Ristretto255Sha512NoSlowHash>::start(
+        let server_login_start_result = ServerLogin::<CS>::start(
             &mut server_private_keyshare_and_nonce_rng,
             &server_setup,
             Some(record),
-            CredentialRequest::<Ristretto255Sha512NoSlowHash>::deserialize(&parameters.KE1[..])
-                .unwrap(),
+            CredentialRequest::<CS>::deserialize(&parameters.KE1[..]).unwrap(),
             &parameters.credential_identifier,
-            match parse_identifiers(parameters.client_identity, parameters.server_identity) {
-                None => ServerLoginStartParameters::WithContext(parameters.context.to_vec()),
-                Some(ids) => ServerLoginStartParameters::WithContextAndIdentifiers(
-                    parameters.context.to_vec(),
-                    ids,
-                ),
-            },
+            ServerLoginStartParameters::new(
+                Some(parameters.context.to_vec()),
+                parse_identifiers(parameters.client_identity, parameters.server_identity),
+            ),
         )?;
         assert_eq!(
             hex::encode(&parameters.KE2),
@@ -649,9 +1946,8 @@ fn test_ke2() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-#[test]
-fn test_ke3() -> Result<(), ProtocolError> {
-    for parameters in rfc_to_params!(TEST_VECTORS) {
+fn ke3<CS: CipherSuite>(test_vectors: &[&str]) -> Result<(), ProtocolError> {
+    for parameters in rfc_to_params!(CS, test_vectors) {
         let client_login_start = [
             parameters.blind_login,
             parameters.client_private_keyshare,
@@ -659,19 +1955,16 @@ fn test_ke3() -> Result<(), ProtocolError> {
         ]
         .concat();
         let mut client_login_start_rng = CycleRng::new(client_login_start);
-        let client_login_start_result = ClientLogin::<Ristretto255Sha512NoSlowHash>::start(
-            &mut client_login_start_rng,
-            &parameters.password,
-        )?;
+        let client_login_start_result =
+            ClientLogin::<CS>::start(&mut client_login_start_rng, &parameters.password)?;
 
         let client_login_finish_result = client_login_start_result.state.finish(
-            CredentialResponse::<Ristretto255Sha512NoSlowHash>::deserialize(&parameters.KE2[..])?,
-            match parse_identifiers(parameters.client_identity, parameters.server_identity) {
-                None => ClientLoginFinishParameters::WithContext(parameters.context),
-                Some(ids) => {
-                    ClientLoginFinishParameters::WithContextAndIdentifiers(parameters.context, ids)
-                }
-            },
+            CredentialResponse::<CS>::deserialize(&parameters.KE2[..])?,
+            ClientLoginFinishParameters::new(
+                Some(parameters.context),
+                parse_identifiers(parameters.client_identity, parameters.server_identity),
+                None,
+            ),
         )?;
 
         assert_eq!(
@@ -690,10 +1983,9 @@ fn test_ke3() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-#[test]
-fn test_server_login_finish() -> Result<(), ProtocolError> {
-    for parameters in rfc_to_params!(TEST_VECTORS) {
-        let server_setup = ServerSetup::<Ristretto255Sha512NoSlowHash>::deserialize(
+fn server_login_finish<CS: CipherSuite>(test_vectors: &[&str]) -> Result<(), ProtocolError> {
+    for parameters in rfc_to_params!(CS, test_vectors) {
+        let server_setup = ServerSetup::<CS>::deserialize(
             &[
                 &parameters.oprf_seed[..],
                 &parameters.server_private_key[..],
@@ -702,8 +1994,8 @@ fn test_server_login_finish() -> Result<(), ProtocolError> {
             .concat(),
         )?;
 
-        let record = ServerRegistration::<Ristretto255Sha512NoSlowHash>::deserialize(
-            &get_password_file_bytes(&parameters)?[..],
+        let record = ServerRegistration::<CS>::deserialize(
+            &get_password_file_bytes::<CS>(&parameters)?[..],
         )?;
 
         let mut server_private_keyshare_and_nonce_rng = CycleRng::new(
@@ -714,25 +2006,22 @@ fn test_server_login_finish() -> Result<(), ProtocolError> {
             ]
             .concat(),
         );
-        let server_login_start_result = ServerLogin::<Ristretto255Sha512NoSlowHash>::start(
+        let server_login_start_result = ServerLogin::<CS>::start(
             &mut server_private_keyshare_and_nonce_rng,
             &server_setup,
             Some(record),
-            CredentialRequest::<Ristretto255Sha512NoSlowHash>::deserialize(&parameters.KE1[..])
-                .unwrap(),
+            CredentialRequest::<CS>::deserialize(&parameters.KE1[..]).unwrap(),
             &parameters.credential_identifier,
-            match parse_identifiers(parameters.client_identity, parameters.server_identity) {
-                None => ServerLoginStartParameters::WithContext(parameters.context.to_vec()),
-                Some(ids) => ServerLoginStartParameters::WithContextAndIdentifiers(
-                    parameters.context.to_vec(),
-                    ids,
-                ),
-            },
+            ServerLoginStartParameters::new(
+                Some(parameters.context.to_vec()),
+                parse_identifiers(parameters.client_identity, parameters.server_identity),
+            ),
         )?;
 
-        let server_login_result = server_login_start_result
-            .state
-            .finish(CredentialFinalization::deserialize(&parameters.KE3[..])?)?;
+        let server_login_result = server_login_start_result.state.finish(
+            CredentialFinalization::deserialize(&parameters.KE3[..])?,
+            ServerLoginFinishParameters::default(),
+        )?;
 
         assert_eq!(
             hex::encode(parameters.session_key),
@@ -742,10 +2031,9 @@ fn test_server_login_finish() -> Result<(), ProtocolError> {
     Ok(())
 }
 
-#[test]
-fn test_fake_vectors() -> Result<(), ProtocolError> {
-    for parameters in rfc_to_params!(FAKE_TEST_VECTORS) {
-        let server_setup = ServerSetup::<Ristretto255Sha512NoSlowHash>::deserialize(
+fn fake_vectors<CS: CipherSuite>(test_vectors: &[&str]) -> Result<(), ProtocolError> {
+    for parameters in rfc_to_params!(CS, test_vectors) {
+        let server_setup = ServerSetup::<CS>::deserialize(
             &[
                 &parameters.oprf_seed[..],
                 &parameters.server_private_key[..],
@@ -763,20 +2051,16 @@ fn test_fake_vectors() -> Result<(), ProtocolError> {
             ]
             .concat(),
         );
-        let server_login_start_result = ServerLogin::<Ristretto255Sha512NoSlowHash>::start(
+        let server_login_start_result = ServerLogin::<CS>::start(
             &mut server_private_keyshare_and_nonce_rng,
             &server_setup,
             None,
-            CredentialRequest::<Ristretto255Sha512NoSlowHash>::deserialize(&parameters.KE1[..])
-                .unwrap(),
+            CredentialRequest::<CS>::deserialize(&parameters.KE1[..]).unwrap(),
             &parameters.credential_identifier,
-            match parse_identifiers(parameters.client_identity, parameters.server_identity) {
-                None => ServerLoginStartParameters::WithContext(parameters.context.to_vec()),
-                Some(ids) => ServerLoginStartParameters::WithContextAndIdentifiers(
-                    parameters.context.to_vec(),
-                    ids,
-                ),
-            },
+            ServerLoginStartParameters::new(
+                Some(parameters.context.to_vec()),
+                parse_identifiers(parameters.client_identity, parameters.server_identity),
+            ),
         )?;
         assert_eq!(
             hex::encode(&parameters.KE2),
@@ -785,3 +2069,115 @@ fn test_fake_vectors() -> Result<(), ProtocolError> {
     }
     Ok(())
 }
+
+#[test]
+fn test_registration_request() -> Result<(), ProtocolError> {
+    registration_request::<Ristretto255Sha512NoSlowHash>(TEST_VECTORS)?;
+    #[cfg(feature = "p256")]
+    registration_request::<P256Sha256NoSlowHash>(P256_TEST_VECTORS)?;
+    #[cfg(feature = "p384")]
+    registration_request::<P384Sha384NoSlowHash>(P384_TEST_VECTORS)?;
+    #[cfg(feature = "p521")]
+    registration_request::<P521Sha512NoSlowHash>(P521_TEST_VECTORS)?;
+    #[cfg(feature = "sigma")]
+    registration_request::<Ristretto255Sha512SigmaNoSlowHash>(SIGMA_TEST_VECTORS)?;
+    Ok(())
+}
+
+#[test]
+fn test_registration_response() -> Result<(), ProtocolError> {
+    registration_response::<Ristretto255Sha512NoSlowHash>(TEST_VECTORS)?;
+    #[cfg(feature = "p256")]
+    registration_response::<P256Sha256NoSlowHash>(P256_TEST_VECTORS)?;
+    #[cfg(feature = "p384")]
+    registration_response::<P384Sha384NoSlowHash>(P384_TEST_VECTORS)?;
+    #[cfg(feature = "p521")]
+    registration_response::<P521Sha512NoSlowHash>(P521_TEST_VECTORS)?;
+    #[cfg(feature = "sigma")]
+    registration_response::<Ristretto255Sha512SigmaNoSlowHash>(SIGMA_TEST_VECTORS)?;
+    Ok(())
+}
+
+#[test]
+fn test_registration_upload() -> Result<(), ProtocolError> {
+    registration_upload::<Ristretto255Sha512NoSlowHash>(TEST_VECTORS)?;
+    #[cfg(feature = "p256")]
+    registration_upload::<P256Sha256NoSlowHash>(P256_TEST_VECTORS)?;
+    #[cfg(feature = "p384")]
+    registration_upload::<P384Sha384NoSlowHash>(P384_TEST_VECTORS)?;
+    #[cfg(feature = "p521")]
+    registration_upload::<P521Sha512NoSlowHash>(P521_TEST_VECTORS)?;
+    #[cfg(feature = "sigma")]
+    registration_upload::<Ristretto255Sha512SigmaNoSlowHash>(SIGMA_TEST_VECTORS)?;
+    Ok(())
+}
+
+#[test]
+fn test_ke1() -> Result<(), ProtocolError> {
+    ke1::<Ristretto255Sha512NoSlowHash>(TEST_VECTORS)?;
+    #[cfg(feature = "p256")]
+    ke1::<P256Sha256NoSlowHash>(P256_TEST_VECTORS)?;
+    #[cfg(feature = "p384")]
+    ke1::<P384Sha384NoSlowHash>(P384_TEST_VECTORS)?;
+    #[cfg(feature = "p521")]
+    ke1::<P521Sha512NoSlowHash>(P521_TEST_VECTORS)?;
+    #[cfg(feature = "sigma")]
+    ke1::<Ristretto255Sha512SigmaNoSlowHash>(SIGMA_TEST_VECTORS)?;
+    Ok(())
+}
+
+#[test]
+fn test_ke2() -> Result<(), ProtocolError> {
+    ke2::<Ristretto255Sha512NoSlowHash>(TEST_VECTORS)?;
+    #[cfg(feature = "p256")]
+    ke2::<P256Sha256NoSlowHash>(P256_TEST_VECTORS)?;
+    #[cfg(feature = "p384")]
+    ke2::<P384Sha384NoSlowHash>(P384_TEST_VECTORS)?;
+    #[cfg(feature = "p521")]
+    ke2::<P521Sha512NoSlowHash>(P521_TEST_VECTORS)?;
+    #[cfg(feature = "sigma")]
+    ke2::<Ristretto255Sha512SigmaNoSlowHash>(SIGMA_TEST_VECTORS)?;
+    Ok(())
+}
+
+#[test]
+fn test_ke3() -> Result<(), ProtocolError> {
+    ke3::<Ristretto255Sha512NoSlowHash>(TEST_VECTORS)?;
+    #[cfg(feature = "p256")]
+    ke3::<P256Sha256NoSlowHash>(P256_TEST_VECTORS)?;
+    #[cfg(feature = "p384")]
+    ke3::<P384Sha384NoSlowHash>(P384_TEST_VECTORS)?;
+    #[cfg(feature = "p521")]
+    ke3::<P521Sha512NoSlowHash>(P521_TEST_VECTORS)?;
+    #[cfg(feature = "sigma")]
+    ke3::<Ristretto255Sha512SigmaNoSlowHash>(SIGMA_TEST_VECTORS)?;
+    Ok(())
+}
+
+#[test]
+fn test_server_login_finish() -> Result<(), ProtocolError> {
+    server_login_finish::<Ristretto255Sha512NoSlowHash>(TEST_VECTORS)?;
+    #[cfg(feature = "p256")]
+    server_login_finish::<P256Sha256NoSlowHash>(P256_TEST_VECTORS)?;
+    #[cfg(feature = "p384")]
+    server_login_finish::<P384Sha384NoSlowHash>(P384_TEST_VECTORS)?;
+    #[cfg(feature = "p521")]
+    server_login_finish::<P521Sha512NoSlowHash>(P521_TEST_VECTORS)?;
+    #[cfg(feature = "sigma")]
+    server_login_finish::<Ristretto255Sha512SigmaNoSlowHash>(SIGMA_TEST_VECTORS)?;
+    Ok(())
+}
+
+#[test]
+fn test_fake_vectors() -> Result<(), ProtocolError> {
+    fake_vectors::<Ristretto255Sha512NoSlowHash>(FAKE_TEST_VECTORS)?;
+    #[cfg(feature = "p256")]
+    fake_vectors::<P256Sha256NoSlowHash>(P256_FAKE_TEST_VECTORS)?;
+    #[cfg(feature = "p384")]
+    fake_vectors::<P384Sha384NoSlowHash>(P384_FAKE_TEST_VECTORS)?;
+    #[cfg(feature = "p521")]
+    fake_vectors::<P521Sha512NoSlowHash>(P521_FAKE_TEST_VECTORS)?;
+    #[cfg(feature = "sigma")]
+    fake_vectors::<Ristretto255Sha512SigmaNoSlowHash>(SIGMA_FAKE_TEST_VECTORS)?;
+    Ok(())
+}
@@ -3,6 +3,9 @@
 // This source code is licensed under the MIT license found in the
 // LICENSE file in the root directory of this source tree.
 
+// The tests only go through the public API of the oprf module, so that they
+// double as conformance tests for its users
+
 use crate::tests::mock_rng::CycleRng;
 use crate::{errors::*, group::Group, oprf};
 use curve25519_dalek::ristretto::RistrettoPoint;
@@ -44,6 +47,70 @@ static OPRF_RISTRETTO255_SHA512: &[&str] = &[
     "#,
 ];
 
+// The values of the elements of a batch are separated by commas
+struct VerifiableVOPRFTestVectorParameters {
+    sksm: Vec<u8>,
+    pksm: Vec<u8>,
+    input: Vec<Vec<u8>>,
+    blind: Vec<Vec<u8>>,
+    blinded_element: Vec<Vec<u8>>,
+    evaluation_element: Vec<Vec<u8>>,
+    proof: Vec<u8>,
+    proof_random_scalar: Vec<u8>,
+    output: Vec<Vec<u8>>,
+}
+
+// These are not the verifiable mode vectors published in
+// https://www.ietf.org/archive/id/draft-irtf-cfrg-voprf-06.txt, which should
+// replace them. They were computed with a standalone Python implementation of
+// Section 3.3 of that draft (ristretto255-SHA512, mode 0x01), written apart
+// from this crate. That implementation reproduces the published base mode
+// vectors above, which covers the hash-to-group, blinding and finalization,
+// whereas the DLEQ proof and its composite computation are only checked
+// against it. The proof is randomized, so the vectors also contain its random
+// scalar. The last vector proves a batch of two evaluations at once
+static VOPRF_RISTRETTO255_SHA512: &[&str] = &[
+    r#"
+    {
+        "sksm": "5a6ca90b7f1cd2940e5726b6843032c89ce727c5bac99a5c899e534aafa60204",
+        "pksm": "c6dfc1a048b050110e318d2620ffb2fdecc308bd29c3ac5491d77d45ea12c534",
+        "input": "00",
+        "blind": "0ef8fb286fe10d6d2805032edec3b6a0f2fe38862244f7818174858e88e20005",
+        "blinded_element": "1c269503ae2151e005dffd086295c37f95a66aa77bf7f86f7125cc59487f945b",
+        "evaluation_element": "b29892c38444c96bb9e6a9f2907c366ba731ef2e59049d04fb5e619cd13a0b28",
+        "proof": "44ed07aca83c821802805f14d64414968eb6e7118aa9dd97aaf90718f4ff3e089c61dbb641c9b3952587341a5cf4121a69cc1721c6919ec6004c952e23b82b01",
+        "proof_random_scalar": "0ab931e8816b463e4460bc08301cb32d2d4e10cdfa737b8456ca516cc4409208",
+        "output": "714f590800552351dd445273f7cc96847c8f693b5016f29c5b6c67aba09eb07312c40195e1cc4cccbb1ca3789bc66f6cb9964b1660d1c9ed2fcf382d11a2c4a2"
+    }
+    "#,
+    r#"
+    {
+        "sksm": "5a6ca90b7f1cd2940e5726b6843032c89ce727c5bac99a5c899e534aafa60204",
+        "pksm": "c6dfc1a048b050110e318d2620ffb2fdecc308bd29c3ac5491d77d45ea12c534",
+        "input": "5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a",
+        "blind": "a90f7380ecccad571e401c18b3e74c119a77293fdc194fa6b8ed0dfc70807405",
+        "blinded_element": "4a42be732045aaf1bfd737bc07ede40d936b52adad0b9ca9a212370fcddd0814",
+        "evaluation_element": "381f00fb423dde3267206cb673a84b5f17b03022ae347d6e64b550e9826a7e18",
+        "proof": "eda8f70f6726f01ad08073475c37cce28a57d18a417e1ab88711aa840036c008f216ddd6b4925c6bf0131ea45e01b1839bc3c75cd8c3874f4aeedd5fe2e1360d",
+        "proof_random_scalar": "8b2f5d419fb82190c29e38e41f447d2bb2288066b6b99a478db73bc53a81ec06",
+        "output": "ad8a1ae5a10b25cd252f2a4f7f78173aa16ca20e197cabfb6418b2353d2c119024e0e8a78e9af90d4f011f2bb07f8e7a1b692f0bcd2e485a2d12a5b8586ee45a"
+    }
+    "#,
+    r#"
+    {
+        "sksm": "5a6ca90b7f1cd2940e5726b6843032c89ce727c5bac99a5c899e534aafa60204",
+        "pksm": "c6dfc1a048b050110e318d2620ffb2fdecc308bd29c3ac5491d77d45ea12c534",
+        "input": "00,5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a",
+        "blind": "4ac7e3e5fa5e2dd070702fd0602fe9e022e69655c41e6769018016de7374e902,7039d4d73098076de98078f44792c33d0fe8740d86b2edd622cf454e3392c303",
+        "blinded_element": "62316bac304f37f71a59643b8b06682f07eb6706fd6f67a8cf6dd57ef7a95a35,c8e97173d353f219e327dd79878a43aa38d2726c9298bc2fc0816924ad288977",
+        "evaluation_element": "aa5e18d0a42954f2def2152a155feaad136b17611861ec3580fe47342eabe34e,2a265a565a57a7b7504f7cf98814ea4858050a2a87fae7ff8c3e59b5892e451f",
+        "proof": "89eb73f77059fb734ce98cef0f16ddf643294d9f234414205c0da3a0cf12b50dd9e747e62ae7f0b0e9a7288439d190151ac8d6b26314bbcc19c87b7d5fc56303",
+        "proof_random_scalar": "726103b03c8ad0f8e9e1f75d083d996c1182753d52521c66452226866c5dc00a",
+        "output": "714f590800552351dd445273f7cc96847c8f693b5016f29c5b6c67aba09eb07312c40195e1cc4cccbb1ca3789bc66f6cb9964b1660d1c9ed2fcf382d11a2c4a2,ad8a1ae5a10b25cd252f2a4f7f78173aa16ca20e197cabfb6418b2353d2c119024e0e8a78e9af90d4f011f2bb07f8e7a1b692f0bcd2e485a2d12a5b8586ee45a"
+    }
+    "#,
+];
+
 fn decode(values: &Value, key: &str) -> Option<Vec<u8>> {
     values[key]
         .as_str()
@@ -61,44 +128,75 @@ fn populate_test_vectors(values: &Value) -> VOPRFTestVectorParameters {
     }
 }
 
+fn decode_batch(values: &Value, key: &str) -> Option<Vec<Vec<u8>>> {
+    values[key].as_str().and_then(|s| {
+        s.split(',')
+            .map(|x| hex::decode(x).ok())
+            .collect::<Option<Vec<_>>>()
+    })
+}
+
+fn populate_verifiable_test_vectors(values: &Value) -> VerifiableVOPRFTestVectorParameters {
+    VerifiableVOPRFTestVectorParameters {
+        sksm: decode(&values, "sksm").unwrap(),
+        pksm: decode(&values, "pksm").unwrap(),
+        input: decode_batch(&values, "input").unwrap(),
+        blind: decode_batch(&values, "blind").unwrap(),
+        blinded_element: decode_batch(&values, "blinded_element").unwrap(),
+        evaluation_element: decode_batch(&values, "evaluation_element").unwrap(),
+        proof: decode(&values, "proof").unwrap(),
+        proof_random_scalar: decode(&values, "proof_random_scalar").unwrap(),
+        output: decode_batch(&values, "output").unwrap(),
+    }
+}
+
+fn verifiable_tokens(
+    parameters: &VerifiableVOPRFTestVectorParameters,
+) -> Result<Vec<oprf::Token<RistrettoPoint>>, ProtocolError> {
+    let public_key =
+        RistrettoPoint::from_element_slice(GenericArray::from_slice(&parameters.pksm))?;
+    parameters
+        .input
+        .iter()
+        .zip(&parameters.blind)
+        .map(|(input, blind)| {
+            let mut rng = CycleRng::new(blind.to_vec());
+            Ok(
+                oprf::blind_verifiable::<_, RistrettoPoint, Sha512>(input, &public_key, &mut rng)?
+                    .0,
+            )
+        })
+        .collect()
+}
+
 // Tests input -> blind, blinded_element
 #[test]
-fn test_blind() -> Result<(), PakeError> {
+fn test_blind() -> Result<(), ProtocolError> {
     for tv in OPRF_RISTRETTO255_SHA512 {
         let parameters = populate_test_vectors(&serde_json::from_str(tv).unwrap());
         let mut rng = CycleRng::new(parameters.blind.to_vec());
 
-        let (token, blinded_element) =
+        let (_, blinded_element) =
             oprf::blind::<_, RistrettoPoint, Sha512>(&parameters.input, &mut rng)?;
 
-        assert_eq!(
-            &parameters.blind,
-            &RistrettoPoint::scalar_as_bytes(&token.blind).to_vec()
-        );
-        assert_eq!(
-            &parameters.blinded_element,
-            &blinded_element.to_arr().to_vec()
-        );
+        assert_eq!(&parameters.blinded_element, &blinded_element.serialize());
     }
     Ok(())
 }
 
 // Tests sksm, blinded_element -> evaluation_element
 #[test]
-fn test_evaluate() -> Result<(), PakeError> {
+fn test_evaluate() -> Result<(), ProtocolError> {
     for tv in OPRF_RISTRETTO255_SHA512 {
         let parameters = populate_test_vectors(&serde_json::from_str(tv).unwrap());
-        let evaluation_element = oprf::evaluate::<RistrettoPoint>(
-            RistrettoPoint::from_element_slice(GenericArray::from_slice(
-                &parameters.blinded_element,
-            ))
-            .unwrap(),
-            &RistrettoPoint::from_scalar_slice(GenericArray::from_slice(&parameters.sksm)).unwrap(),
+        let evaluation_element = oprf::evaluate(
+            &oprf::BlindedElement::<RistrettoPoint>::deserialize(&parameters.blinded_element)?,
+            &RistrettoPoint::from_scalar_slice(GenericArray::from_slice(&parameters.sksm))?,
         );
 
         assert_eq!(
             &parameters.evaluation_element,
-            &evaluation_element.to_arr().to_vec()
+            &evaluation_element.serialize()
         );
     }
     Ok(())
@@ -106,19 +204,107 @@ fn test_evaluate() -> Result<(), PakeError> {
 
 // Tests input, blind, evaluation_element -> output
 #[test]
-fn test_finalize() -> Result<(), PakeError> {
+fn test_finalize() -> Result<(), ProtocolError> {
     for tv in OPRF_RISTRETTO255_SHA512 {
         let parameters = populate_test_vectors(&serde_json::from_str(tv).unwrap());
+        let mut rng = CycleRng::new(parameters.blind.to_vec());
+        let (token, _) = oprf::blind::<_, RistrettoPoint, Sha512>(&parameters.input, &mut rng)?;
 
         let output = oprf::finalize::<RistrettoPoint, Sha512>(
-            &parameters.input,
-            &RistrettoPoint::from_scalar_slice(GenericArray::from_slice(&parameters.blind))?,
-            RistrettoPoint::from_element_slice(GenericArray::from_slice(
-                &parameters.evaluation_element,
-            ))?,
+            &token,
+            &oprf::EvaluatedElement::deserialize(&parameters.evaluation_element)?,
         );
 
         assert_eq!(&parameters.output, &output.to_vec());
     }
     Ok(())
 }
+
+// Tests sksm, input, blind -> pksm, blinded_element in the verifiable mode
+#[test]
+fn test_verifiable_blind() -> Result<(), ProtocolError> {
+    for tv in VOPRF_RISTRETTO255_SHA512 {
+        let parameters = populate_verifiable_test_vectors(&serde_json::from_str(tv).unwrap());
+        let sksm = RistrettoPoint::from_scalar_slice(GenericArray::from_slice(&parameters.sksm))?;
+        let public_key = RistrettoPoint::base_point() * sksm;
+        assert_eq!(&parameters.pksm, &public_key.to_arr().to_vec());
+
+        for ((input, blind), expected) in parameters
+            .input
+            .iter()
+            .zip(&parameters.blind)
+            .zip(&parameters.blinded_element)
+        {
+            let mut rng = CycleRng::new(blind.to_vec());
+            let (_, blinded_element) =
+                oprf::blind_verifiable::<_, RistrettoPoint, Sha512>(input, &public_key, &mut rng)?;
+            assert_eq!(expected, &blinded_element.serialize());
+        }
+    }
+    Ok(())
+}
+
+// Tests sksm, blinded_element, proof_random_scalar -> evaluation_element,
+// proof in the verifiable mode
+#[test]
+fn test_verifiable_evaluate() -> Result<(), ProtocolError> {
+    for tv in VOPRF_RISTRETTO255_SHA512 {
+        let parameters = populate_verifiable_test_vectors(&serde_json::from_str(tv).unwrap());
+        let sksm = RistrettoPoint::from_scalar_slice(GenericArray::from_slice(&parameters.sksm))?;
+        let blinded_elements = parameters
+            .blinded_element
+            .iter()
+            .map(|x| oprf::BlindedElement::<RistrettoPoint>::deserialize(x))
+            .collect::<Result<Vec<_>, ProtocolError>>()?;
+
+        let mut rng = CycleRng::new(parameters.proof_random_scalar.to_vec());
+        let (evaluation_elements, proof) =
+            oprf::evaluate_batch::<_, RistrettoPoint, Sha512>(&mut rng, &blinded_elements, &sksm)?;
+        assert_eq!(
+            parameters.evaluation_element,
+            evaluation_elements
+                .iter()
+                .map(|x| x.serialize())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(&parameters.proof, &proof.serialize());
+    }
+    Ok(())
+}
+
+// Tests pksm, input, blind, evaluation_element, proof -> output in the
+// verifiable mode
+#[test]
+fn test_verifiable_finalize() -> Result<(), ProtocolError> {
+    for tv in VOPRF_RISTRETTO255_SHA512 {
+        let parameters = populate_verifiable_test_vectors(&serde_json::from_str(tv).unwrap());
+        let tokens = verifiable_tokens(&parameters)?;
+        let evaluation_elements = parameters
+            .evaluation_element
+            .iter()
+            .map(|x| oprf::EvaluatedElement::<RistrettoPoint>::deserialize(x))
+            .collect::<Result<Vec<_>, ProtocolError>>()?;
+        let proof = oprf::Proof::<RistrettoPoint>::deserialize(&parameters.proof)?;
+
+        let outputs =
+            oprf::finalize_batch::<RistrettoPoint, Sha512>(&tokens, &evaluation_elements, &proof)?;
+        assert_eq!(
+            parameters.output,
+            outputs.iter().map(|x| x.to_vec()).collect::<Vec<_>>()
+        );
+
+        // The proof does not verify for the elements of the batch in another
+        // order
+        if tokens.len() > 1 {
+            let reversed_tokens: Vec<_> = tokens.iter().rev().cloned().collect();
+            let reversed_elements: Vec<_> = evaluation_elements.iter().rev().cloned().collect();
+            assert!(oprf::finalize_batch::<RistrettoPoint, Sha512>(
+                &reversed_tokens,
+                &reversed_elements,
+                &proof
+            )
+            .is_err());
+        }
+    }
+    Ok(())
+}
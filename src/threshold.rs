@@ -0,0 +1,465 @@
+// Copyright (c) Facebook, Inc. and its affiliates.
+//
+// This source code is licensed under the MIT license found in the
+// LICENSE file in the root directory of this source tree.
+
+//! Threshold evaluation of the OPRF across multiple servers
+//!
+//! In this mode, the OPRF key of a credential is split into shares with
+//! Shamir's secret sharing, so that any `threshold` of the servers holding a
+//! share are needed in order to evaluate the OPRF. The server running the
+//! protocol holds one of the shares, and each of the other servers returns a
+//! [PartialEvaluation] of the client's blinded password. The server running
+//! the protocol combines them with its own evaluation through Lagrange
+//! interpolation, so that the OPRF key is never held by any of the servers,
+//! and the client proceeds as it would with a single server.
+//!
+//! Each partial evaluation carries a DLEQ proof that it was computed with the
+//! share whose [PublicShare] was published when the key was split, which the
+//! server running the protocol checks before combining it. A share holder
+//! returning a wrong evaluation is thereby identified by the index of its
+//! share, instead of silently failing the login.
+
+use crate::{
+    ciphersuite::CipherSuite,
+    errors::{utils::check_slice_size, InternalPakeError, PakeError, ProtocolError},
+    group::Group,
+    messages::{CredentialRequest, RegistrationRequest},
+    oprf::{self, Proof},
+};
+use generic_array::{typenum::Unsigned, GenericArray};
+use rand::{CryptoRng, RngCore};
+use zeroize::Zeroize;
+
+/// A share of the OPRF key of a credential, held by one of the servers
+pub struct OprfKeyShare<CS: CipherSuite> {
+    /// The non-zero index of the share, at which the sharing polynomial was
+    /// evaluated
+    pub(crate) index: u16,
+    pub(crate) share: <CS::Group as Group>::Scalar,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for OprfKeyShare<CS> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            share: self.share.clone(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> Zeroize for OprfKeyShare<CS> {
+    fn zeroize(&mut self) {
+        self.share.zeroize();
+    }
+}
+
+impl<CS: CipherSuite> Drop for OprfKeyShare<CS> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<CS: CipherSuite> OprfKeyShare<CS> {
+    /// Generates a random OPRF key and splits it into `num_shares` shares,
+    /// any `threshold` of which are needed to evaluate the OPRF. The key
+    /// itself is not retained
+    pub fn generate<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        threshold: u16,
+        num_shares: u16,
+    ) -> Result<Vec<Self>, ProtocolError> {
+        let mut oprf_key = CS::Group::random_nonzero_scalar(rng);
+        let shares = Self::split(rng, &oprf_key, threshold, num_shares);
+        oprf_key.zeroize();
+        shares
+    }
+
+    /// Splits an existing OPRF key into `num_shares` shares, any `threshold`
+    /// of which are needed to evaluate the OPRF
+    pub fn split<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        oprf_key: &<CS::Group as Group>::Scalar,
+        threshold: u16,
+        num_shares: u16,
+    ) -> Result<Vec<Self>, ProtocolError> {
+        if threshold == 0 || threshold > num_shares {
+            return Err(InternalPakeError::InvalidThresholdError.into());
+        }
+
+        // The sharing polynomial has the OPRF key as its constant term
+        let mut coefficients = vec![oprf_key.clone()];
+        for _ in 1..threshold {
+            coefficients.push(CS::Group::random_nonzero_scalar(rng));
+        }
+
+        let shares = (1..=num_shares)
+            .map(|index| {
+                let x = CS::Group::scalar_from_u64(index.into());
+                // Horner's method
+                let share = coefficients
+                    .iter()
+                    .rev()
+                    .skip(1)
+                    .fold(coefficients[coefficients.len() - 1].clone(), |acc, c| {
+                        CS::Group::scalar_add(&CS::Group::scalar_mul(&acc, &x), c)
+                    });
+                Self { index, share }
+            })
+            .collect();
+
+        coefficients.iter_mut().for_each(Zeroize::zeroize);
+        Ok(shares)
+    }
+
+    /// Returns the index of the share
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the public part of the share, which is published along with
+    /// the index of the share so that its partial evaluations can be verified
+    pub fn public_share(&self) -> PublicShare<CS> {
+        PublicShare {
+            index: self.index,
+            element: CS::Group::base_point() * &self.share,
+        }
+    }
+
+    /// Evaluates the share on the blinded password of a [RegistrationRequest]
+    pub fn evaluate_registration_request<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        message: &RegistrationRequest<CS>,
+    ) -> Result<PartialEvaluation<CS>, ProtocolError> {
+        self.evaluate(rng, message.alpha)
+    }
+
+    /// Evaluates the share on the blinded password of a [CredentialRequest]
+    pub fn evaluate_credential_request<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        message: &CredentialRequest<CS>,
+    ) -> Result<PartialEvaluation<CS>, ProtocolError> {
+        self.evaluate(rng, message.alpha)
+    }
+
+    fn evaluate<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        alpha: CS::Group,
+    ) -> Result<PartialEvaluation<CS>, ProtocolError> {
+        let beta = oprf::evaluate_point::<CS::Group>(alpha, &self.share);
+        let proof =
+            oprf::generate_proof::<_, CS::Group, CS::Hash>(rng, &self.share, &[alpha], &[beta])?;
+        Ok(PartialEvaluation {
+            index: self.index,
+            beta,
+            proof,
+        })
+    }
+
+    // Evaluates the share on a blinded password, and combines the result with
+    // the partial evaluations of other share holders, each of which must be
+    // proven against the public share of the same index
+    pub(crate) fn evaluate_and_combine(
+        &self,
+        alpha: CS::Group,
+        partial_evaluations: &[PartialEvaluation<CS>],
+        public_shares: &[PublicShare<CS>],
+    ) -> Result<CS::Group, ProtocolError> {
+        for partial_evaluation in partial_evaluations {
+            let public_share = public_shares
+                .iter()
+                .find(|public_share| public_share.index == partial_evaluation.index)
+                .ok_or(ProtocolError::InvalidPartialEvaluationError(
+                    partial_evaluation.index,
+                ))?;
+            oprf::verify_proof::<CS::Group, CS::Hash>(
+                public_share.element,
+                &[alpha],
+                &[partial_evaluation.beta],
+                &partial_evaluation.proof,
+            )
+            .map_err(|_| ProtocolError::InvalidPartialEvaluationError(partial_evaluation.index))?;
+        }
+
+        let evaluations: Vec<_> = std::iter::once((
+            self.index,
+            oprf::evaluate_point::<CS::Group>(alpha, &self.share),
+        ))
+        .chain(partial_evaluations.iter().map(|e| (e.index, e.beta)))
+        .collect();
+        Ok(combine::<CS>(&evaluations)?)
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &self.index.to_be_bytes()[..],
+            &CS::Group::scalar_as_bytes(&self.share),
+        ]
+        .concat()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let scalar_len = <CS::Group as Group>::ScalarLen::to_usize();
+        let checked_slice = check_slice_size(input, 2 + scalar_len, "oprf_key_share")?;
+        let index = u16::from_be_bytes([checked_slice[0], checked_slice[1]]);
+        if index == 0 {
+            return Err(PakeError::SerializationError.into());
+        }
+        Ok(Self {
+            index,
+            share: CS::Group::from_scalar_slice(GenericArray::from_slice(&checked_slice[2..]))?,
+        })
+    }
+}
+
+impl_serialize_and_deserialize_for!(OprfKeyShare);
+
+/// The public part of an [OprfKeyShare], against which the proofs of its
+/// [PartialEvaluation]s are verified
+pub struct PublicShare<CS: CipherSuite> {
+    pub(crate) index: u16,
+    pub(crate) element: CS::Group,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for PublicShare<CS> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            element: self.element,
+        }
+    }
+}
+
+impl<CS: CipherSuite> PublicShare<CS> {
+    /// Returns the index of the share
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> Vec<u8> {
+        [&self.index.to_be_bytes()[..], &self.element.to_arr()].concat()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let (index, element) = deserialize_index_and_element::<CS>(input, "public_share")?;
+        Ok(Self { index, element })
+    }
+}
+
+impl_serialize_and_deserialize_for!(PublicShare);
+
+/// The evaluation of the OPRF under one [OprfKeyShare], along with a proof
+/// that it was computed with the share, sent by its holder to the server
+/// running the protocol
+pub struct PartialEvaluation<CS: CipherSuite> {
+    pub(crate) index: u16,
+    pub(crate) beta: CS::Group,
+    pub(crate) proof: Proof<CS::Group>,
+}
+
+// Cannot be derived because it would require for CS to be Clone.
+impl<CS: CipherSuite> Clone for PartialEvaluation<CS> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            beta: self.beta,
+            proof: self.proof.clone(),
+        }
+    }
+}
+
+impl<CS: CipherSuite> PartialEvaluation<CS> {
+    /// Returns the index of the share this evaluation was computed with
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Serialization into bytes
+    pub fn serialize(&self) -> Vec<u8> {
+        [
+            &self.index.to_be_bytes()[..],
+            &self.beta.to_arr(),
+            &self.proof.serialize(),
+        ]
+        .concat()
+    }
+
+    /// Deserialization from bytes
+    pub fn deserialize(input: &[u8]) -> Result<Self, ProtocolError> {
+        let elem_len = <CS::Group as Group>::ElemLen::to_usize();
+        let checked_slice = check_slice_size(
+            input,
+            2 + elem_len + Proof::<CS::Group>::len(),
+            "partial_evaluation",
+        )?;
+        let (index, beta) = deserialize_index_and_element::<CS>(
+            &checked_slice[..2 + elem_len],
+            "partial_evaluation",
+        )?;
+        let proof = Proof::deserialize(&checked_slice[2 + elem_len..])?;
+        Ok(Self { index, beta, proof })
+    }
+}
+
+impl_serialize_and_deserialize_for!(PartialEvaluation);
+
+// Deserializes a non-zero share index followed by a group element other than
+// the identity
+fn deserialize_index_and_element<CS: CipherSuite>(
+    input: &[u8],
+    arg_name: &'static str,
+) -> Result<(u16, CS::Group), ProtocolError> {
+    let elem_len = <CS::Group as Group>::ElemLen::to_usize();
+    let checked_slice = check_slice_size(input, 2 + elem_len, arg_name)?;
+    let index = u16::from_be_bytes([checked_slice[0], checked_slice[1]]);
+    if index == 0 {
+        return Err(PakeError::SerializationError.into());
+    }
+
+    let element = CS::Group::from_element_slice(GenericArray::from_slice(&checked_slice[2..]))?;
+    // Throw an error if the identity group element is encountered
+    if element.is_identity() {
+        return Err(PakeError::IdentityGroupElementError.into());
+    }
+    Ok((index, element))
+}
+
+// Combines evaluations from distinct shares, given with the index of their
+// share, into the evaluation under the shared OPRF key, by interpolating the
+// sharing polynomial at zero in the exponent
+fn combine<CS: CipherSuite>(
+    evaluations: &[(u16, CS::Group)],
+) -> Result<CS::Group, InternalPakeError> {
+    if evaluations.is_empty() {
+        return Err(InternalPakeError::InvalidThresholdError);
+    }
+    for (i, (index, _)) in evaluations.iter().enumerate() {
+        if evaluations[..i].iter().any(|(other, _)| other == index) {
+            return Err(InternalPakeError::DuplicateShareIndexError);
+        }
+    }
+
+    let mut combined: Option<CS::Group> = None;
+    for (index, beta) in evaluations {
+        let x_i = CS::Group::scalar_from_u64((*index).into());
+        let mut numerator = CS::Group::scalar_from_u64(1);
+        let mut denominator = CS::Group::scalar_from_u64(1);
+        for (other, _) in evaluations.iter().filter(|(other, _)| other != index) {
+            let x_j = CS::Group::scalar_from_u64((*other).into());
+            numerator = CS::Group::scalar_mul(&numerator, &x_j);
+            denominator = CS::Group::scalar_mul(&denominator, &CS::Group::scalar_sub(&x_j, &x_i));
+        }
+        let lagrange_coefficient =
+            CS::Group::scalar_mul(&numerator, &CS::Group::scalar_invert(&denominator));
+        let term = *beta * &lagrange_coefficient;
+        combined = Some(match combined {
+            Some(sum) => sum.add_element(&term),
+            None => term,
+        });
+    }
+
+    // The loop runs at least once, as evaluations is not empty
+    Ok(combined.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{key_exchange::tripledh::TripleDH, slow_hash::NoOpHash};
+    use curve25519_dalek::ristretto::RistrettoPoint;
+    use rand::rngs::OsRng;
+
+    struct Default;
+    impl CipherSuite for Default {
+        type Group = RistrettoPoint;
+        type KeyExchange = TripleDH;
+        type Hash = sha2::Sha512;
+        type SlowHash = NoOpHash;
+        const ID: u16 = 0x0001;
+    }
+
+    #[test]
+    fn combine_any_threshold_subset() -> Result<(), ProtocolError> {
+        let mut rng = OsRng;
+        let oprf_key = RistrettoPoint::random_nonzero_scalar(&mut rng);
+        let shares = OprfKeyShare::<Default>::split(&mut rng, &oprf_key, 3, 5)?;
+        let alpha = RistrettoPoint::base_point() * RistrettoPoint::random_nonzero_scalar(&mut rng);
+        let expected = oprf::evaluate_point::<RistrettoPoint>(alpha, &oprf_key);
+
+        for subset in &[[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let evaluations: Vec<_> = subset
+                .iter()
+                .map(|&i| evaluate(&shares[i], alpha))
+                .collect();
+            assert_eq!(combine::<Default>(&evaluations)?, expected);
+        }
+
+        // Fewer shares than the threshold yield an unrelated element
+        let evaluations: Vec<_> = shares[..2].iter().map(|s| evaluate(s, alpha)).collect();
+        assert_ne!(combine::<Default>(&evaluations)?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        let mut rng = OsRng;
+        assert!(OprfKeyShare::<Default>::generate(&mut rng, 0, 3).is_err());
+        assert!(OprfKeyShare::<Default>::generate(&mut rng, 4, 3).is_err());
+
+        let shares = OprfKeyShare::<Default>::generate(&mut rng, 2, 3).unwrap();
+        let alpha = RistrettoPoint::base_point();
+        let evaluation = evaluate(&shares[0], alpha);
+        assert!(combine::<Default>(&[evaluation, evaluation]).is_err());
+        assert!(combine::<Default>(&[]).is_err());
+    }
+
+    #[test]
+    fn verify_partial_evaluations() -> Result<(), ProtocolError> {
+        let mut rng = OsRng;
+        let shares = OprfKeyShare::<Default>::generate(&mut rng, 2, 3)?;
+        let public_shares: Vec<_> = shares
+            .iter()
+            .map(|share| PublicShare::deserialize(&share.public_share().serialize()))
+            .collect::<Result<_, _>>()?;
+        let alpha = RistrettoPoint::base_point() * RistrettoPoint::random_nonzero_scalar(&mut rng);
+        let partial_evaluations = vec![PartialEvaluation::deserialize(
+            &shares[1].evaluate(&mut rng, alpha)?.serialize(),
+        )?];
+        shares[0].evaluate_and_combine(alpha, &partial_evaluations, &public_shares)?;
+
+        // The evaluation is not proven against another public share, or for
+        // another blinded element, and the missing public share is reported
+        let mut swapped_public_shares = public_shares.clone();
+        swapped_public_shares[1].element = public_shares[2].element;
+        let other_alpha = alpha.add_element(&RistrettoPoint::base_point());
+        for (alpha, public_shares) in &[
+            (alpha, &swapped_public_shares[..]),
+            (other_alpha, &public_shares[..]),
+            (alpha, &public_shares[..1]),
+        ] {
+            assert!(matches!(
+                shares[0].evaluate_and_combine(*alpha, &partial_evaluations, public_shares),
+                Err(ProtocolError::InvalidPartialEvaluationError(2))
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn evaluate(share: &OprfKeyShare<Default>, alpha: RistrettoPoint) -> (u16, RistrettoPoint) {
+        (
+            share.index,
+            oprf::evaluate_point::<RistrettoPoint>(alpha, &share.share),
+        )
+    }
+}